@@ -1,6 +1,7 @@
 use crate::{
     instructions::Instruction, DEFAULT_MEMORY_SIZE, MEMORY_FRAMESIZE, MEMORY_FRAME_SHIFTS,
-    RISCV_GENERAL_REGISTER_NUMBER, RISCV_PAGESIZE,
+    RISCV_FLOAT_REGISTER_NUMBER, RISCV_GENERAL_REGISTER_NUMBER, RISCV_PAGESIZE,
+    RISCV_VECTOR_REGISTER_NUMBER, RISCV_VLENB, RISCV_VTYPE_VILL,
 };
 use std::alloc::{alloc, alloc_zeroed, dealloc, Layout};
 
@@ -18,6 +19,7 @@ pub const RET_OUT_OF_BOUND: u8 = 7;
 pub const RET_INVALID_PERMISSION: u8 = 8;
 pub const RET_SLOWPATH: u8 = 9;
 pub const RET_PAUSE: u8 = 10;
+pub const RET_STACK_OVERFLOW: u8 = 11;
 
 #[inline(always)]
 pub fn calculate_slot(addr: u64) -> usize {
@@ -92,17 +94,39 @@ pub struct AsmCoreMachine {
     pub version: u32,
 
     pub error_arg0: u64,
+    // INST_ARGS of the instruction raising a memory error, which locates the
+    // failed instruction in its trace.
+    pub error_arg1: u64,
 
     pub memory_size: u64,
     pub frames_size: u64,
     pub flags_size: u64,
 
+    // Loads and stores in [stack_guard_start, stack_guard_end) fail with a
+    // stack overflow, the range is empty when no guard is set.
+    pub stack_guard_start: u64,
+    pub stack_guard_end: u64,
+
     pub last_read_frame: u64,
     pub last_write_page: u64,
 
     pub memory_ptr: u64,
     pub flags_ptr: u64,
     pub frames_ptr: u64,
+
+    // Floating-point state is only accessed from Rust, since F and D
+    // instructions are slowpath instructions.
+    pub fregisters: [u64; RISCV_FLOAT_REGISTER_NUMBER],
+    pub fcsr: u32,
+
+    // Vector state, V instructions are slowpath instructions as well.
+    pub vregisters: [u8; RISCV_VLENB * RISCV_VECTOR_REGISTER_NUMBER],
+    pub vl: u64,
+    pub vtype: u64,
+
+    // Shadow memory of memcheck with one bit per byte of memory, which is
+    // only allocated once memcheck is enabled and is maintained from Rust.
+    pub memcheck_ptr: u64,
 }
 
 impl Drop for AsmCoreMachine {
@@ -113,6 +137,10 @@ impl Drop for AsmCoreMachine {
         unsafe { dealloc(self.flags_ptr as *mut u8, flags_layout) };
         let frames_layout = Layout::array::<u8>(self.frames_size as usize).unwrap();
         unsafe { dealloc(self.frames_ptr as *mut u8, frames_layout) };
+        if self.memcheck_ptr != 0 {
+            let memcheck_layout = Layout::array::<u8>(self.memcheck_size()).unwrap();
+            unsafe { dealloc(self.memcheck_ptr as *mut u8, memcheck_layout) };
+        }
     }
 }
 
@@ -142,6 +170,7 @@ impl AsmCoreMachine {
         machine.load_reservation_address = u64::MAX;
         machine.version = version;
         machine.isa = isa;
+        machine.vtype = RISCV_VTYPE_VILL;
 
         machine.memory_size = memory_size as u64;
         machine.frames_size = (memory_size / MEMORY_FRAMESIZE) as u64;
@@ -163,6 +192,19 @@ impl AsmCoreMachine {
     pub fn set_max_cycles(&mut self, cycles: u64) {
         self.max_cycles = cycles;
     }
+
+    // Allocates the shadow memory of memcheck, this must happen before the
+    // program is loaded so the loaded bytes are marked as defined.
+    pub fn enable_memcheck(&mut self) {
+        if self.memcheck_ptr == 0 {
+            let memcheck_layout = Layout::array::<u8>(self.memcheck_size()).unwrap();
+            self.memcheck_ptr = unsafe { alloc_zeroed(memcheck_layout) } as u64;
+        }
+    }
+
+    pub fn memcheck_size(&self) -> usize {
+        (self.memory_size as usize + 7) / 8
+    }
 }
 
 impl AsmCoreMachine {
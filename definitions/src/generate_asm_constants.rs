@@ -2,12 +2,12 @@ use ckb_vm_definitions::{
     asm::{
         AsmCoreMachine, FixedTrace, InvokeData, RET_CYCLES_OVERFLOW, RET_DECODE_TRACE,
         RET_DYNAMIC_JUMP, RET_EBREAK, RET_ECALL, RET_INVALID_PERMISSION, RET_MAX_CYCLES_EXCEEDED,
-        RET_OUT_OF_BOUND, RET_PAUSE, RET_SLOWPATH, TRACE_ITEM_LENGTH,
+        RET_OUT_OF_BOUND, RET_PAUSE, RET_SLOWPATH, RET_STACK_OVERFLOW, TRACE_ITEM_LENGTH,
     },
     for_each_inst,
     instructions::{instruction_opcode_name, MAXIMUM_OPCODE, MINIMAL_OPCODE},
     memory::{FLAG_DIRTY, FLAG_EXECUTABLE, FLAG_FREEZED, FLAG_WRITABLE, FLAG_WXORX_BIT},
-    registers::{RA, SP},
+    registers::{A0, A1, RA, SP},
     MEMORY_FRAMESIZE, MEMORY_FRAME_PAGE_SHIFTS, MEMORY_FRAME_SHIFTS, RISCV_PAGESIZE,
     RISCV_PAGE_SHIFTS,
 };
@@ -69,10 +69,16 @@ fn main() {
     );
     println!("#define CKB_VM_ASM_RET_SLOWPATH {}", RET_SLOWPATH);
     println!("#define CKB_VM_ASM_RET_PAUSE {}", RET_PAUSE);
+    println!(
+        "#define CKB_VM_ASM_RET_STACK_OVERFLOW {}",
+        RET_STACK_OVERFLOW
+    );
     println!();
 
     println!("#define CKB_VM_ASM_REGISTER_RA {}", RA);
     println!("#define CKB_VM_ASM_REGISTER_SP {}", SP);
+    println!("#define CKB_VM_ASM_REGISTER_A0 {}", A0);
+    println!("#define CKB_VM_ASM_REGISTER_A1 {}", A1);
     println!();
 
     println!("#define CKB_VM_ASM_MEMORY_FLAG_FREEZED {}", FLAG_FREEZED);
@@ -163,6 +169,10 @@ fn main() {
         "#define CKB_VM_ASM_ASM_CORE_MACHINE_OFFSET_ERROR_ARG0 {}",
         (&m.error_arg0 as *const u64 as usize) - m_address
     );
+    println!(
+        "#define CKB_VM_ASM_ASM_CORE_MACHINE_OFFSET_ERROR_ARG1 {}",
+        (&m.error_arg1 as *const u64 as usize) - m_address
+    );
     println!(
         "#define CKB_VM_ASM_ASM_CORE_MACHINE_OFFSET_MEMORY_SIZE {}",
         (&m.memory_size as *const u64 as usize) - m_address
@@ -175,6 +185,14 @@ fn main() {
         "#define CKB_VM_ASM_ASM_CORE_MACHINE_OFFSET_FLAGS_SIZE {}",
         (&m.flags_size as *const u64 as usize) - m_address
     );
+    println!(
+        "#define CKB_VM_ASM_ASM_CORE_MACHINE_OFFSET_STACK_GUARD_START {}",
+        (&m.stack_guard_start as *const u64 as usize) - m_address
+    );
+    println!(
+        "#define CKB_VM_ASM_ASM_CORE_MACHINE_OFFSET_STACK_GUARD_END {}",
+        (&m.stack_guard_end as *const u64 as usize) - m_address
+    );
 
     println!(
         "#define CKB_VM_ASM_ASM_CORE_MACHINE_OFFSET_LAST_READ_FRAME {}",
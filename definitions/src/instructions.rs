@@ -266,25 +266,305 @@ macro_rules! __for_each_inst_inner {
             (ADD3C, 0x99),
             (CUSTOM_LOAD_UIMM, 0x9a),
             (CUSTOM_LOAD_IMM, 0x9b),
+            // K
+            (BREV8, 0x9c),
+            (PACK, 0x9d),
+            (PACKH, 0x9e),
+            (PACKW, 0x9f),
+            (SHA256SIG0, 0xa0),
+            (SHA256SIG1, 0xa1),
+            (SHA256SUM0, 0xa2),
+            (SHA256SUM1, 0xa3),
+            (SHA512SIG0, 0xa4),
+            (SHA512SIG1, 0xa5),
+            (SHA512SUM0, 0xa6),
+            (SHA512SUM1, 0xa7),
+            (SM3P0, 0xa8),
+            (SM3P1, 0xa9),
+            (XPERM4, 0xaa),
+            (XPERM8, 0xab),
+            // Zicond and Zcmp
+            (CZERO_EQZ, 0xac),
+            (CZERO_NEZ, 0xad),
+            (CM_MVA01S, 0xae),
+            (CM_MVSA01, 0xaf),
             // All branches
-            (AUIPC, 0x9c),
-            (BEQ, 0x9d),
-            (BGE, 0x9e),
-            (BGEU, 0x9f),
-            (BLT, 0xa0),
-            (BLTU, 0xa1),
-            (BNE, 0xa2),
-            (EBREAK, 0xa3),
-            (ECALL, 0xa4),
-            (FENCE, 0xa5),
-            (FENCEI, 0xa6),
-            (JAL, 0xa7),
-            (JALR_VERSION0, 0xa8),
-            (JALR_VERSION1, 0xa9),
-            (FAR_JUMP_REL, 0xaa),
-            (FAR_JUMP_ABS, 0xab),
-            (CUSTOM_ASM_TRACE_JUMP, 0xac),
-            (CUSTOM_TRACE_END, 0xad)
+            (AUIPC, 0xb0),
+            (BEQ, 0xb1),
+            (BGE, 0xb2),
+            (BGEU, 0xb3),
+            (BLT, 0xb4),
+            (BLTU, 0xb5),
+            (BNE, 0xb6),
+            (EBREAK, 0xb7),
+            (ECALL, 0xb8),
+            (FENCE, 0xb9),
+            (FENCEI, 0xba),
+            (JAL, 0xbb),
+            (JALR_VERSION0, 0xbc),
+            (JALR_VERSION1, 0xbd),
+            (FAR_JUMP_REL, 0xbe),
+            (FAR_JUMP_ABS, 0xbf),
+            (CUSTOM_ASM_TRACE_JUMP, 0xc0),
+            (CUSTOM_TRACE_END, 0xc1)
+        );
+    };
+}
+
+// Slowpath instructions have an op value of 0x00-0x0f, the asm machine exits
+// to Rust to execute them. They are not defined sequentially, so they are
+// listed apart from the fastpath instructions above.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __for_each_slowpath_inst_inner {
+    ($callback:tt) => {
+        $crate::__apply!(
+            $callback,
+            // F
+            (FLW, 0x0001),
+            (FSW, 0x0101),
+            (FMADD_S, 0x0201),
+            (FMSUB_S, 0x0301),
+            (FNMSUB_S, 0x0401),
+            (FNMADD_S, 0x0501),
+            (FADD_S, 0x0601),
+            (FSUB_S, 0x0701),
+            (FMUL_S, 0x0801),
+            (FDIV_S, 0x0901),
+            (FSQRT_S, 0x0a01),
+            (FSGNJ_S, 0x0b01),
+            (FSGNJN_S, 0x0c01),
+            (FSGNJX_S, 0x0d01),
+            (FMIN_S, 0x0e01),
+            (FMAX_S, 0x0f01),
+            (FCVT_W_S, 0x1001),
+            (FCVT_WU_S, 0x1101),
+            (FCVT_L_S, 0x1201),
+            (FCVT_LU_S, 0x1301),
+            (FMV_X_W, 0x1401),
+            (FEQ_S, 0x1501),
+            (FLT_S, 0x1601),
+            (FLE_S, 0x1701),
+            (FCLASS_S, 0x1801),
+            (FCVT_S_W, 0x1901),
+            (FCVT_S_WU, 0x1a01),
+            (FCVT_S_L, 0x1b01),
+            (FCVT_S_LU, 0x1c01),
+            (FMV_W_X, 0x1d01),
+            // D
+            (FLD, 0x0002),
+            (FSD, 0x0102),
+            (FMADD_D, 0x0202),
+            (FMSUB_D, 0x0302),
+            (FNMSUB_D, 0x0402),
+            (FNMADD_D, 0x0502),
+            (FADD_D, 0x0602),
+            (FSUB_D, 0x0702),
+            (FMUL_D, 0x0802),
+            (FDIV_D, 0x0902),
+            (FSQRT_D, 0x0a02),
+            (FSGNJ_D, 0x0b02),
+            (FSGNJN_D, 0x0c02),
+            (FSGNJX_D, 0x0d02),
+            (FMIN_D, 0x0e02),
+            (FMAX_D, 0x0f02),
+            (FCVT_S_D, 0x1002),
+            (FCVT_D_S, 0x1102),
+            (FCVT_W_D, 0x1202),
+            (FCVT_WU_D, 0x1302),
+            (FCVT_L_D, 0x1402),
+            (FCVT_LU_D, 0x1502),
+            (FMV_X_D, 0x1602),
+            (FEQ_D, 0x1702),
+            (FLT_D, 0x1802),
+            (FLE_D, 0x1902),
+            (FCLASS_D, 0x1a02),
+            (FCVT_D_W, 0x1b02),
+            (FCVT_D_WU, 0x1c02),
+            (FCVT_D_L, 0x1d02),
+            (FCVT_D_LU, 0x1e02),
+            (FMV_D_X, 0x1f02),
+            // Zicsr, only floating-point and vector CSRs are supported
+            (CSRRW, 0x0003),
+            (CSRRS, 0x0103),
+            (CSRRC, 0x0203),
+            (CSRRWI, 0x0303),
+            (CSRRSI, 0x0403),
+            (CSRRCI, 0x0503),
+            // V, the configuration, load/store and integer arithmetic subset
+            (VSETVLI, 0x0004),
+            (VSETIVLI, 0x0104),
+            (VSETVL, 0x0204),
+            (VLE8_V, 0x0304),
+            (VLE16_V, 0x0404),
+            (VLE32_V, 0x0504),
+            (VLE64_V, 0x0604),
+            (VSE8_V, 0x0704),
+            (VSE16_V, 0x0804),
+            (VSE32_V, 0x0904),
+            (VSE64_V, 0x0a04),
+            (VLSE8_V, 0x0b04),
+            (VLSE16_V, 0x0c04),
+            (VLSE32_V, 0x0d04),
+            (VLSE64_V, 0x0e04),
+            (VSSE8_V, 0x0f04),
+            (VSSE16_V, 0x1004),
+            (VSSE32_V, 0x1104),
+            (VSSE64_V, 0x1204),
+            (VLM_V, 0x1304),
+            (VSM_V, 0x1404),
+            (VLR_V, 0x1504),
+            (VSR_V, 0x1604),
+            (VADD_VV, 0x1704),
+            (VADD_VX, 0x1804),
+            (VADD_VI, 0x1904),
+            (VSUB_VV, 0x1a04),
+            (VSUB_VX, 0x1b04),
+            (VRSUB_VX, 0x1c04),
+            (VRSUB_VI, 0x1d04),
+            (VMINU_VV, 0x1e04),
+            (VMINU_VX, 0x1f04),
+            (VMIN_VV, 0x2004),
+            (VMIN_VX, 0x2104),
+            (VMAXU_VV, 0x2204),
+            (VMAXU_VX, 0x2304),
+            (VMAX_VV, 0x2404),
+            (VMAX_VX, 0x2504),
+            (VAND_VV, 0x2604),
+            (VAND_VX, 0x2704),
+            (VAND_VI, 0x2804),
+            (VOR_VV, 0x2904),
+            (VOR_VX, 0x2a04),
+            (VOR_VI, 0x2b04),
+            (VXOR_VV, 0x2c04),
+            (VXOR_VX, 0x2d04),
+            (VXOR_VI, 0x2e04),
+            (VADC_VVM, 0x2f04),
+            (VADC_VXM, 0x3004),
+            (VADC_VIM, 0x3104),
+            (VMADC_VV, 0x3204),
+            (VMADC_VX, 0x3304),
+            (VMADC_VI, 0x3404),
+            (VSBC_VVM, 0x3504),
+            (VSBC_VXM, 0x3604),
+            (VMSBC_VV, 0x3704),
+            (VMSBC_VX, 0x3804),
+            (VMERGE_VVM, 0x3904),
+            (VMERGE_VXM, 0x3a04),
+            (VMERGE_VIM, 0x3b04),
+            (VMV_V_V, 0x3c04),
+            (VMV_V_X, 0x3d04),
+            (VMV_V_I, 0x3e04),
+            (VMSEQ_VV, 0x3f04),
+            (VMSEQ_VX, 0x4004),
+            (VMSEQ_VI, 0x4104),
+            (VMSNE_VV, 0x4204),
+            (VMSNE_VX, 0x4304),
+            (VMSNE_VI, 0x4404),
+            (VMSLTU_VV, 0x4504),
+            (VMSLTU_VX, 0x4604),
+            (VMSLT_VV, 0x4704),
+            (VMSLT_VX, 0x4804),
+            (VMSLEU_VV, 0x4904),
+            (VMSLEU_VX, 0x4a04),
+            (VMSLEU_VI, 0x4b04),
+            (VMSLE_VV, 0x4c04),
+            (VMSLE_VX, 0x4d04),
+            (VMSLE_VI, 0x4e04),
+            (VMSGTU_VX, 0x4f04),
+            (VMSGTU_VI, 0x5004),
+            (VMSGT_VX, 0x5104),
+            (VMSGT_VI, 0x5204),
+            (VSLL_VV, 0x5304),
+            (VSLL_VX, 0x5404),
+            (VSLL_VI, 0x5504),
+            (VSRL_VV, 0x5604),
+            (VSRL_VX, 0x5704),
+            (VSRL_VI, 0x5804),
+            (VSRA_VV, 0x5904),
+            (VSRA_VX, 0x5a04),
+            (VSRA_VI, 0x5b04),
+            (VSLIDEUP_VX, 0x5c04),
+            (VSLIDEUP_VI, 0x5d04),
+            (VSLIDEDOWN_VX, 0x5e04),
+            (VSLIDEDOWN_VI, 0x5f04),
+            (VMVNR_V, 0x6004),
+            (VMUL_VV, 0x6104),
+            (VMUL_VX, 0x6204),
+            (VMULH_VV, 0x6304),
+            (VMULH_VX, 0x6404),
+            (VMULHU_VV, 0x6504),
+            (VMULHU_VX, 0x6604),
+            (VMULHSU_VV, 0x6704),
+            (VMULHSU_VX, 0x6804),
+            (VDIVU_VV, 0x6904),
+            (VDIVU_VX, 0x6a04),
+            (VDIV_VV, 0x6b04),
+            (VDIV_VX, 0x6c04),
+            (VREMU_VV, 0x6d04),
+            (VREMU_VX, 0x6e04),
+            (VREM_VV, 0x6f04),
+            (VREM_VX, 0x7004),
+            (VWADDU_VV, 0x7104),
+            (VWADDU_VX, 0x7204),
+            (VWADD_VV, 0x7304),
+            (VWADD_VX, 0x7404),
+            (VWSUBU_VV, 0x7504),
+            (VWSUBU_VX, 0x7604),
+            (VWSUB_VV, 0x7704),
+            (VWSUB_VX, 0x7804),
+            (VWMULU_VV, 0x7904),
+            (VWMULU_VX, 0x7a04),
+            (VWMUL_VV, 0x7b04),
+            (VWMUL_VX, 0x7c04),
+            (VWMULSU_VV, 0x7d04),
+            (VWMULSU_VX, 0x7e04),
+            (VMACC_VV, 0x7f04),
+            (VMACC_VX, 0x8004),
+            (VNMSAC_VV, 0x8104),
+            (VNMSAC_VX, 0x8204),
+            (VMADD_VV, 0x8304),
+            (VMADD_VX, 0x8404),
+            (VNMSUB_VV, 0x8504),
+            (VNMSUB_VX, 0x8604),
+            (VWMACCU_VV, 0x8704),
+            (VWMACCU_VX, 0x8804),
+            (VWMACC_VV, 0x8904),
+            (VWMACC_VX, 0x8a04),
+            (VREDSUM_VS, 0x8b04),
+            (VREDAND_VS, 0x8c04),
+            (VREDOR_VS, 0x8d04),
+            (VREDXOR_VS, 0x8e04),
+            (VREDMINU_VS, 0x8f04),
+            (VREDMIN_VS, 0x9004),
+            (VREDMAXU_VS, 0x9104),
+            (VREDMAX_VS, 0x9204),
+            (VMAND_MM, 0x9304),
+            (VMNAND_MM, 0x9404),
+            (VMANDN_MM, 0x9504),
+            (VMXOR_MM, 0x9604),
+            (VMOR_MM, 0x9704),
+            (VMNOR_MM, 0x9804),
+            (VMORN_MM, 0x9904),
+            (VMXNOR_MM, 0x9a04),
+            (VMV_X_S, 0x9b04),
+            (VMV_S_X, 0x9c04),
+            (VCPOP_M, 0x9d04),
+            (VFIRST_M, 0x9e04),
+            (VID_V, 0x9f04),
+            (VZEXT_VF2, 0xa004),
+            (VZEXT_VF4, 0xa104),
+            (VZEXT_VF8, 0xa204),
+            (VSEXT_VF2, 0xa304),
+            (VSEXT_VF4, 0xa404),
+            (VSEXT_VF8, 0xa504),
+            (VSLIDE1UP_VX, 0xa604),
+            (VSLIDE1DOWN_VX, 0xa704),
+            // Zcmp push and pop, they access a list of registers at once
+            (CM_PUSH, 0x0005),
+            (CM_POP, 0x0105),
+            (CM_POPRET, 0x0205),
+            (CM_POPRETZ, 0x0305)
         );
     };
 }
@@ -384,6 +664,29 @@ macro_rules! for_each_inst_array2 {
     }};
 }
 
+#[macro_export]
+macro_rules! for_each_slowpath_inst {
+    ($callback:ident) => {
+        $crate::__for_each_slowpath_inst_inner!((0, $callback));
+    };
+}
+
+#[macro_export]
+macro_rules! for_each_slowpath_inst_match {
+    ($callback:ident, $val:expr, $others:expr) => {{
+        $crate::__for_each_slowpath_inst_inner!((100, __res__, $val, $callback, $others));
+        __res__
+    }};
+}
+
+#[macro_export]
+macro_rules! for_each_slowpath_inst_match2 {
+    ($callback:ident, $val:expr, $others:expr, $x:ident, $y:ident) => {{
+        $crate::__for_each_slowpath_inst_inner!((102, $x, $y, __res__, $val, $callback, $others));
+        __res__
+    }};
+}
+
 // Define the actual opcodes
 macro_rules! define_instruction {
     ($name:ident, $real_name:ident, $code:expr) => {
@@ -391,6 +694,7 @@ macro_rules! define_instruction {
     };
 }
 for_each_inst!(define_instruction);
+for_each_slowpath_inst!(define_instruction);
 
 pub const MINIMAL_OPCODE: InstructionOpcode = OP_UNLOADED;
 pub const MAXIMUM_OPCODE: InstructionOpcode = OP_CUSTOM_TRACE_END;
@@ -398,6 +702,20 @@ pub const MAXIMUM_OPCODE: InstructionOpcode = OP_CUSTOM_TRACE_END;
 pub const MINIMAL_BASIC_BLOCK_END_OPCODE: InstructionOpcode = OP_AUIPC;
 pub const MAXIMUM_BASIC_BLOCK_END_OPCODE: InstructionOpcode = OP_FAR_JUMP_ABS;
 
+// Custom instructions registered at runtime are slowpath instructions with
+// the low byte 0x06, the high byte is the index of the custom instruction.
+pub const OP_CUSTOM: InstructionOpcode = 0x0006;
+pub const MAXIMUM_CUSTOM_INSTRUCTIONS: usize = 256;
+
+pub fn is_custom_opcode(i: InstructionOpcode) -> bool {
+    i & 0xff == OP_CUSTOM
+}
+
+pub fn custom_opcode(index: usize) -> InstructionOpcode {
+    debug_assert!(index < MAXIMUM_CUSTOM_INSTRUCTIONS);
+    ((index as InstructionOpcode) << 8) | OP_CUSTOM
+}
+
 macro_rules! inst_real_name {
     ($name:ident, $real_name:ident, $code:expr) => {
         stringify!($real_name)
@@ -405,5 +723,12 @@ macro_rules! inst_real_name {
 }
 
 pub fn instruction_opcode_name(i: InstructionOpcode) -> &'static str {
-    for_each_inst_match!(inst_real_name, i, "UNKNOWN_INSTRUCTION!")
+    for_each_inst_match!(inst_real_name, i, slowpath_instruction_opcode_name(i))
+}
+
+fn slowpath_instruction_opcode_name(i: InstructionOpcode) -> &'static str {
+    if is_custom_opcode(i) {
+        return "CUSTOM";
+    }
+    for_each_slowpath_inst_match!(inst_real_name, i, "UNKNOWN_INSTRUCTION!")
 }
@@ -6,6 +6,14 @@ pub mod registers;
 pub const RISCV_PAGE_SHIFTS: usize = 12;
 pub const RISCV_PAGESIZE: usize = 1 << RISCV_PAGE_SHIFTS;
 pub const RISCV_GENERAL_REGISTER_NUMBER: usize = 32;
+pub const RISCV_FLOAT_REGISTER_NUMBER: usize = 32;
+pub const RISCV_VECTOR_REGISTER_NUMBER: usize = 32;
+// VLEN of the V extension, the number of bits in a vector register
+pub const RISCV_VLEN: usize = 256;
+pub const RISCV_VLENB: usize = RISCV_VLEN / 8;
+// The vill bit of vtype, a machine starts with an illegal vtype like a hart
+// out of reset.
+pub const RISCV_VTYPE_VILL: u64 = 1 << 63;
 pub const MEMORY_FRAME_SHIFTS: usize = 18;
 pub const MEMORY_FRAMESIZE: usize = 1 << MEMORY_FRAME_SHIFTS; // 256 KB
 pub const MEMORY_FRAME_PAGE_SHIFTS: usize = MEMORY_FRAME_SHIFTS - RISCV_PAGE_SHIFTS;
@@ -16,3 +24,10 @@ pub const ISA_IMC: u8 = 0b0000_0000;
 pub const ISA_B: u8 = 0b0000_0001;
 pub const ISA_MOP: u8 = 0b0000_0010;
 pub const ISA_A: u8 = 0b0000_0100;
+pub const ISA_F: u8 = 0b0000_1000;
+pub const ISA_D: u8 = 0b0001_0000;
+pub const ISA_V: u8 = 0b0010_0000;
+// Scalar cryptography, covering Zbkb, Zbkc, Zbkx, Zknh and Zksh.
+pub const ISA_K: u8 = 0b0100_0000;
+// Code size reduction, covering Zicond, Zcb and Zcmp.
+pub const ISA_ZC: u8 = 0b1000_0000;
@@ -0,0 +1,37 @@
+#![no_main]
+use ckb_vm::cost_model::constant_cycles;
+use ckb_vm::differential::Differential;
+use ckb_vm::machine::asm::AsmCoreMachine;
+use ckb_vm::machine::{DefaultCoreMachine, DefaultMachine, DefaultMachineBuilder, VERSION2};
+use ckb_vm::memory::sparse::SparseMemory;
+use ckb_vm::memory::wxorx::WXorXMemory;
+use ckb_vm::{Bytes, ISA_A, ISA_B, ISA_IMC, ISA_MOP};
+use libfuzzer_sys::fuzz_target;
+
+type CoreMachine = DefaultCoreMachine<u64, WXorXMemory<SparseMemory<u64>>>;
+
+const ISA: u8 = ISA_IMC | ISA_A | ISA_B | ISA_MOP;
+
+fn build_int() -> DefaultMachine<CoreMachine> {
+    let machine_memory = WXorXMemory::new(SparseMemory::<u64>::default());
+    let machine_core = DefaultCoreMachine::new_with_memory(ISA, VERSION2, 200_000, machine_memory);
+    DefaultMachineBuilder::new(machine_core)
+        .instruction_cycle_func(Box::new(constant_cycles))
+        .build()
+}
+
+fuzz_target!(|data: &[u8]| {
+    let asm_core = AsmCoreMachine::new(ISA, VERSION2, 200_000);
+    let asm = DefaultMachineBuilder::<Box<AsmCoreMachine>>::new(asm_core)
+        .instruction_cycle_func(Box::new(constant_cycles))
+        .build();
+    let mut differential = Differential::new(build_int(), build_int()).with_asm(asm);
+    differential.set_memory_check_interval(16);
+    let program = Bytes::copy_from_slice(data);
+    if differential.load_program(&program, &[]).is_err() {
+        return;
+    }
+    if let Err(divergence) = differential.run() {
+        panic!("{}", divergence);
+    }
+});
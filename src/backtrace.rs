@@ -0,0 +1,300 @@
+// Guest stack backtraces. When a program stops on an error only the PC is
+// known, the Unwinder rebuilds the call stack from the registers and memory of
+// the stopped machine, and symbolizes each frame with the function name and
+// the source line.
+//
+// Each frame is unwound with the first method that works:
+//
+// 1. The call frame information in .debug_frame or .eh_frame.
+// 2. Analysis of the function prologue, starting from the function symbol,
+//    the standard RISC-V prologue adjusts sp, saves ra and the s registers on
+//    the stack, and optionally sets s0 as the frame pointer.
+// 3. The frame pointer chain, where ra and the previous s0 are saved right
+//    below the address held by s0.
+use crate::{
+    decoder::{build_decoder, Decoder},
+    dwarf::{CallFrameTable, LineTable, RegisterRule},
+    elf::Symbols,
+    instructions::{
+        extract_opcode, instruction_length, insts, is_basic_block_end_instruction, Itype, Rtype,
+        Stype,
+    },
+    machine::CoreMachine,
+    registers::{RA, S0, SP},
+    Error, Memory, Register, RISCV_GENERAL_REGISTER_NUMBER,
+};
+use std::fmt;
+
+/// Frames beyond this depth are dropped, this also stops unwinding corrupted
+/// stacks that loop.
+pub const MAXIMUM_FRAMES: usize = 64;
+
+// Instructions scanned from the start of a function when looking for its
+// prologue.
+const MAXIMUM_PROLOGUE_INSTRUCTIONS: usize = 64;
+
+type Registers = [u64; RISCV_GENERAL_REGISTER_NUMBER];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    /// The PC of the innermost frame, or the return address of the callers.
+    pub pc: u64,
+    pub sp: u64,
+    /// Name of the function and offset of the PC into it.
+    pub function: Option<(String, u64)>,
+    /// Source file and line.
+    pub location: Option<(String, u64)>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Backtrace {
+    pub frames: Vec<Frame>,
+}
+
+impl fmt::Display for Backtrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, frame) in self.frames.iter().enumerate() {
+            write!(f, "#{:<3}0x{:016x}", i, frame.pc)?;
+            match &frame.function {
+                Some((name, 0)) => write!(f, " in {}", name)?,
+                Some((name, offset)) => write!(f, " in {}+0x{:x}", name, offset)?,
+                None => write!(f, " in ??")?,
+            }
+            if let Some((file, line)) = &frame.location {
+                write!(f, " at {}:{}", file, line)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Unwinder {
+    symbols: Symbols,
+    lines: LineTable,
+    frames: CallFrameTable,
+}
+
+impl Unwinder {
+    pub fn new(program: &[u8]) -> Result<Self, Error> {
+        Ok(Self {
+            symbols: Symbols::parse(program)?,
+            lines: LineTable::parse(program)?,
+            frames: CallFrameTable::parse(program)?,
+        })
+    }
+
+    /// Unwinds the stack of a stopped machine. The innermost frame is the
+    /// current PC, unwinding stops at the first frame that cannot be unwound.
+    pub fn backtrace<Mac: CoreMachine>(&self, machine: &mut Mac) -> Backtrace {
+        let mut decoder = build_decoder::<Mac::REG>(machine.isa(), machine.version());
+        let mut registers = [0u64; RISCV_GENERAL_REGISTER_NUMBER];
+        for (i, value) in machine.registers().iter().enumerate() {
+            registers[i] = value.to_u64();
+        }
+        let mut pc = machine.pc().to_u64();
+        let mut frames = vec![];
+        loop {
+            let innermost = frames.is_empty();
+            frames.push(self.symbolize(pc, registers[SP], innermost));
+            if frames.len() >= MAXIMUM_FRAMES {
+                break;
+            }
+            // The return address points past the call, use the call itself to
+            // find the rules of a caller.
+            let address = if innermost { pc } else { pc.wrapping_sub(1) };
+            let caller = self
+                .unwind_cfi(machine, address, &registers)
+                .or_else(|| {
+                    self.unwind_prologue(machine, &mut decoder, address, pc, &registers, innermost)
+                })
+                .or_else(|| unwind_frame_pointer(machine, &registers));
+            let (caller_pc, caller_registers) = match caller {
+                Some(caller) => caller,
+                None => break,
+            };
+            // Only the innermost frame can be a leaf function that keeps the
+            // stack untouched, otherwise the stack must move up.
+            let caller_sp = caller_registers[SP];
+            if caller_pc == 0
+                || caller_sp < registers[SP]
+                || (caller_sp == registers[SP] && !innermost)
+            {
+                break;
+            }
+            pc = caller_pc;
+            registers = caller_registers;
+        }
+        Backtrace { frames }
+    }
+
+    fn symbolize(&self, pc: u64, sp: u64, innermost: bool) -> Frame {
+        let address = if innermost { pc } else { pc.wrapping_sub(1) };
+        Frame {
+            pc,
+            sp,
+            function: self
+                .symbols
+                .lookup(address)
+                .map(|s| (s.name.clone(), pc.wrapping_sub(s.address))),
+            location: self
+                .lines
+                .lookup(address)
+                .map(|(file, line)| (file.to_string(), line)),
+        }
+    }
+
+    fn unwind_cfi<Mac: CoreMachine>(
+        &self,
+        machine: &mut Mac,
+        address: u64,
+        registers: &Registers,
+    ) -> Option<(u64, Registers)> {
+        let row = self.frames.find(address)?;
+        let (cfa_register, cfa_offset) = row.cfa?;
+        let cfa = registers
+            .get(cfa_register as usize)?
+            .wrapping_add(cfa_offset as u64);
+        let recover = |machine: &mut Mac, register: u16| -> Option<u64> {
+            match row.rule(register) {
+                RegisterRule::Undefined => Some(0),
+                RegisterRule::SameValue => registers.get(register as usize).copied(),
+                RegisterRule::Offset(offset) => load(machine, cfa.wrapping_add(offset as u64)),
+                RegisterRule::ValOffset(offset) => Some(cfa.wrapping_add(offset as u64)),
+                RegisterRule::Register(other) => registers.get(other as usize).copied(),
+                RegisterRule::Expression => None,
+            }
+        };
+        let mut caller = *registers;
+        for (i, value) in caller.iter_mut().enumerate().skip(1) {
+            if i != SP {
+                *value = recover(machine, i as u16)?;
+            }
+        }
+        caller[SP] = cfa;
+        let caller_pc = recover(machine, row.return_address)?;
+        Some((caller_pc, caller))
+    }
+
+    fn unwind_prologue<Mac: CoreMachine>(
+        &self,
+        machine: &mut Mac,
+        decoder: &mut Decoder,
+        address: u64,
+        pc: u64,
+        registers: &Registers,
+        innermost: bool,
+    ) -> Option<(u64, Registers)> {
+        let symbol = self.symbols.lookup(address)?;
+        // A function stopped on its ret has already restored the stack.
+        if innermost {
+            if let Ok(i) = decoder.decode_raw(machine.memory_mut(), pc) {
+                if is_return(i) {
+                    return Some((registers[RA], *registers));
+                }
+            }
+        }
+        // Offsets of sp and s0, and the saving slots of registers, relative to
+        // the CFA.
+        let mut sp_offset = Some(0i64);
+        let mut fp_offset = None;
+        let mut saved = [None; RISCV_GENERAL_REGISTER_NUMBER];
+        let mut current = symbol.address;
+        for _ in 0..MAXIMUM_PROLOGUE_INSTRUCTIONS {
+            if current >= pc {
+                break;
+            }
+            let i = match decoder.decode_raw(machine.memory_mut(), current) {
+                Ok(i) if !is_basic_block_end_instruction(i) => i,
+                _ => break,
+            };
+            match extract_opcode(i) {
+                insts::OP_ADDI => {
+                    let i = Itype(i);
+                    if i.rd() == SP && i.rs1() == SP {
+                        sp_offset = sp_offset.map(|o| o + i64::from(i.immediate_s()));
+                    } else if i.rd() == S0 && i.rs1() == SP {
+                        fp_offset = sp_offset.map(|o| o + i64::from(i.immediate_s()));
+                    }
+                }
+                insts::OP_SD | insts::OP_SW => {
+                    let i = Stype(i);
+                    let base = match i.rs1() {
+                        SP => sp_offset,
+                        S0 => fp_offset,
+                        _ => None,
+                    };
+                    if let Some(base) = base {
+                        if saved[i.rs2()].is_none() && is_callee_saved(i.rs2()) {
+                            saved[i.rs2()] = Some(base + i64::from(i.immediate_s()));
+                        }
+                    }
+                }
+                // Frames larger than 2KB adjust sp with a register.
+                insts::OP_ADD | insts::OP_SUB => {
+                    let i = Rtype(i);
+                    if i.rd() == SP {
+                        sp_offset = None;
+                    }
+                }
+                _ => (),
+            }
+            current = current.wrapping_add(u64::from(instruction_length(i)));
+        }
+        let cfa = match (fp_offset, sp_offset) {
+            (Some(offset), _) => registers[S0].wrapping_sub(offset as u64),
+            (None, Some(offset)) => registers[SP].wrapping_sub(offset as u64),
+            (None, None) => return None,
+        };
+        if saved[RA].is_none() && !innermost {
+            return None;
+        }
+        let mut caller = *registers;
+        for (i, offset) in saved.iter().enumerate() {
+            if let Some(offset) = offset {
+                caller[i] = load(machine, cfa.wrapping_add(*offset as u64))?;
+            }
+        }
+        caller[SP] = cfa;
+        Some((caller[RA], caller))
+    }
+}
+
+fn unwind_frame_pointer<Mac: CoreMachine>(
+    machine: &mut Mac,
+    registers: &Registers,
+) -> Option<(u64, Registers)> {
+    let fp = registers[S0];
+    let size = u64::from(Mac::REG::BITS / 8);
+    if fp == 0 || fp < registers[SP] || fp % size != 0 {
+        return None;
+    }
+    let mut caller = *registers;
+    caller[RA] = load(machine, fp.wrapping_sub(size))?;
+    caller[S0] = load(machine, fp.wrapping_sub(size * 2))?;
+    caller[SP] = fp;
+    Some((caller[RA], caller))
+}
+
+fn is_return(i: u64) -> bool {
+    let opcode = extract_opcode(i);
+    (opcode == insts::OP_JALR_VERSION0 || opcode == insts::OP_JALR_VERSION1) && {
+        let i = Itype(i);
+        i.rd() == 0 && i.rs1() == RA && i.immediate_s() == 0
+    }
+}
+
+// ra, s0 - s1 and s2 - s11
+fn is_callee_saved(register: usize) -> bool {
+    register == RA || register == S0 || register == S0 + 1 || (18..=27).contains(&register)
+}
+
+fn load<Mac: CoreMachine>(machine: &mut Mac, address: u64) -> Option<u64> {
+    let address = Mac::REG::from_u64(address);
+    let value = if Mac::REG::BITS == 64 {
+        machine.memory_mut().load64(&address)
+    } else {
+        machine.memory_mut().load32(&address)
+    };
+    value.ok().map(|v| v.to_u64())
+}
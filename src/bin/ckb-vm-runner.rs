@@ -0,0 +1,500 @@
+use ckb_vm::backtrace::Unwinder;
+use ckb_vm::coredump::{signal, write_core_dump};
+use ckb_vm::cost_model::{constant_cycles, estimate_cycles};
+use ckb_vm::disasm::Disassembler;
+use ckb_vm::elf::parse_elf;
+use ckb_vm::machine::stack::StackConfig;
+use ckb_vm::machine::{VERSION0, VERSION1, VERSION2, VERSION3};
+use ckb_vm::record::{decode_snapshot, encode_snapshot};
+use ckb_vm::registers::{A0, A1, A2, A7, REGISTER_ABI_NAMES};
+use ckb_vm::snapshot2::{DataSource, Snapshot2Context};
+use ckb_vm::{
+    Bytes, CoreMachine, DefaultCoreMachine, DefaultMachine, DefaultMachineBuilder, Error,
+    Instruction, Memory, Register, SparseMemory, SupportMachine, SyscallSchema, SyscallTrace,
+    Syscalls, TraceMachine, WXorXMemory, DEFAULT_MEMORY_SIZE, ISA_A, ISA_B, ISA_D, ISA_F, ISA_IMC,
+    ISA_K, ISA_MOP, ISA_V, ISA_ZC, MEMORY_FRAMESIZE, RISCV_PAGESIZE,
+};
+use std::io::{Read, Write};
+use std::process::exit;
+
+const USAGE: &str = "\
+Usage: ckb-vm-runner [OPTIONS] <PROGRAM> [ARGS]...
+       ckb-vm-runner disasm [OPTIONS] <PROGRAM>
+
+Runs a RISC-V program, ARGS are passed to the program as argv. When the
+program fails, the backtrace of the guest is printed along with the error.
+The disasm command prints the instructions of PROGRAM as the VM decodes them,
+in the format of objdump -d, only --isa, --vm-version, --memory-size and
+--fuse apply.
+
+Options:
+      --isa <LIST>           Comma separated ISA among imc, a, b, mop, f, d, v, k and zc, imc is always enabled [default: imc,a,b,mop]
+      --vm-version <N>       VM version, 0, 1, 2 or 3 [default: 2]
+      --max-cycles <N>       Maximum cycles the program can consume [default: unlimited]
+      --memory-size <N>      Memory size in bytes, K and M suffixes are accepted [default: 4M]
+      --stack-size <N>       Stack size in bytes at the top of memory, K and M suffixes are accepted [default: a quarter of memory]
+      --stack-guard <N>      Size of a region below the stack that stops the program with a stack overflow when accessed [default: 0]
+      --env <KEY=VALUE>      Passes an environment variable to the program, can be repeated
+      --auxv                 Pushes envp and the auxiliary vector on the stack as Linux does
+      --engine <ENGINE>      interpreter, trace or asm [default: asm when available, otherwise trace]
+      --cost-model <MODEL>   estimate or constant [default: estimate]
+      --dump-registers       Prints the registers when the program stops
+      --snapshot <FILE>      Writes a snapshot to FILE when the program runs out of cycles
+      --resume <FILE>        Resumes from a snapshot written by --snapshot, PROGRAM must be the same
+      --core <FILE>          Writes an ELF core file of the guest to FILE when the program fails
+      --error-report <FILE>  Writes the context of the failed instruction to FILE as JSON when the program fails
+      --strace <FILE>        Writes a line per syscall made by the program to FILE
+      --fuse                 Shows the macro-op fused instructions in disasm, requires mop
+  -h, --help                 Prints this message
+
+Syscalls:
+  63    read(fd, buf, count), only fd 0(stdin) is supported
+  64    write(fd, buf, count), only fd 1(stdout) and 2(stderr) are supported
+  93    exit(code)
+  2177  debug(str), prints a NUL terminated string to stderr";
+
+const SNAPSHOT_MAGIC: &[u8; 8] = b"CKBVMSNP";
+
+const SYSCALL_READ: u64 = 63;
+const SYSCALL_WRITE: u64 = 64;
+const SYSCALL_DEBUG: u64 = 2177;
+const EBADF: i64 = 9;
+const EIO: i64 = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Engine {
+    Interpreter,
+    Trace,
+    Asm,
+}
+
+struct Options {
+    isa: u8,
+    version: u32,
+    max_cycles: u64,
+    memory_size: usize,
+    stack: StackConfig,
+    engine: Engine,
+    cost_model: fn(Instruction) -> u64,
+    dump_registers: bool,
+    snapshot: Option<String>,
+    resume: Option<String>,
+    core: Option<String>,
+    error_report: Option<String>,
+    strace: Option<String>,
+    disasm: bool,
+    fuse: bool,
+    program: String,
+    args: Vec<Bytes>,
+}
+
+impl Options {
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Self, String> {
+        let mut options = Options {
+            isa: ISA_IMC | ISA_A | ISA_B | ISA_MOP,
+            version: VERSION2,
+            max_cycles: u64::max_value(),
+            memory_size: DEFAULT_MEMORY_SIZE,
+            stack: StackConfig::default(),
+            engine: if cfg!(has_asm) {
+                Engine::Asm
+            } else {
+                Engine::Trace
+            },
+            cost_model: estimate_cycles,
+            dump_registers: false,
+            snapshot: None,
+            resume: None,
+            core: None,
+            error_report: None,
+            strace: None,
+            disasm: false,
+            fuse: false,
+            program: String::new(),
+            args: vec![],
+        };
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or_else(|| format!("{} requires a value", name))
+            };
+            match arg.as_str() {
+                "-h" | "--help" => {
+                    println!("{}", USAGE);
+                    exit(0);
+                }
+                "--isa" => options.isa = parse_isa(&value(&arg)?)?,
+                "--vm-version" => {
+                    options.version = match value(&arg)?.as_str() {
+                        "0" => VERSION0,
+                        "1" => VERSION1,
+                        "2" => VERSION2,
+                        "3" => VERSION3,
+                        v => return Err(format!("invalid VM version {}", v)),
+                    }
+                }
+                "--max-cycles" => options.max_cycles = parse_number(&value(&arg)?)?,
+                "--memory-size" => {
+                    let size = parse_number(&value(&arg)?)? as usize;
+                    if size == 0 || size % RISCV_PAGESIZE != 0 {
+                        return Err(format!(
+                            "memory size must be a non zero multiple of {}",
+                            RISCV_PAGESIZE
+                        ));
+                    }
+                    options.memory_size = size;
+                }
+                "--stack-size" => options.stack.size = Some(parse_number(&value(&arg)?)?),
+                "--stack-guard" => options.stack.guard_size = parse_number(&value(&arg)?)?,
+                "--env" => options.stack.envs.push(value(&arg)?.into()),
+                "--auxv" => options.stack.auxv = true,
+                "--engine" => {
+                    options.engine = match value(&arg)?.as_str() {
+                        "interpreter" => Engine::Interpreter,
+                        "trace" => Engine::Trace,
+                        "asm" if cfg!(has_asm) => Engine::Asm,
+                        "asm" => return Err("asm engine is not available on this build".into()),
+                        e => return Err(format!("invalid engine {}", e)),
+                    }
+                }
+                "--cost-model" => {
+                    options.cost_model = match value(&arg)?.as_str() {
+                        "estimate" => estimate_cycles,
+                        "constant" => constant_cycles,
+                        m => return Err(format!("invalid cost model {}", m)),
+                    }
+                }
+                "--dump-registers" => options.dump_registers = true,
+                "--snapshot" => options.snapshot = Some(value(&arg)?),
+                "--resume" => options.resume = Some(value(&arg)?),
+                "--core" => options.core = Some(value(&arg)?),
+                "--error-report" => options.error_report = Some(value(&arg)?),
+                "--strace" => options.strace = Some(value(&arg)?),
+                "--fuse" => options.fuse = true,
+                "disasm" if !options.disasm && options.program.is_empty() => options.disasm = true,
+                "--" => {
+                    options.program = args.next().ok_or("missing PROGRAM")?;
+                    break;
+                }
+                _ if arg.starts_with('-') => return Err(format!("unknown option {}", arg)),
+                _ => {
+                    options.program = arg;
+                    break;
+                }
+            }
+        }
+        if options.program.is_empty() {
+            return Err("missing PROGRAM".into());
+        }
+        if options.fuse && (!options.disasm || options.isa & ISA_MOP == 0) {
+            return Err("--fuse only applies to disasm with the mop extension".into());
+        }
+        if options.engine == Engine::Asm && options.memory_size % MEMORY_FRAMESIZE != 0 {
+            return Err(format!(
+                "memory size must be a multiple of {} for the asm engine",
+                MEMORY_FRAMESIZE
+            ));
+        }
+        options.args = args.map(Bytes::from).collect();
+        Ok(options)
+    }
+}
+
+fn parse_isa(list: &str) -> Result<u8, String> {
+    let mut isa = 0;
+    for extension in list.split(',') {
+        isa |= match extension.trim().to_lowercase().as_str() {
+            "imc" => ISA_IMC,
+            "a" => ISA_A,
+            "b" => ISA_B,
+            "mop" => ISA_MOP,
+            "f" => ISA_F,
+            "d" => ISA_D,
+            "v" => ISA_V,
+            "k" => ISA_K,
+            "zc" => ISA_ZC,
+            e => return Err(format!("invalid ISA extension {}", e)),
+        };
+    }
+    Ok(isa)
+}
+
+fn parse_number(s: &str) -> Result<u64, String> {
+    let (digits, unit) = match s.as_bytes().last() {
+        Some(b'K') | Some(b'k') => (&s[..s.len() - 1], 1 << 10),
+        Some(b'M') | Some(b'm') => (&s[..s.len() - 1], 1 << 20),
+        _ => (s, 1),
+    };
+    let n = match digits.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => digits.parse::<u64>(),
+    }
+    .map_err(|_| format!("invalid number {}", s))?;
+    n.checked_mul(unit)
+        .ok_or_else(|| format!("number too large {}", s))
+}
+
+// The program file, used as the data source of snapshots so program pages
+// that are never modified do not need to be stored.
+struct ProgramSource(Bytes);
+
+impl DataSource<u64> for ProgramSource {
+    fn load_data(&self, _id: &u64, offset: u64, length: u64) -> Result<Bytes, Error> {
+        match offset.checked_add(length) {
+            Some(end) if end <= self.0.len() as u64 => {
+                Ok(self.0.slice(offset as usize..end as usize))
+            }
+            _ => Err(Error::Unexpected(String::from(
+                "snapshot refers to data out of the program",
+            ))),
+        }
+    }
+}
+
+struct StdioSyscalls;
+
+impl<Mac: SupportMachine> Syscalls<Mac> for StdioSyscalls {
+    fn initialize(&mut self, _machine: &mut Mac) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn ecall(&mut self, machine: &mut Mac) -> Result<bool, Error> {
+        let fd = machine.registers()[A0].to_u64();
+        let buffer = machine.registers()[A1].to_u64();
+        let count = machine.registers()[A2].to_u64();
+        let result = match machine.registers()[A7].to_u64() {
+            SYSCALL_READ => {
+                if fd != 0 {
+                    -EBADF
+                } else {
+                    let mut data = vec![0; count.min(RISCV_PAGESIZE as u64) as usize];
+                    match std::io::stdin().read(&mut data) {
+                        Ok(n) => {
+                            machine.memory_mut().store_bytes(buffer, &data[..n])?;
+                            n as i64
+                        }
+                        Err(_) => -EIO,
+                    }
+                }
+            }
+            SYSCALL_WRITE => {
+                let written = match fd {
+                    1 | 2 => {
+                        let data = machine.memory_mut().load_bytes(buffer, count)?;
+                        if fd == 1 {
+                            std::io::stdout().write_all(&data)
+                        } else {
+                            std::io::stderr().write_all(&data)
+                        }
+                    }
+                    _ => Err(std::io::ErrorKind::NotFound.into()),
+                };
+                match written {
+                    Ok(()) => count as i64,
+                    Err(_) if fd != 1 && fd != 2 => -EBADF,
+                    Err(_) => -EIO,
+                }
+            }
+            SYSCALL_DEBUG => {
+                let mut addr = fd;
+                let mut data = vec![];
+                loop {
+                    let byte = machine
+                        .memory_mut()
+                        .load8(&Mac::REG::from_u64(addr))?
+                        .to_u8();
+                    if byte == 0 {
+                        break;
+                    }
+                    data.push(byte);
+                    addr += 1;
+                }
+                eprintln!("{}", String::from_utf8_lossy(&data));
+                return Ok(true);
+            }
+            _ => return Ok(false),
+        };
+        machine.set_register(A0, Mac::REG::from_i64(result));
+        Ok(true)
+    }
+
+    fn name(&self) -> &str {
+        "stdio"
+    }
+}
+
+type Core = DefaultCoreMachine<u64, WXorXMemory<SparseMemory<u64>>>;
+
+fn build<Inner: SupportMachine>(
+    core: Inner,
+    options: &Options,
+) -> Result<DefaultMachine<Inner>, Box<dyn std::error::Error>> {
+    let mut builder = DefaultMachineBuilder::new(core)
+        .instruction_cycle_func(Box::new(options.cost_model))
+        .syscall(Box::new(StdioSyscalls))
+        .stack(options.stack.clone())
+        .error_report(options.error_report.is_some());
+    if let Some(path) = &options.strace {
+        let schema = SyscallSchema::default()
+            .syscall(SYSCALL_READ, "read", &["fd", "buf", "count"])
+            .syscall(SYSCALL_WRITE, "write", &["fd", "buf", "count"])
+            .syscall(93, "exit", &["code"])
+            .syscall(SYSCALL_DEBUG, "debug", &["str"]);
+        let file = std::fs::File::create(path)?;
+        builder = builder.syscall_trace(SyscallTrace::new(schema).log(Box::new(file)));
+    }
+    Ok(builder.build())
+}
+
+fn build_core(options: &Options) -> Core {
+    Core::new_with_memory(
+        options.isa,
+        options.version,
+        options.max_cycles,
+        WXorXMemory::new(SparseMemory::new_with_memory(options.memory_size)),
+    )
+}
+
+// Loads the program or resumes from a snapshot.
+fn prepare<Inner: SupportMachine>(
+    machine: &mut DefaultMachine<Inner>,
+    program: &Bytes,
+    options: &Options,
+) -> Result<Snapshot2Context<u64, ProgramSource>, Box<dyn std::error::Error>> {
+    let mut context = Snapshot2Context::new(ProgramSource(program.clone()));
+    match &options.resume {
+        Some(path) => {
+            let data = std::fs::read(path)?;
+            let mut data = data
+                .strip_prefix(&SNAPSHOT_MAGIC[..])
+                .ok_or_else(|| format!("{} is not a snapshot", path))?;
+            let snapshot = decode_snapshot(&mut data)?;
+            context.resume(machine, &snapshot)?;
+            machine.set_max_cycles(options.max_cycles);
+        }
+        None => {
+            let metadata = parse_elf::<u64>(program, options.version)?;
+            machine.load_program_with_metadata(program, &metadata, &options.args)?;
+            context.mark_program(machine, &metadata, &0, 0)?;
+        }
+    }
+    Ok(context)
+}
+
+fn finish<Inner: SupportMachine>(
+    machine: &mut DefaultMachine<Inner>,
+    program: &Bytes,
+    context: &Snapshot2Context<u64, ProgramSource>,
+    result: Result<i8, Error>,
+    options: &Options,
+) -> Result<i32, Box<dyn std::error::Error>> {
+    if options.dump_registers {
+        for (i, name) in REGISTER_ABI_NAMES.iter().enumerate() {
+            eprintln!("{:>4} 0x{:016x}", name, machine.registers()[i].to_u64());
+        }
+        eprintln!("{:>4} 0x{:016x}", "pc", machine.pc().to_u64());
+    }
+    eprintln!("cycles: {}", machine.cycles());
+    match result {
+        Ok(code) => {
+            eprintln!("exit: {}", code);
+            Ok(i32::from(code))
+        }
+        Err(Error::CyclesExceeded) if options.snapshot.is_some() => {
+            let path = options.snapshot.as_ref().unwrap();
+            let snapshot = context.make_snapshot(machine)?;
+            let mut data = SNAPSHOT_MAGIC.to_vec();
+            encode_snapshot(&mut data, &snapshot);
+            std::fs::write(path, data)?;
+            eprintln!(
+                "error: {}, snapshot written to {}",
+                Error::CyclesExceeded,
+                path
+            );
+            Ok(-1)
+        }
+        Err(e) => {
+            if let (Some(path), Some(report)) = (&options.error_report, machine.error_report()) {
+                std::fs::write(path, report.to_json())?;
+            }
+            if let Some(path) = &options.core {
+                let mut core = vec![];
+                write_core_dump(machine, signal(&e), &mut core)?;
+                std::fs::write(path, core)?;
+            }
+            // The backtrace is best effort, a program whose symbols cannot be
+            // read still reports the error.
+            if let Ok(unwinder) = Unwinder::new(program) {
+                eprint!("backtrace:\n{}", unwinder.backtrace(machine));
+            }
+            Err(e.into())
+        }
+    }
+}
+
+fn disasm(options: &Options) -> Result<i32, Box<dyn std::error::Error>> {
+    let program: Bytes = std::fs::read(&options.program)?.into();
+    let mut disassembler =
+        Disassembler::new(&program, options.isa, options.version, options.memory_size)?;
+    disassembler.set_fusion(options.fuse)?;
+    let file_name = std::path::Path::new(&options.program)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    disassembler.write_objdump(&file_name, &mut std::io::stdout().lock())?;
+    Ok(0)
+}
+
+fn run(options: &Options) -> Result<i32, Box<dyn std::error::Error>> {
+    if options.disasm {
+        return disasm(options);
+    }
+    let program: Bytes = std::fs::read(&options.program)?.into();
+    match options.engine {
+        Engine::Interpreter => {
+            let mut machine = build(build_core(options), options)?;
+            let context = prepare(&mut machine, &program, options)?;
+            let result = machine.run();
+            finish(&mut machine, &program, &context, result, options)
+        }
+        Engine::Trace => {
+            let mut machine = TraceMachine::new(build(build_core(options), options)?);
+            let context = prepare(&mut machine.machine, &program, options)?;
+            let result = machine.run();
+            finish(&mut machine.machine, &program, &context, result, options)
+        }
+        #[cfg(has_asm)]
+        Engine::Asm => {
+            use ckb_vm::machine::asm::{AsmCoreMachine, AsmMachine};
+            let core = AsmCoreMachine::new_with_memory(
+                options.isa,
+                options.version,
+                options.max_cycles,
+                options.memory_size,
+            );
+            let mut machine = AsmMachine::new(build(core, options)?);
+            let context = prepare(&mut machine.machine, &program, options)?;
+            let result = machine.run();
+            finish(&mut machine.machine, &program, &context, result, options)
+        }
+        #[cfg(not(has_asm))]
+        Engine::Asm => unreachable!(),
+    }
+}
+
+fn main() {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            exit(2);
+        }
+    };
+    match run(&options) {
+        Ok(code) => exit(code),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            exit(-1);
+        }
+    }
+}
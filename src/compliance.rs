@@ -0,0 +1,278 @@
+// Runner for riscv-arch-test / riscv-tests style compliance programs.
+//
+// A compliance program writes its results into the memory between the
+// `begin_signature` and `end_signature` symbols, then halts by storing a
+// non-zero value to `tohost`(1 means pass in riscv-tests), usually followed
+// by an endless loop. An exit syscall halts the program as well. The
+// signature is then compared with a reference signature, which is a text file
+// of hex words, one per line with the most significant digit first.
+//
+// Programs must be linked to fit in the memory of CKB-VM and must not rely on
+// CSRs or privileged instructions, which means using a model of the test
+// framework targeting CKB-VM instead of the ones linking at 0x80000000.
+use crate::{
+    cost_model::constant_cycles,
+    decoder::{build_decoder, InstDecoder},
+    differential::Engine,
+    machine::{trace::TraceMachine, DefaultMachine},
+    Bytes, CoreMachine, DefaultCoreMachine, DefaultMachineBuilder, Error, Memory, Register,
+    SparseMemory, SupportMachine, WXorXMemory,
+};
+use std::fmt::Write;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Termination {
+    /// The value stored to tohost
+    ToHost(u64),
+    /// The exit code of the exit syscall
+    Exit(i8),
+}
+
+impl Termination {
+    pub fn passed(&self) -> bool {
+        matches!(self, Termination::ToHost(1) | Termination::Exit(0))
+    }
+}
+
+/// Result of running a compliance program on one engine.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Outcome {
+    pub termination: Termination,
+    pub cycles: u64,
+    pub signature: Signature,
+}
+
+/// Content of the signature region.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Signature {
+    bytes: Vec<u8>,
+}
+
+impl Signature {
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self { bytes }
+    }
+
+    /// Parses a reference signature. The word size is taken from the width of
+    /// the lines, so 8 hex digits a line are 4 bytes words.
+    pub fn parse_reference(text: &str) -> Result<Self, Error> {
+        let mut bytes = Vec::new();
+        for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            if line.len() % 2 != 0 || !line.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(Error::Unexpected(format!(
+                    "Invalid reference signature line: {}",
+                    line
+                )));
+            }
+            let start = bytes.len();
+            for i in (0..line.len()).step_by(2) {
+                bytes.push(u8::from_str_radix(&line[i..i + 2], 16).unwrap());
+            }
+            bytes[start..].reverse();
+        }
+        Ok(Self { bytes })
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Formats the signature the way a reference signature is written, with
+    /// words of `granularity` bytes.
+    pub fn format(&self, granularity: usize) -> String {
+        let mut text = String::new();
+        for word in self.bytes.chunks(granularity) {
+            for byte in word.iter().rev() {
+                write!(text, "{:02x}", byte).unwrap();
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Index of the first word of `granularity` bytes differing from the
+    /// reference. A reference shorter or longer than the signature differs at
+    /// the end of the shorter one.
+    pub fn mismatch(&self, reference: &Signature, granularity: usize) -> Option<usize> {
+        let a = self.bytes.chunks(granularity);
+        let b = reference.bytes.chunks(granularity);
+        let words = a.len().max(b.len());
+        a.map(Some)
+            .chain(std::iter::repeat(None))
+            .zip(b.map(Some).chain(std::iter::repeat(None)))
+            .take(words)
+            .position(|(a, b)| a != b)
+    }
+}
+
+pub struct ComplianceTest {
+    program: Bytes,
+    begin_signature: u64,
+    end_signature: u64,
+    tohost: Option<u64>,
+}
+
+impl ComplianceTest {
+    /// Finds the signature region and tohost of the program from its symbols.
+    pub fn new(program: Bytes) -> Result<Self, Error> {
+        let elf = goblin_v040::elf::Elf::parse(&program)?;
+        let symbol = |name: &str| {
+            elf.syms
+                .iter()
+                .find(|sym| elf.strtab.get(sym.st_name).and_then(|n| n.ok()) == Some(name))
+                .map(|sym| sym.st_value)
+        };
+        let missing = |name: &str| Error::Unexpected(format!("Missing symbol {}", name));
+        let begin_signature =
+            symbol("begin_signature").ok_or_else(|| missing("begin_signature"))?;
+        let end_signature = symbol("end_signature").ok_or_else(|| missing("end_signature"))?;
+        if end_signature < begin_signature {
+            return Err(Error::Unexpected(String::from(
+                "end_signature is before begin_signature",
+            )));
+        }
+        let tohost = symbol("tohost");
+        Ok(Self {
+            program,
+            begin_signature,
+            end_signature,
+            tohost,
+        })
+    }
+
+    pub fn signature_range(&self) -> std::ops::Range<u64> {
+        self.begin_signature..self.end_signature
+    }
+
+    pub fn run(
+        &self,
+        engine: Engine,
+        isa: u8,
+        version: u32,
+        max_cycles: u64,
+    ) -> Result<Outcome, Error> {
+        match engine {
+            Engine::Interpreter => self.run_interpreter(isa, version, max_cycles),
+            Engine::Trace => self.run_trace(isa, version, max_cycles),
+            #[cfg(has_asm)]
+            Engine::Asm => self.run_asm(isa, version, max_cycles),
+            #[cfg(not(has_asm))]
+            Engine::Asm => Err(Error::Unexpected(String::from(
+                "Asm engine is not available",
+            ))),
+        }
+    }
+
+    /// Runs the program on every available engine.
+    pub fn run_all(
+        &self,
+        isa: u8,
+        version: u32,
+        max_cycles: u64,
+    ) -> Vec<(Engine, Result<Outcome, Error>)> {
+        let mut engines = vec![Engine::Interpreter, Engine::Trace];
+        if cfg!(has_asm) {
+            engines.push(Engine::Asm);
+        }
+        engines
+            .into_iter()
+            .map(|engine| (engine, self.run(engine, isa, version, max_cycles)))
+            .collect()
+    }
+
+    fn build_machine(
+        isa: u8,
+        version: u32,
+        max_cycles: u64,
+    ) -> DefaultMachine<DefaultCoreMachine<u64, WXorXMemory<SparseMemory<u64>>>> {
+        let core_machine = DefaultCoreMachine::<u64, WXorXMemory<SparseMemory<u64>>>::new(
+            isa, version, max_cycles,
+        );
+        DefaultMachineBuilder::new(core_machine)
+            .instruction_cycle_func(Box::new(constant_cycles))
+            .build()
+    }
+
+    fn run_interpreter(&self, isa: u8, version: u32, max_cycles: u64) -> Result<Outcome, Error> {
+        let mut machine = Self::build_machine(isa, version, max_cycles);
+        machine.load_program(&self.program, &[])?;
+        let mut decoder = build_decoder::<u64>(isa, version);
+        machine.set_running(true);
+        loop {
+            if let Some(termination) = self.halted(&mut machine)? {
+                return self.outcome(&mut machine, termination);
+            }
+            if machine.reset_signal() {
+                decoder.reset_instructions_cache()?;
+            }
+            machine.step(&mut decoder)?;
+        }
+    }
+
+    fn run_trace(&self, isa: u8, version: u32, max_cycles: u64) -> Result<Outcome, Error> {
+        let mut machine = TraceMachine::new(Self::build_machine(isa, version, max_cycles));
+        machine.load_program(&self.program, &[])?;
+        let mut decoder = build_decoder::<u64>(isa, version);
+        machine.machine.set_running(true);
+        loop {
+            if let Some(termination) = self.halted(&mut machine.machine)? {
+                return self.outcome(&mut machine.machine, termination);
+            }
+            machine.step_trace(&mut decoder)?;
+        }
+    }
+
+    #[cfg(has_asm)]
+    fn run_asm(&self, isa: u8, version: u32, max_cycles: u64) -> Result<Outcome, Error> {
+        use crate::machine::asm::{AsmCoreMachine, AsmMachine};
+
+        let core = DefaultMachineBuilder::new(AsmCoreMachine::new(isa, version, max_cycles))
+            .instruction_cycle_func(Box::new(constant_cycles))
+            .build();
+        let mut machine = AsmMachine::new(core);
+        machine.load_program(&self.program, &[])?;
+        let mut decoder = build_decoder::<u64>(isa, version);
+        machine.machine.set_running(true);
+        loop {
+            if let Some(termination) = self.halted(&mut machine.machine)? {
+                return self.outcome(&mut machine.machine, termination);
+            }
+            machine.step_trace(&mut decoder)?;
+        }
+    }
+
+    fn halted<M: SupportMachine>(
+        &self,
+        machine: &mut DefaultMachine<M>,
+    ) -> Result<Option<Termination>, Error> {
+        if !machine.running() {
+            return Ok(Some(Termination::Exit(machine.exit_code())));
+        }
+        if let Some(tohost) = self.tohost {
+            let value = machine
+                .memory_mut()
+                .load64(&M::REG::from_u64(tohost))?
+                .to_u64();
+            if value != 0 {
+                return Ok(Some(Termination::ToHost(value)));
+            }
+        }
+        Ok(None)
+    }
+
+    fn outcome<M: SupportMachine>(
+        &self,
+        machine: &mut DefaultMachine<M>,
+        termination: Termination,
+    ) -> Result<Outcome, Error> {
+        let signature = machine.memory_mut().load_bytes(
+            self.begin_signature,
+            self.end_signature - self.begin_signature,
+        )?;
+        Ok(Outcome {
+            termination,
+            cycles: machine.cycles(),
+            signature: Signature::new(signature.to_vec()),
+        })
+    }
+}
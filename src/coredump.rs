@@ -0,0 +1,293 @@
+// ELF core files of the guest, a post-mortem artifact for a program that
+// stopped on an error. The layout follows the cores written by Linux on
+// RISC-V, so debuggers load them together with the program:
+//
+//   riscv64-unknown-elf-gdb program core
+//
+// The core starts with a PT_NOTE segment holding an NT_PRSTATUS note with the
+// general purpose registers and the PC, and an NT_PRFPREG note with the
+// floating point registers when F or D is enabled. Each run of non-zero pages
+// sharing the same flags becomes a PT_LOAD segment, executable pages are
+// mapped R+X, frozen pages R and the others R+W.
+use crate::{
+    machine::CoreMachine,
+    memory::{Memory, FLAG_EXECUTABLE, FLAG_FREEZED},
+    Error, Register, ISA_D, ISA_F, RISCV_PAGESIZE,
+};
+use std::io::Write;
+
+const ET_CORE: u16 = 4;
+const EM_RISCV: u16 = 243;
+const PT_LOAD: u32 = 1;
+const PT_NOTE: u32 = 4;
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+const NT_PRSTATUS: u32 = 1;
+const NT_PRFPREG: u32 = 2;
+
+pub const SIGILL: u32 = 4;
+pub const SIGTRAP: u32 = 5;
+pub const SIGABRT: u32 = 6;
+pub const SIGSEGV: u32 = 11;
+pub const SIGXCPU: u32 = 24;
+
+/// The signal a Linux process would have received for the error, as shown by
+/// debuggers loading the core.
+pub fn signal(error: &Error) -> u32 {
+    match error {
+        Error::MemOutOfBound(..)
+        | Error::MemOutOfStack
+        | Error::MemPageUnalignedAccess(_)
+        | Error::MemWriteOnExecutablePage(_)
+        | Error::MemWriteOnFreezedPage(_)
+        | Error::StackOverflow { .. }
+        | Error::Watchpoint { .. } => SIGSEGV,
+        Error::InvalidInstruction { .. } | Error::InvalidOp(_) => SIGILL,
+        Error::Breakpoint(_) => SIGTRAP,
+        Error::CyclesExceeded => SIGXCPU,
+        _ => SIGABRT,
+    }
+}
+
+// A PT_LOAD segment, made of consecutive pages.
+struct Segment {
+    address: u64,
+    flags: u32,
+    data: Vec<u8>,
+}
+
+// Writes little endian words of the machine width, 32 bit machines produce
+// ELFCLASS32 cores.
+struct Writer {
+    buffer: Vec<u8>,
+    bits: u8,
+}
+
+impl Writer {
+    fn u16(&mut self, value: u16) {
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn word(&mut self, value: u64) {
+        if self.bits == 64 {
+            self.u64(value);
+        } else {
+            self.u32(value as u32);
+        }
+    }
+
+    fn pad(&mut self, alignment: usize) {
+        let length = (self.buffer.len() + alignment - 1) / alignment * alignment;
+        self.buffer.resize(length, 0);
+    }
+
+    fn note(&mut self, kind: u32, desc: &[u8]) {
+        self.u32(5);
+        self.u32(desc.len() as u32);
+        self.u32(kind);
+        self.buffer.extend_from_slice(b"CORE\0");
+        self.pad(4);
+        self.buffer.extend_from_slice(desc);
+        self.pad(4);
+    }
+}
+
+/// Writes a core file of the machine, signal is reported as the reason the
+/// program stopped, see signal(). The PC is the current PC of the machine.
+pub fn write_core_dump<Mac: CoreMachine, W: Write>(
+    machine: &mut Mac,
+    signal: u32,
+    w: &mut W,
+) -> Result<(), Error> {
+    let bits = Mac::REG::BITS;
+    let notes = {
+        let mut notes = Writer {
+            buffer: vec![],
+            bits,
+        };
+        notes.note(NT_PRSTATUS, &prstatus(machine, signal));
+        if machine.isa() & (ISA_F | ISA_D) != 0 {
+            notes.note(NT_PRFPREG, &prfpreg(machine));
+        }
+        notes.buffer
+    };
+    let segments = segments(machine)?;
+
+    let (header_size, program_header_size) = if bits == 64 { (64, 56) } else { (52, 32) };
+    let mut core = Writer {
+        buffer: vec![],
+        bits,
+    };
+    // ELF header
+    core.buffer.extend_from_slice(b"\x7fELF");
+    core.buffer.push(if bits == 64 { 2 } else { 1 });
+    core.buffer.push(1);
+    core.buffer.push(1);
+    core.buffer.resize(16, 0);
+    core.u16(ET_CORE);
+    core.u16(EM_RISCV);
+    core.u32(1);
+    core.word(0);
+    core.word(header_size);
+    core.word(0);
+    core.u32(0);
+    core.u16(header_size as u16);
+    core.u16(program_header_size);
+    core.u16(segments.len() as u16 + 1);
+    core.u16(0);
+    core.u16(0);
+    core.u16(0);
+
+    // Program headers, segment data is page aligned after the notes.
+    let notes_offset = header_size + u64::from(program_header_size) * (segments.len() as u64 + 1);
+    let page_size = RISCV_PAGESIZE as u64;
+    let data_offset = (notes_offset + notes.len() as u64 + page_size - 1) / page_size * page_size;
+    program_header(
+        &mut core,
+        PT_NOTE,
+        0,
+        notes_offset,
+        0,
+        notes.len() as u64,
+        4,
+    );
+    for (i, segment) in segments.iter().enumerate() {
+        let offset = data_offset
+            + segments[..i]
+                .iter()
+                .map(|s| s.data.len() as u64)
+                .sum::<u64>();
+        program_header(
+            &mut core,
+            PT_LOAD,
+            segment.flags,
+            offset,
+            segment.address,
+            segment.data.len() as u64,
+            page_size,
+        );
+    }
+    core.buffer.extend_from_slice(&notes);
+    core.pad(RISCV_PAGESIZE);
+    for segment in &segments {
+        core.buffer.extend_from_slice(&segment.data);
+    }
+    w.write_all(&core.buffer)?;
+    Ok(())
+}
+
+fn program_header(
+    w: &mut Writer,
+    kind: u32,
+    flags: u32,
+    offset: u64,
+    address: u64,
+    size: u64,
+    alignment: u64,
+) {
+    w.u32(kind);
+    // p_flags comes right after p_type in ELFCLASS64 only.
+    if w.bits == 64 {
+        w.u32(flags);
+    }
+    w.word(offset);
+    w.word(address);
+    w.word(address);
+    w.word(size);
+    w.word(size);
+    if w.bits != 64 {
+        w.u32(flags);
+    }
+    w.word(alignment);
+}
+
+// struct elf_prstatus, pr_reg holds the PC followed by x1 - x31.
+fn prstatus<Mac: CoreMachine>(machine: &Mac, signal: u32) -> Vec<u8> {
+    let mut w = Writer {
+        buffer: vec![],
+        bits: Mac::REG::BITS,
+    };
+    // si_signo, si_code, si_errno
+    w.u32(signal);
+    w.u32(0);
+    w.u32(0);
+    // pr_cursig
+    w.u16(signal as u16);
+    w.pad(w.bits as usize / 8);
+    // pr_sigpend, pr_sighold
+    w.word(0);
+    w.word(0);
+    // pr_pid, pr_ppid, pr_pgrp, pr_sid
+    w.u32(1);
+    w.u32(0);
+    w.u32(1);
+    w.u32(1);
+    // pr_utime, pr_stime, pr_cutime, pr_cstime
+    for _ in 0..8 {
+        w.word(0);
+    }
+    w.word(machine.pc().to_u64());
+    for register in &machine.registers()[1..] {
+        w.word(register.to_u64());
+    }
+    // pr_fpvalid
+    w.u32(u32::from(machine.isa() & (ISA_F | ISA_D) != 0));
+    w.pad(w.bits as usize / 8);
+    w.buffer
+}
+
+// struct __riscv_d_ext_state
+fn prfpreg<Mac: CoreMachine>(machine: &Mac) -> Vec<u8> {
+    let mut w = Writer {
+        buffer: vec![],
+        bits: 64,
+    };
+    for register in machine.fregisters() {
+        w.u64(*register);
+    }
+    w.u32(machine.fcsr());
+    w.pad(8);
+    w.buffer
+}
+
+fn segments<Mac: CoreMachine>(machine: &mut Mac) -> Result<Vec<Segment>, Error> {
+    let mut segments: Vec<Segment> = vec![];
+    let page_size = RISCV_PAGESIZE as u64;
+    for page in 0..machine.memory().memory_pages() as u64 {
+        let address = page * page_size;
+        let data = machine.memory_mut().load_bytes(address, page_size)?;
+        if data.iter().all(|b| *b == 0) {
+            continue;
+        }
+        let flag = machine.memory_mut().fetch_flag(page)?;
+        let flags = if flag & FLAG_EXECUTABLE != 0 {
+            PF_R | PF_X
+        } else if flag & FLAG_FREEZED != 0 {
+            PF_R
+        } else {
+            PF_R | PF_W
+        };
+        match segments.last_mut() {
+            Some(last)
+                if last.flags == flags && last.address + last.data.len() as u64 == address =>
+            {
+                last.data.extend_from_slice(&data);
+            }
+            _ => segments.push(Segment {
+                address,
+                flags,
+                data: data.to_vec(),
+            }),
+        }
+    }
+    Ok(segments)
+}
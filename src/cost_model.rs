@@ -1,6 +1,6 @@
 use crate::{
-    instructions::{extract_opcode, insts},
-    Instruction,
+    instructions::{extract_opcode, insts, rvc, vector::VType, Itype},
+    Instruction, RISCV_VLEN,
 };
 
 // Returns the spent cycles to execute the secific instruction.
@@ -88,6 +88,91 @@ pub fn estimate_cycles(i: Instruction) -> u64 {
         insts::OP_WIDE_DIVU => 32,
         insts::OP_FAR_JUMP_REL => 3,
         insts::OP_FAR_JUMP_ABS => 3,
+        // F and D
+        insts::OP_FLW => 3,
+        insts::OP_FSW => 3,
+        insts::OP_FLD => 2,
+        insts::OP_FSD => 2,
+        insts::OP_FMADD_S | insts::OP_FMSUB_S | insts::OP_FNMSUB_S | insts::OP_FNMADD_S => 5,
+        insts::OP_FMADD_D | insts::OP_FMSUB_D | insts::OP_FNMSUB_D | insts::OP_FNMADD_D => 5,
+        insts::OP_FADD_S | insts::OP_FSUB_S | insts::OP_FMUL_S => 5,
+        insts::OP_FADD_D | insts::OP_FSUB_D | insts::OP_FMUL_D => 5,
+        insts::OP_FDIV_S | insts::OP_FSQRT_S | insts::OP_FDIV_D | insts::OP_FSQRT_D => 32,
+        insts::OP_FCVT_W_S | insts::OP_FCVT_WU_S | insts::OP_FCVT_L_S | insts::OP_FCVT_LU_S => 3,
+        insts::OP_FCVT_S_W | insts::OP_FCVT_S_WU | insts::OP_FCVT_S_L | insts::OP_FCVT_S_LU => 3,
+        insts::OP_FCVT_W_D | insts::OP_FCVT_WU_D | insts::OP_FCVT_L_D | insts::OP_FCVT_LU_D => 3,
+        insts::OP_FCVT_D_W | insts::OP_FCVT_D_WU | insts::OP_FCVT_D_L | insts::OP_FCVT_D_LU => 3,
+        insts::OP_FCVT_S_D | insts::OP_FCVT_D_S => 3,
+        // K, each hash function replaces a handful of rotations, shifts and
+        // xors.
+        insts::OP_SHA256SIG0 | insts::OP_SHA256SIG1 => 2,
+        insts::OP_SHA256SUM0 | insts::OP_SHA256SUM1 => 2,
+        insts::OP_SHA512SIG0 | insts::OP_SHA512SIG1 => 2,
+        insts::OP_SHA512SUM0 | insts::OP_SHA512SUM1 => 2,
+        insts::OP_SM3P0 | insts::OP_SM3P1 => 2,
+        insts::OP_XPERM4 | insts::OP_XPERM8 => 2,
+        insts::OP_PACK | insts::OP_PACKH | insts::OP_PACKW | insts::OP_BREV8 => 1,
+        // Zcmp, push and pop cost as much as the loads and stores they
+        // replace, popret adds the jump.
+        insts::OP_CM_PUSH | insts::OP_CM_POP => 1 + 2 * zcmp_registers(i),
+        insts::OP_CM_POPRET | insts::OP_CM_POPRETZ => 4 + 2 * zcmp_registers(i),
+        // V, the cost of one vector register, see vector_registers for how
+        // many times it is charged.
+        insts::OP_VLE8_V | insts::OP_VLE16_V | insts::OP_VLE32_V | insts::OP_VLE64_V => 4,
+        insts::OP_VSE8_V | insts::OP_VSE16_V | insts::OP_VSE32_V | insts::OP_VSE64_V => 4,
+        insts::OP_VLSE8_V | insts::OP_VLSE16_V | insts::OP_VLSE32_V | insts::OP_VLSE64_V => 8,
+        insts::OP_VSSE8_V | insts::OP_VSSE16_V | insts::OP_VSSE32_V | insts::OP_VSSE64_V => 8,
+        insts::OP_VLM_V | insts::OP_VSM_V | insts::OP_VLR_V | insts::OP_VSR_V => 4,
+        insts::OP_VMUL_VV | insts::OP_VMUL_VX | insts::OP_VMULH_VV | insts::OP_VMULH_VX => 5,
+        insts::OP_VMULHU_VV | insts::OP_VMULHU_VX => 5,
+        insts::OP_VMULHSU_VV | insts::OP_VMULHSU_VX => 5,
+        insts::OP_VWMULU_VV | insts::OP_VWMULU_VX | insts::OP_VWMUL_VV | insts::OP_VWMUL_VX => 5,
+        insts::OP_VWMULSU_VV | insts::OP_VWMULSU_VX => 5,
+        insts::OP_VMACC_VV | insts::OP_VMACC_VX | insts::OP_VNMSAC_VV | insts::OP_VNMSAC_VX => 5,
+        insts::OP_VMADD_VV | insts::OP_VMADD_VX | insts::OP_VNMSUB_VV | insts::OP_VNMSUB_VX => 5,
+        insts::OP_VWMACCU_VV | insts::OP_VWMACCU_VX => 5,
+        insts::OP_VWMACC_VV | insts::OP_VWMACC_VX => 5,
+        insts::OP_VDIVU_VV | insts::OP_VDIVU_VX | insts::OP_VDIV_VV | insts::OP_VDIV_VX => 32,
+        insts::OP_VREMU_VV | insts::OP_VREMU_VX | insts::OP_VREM_VV | insts::OP_VREM_VX => 32,
+        insts::OP_VREDSUM_VS | insts::OP_VREDAND_VS => 4,
+        insts::OP_VREDOR_VS | insts::OP_VREDXOR_VS => 4,
+        insts::OP_VREDMINU_VS | insts::OP_VREDMIN_VS => 4,
+        insts::OP_VREDMAXU_VS | insts::OP_VREDMAX_VS => 4,
+        insts::OP_VSLIDEUP_VX | insts::OP_VSLIDEUP_VI | insts::OP_VSLIDEDOWN_VX => 3,
+        insts::OP_VSLIDEDOWN_VI | insts::OP_VSLIDE1UP_VX | insts::OP_VSLIDE1DOWN_VX => 3,
+        insts::OP_VMV_X_S | insts::OP_VMV_S_X | insts::OP_VCPOP_M | insts::OP_VFIRST_M => 2,
+        insts::OP_VSETVLI | insts::OP_VSETIVLI | insts::OP_VSETVL => 1,
+        // The remaining arithmetic, mask and permutation instructions, all V
+        // opcodes share the low byte.
+        op if op & 0xff == insts::OP_VSETVLI & 0xff => 2,
         _ => 1,
     }
 }
+
+fn zcmp_registers(i: Instruction) -> u64 {
+    rvc::zcmp_registers(Itype(i).rs1()).len() as u64
+}
+
+// Returns how many times the cycles of an instruction are charged, vector
+// instructions are charged once for every register holding their vl
+// elements, so their cost grows with both vl and LMUL. The configuration
+// instructions and the ones working on a single register or element are
+// charged once, so are all the other instructions.
+pub fn vector_registers(i: Instruction, vl: u64, vtype: u64) -> u64 {
+    let op = extract_opcode(i);
+    if op & 0xff != insts::OP_VSETVLI & 0xff {
+        return 1;
+    }
+    let eew = match op {
+        insts::OP_VSETVLI | insts::OP_VSETIVLI | insts::OP_VSETVL => return 1,
+        insts::OP_VLM_V | insts::OP_VSM_V | insts::OP_VLR_V | insts::OP_VSR_V => return 1,
+        insts::OP_VMV_X_S | insts::OP_VMV_S_X | insts::OP_VCPOP_M | insts::OP_VFIRST_M => return 1,
+        insts::OP_VLE8_V | insts::OP_VSE8_V | insts::OP_VLSE8_V | insts::OP_VSSE8_V => 8,
+        insts::OP_VLE16_V | insts::OP_VSE16_V | insts::OP_VLSE16_V | insts::OP_VSSE16_V => 16,
+        insts::OP_VLE32_V | insts::OP_VSE32_V | insts::OP_VLSE32_V | insts::OP_VSSE32_V => 32,
+        insts::OP_VLE64_V | insts::OP_VSE64_V | insts::OP_VLSE64_V | insts::OP_VSSE64_V => 64,
+        _ => VType(vtype).sew() as u64,
+    };
+    // vl never exceeds VLMAX, the elements fit in 8 registers.
+    std::cmp::max(1, (vl * eew + RISCV_VLEN as u64 - 1) / RISCV_VLEN as u64)
+}
@@ -0,0 +1,278 @@
+// Instruction level code coverage. CoverageDecoder wraps an instruction
+// decoder and records every decoded PC, since the interpreter decodes each
+// instruction right before executing it, this gives the exact hit count of
+// every instruction. Trace based machines(TraceMachine and AsmMachine) decode a
+// basic block once and run it many times, wrapping the decoder used to build
+// traces still tells which instructions were run, but the counts are those of
+// trace preparation.
+//
+// The collected Coverage maps back to source lines through the DWARF line
+// table of the program, and can be exported in lcov or Cobertura format.
+use crate::{decoder::InstDecoder, dwarf::LineTable, elf::Symbols, Error, Instruction, Memory};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// Hit count of each executed instruction, keyed by PC.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Coverage {
+    hits: BTreeMap<u64, u64>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, pc: u64) {
+        let hits = self.hits.entry(pc).or_insert(0);
+        *hits = hits.saturating_add(1);
+    }
+
+    /// Adds the hit counts of another coverage, so results of different runs
+    /// can be combined.
+    pub fn merge(&mut self, other: &Coverage) {
+        for (pc, count) in &other.hits {
+            let hits = self.hits.entry(*pc).or_insert(0);
+            *hits = hits.saturating_add(*count);
+        }
+    }
+
+    pub fn hits(&self, pc: u64) -> u64 {
+        self.hits.get(&pc).copied().unwrap_or(0)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.hits.iter().map(|(pc, count)| (*pc, *count))
+    }
+
+    pub fn len(&self) -> usize {
+        self.hits.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hits.is_empty()
+    }
+}
+
+pub struct CoverageDecoder<D: InstDecoder> {
+    decoder: D,
+    coverage: Coverage,
+}
+
+impl<D: InstDecoder> CoverageDecoder<D> {
+    pub fn new(decoder: D) -> Self {
+        Self {
+            decoder,
+            coverage: Coverage::default(),
+        }
+    }
+
+    pub fn coverage(&self) -> &Coverage {
+        &self.coverage
+    }
+
+    pub fn coverage_mut(&mut self) -> &mut Coverage {
+        &mut self.coverage
+    }
+
+    /// Takes the coverage collected so far, leaving an empty one behind.
+    pub fn take_coverage(&mut self) -> Coverage {
+        std::mem::take(&mut self.coverage)
+    }
+
+    pub fn into_inner(self) -> (D, Coverage) {
+        (self.decoder, self.coverage)
+    }
+}
+
+impl<D: InstDecoder> InstDecoder for CoverageDecoder<D> {
+    fn decode<M: Memory>(&mut self, memory: &mut M, pc: u64) -> Result<Instruction, Error> {
+        let instruction = self.decoder.decode(memory, pc)?;
+        self.coverage.record(pc);
+        Ok(instruction)
+    }
+
+    fn reset_instructions_cache(&mut self) -> Result<(), Error> {
+        self.decoder.reset_instructions_cache()
+    }
+}
+
+// Line -> hit count
+type FileCoverage = BTreeMap<u64, u64>;
+
+/// Maps instruction coverage to source lines of a program.
+pub struct CoverageReport {
+    lines: LineTable,
+    symbols: Symbols,
+}
+
+impl CoverageReport {
+    pub fn new(program: &[u8]) -> Result<Self, Error> {
+        Ok(Self::new_with_debug_info(
+            LineTable::parse(program)?,
+            Symbols::parse(program)?,
+        ))
+    }
+
+    pub fn new_with_debug_info(lines: LineTable, symbols: Symbols) -> Self {
+        Self { lines, symbols }
+    }
+
+    pub fn lines(&self) -> &LineTable {
+        &self.lines
+    }
+
+    /// Hit count of every line with code, grouped by source file. A line
+    /// is counted as many times as its most executed instruction. Lines
+    /// whose code never runs are reported with a count of 0.
+    pub fn line_hits(&self, coverage: &Coverage) -> BTreeMap<String, FileCoverage> {
+        let mut result: BTreeMap<String, FileCoverage> = BTreeMap::new();
+        for range in self.lines.ranges() {
+            let hits = coverage
+                .hits
+                .range(range.start..range.end)
+                .map(|(_, count)| *count)
+                .max()
+                .unwrap_or(0);
+            let count = result
+                .entry(self.lines.files()[range.file].clone())
+                .or_default()
+                .entry(range.line)
+                .or_insert(0);
+            *count = (*count).max(hits);
+        }
+        result
+    }
+
+    /// Writes an lcov tracefile. Functions are reported from the symbol
+    /// table, a function is hit as many times as its entry instruction.
+    pub fn write_lcov<W: Write>(
+        &self,
+        coverage: &Coverage,
+        test_name: &str,
+        w: &mut W,
+    ) -> Result<(), Error> {
+        let mut functions: BTreeMap<&str, Vec<(u64, &str, u64)>> = BTreeMap::new();
+        for symbol in self.symbols.iter() {
+            if let Some((file, line)) = self.lines.lookup(symbol.address) {
+                functions.entry(file).or_default().push((
+                    line,
+                    &symbol.name,
+                    coverage.hits(symbol.address),
+                ));
+            }
+        }
+        for (file, lines) in self.line_hits(coverage) {
+            writeln!(w, "TN:{}", test_name)?;
+            writeln!(w, "SF:{}", file)?;
+            let functions = functions.remove(file.as_str()).unwrap_or_default();
+            for (line, name, _) in &functions {
+                writeln!(w, "FN:{},{}", line, name)?;
+            }
+            for (_, name, hits) in &functions {
+                writeln!(w, "FNDA:{},{}", hits, name)?;
+            }
+            writeln!(w, "FNF:{}", functions.len())?;
+            writeln!(
+                w,
+                "FNH:{}",
+                functions.iter().filter(|(_, _, hits)| *hits > 0).count()
+            )?;
+            for (line, hits) in &lines {
+                writeln!(w, "DA:{},{}", line, hits)?;
+            }
+            writeln!(w, "LF:{}", lines.len())?;
+            writeln!(w, "LH:{}", lines.values().filter(|hits| **hits > 0).count())?;
+            writeln!(w, "end_of_record")?;
+        }
+        Ok(())
+    }
+
+    /// Writes a Cobertura XML report, each source file is a class. Branch
+    /// coverage is not collected.
+    pub fn write_cobertura<W: Write>(&self, coverage: &Coverage, w: &mut W) -> Result<(), Error> {
+        let files = self.line_hits(coverage);
+        let (valid, covered) = files.values().fold((0, 0), |(valid, covered), lines| {
+            let (v, c) = line_counts(lines);
+            (valid + v, covered + c)
+        });
+        writeln!(w, r#"<?xml version="1.0" ?>"#)?;
+        writeln!(
+            w,
+            r#"<!DOCTYPE coverage SYSTEM "http://cobertura.sourceforge.net/xml/coverage-04.dtd">"#
+        )?;
+        writeln!(
+            w,
+            r#"<coverage line-rate="{}" branch-rate="0" lines-covered="{}" lines-valid="{}" branches-covered="0" branches-valid="0" complexity="0" version="ckb-vm" timestamp="0">"#,
+            line_rate(valid, covered),
+            covered,
+            valid
+        )?;
+        writeln!(w, "  <sources/>")?;
+        writeln!(w, "  <packages>")?;
+        writeln!(
+            w,
+            r#"    <package name="" line-rate="{}" branch-rate="0" complexity="0">"#,
+            line_rate(valid, covered)
+        )?;
+        writeln!(w, "      <classes>")?;
+        for (file, lines) in &files {
+            let (valid, covered) = line_counts(lines);
+            let file = escape_xml(file);
+            writeln!(
+                w,
+                r#"        <class name="{}" filename="{}" line-rate="{}" branch-rate="0" complexity="0">"#,
+                file,
+                file,
+                line_rate(valid, covered)
+            )?;
+            writeln!(w, "          <methods/>")?;
+            writeln!(w, "          <lines>")?;
+            for (line, hits) in lines {
+                writeln!(
+                    w,
+                    r#"            <line number="{}" hits="{}" branch="false"/>"#,
+                    line, hits
+                )?;
+            }
+            writeln!(w, "          </lines>")?;
+            writeln!(w, "        </class>")?;
+        }
+        writeln!(w, "      </classes>")?;
+        writeln!(w, "    </package>")?;
+        writeln!(w, "  </packages>")?;
+        writeln!(w, "</coverage>")?;
+        Ok(())
+    }
+}
+
+// Returns (valid lines, covered lines)
+fn line_counts(lines: &FileCoverage) -> (usize, usize) {
+    (
+        lines.len(),
+        lines.values().filter(|hits| **hits > 0).count(),
+    )
+}
+
+fn line_rate(valid: usize, covered: usize) -> String {
+    if valid == 0 {
+        return "1".to_string();
+    }
+    format!("{:.4}", covered as f64 / valid as f64)
+}
+
+fn escape_xml(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
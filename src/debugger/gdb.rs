@@ -0,0 +1,673 @@
+// A GDB remote serial protocol(RSP) stub for CKB-VM. It drives a
+// DefaultMachine one instruction at a time, so riscv64-unknown-elf-gdb can
+// attach to a running contract via `target remote`.
+//
+// Breakpoints(Z0 / Z1) and watchpoints(Z2 - Z4) are kept by the stub, the
+// latter are checked against the memory access each instruction is about to
+// make, so any memory can be debugged without WatchpointMemory.
+//
+// A TraceMachine is debugged through the DefaultMachine in its `machine`
+// field, traces are not used while GDB is attached, nor after it detaches as
+// the stub runs the program to the end on that DefaultMachine. AsmMachine is
+// not supported.
+//
+// Only the subset of the protocol required by a single threaded, bare metal
+// target is implemented here, see the following link for the full protocol:
+// https://sourceware.org/gdb/onlinedocs/gdb/Remote-Protocol.html
+use super::Debugger;
+use crate::{
+    decoder::InstDecoder,
+    error::WatchpointKind,
+    machine::{DefaultMachine, VERSION0},
+    memory::watchpoint::{instruction_memory_access, Watchpoints},
+    CoreMachine, Error, Memory, Register, SupportMachine, ISA_MOP, RISCV_GENERAL_REGISTER_NUMBER,
+};
+use ckb_vm_definitions::registers::REGISTER_ABI_NAMES;
+use std::collections::{HashSet, VecDeque};
+use std::io::{ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+// Signal numbers used in stop replies, GDB always uses its own numbering
+// which matches Linux for the signals below.
+pub const SIGINT: u8 = 2;
+pub const SIGILL: u8 = 4;
+pub const SIGTRAP: u8 = 5;
+pub const SIGABRT: u8 = 6;
+pub const SIGSEGV: u8 = 11;
+pub const SIGXCPU: u8 = 24;
+
+// GDB uses register number 32 for PC on RISC-V.
+const PC_REGISTER_NUMBER: usize = RISCV_GENERAL_REGISTER_NUMBER;
+// Maximum packet size we advertise to GDB in qSupported, in bytes.
+const MAX_PACKET_SIZE: usize = 0x4000;
+// Checking the connection for an interrupt request requires a syscall, so we
+// only do it once in a while when continuing.
+const INTERRUPT_POLL_INTERVAL: u64 = 0x10000;
+
+/// A connection to the debugger. Besides plain reading and writing, the stub
+/// needs to read without blocking while the machine is running, to find
+/// interrupt requests (Ctrl-C in GDB, sent as a single 0x03 byte). Other
+/// bytes read this way are kept by the stub for the next packet.
+pub trait GdbConnection: Read + Write {
+    /// Reads one byte if it is available right away.
+    fn try_read_byte(&mut self) -> Result<Option<u8>, std::io::Error> {
+        Ok(None)
+    }
+}
+
+impl GdbConnection for TcpStream {
+    fn try_read_byte(&mut self) -> Result<Option<u8>, std::io::Error> {
+        self.set_nonblocking(true)?;
+        let mut buf = [0u8; 1];
+        let result = self.read(&mut buf);
+        self.set_nonblocking(false)?;
+        nonblocking_byte(result, buf[0])
+    }
+}
+
+#[cfg(unix)]
+impl GdbConnection for std::os::unix::net::UnixStream {
+    fn try_read_byte(&mut self) -> Result<Option<u8>, std::io::Error> {
+        self.set_nonblocking(true)?;
+        let mut buf = [0u8; 1];
+        let result = self.read(&mut buf);
+        self.set_nonblocking(false)?;
+        nonblocking_byte(result, buf[0])
+    }
+}
+
+fn nonblocking_byte(
+    result: Result<usize, std::io::Error>,
+    byte: u8,
+) -> Result<Option<u8>, std::io::Error> {
+    match result {
+        Ok(1) => Ok(Some(byte)),
+        Ok(_) => Ok(None),
+        Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Why the machine stopped and gave control back to GDB.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopReason {
+    Signal(u8),
+    // The data address accessed and the kind of the watchpoint it hit.
+    Watchpoint(u64, WatchpointKind),
+    Exited(i8),
+    Faulted(Error),
+}
+
+/// EBREAK handler that hands control to GDB when the guest executes an
+/// EBREAK instruction. Use GdbStub::ebreak_handler to create one.
+pub struct GdbEbreakHandler {
+    hit: Arc<AtomicBool>,
+}
+
+impl<Mac: SupportMachine> Debugger<Mac> for GdbEbreakHandler {
+    fn initialize(&mut self, _machine: &mut Mac) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn ebreak(&mut self, _machine: &mut Mac) -> Result<(), Error> {
+        self.hit.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+// Errors raised while serving a packet, a malformed packet is answered with
+// an error reply and the session goes on, the others end the session.
+enum PacketError {
+    Malformed,
+    Fatal(Error),
+}
+
+impl From<Error> for PacketError {
+    fn from(e: Error) -> Self {
+        PacketError::Fatal(e)
+    }
+}
+
+pub struct GdbStub<C: GdbConnection> {
+    connection: C,
+    // Bytes read while polling for interrupts, which are not interrupts.
+    pending: VecDeque<u8>,
+    no_ack_mode: bool,
+    breakpoints: HashSet<u64>,
+    watchpoints: Watchpoints,
+    ebreak_hit: Arc<AtomicBool>,
+}
+
+impl<C: GdbConnection> GdbStub<C> {
+    pub fn new(connection: C) -> Self {
+        Self {
+            connection,
+            pending: VecDeque::new(),
+            no_ack_mode: false,
+            breakpoints: HashSet::default(),
+            watchpoints: Watchpoints::default(),
+            ebreak_hit: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Creates a Debugger which should be installed into the machine via
+    /// DefaultMachineBuilder::debugger, so EBREAK instructions compiled into
+    /// the program stop the machine with SIGTRAP.
+    pub fn ebreak_handler(&self) -> GdbEbreakHandler {
+        GdbEbreakHandler {
+            hit: Arc::clone(&self.ebreak_hit),
+        }
+    }
+
+    pub fn breakpoints(&self) -> &HashSet<u64> {
+        &self.breakpoints
+    }
+
+    pub fn watchpoints(&self) -> &Watchpoints {
+        &self.watchpoints
+    }
+
+    pub fn connection_mut(&mut self) -> &mut C {
+        &mut self.connection
+    }
+
+    /// Serves GDB until the program exits, or GDB kills / detaches from it.
+    /// The machine should already have its program loaded.
+    pub fn run<Inner: SupportMachine>(
+        &mut self,
+        machine: &mut DefaultMachine<Inner>,
+    ) -> Result<i8, Error> {
+        let mut decoder = machine.build_decoder();
+        self.run_with_decoder(machine, &mut decoder)
+    }
+
+    pub fn run_with_decoder<Inner: SupportMachine, D: InstDecoder>(
+        &mut self,
+        machine: &mut DefaultMachine<Inner>,
+        decoder: &mut D,
+    ) -> Result<i8, Error> {
+        if machine.isa() & ISA_MOP != 0 && machine.version() == VERSION0 {
+            return Err(Error::InvalidVersion);
+        }
+        machine.set_running(true);
+        let mut stop = StopReason::Signal(SIGTRAP);
+        loop {
+            let packet = self.read_packet()?;
+            match self.handle_packet(machine, decoder, &packet, &mut stop) {
+                Ok(None) => (),
+                Ok(Some(result)) => return result,
+                Err(PacketError::Malformed) => self.write_packet(b"E01")?,
+                Err(PacketError::Fatal(e)) => return Err(e),
+            }
+        }
+    }
+
+    // Serves one packet, returns the result of the session once it ends.
+    fn handle_packet<Inner: SupportMachine, D: InstDecoder>(
+        &mut self,
+        machine: &mut DefaultMachine<Inner>,
+        decoder: &mut D,
+        packet: &[u8],
+        stop: &mut StopReason,
+    ) -> Result<Option<Result<i8, Error>>, PacketError> {
+        let (command, body) = match packet.split_first() {
+            Some((command, body)) => (*command, body),
+            None => {
+                self.write_packet(b"")?;
+                return Ok(None);
+            }
+        };
+        match command {
+            b'?' => self.write_stop_reply(stop)?,
+            b'g' => {
+                let mut reply = Vec::new();
+                for i in 0..=PC_REGISTER_NUMBER {
+                    encode_register(&mut reply, read_register(machine, i));
+                }
+                self.write_packet(&reply)?;
+            }
+            b'G' => {
+                let bytes = decode_hex(body)?;
+                let size = Inner::REG::BITS as usize / 8;
+                for (i, chunk) in bytes.chunks(size).enumerate().take(PC_REGISTER_NUMBER + 1) {
+                    write_register(machine, i, decode_register(chunk));
+                }
+                self.write_packet(b"OK")?;
+            }
+            b'p' => {
+                let i = parse_hex(body)? as usize;
+                if i <= PC_REGISTER_NUMBER {
+                    let mut reply = Vec::new();
+                    encode_register(&mut reply, read_register(machine, i));
+                    self.write_packet(&reply)?;
+                } else {
+                    self.write_packet(b"E00")?;
+                }
+            }
+            b'P' => {
+                let (i, value) = split_once(body, b'=')?;
+                let i = parse_hex(i)? as usize;
+                if i <= PC_REGISTER_NUMBER {
+                    write_register(machine, i, decode_register(&decode_hex(value)?));
+                    self.write_packet(b"OK")?;
+                } else {
+                    self.write_packet(b"E00")?;
+                }
+            }
+            b'm' => {
+                let (addr, length) = split_once(body, b',')?;
+                let (addr, length) = (parse_hex(addr)?, parse_hex(length)?);
+                let length = length.min(MAX_PACKET_SIZE as u64 / 2 - 1);
+                match machine.memory_mut().load_bytes(addr, length) {
+                    Ok(data) => self.write_packet(encode_hex(&data).as_bytes())?,
+                    Err(_) => self.write_packet(b"E14")?,
+                }
+            }
+            b'M' => {
+                let (range, data) = split_once(body, b':')?;
+                let (addr, _) = split_once(range, b',')?;
+                let data = decode_hex(data)?;
+                match machine.memory_mut().store_bytes(parse_hex(addr)?, &data) {
+                    Ok(()) => {
+                        decoder.reset_instructions_cache()?;
+                        self.write_packet(b"OK")?
+                    }
+                    Err(_) => self.write_packet(b"E14")?,
+                }
+            }
+            b'Z' | b'z' => {
+                let mut parts = body.split(|c| *c == b',');
+                let point_type = parts.next().unwrap_or_default();
+                let addr = parse_hex(parts.next().unwrap_or_default())?;
+                let kind = match point_type {
+                    b"2" => Some(WatchpointKind::Write),
+                    b"3" => Some(WatchpointKind::Read),
+                    b"4" => Some(WatchpointKind::Access),
+                    _ => None,
+                };
+                match (point_type, kind) {
+                    // Software and hardware breakpoints are handled the
+                    // same way, since we never patch guest code.
+                    (b"0" | b"1", _) => {
+                        if command == b'Z' {
+                            self.breakpoints.insert(addr);
+                        } else {
+                            self.breakpoints.remove(&addr);
+                        }
+                        self.write_packet(b"OK")?;
+                    }
+                    (_, Some(kind)) => {
+                        let size = parse_hex(parts.next().unwrap_or_default())?;
+                        if command == b'Z' {
+                            self.watchpoints.add(addr, size, kind);
+                        } else {
+                            self.watchpoints.remove(addr, size, kind);
+                        }
+                        self.write_packet(b"OK")?;
+                    }
+                    _ => self.write_packet(b"")?,
+                }
+            }
+            b'c' | b's' | b'C' | b'S' => {
+                // The optional signal number in C / S is ignored since
+                // there is no signal delivery in CKB-VM.
+                let addr = match command {
+                    b'c' | b's' => body,
+                    _ => split_once(body, b';').map(|(_, a)| a).unwrap_or_default(),
+                };
+                if !addr.is_empty() {
+                    write_register(machine, PC_REGISTER_NUMBER, parse_hex(addr)?);
+                }
+                let single_step = command == b's' || command == b'S';
+                *stop = self.resume(machine, decoder, single_step, stop.clone())?;
+                self.write_stop_reply(stop)?;
+                if let StopReason::Exited(code) = *stop {
+                    return Ok(Some(Ok(code)));
+                }
+            }
+            b'k' => return Ok(Some(self.kill(stop.clone()))),
+            b'D' => {
+                self.write_packet(b"OK")?;
+                return Ok(Some(match stop.clone() {
+                    StopReason::Faulted(e) => Err(e),
+                    StopReason::Exited(code) => Ok(code),
+                    StopReason::Signal(_) | StopReason::Watchpoint(..) => {
+                        machine.run_with_decoder(decoder)
+                    }
+                }));
+            }
+            b'H' | b'T' => self.write_packet(b"OK")?,
+            b'q' | b'Q' | b'v' => {
+                if body.starts_with(b"Cont?") {
+                    self.write_packet(b"vCont;c;C;s;S")?;
+                } else if let Some(actions) = body.strip_prefix(b"Cont;") {
+                    // Only one thread exists, so the first action is the
+                    // one that applies.
+                    let single_step = matches!(actions.first(), Some(b's') | Some(b'S'));
+                    *stop = self.resume(machine, decoder, single_step, stop.clone())?;
+                    self.write_stop_reply(stop)?;
+                    if let StopReason::Exited(code) = *stop {
+                        return Ok(Some(Ok(code)));
+                    }
+                } else if body.starts_with(b"Kill") {
+                    self.write_packet(b"OK")?;
+                    return Ok(Some(self.kill(stop.clone())));
+                } else {
+                    self.handle_query(body, Inner::REG::BITS)?;
+                }
+            }
+            _ => self.write_packet(b"")?,
+        }
+        Ok(None)
+    }
+
+    fn kill(&mut self, stop: StopReason) -> Result<i8, Error> {
+        match stop {
+            StopReason::Faulted(e) => Err(e),
+            StopReason::Exited(code) => Ok(code),
+            StopReason::Signal(_) | StopReason::Watchpoint(..) => {
+                Err(Error::Unexpected(String::from("Killed by debugger")))
+            }
+        }
+    }
+
+    fn handle_query(&mut self, body: &[u8], bits: u8) -> Result<(), PacketError> {
+        if body.starts_with(b"Supported") {
+            let reply = format!(
+                "PacketSize={:x};qXfer:features:read+;QStartNoAckMode+;swbreak+;vContSupported+",
+                MAX_PACKET_SIZE
+            );
+            self.write_packet(reply.as_bytes())?;
+        } else if body.starts_with(b"StartNoAckMode") {
+            self.write_packet(b"OK")?;
+            self.no_ack_mode = true;
+        } else if let Some(args) = body.strip_prefix(b"Xfer:features:read:target.xml:") {
+            let (offset, length) = split_once(args, b',')?;
+            let (offset, length) = (parse_hex(offset)? as usize, parse_hex(length)? as usize);
+            let xml = target_xml(bits);
+            let xml = xml.as_bytes();
+            let start = offset.min(xml.len());
+            let end = offset.saturating_add(length).min(xml.len());
+            let mut reply = vec![if end == xml.len() { b'l' } else { b'm' }];
+            reply.extend_from_slice(&xml[start..end]);
+            self.write_packet(&reply)?;
+        } else if body.starts_with(b"Attached") {
+            self.write_packet(b"1")?;
+        } else if body == b"C" {
+            self.write_packet(b"QC1")?;
+        } else if body.starts_with(b"fThreadInfo") {
+            self.write_packet(b"m1")?;
+        } else if body.starts_with(b"sThreadInfo") {
+            self.write_packet(b"l")?;
+        } else if body.starts_with(b"Symbol") {
+            self.write_packet(b"OK")?;
+        } else {
+            self.write_packet(b"")?;
+        }
+        Ok(())
+    }
+
+    fn resume<Inner: SupportMachine, D: InstDecoder>(
+        &mut self,
+        machine: &mut DefaultMachine<Inner>,
+        decoder: &mut D,
+        single_step: bool,
+        last_stop: StopReason,
+    ) -> Result<StopReason, Error> {
+        match last_stop {
+            // A faulted or exited machine cannot make any further progress.
+            StopReason::Faulted(_) | StopReason::Exited(_) => return Ok(last_stop),
+            StopReason::Signal(_) | StopReason::Watchpoint(..) => (),
+        }
+        let mut pause = machine.pause();
+        let mut steps: u64 = 0;
+        loop {
+            if machine.reset_signal() {
+                decoder.reset_instructions_cache()?;
+            }
+            // Watchpoints are checked against the access the instruction
+            // is about to make, memory is not required to track them.
+            let access = if self.watchpoints.is_empty() {
+                None
+            } else {
+                let pc = machine.pc().to_u64();
+                decoder
+                    .decode(machine.memory_mut(), pc)
+                    .ok()
+                    .and_then(|i| instruction_memory_access(machine, i))
+            };
+            if let Err(e) = machine.step(decoder) {
+                return Ok(StopReason::Faulted(e));
+            }
+            if !machine.running() {
+                return Ok(StopReason::Exited(machine.exit_code()));
+            }
+            if let Some((address, size, kind)) = access {
+                if let Some(kind) = self.watchpoints.check(address, size, kind) {
+                    return Ok(StopReason::Watchpoint(address, kind));
+                }
+            }
+            if self.ebreak_hit.swap(false, Ordering::SeqCst)
+                || single_step
+                || self.breakpoints.contains(&machine.pc().to_u64())
+            {
+                return Ok(StopReason::Signal(SIGTRAP));
+            }
+            if pause.has_interrupted() {
+                pause.free();
+                return Ok(StopReason::Signal(SIGINT));
+            }
+            steps += 1;
+            if steps % INTERRUPT_POLL_INTERVAL == 0 && self.poll_interrupt()? {
+                return Ok(StopReason::Signal(SIGINT));
+            }
+        }
+    }
+
+    fn write_stop_reply(&mut self, stop: &StopReason) -> Result<(), Error> {
+        let reply = match stop {
+            StopReason::Signal(signal) => format!("S{:02x}", signal),
+            StopReason::Watchpoint(address, kind) => {
+                let name = match kind {
+                    WatchpointKind::Write => "watch",
+                    WatchpointKind::Read => "rwatch",
+                    WatchpointKind::Access => "awatch",
+                };
+                format!("T{:02x}{}:{:x};", SIGTRAP, name, address)
+            }
+            StopReason::Exited(code) => format!("W{:02x}", *code as u8),
+            StopReason::Faulted(e) => format!("S{:02x}", signal_of_error(e)),
+        };
+        self.write_packet(reply.as_bytes())
+    }
+
+    fn poll_interrupt(&mut self) -> Result<bool, Error> {
+        match self.connection.try_read_byte()? {
+            Some(0x03) => Ok(true),
+            Some(c) => {
+                self.pending.push_back(c);
+                Ok(false)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn read_byte(&mut self) -> Result<u8, Error> {
+        if let Some(c) = self.pending.pop_front() {
+            return Ok(c);
+        }
+        let mut buf = [0u8; 1];
+        self.connection.read_exact(&mut buf)?;
+        Ok(buf[0])
+    }
+
+    fn read_packet(&mut self) -> Result<Vec<u8>, Error> {
+        loop {
+            // Skip acks, and interrupt requests arriving when we are already
+            // stopped.
+            while self.read_byte()? != b'$' {}
+            let mut data = Vec::new();
+            let mut checksum: u8 = 0;
+            loop {
+                let c = self.read_byte()?;
+                if c == b'#' {
+                    break;
+                }
+                checksum = checksum.wrapping_add(c);
+                data.push(c);
+            }
+            let expected = [self.read_byte()?, self.read_byte()?];
+            let valid = parse_hex(&expected).ok() == Some(u64::from(checksum));
+            if !self.no_ack_mode {
+                self.connection.write_all(if valid { b"+" } else { b"-" })?;
+                self.connection.flush()?;
+            }
+            if valid {
+                return Ok(unescape(&data));
+            }
+        }
+    }
+
+    fn write_packet(&mut self, data: &[u8]) -> Result<(), Error> {
+        let mut packet = Vec::with_capacity(data.len() + 4);
+        packet.push(b'$');
+        let mut checksum: u8 = 0;
+        for c in data {
+            if matches!(c, b'#' | b'$' | b'}' | b'*') {
+                packet.push(b'}');
+                checksum = checksum.wrapping_add(b'}');
+                packet.push(c ^ 0x20);
+                checksum = checksum.wrapping_add(c ^ 0x20);
+            } else {
+                packet.push(*c);
+                checksum = checksum.wrapping_add(*c);
+            }
+        }
+        packet.extend_from_slice(format!("#{:02x}", checksum).as_bytes());
+        loop {
+            self.connection.write_all(&packet)?;
+            self.connection.flush()?;
+            if self.no_ack_mode {
+                return Ok(());
+            }
+            match self.read_byte()? {
+                b'-' => continue,
+                _ => return Ok(()),
+            }
+        }
+    }
+}
+
+pub fn signal_of_error(e: &Error) -> u8 {
+    match e {
+        Error::MemOutOfBound(_, _)
+        | Error::MemOutOfStack
+        | Error::MemPageUnalignedAccess(_)
+        | Error::MemWriteOnExecutablePage(_)
+        | Error::MemWriteOnFreezedPage(_) => SIGSEGV,
+        Error::InvalidInstruction { .. } | Error::InvalidOp(_) => SIGILL,
+        Error::CyclesExceeded | Error::CyclesOverflow => SIGXCPU,
+        _ => SIGABRT,
+    }
+}
+
+fn target_xml(bits: u8) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\"?><!DOCTYPE target SYSTEM \"gdb-target.dtd\"><target version=\"1.0\">",
+    );
+    xml.push_str(&format!("<architecture>riscv:rv{}</architecture>", bits));
+    xml.push_str("<feature name=\"org.gnu.gdb.riscv.cpu\">");
+    for (i, name) in REGISTER_ABI_NAMES.iter().enumerate() {
+        let reg_type = match i {
+            1 => "code_ptr",
+            2 | 8 => "data_ptr",
+            _ => "int",
+        };
+        xml.push_str(&format!(
+            "<reg name=\"{}\" bitsize=\"{}\" type=\"{}\" regnum=\"{}\"/>",
+            name, bits, reg_type, i
+        ));
+    }
+    xml.push_str(&format!(
+        "<reg name=\"pc\" bitsize=\"{}\" type=\"code_ptr\" regnum=\"{}\"/>",
+        bits, PC_REGISTER_NUMBER
+    ));
+    xml.push_str("</feature></target>");
+    xml
+}
+
+fn read_register<Mac: CoreMachine>(machine: &Mac, i: usize) -> Mac::REG {
+    if i == PC_REGISTER_NUMBER {
+        machine.pc().clone()
+    } else {
+        machine.registers()[i].clone()
+    }
+}
+
+fn write_register<Mac: CoreMachine>(machine: &mut Mac, i: usize, value: u64) {
+    if i == PC_REGISTER_NUMBER {
+        machine.update_pc(Mac::REG::from_u64(value));
+        machine.commit_pc();
+    } else if i > 0 {
+        // x0 is hardwired to zero
+        machine.set_register(i, Mac::REG::from_u64(value));
+    }
+}
+
+fn encode_register<R: Register>(out: &mut Vec<u8>, value: R) {
+    let bytes = value.to_u64().to_le_bytes();
+    out.extend_from_slice(encode_hex(&bytes[..R::BITS as usize / 8]).as_bytes());
+}
+
+fn decode_register(bytes: &[u8]) -> u64 {
+    let mut buf = [0u8; 8];
+    let n = bytes.len().min(8);
+    buf[..n].copy_from_slice(&bytes[..n]);
+    u64::from_le_bytes(buf)
+}
+
+fn encode_hex(data: &[u8]) -> String {
+    let mut result = String::with_capacity(data.len() * 2);
+    for b in data {
+        result.push_str(&format!("{:02x}", b));
+    }
+    result
+}
+
+fn decode_hex(data: &[u8]) -> Result<Vec<u8>, PacketError> {
+    data.chunks(2)
+        .map(|c| parse_hex(c).map(|v| v as u8))
+        .collect()
+}
+
+fn parse_hex(data: &[u8]) -> Result<u64, PacketError> {
+    std::str::from_utf8(data)
+        .ok()
+        .and_then(|s| u64::from_str_radix(s, 16).ok())
+        .ok_or(PacketError::Malformed)
+}
+
+fn split_once(data: &[u8], separator: u8) -> Result<(&[u8], &[u8]), PacketError> {
+    let i = data
+        .iter()
+        .position(|c| *c == separator)
+        .ok_or(PacketError::Malformed)?;
+    Ok((&data[..i], &data[i + 1..]))
+}
+
+fn unescape(data: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(data.len());
+    let mut iter = data.iter();
+    while let Some(c) = iter.next() {
+        if *c == b'}' {
+            if let Some(n) = iter.next() {
+                result.push(n ^ 0x20);
+            }
+        } else {
+            result.push(*c);
+        }
+    }
+    result
+}
@@ -1,3 +1,5 @@
+pub mod gdb;
+
 use crate::{machine::SupportMachine, Error};
 
 pub trait Debugger<Mac: SupportMachine>: Send + Sync {
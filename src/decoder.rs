@@ -3,12 +3,15 @@ use ckb_vm_definitions::registers::{RA, ZERO};
 
 use crate::error::OutOfBoundKind;
 use crate::instructions::{
-    a, b, extract_opcode, i, instruction_length, m, rvc, set_instruction_length_n, Instruction,
-    InstructionFactory, Itype, R4type, R5type, Register, Rtype, Utype,
+    a, b, blank_instruction,
+    custom::{is_custom_major_opcode, CustomDecodeFunc},
+    d, extract_opcode, f, i, instruction_length, k, m, rvc, set_instruction_length_4,
+    set_instruction_length_n, v, zicond, Instruction, InstructionFactory, Itype, R4type, R5type,
+    Register, Rtype, Utype,
 };
 use crate::machine::VERSION2;
 use crate::memory::Memory;
-use crate::{Error, ISA_A, ISA_B, ISA_MOP, RISCV_PAGESIZE};
+use crate::{Error, ISA_A, ISA_B, ISA_D, ISA_F, ISA_K, ISA_MOP, ISA_V, ISA_ZC, RISCV_PAGESIZE};
 
 const RISCV_PAGESIZE_MASK: u64 = RISCV_PAGESIZE as u64 - 1;
 const INSTRUCTION_CACHE_SIZE: usize = 4096;
@@ -20,6 +23,9 @@ pub trait InstDecoder {
 
 pub struct Decoder {
     factories: Vec<InstructionFactory>,
+    // Decoders of the custom instructions, the index in this list is the high
+    // byte of the custom opcode.
+    custom_factories: Vec<CustomDecodeFunc>,
     mop: bool,
     version: u32,
     // Use a cache of instructions to avoid decoding the same instruction
@@ -34,6 +40,7 @@ impl Decoder {
     pub fn new(mop: bool, version: u32) -> Decoder {
         Decoder {
             factories: vec![],
+            custom_factories: vec![],
             mop,
             version,
             instructions_cache: vec![(u64::MAX as u64, 0); INSTRUCTION_CACHE_SIZE],
@@ -44,6 +51,16 @@ impl Decoder {
         self.factories.push(factory);
     }
 
+    // Custom instruction factories are only consulted for the custom-0 and
+    // custom-1 major opcodes, they are tried in the order they are added.
+    pub fn add_custom_instruction_factory(&mut self, factory: CustomDecodeFunc) {
+        assert!(
+            self.custom_factories.len() < insts::MAXIMUM_CUSTOM_INSTRUCTIONS,
+            "too many custom instructions"
+        );
+        self.custom_factories.push(factory);
+    }
+
     // This method is used to decode instruction raw bits from memory pointed
     // by current PC. Right now we support 32-bit instructions and RVC compressed
     // instructions. In future version we might add support for longer instructions.
@@ -125,6 +142,18 @@ impl Decoder {
                 return Ok(instruction);
             }
         }
+        if is_custom_major_opcode(instruction_bits) {
+            for (index, factory) in self.custom_factories.iter().enumerate() {
+                if let Some(operands) = factory(instruction_bits) {
+                    // Clear the opcode and length bits, then fill them in.
+                    let instruction = set_instruction_length_4(
+                        (operands & !0x0fff_00ff) | blank_instruction(insts::custom_opcode(index)),
+                    );
+                    self.instructions_cache[instruction_cache_key] = (pc, instruction);
+                    return Ok(instruction);
+                }
+            }
+        }
         Err(Error::InvalidInstruction {
             pc,
             instruction: instruction_bits,
@@ -886,5 +915,21 @@ pub fn build_decoder<R: Register>(isa: u8, version: u32) -> Decoder {
     if isa & ISA_A != 0 {
         decoder.add_instruction_factory(a::factory::<R>);
     }
+    if isa & ISA_F != 0 {
+        decoder.add_instruction_factory(f::factory::<R>);
+    }
+    if isa & ISA_D != 0 {
+        decoder.add_instruction_factory(d::factory::<R>);
+    }
+    if isa & ISA_V != 0 {
+        decoder.add_instruction_factory(v::factory::<R>);
+    }
+    if isa & ISA_K != 0 {
+        decoder.add_instruction_factory(k::factory::<R>);
+    }
+    if isa & ISA_ZC != 0 {
+        decoder.add_instruction_factory(rvc::zc_factory::<R>);
+        decoder.add_instruction_factory(zicond::factory::<R>);
+    }
     decoder
 }
@@ -0,0 +1,496 @@
+// Differential execution of the same program on the Rust interpreter,
+// TraceMachine and(when available) AsmMachine. Engines are run in lockstep,
+// one trace at a time: a trace is a basic block, or at most
+// TRACE_ITEM_LENGTH instructions of it, which is the unit all engines agree
+// on. After each trace the PC, registers, cycles and dirty memory pages of
+// every engine are compared with the interpreter, the first mismatch is
+// reported as a Divergence.
+//
+// Engines stopping with an error may do so at different instructions of a
+// trace(AsmMachine for example checks cycles for a whole trace before running
+// it), so once an error happens only the kinds of errors are compared: the
+// details, such as the faulting address reported by AsmMachine, might differ.
+use crate::{
+    decoder::{Decoder, InstDecoder},
+    instructions::{instruction_length, is_basic_block_end_instruction, tagged::TaggedInstruction},
+    machine::{trace::TraceMachine, DefaultMachine},
+    registers::REGISTER_ABI_NAMES,
+    Bytes, CoreMachine, Error, Instruction, Memory, Register, SupportMachine,
+    RISCV_FLOAT_REGISTER_NUMBER, RISCV_GENERAL_REGISTER_NUMBER, RISCV_PAGESIZE,
+    RISCV_VECTOR_REGISTER_NUMBER, RISCV_VLENB,
+};
+use std::convert::TryFrom;
+use std::fmt;
+use std::mem::discriminant;
+
+// Exit code or error of an engine once it stops
+type Stopped = Option<Result<i8, Error>>;
+
+// Same as the trace item length of TraceMachine and AsmMachine
+const TRACE_ITEM_LENGTH: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Display)]
+pub enum Engine {
+    Interpreter,
+    Trace,
+    Asm,
+}
+
+/// Architectural state of an engine after a trace.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct State {
+    pub pc: u64,
+    pub registers: [u64; RISCV_GENERAL_REGISTER_NUMBER],
+    pub fregisters: [u64; RISCV_FLOAT_REGISTER_NUMBER],
+    pub fcsr: u32,
+    pub vregisters: Vec<u8>,
+    pub vl: u64,
+    pub vtype: u64,
+    pub cycles: u64,
+    /// Exit code or error once the engine stops, None while it is running.
+    pub result: Option<Result<i8, Error>>,
+}
+
+impl State {
+    fn capture<M: SupportMachine>(machine: &M, result: Option<Result<i8, Error>>) -> Self {
+        let mut registers = [0u64; RISCV_GENERAL_REGISTER_NUMBER];
+        for (i, v) in machine.registers().iter().enumerate() {
+            registers[i] = v.to_u64();
+        }
+        // Machines without the F and D extensions have no floating-point
+        // registers, they compare as zeros.
+        let mut fregisters = [0u64; RISCV_FLOAT_REGISTER_NUMBER];
+        for (v, r) in fregisters.iter_mut().zip(machine.fregisters()) {
+            *v = *r;
+        }
+        Self {
+            pc: machine.pc().to_u64(),
+            registers,
+            fregisters,
+            fcsr: machine.fcsr(),
+            vregisters: machine.vregisters().to_vec(),
+            vl: machine.vl(),
+            vtype: machine.vtype(),
+            cycles: machine.cycles(),
+            result,
+        }
+    }
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "  pc: 0x{:x} cycles: {}", self.pc, self.cycles)?;
+        if let Some(result) = &self.result {
+            writeln!(f, "  result: {:?}", result)?;
+        }
+        for (i, chunk) in self.registers.chunks(4).enumerate() {
+            write!(f, " ")?;
+            for (j, value) in chunk.iter().enumerate() {
+                write!(f, " {:>4}: 0x{:016x}", REGISTER_ABI_NAMES[i * 4 + j], value)?;
+            }
+            writeln!(f)?;
+        }
+        // Floating-point state is only shown once it is used
+        if self.fcsr != 0 || self.fregisters.iter().any(|v| *v != 0) {
+            writeln!(f, "  fcsr: 0x{:02x}", self.fcsr)?;
+            for (i, chunk) in self.fregisters.chunks(4).enumerate() {
+                write!(f, " ")?;
+                for (j, value) in chunk.iter().enumerate() {
+                    write!(f, " {:>4}: 0x{:016x}", format!("f{}", i * 4 + j), value)?;
+                }
+                writeln!(f)?;
+            }
+        }
+        // So is the vector state, registers are shown most significant byte
+        // first
+        if self.vl != 0 || self.vregisters.iter().any(|v| *v != 0) {
+            writeln!(f, "  vl: {} vtype: 0x{:x}", self.vl, self.vtype)?;
+            for (i, register) in self.vregisters.chunks(RISCV_VLENB).enumerate() {
+                write!(f, "  {:>4}: 0x", format!("v{}", i))?;
+                for byte in register.iter().rev() {
+                    write!(f, "{:02x}", byte)?;
+                }
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Mismatch {
+    /// Engines stopped differently, or one of them did not stop
+    Result,
+    Pc,
+    Register(usize),
+    FloatRegister(usize),
+    Fcsr,
+    VectorRegister(usize),
+    /// vl or vtype differ
+    VectorConfig,
+    Cycles,
+    /// Flags of a memory page differ
+    MemoryFlag {
+        page: u64,
+        expected: u8,
+        actual: u8,
+    },
+    /// First differing byte of memory
+    Memory {
+        address: u64,
+        expected: u8,
+        actual: u8,
+    },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::Result => write!(f, "result"),
+            Mismatch::Pc => write!(f, "pc"),
+            Mismatch::Register(i) => write!(f, "register {}", REGISTER_ABI_NAMES[*i]),
+            Mismatch::FloatRegister(i) => write!(f, "register f{}", i),
+            Mismatch::Fcsr => write!(f, "fcsr"),
+            Mismatch::VectorRegister(i) => write!(f, "register v{}", i),
+            Mismatch::VectorConfig => write!(f, "vl or vtype"),
+            Mismatch::Cycles => write!(f, "cycles"),
+            Mismatch::MemoryFlag {
+                page,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "flag of page {}: expected 0x{:x}, actual 0x{:x}",
+                page, expected, actual
+            ),
+            Mismatch::Memory {
+                address,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "memory at 0x{:x}: expected 0x{:02x}, actual 0x{:02x}",
+                address, expected, actual
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Divergence {
+    /// The engine disagreeing with the interpreter
+    pub engine: Engine,
+    /// Number of traces run before the diverging one
+    pub trace: u64,
+    /// Instructions of the diverging trace as (pc, instruction), decoded by
+    /// the interpreter.
+    pub instructions: Vec<(u64, Instruction)>,
+    pub mismatch: Mismatch,
+    /// State of the interpreter
+    pub expected: State,
+    /// State of the diverging engine
+    pub actual: State,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} diverges from Interpreter on {} after trace #{}",
+            self.engine, self.mismatch, self.trace
+        )?;
+        for (pc, instruction) in &self.instructions {
+            match TaggedInstruction::try_from(*instruction) {
+                Ok(tagged) => writeln!(f, "  0x{:x}: {} ({:?})", pc, tagged, tagged)?,
+                Err(_) => writeln!(f, "  0x{:x}: 0x{:x}", pc, instruction)?,
+            }
+        }
+        writeln!(f, "Interpreter:")?;
+        write!(f, "{}", self.expected)?;
+        writeln!(f, "{}:", self.engine)?;
+        write!(f, "{}", self.actual)
+    }
+}
+
+pub struct Differential<Inner: SupportMachine> {
+    interpreter: DefaultMachine<Inner>,
+    interpreter_decoder: Decoder,
+    trace: TraceMachine<Inner>,
+    trace_decoder: Decoder,
+    #[cfg(has_asm)]
+    asm: Option<(crate::machine::asm::AsmMachine, Decoder)>,
+    memory_check_interval: u64,
+    traces: u64,
+}
+
+impl<Inner: SupportMachine> Differential<Inner> {
+    /// Creates a runner comparing the interpreter with TraceMachine, both
+    /// machines must be built the same way.
+    pub fn new(interpreter: DefaultMachine<Inner>, trace: DefaultMachine<Inner>) -> Self {
+        let interpreter_decoder = interpreter.build_decoder();
+        let trace_decoder = trace.build_decoder();
+        Self {
+            interpreter,
+            interpreter_decoder,
+            trace: TraceMachine::new(trace),
+            trace_decoder,
+            #[cfg(has_asm)]
+            asm: None,
+            memory_check_interval: 1,
+            traces: 0,
+        }
+    }
+
+    /// Adds AsmMachine to the comparison. Chaos mode is turned off, since
+    /// the other engines start from zeroed memory, and so must the asm one
+    /// for memory the guest has not written to compare equal.
+    #[cfg(has_asm)]
+    pub fn with_asm(
+        mut self,
+        mut machine: DefaultMachine<Box<crate::machine::asm::AsmCoreMachine>>,
+    ) -> Self {
+        machine.inner_mut().chaos_mode = 0;
+        let decoder = machine.build_decoder();
+        self.asm = Some((crate::machine::asm::AsmMachine::new(machine), decoder));
+        self
+    }
+
+    /// Memory is compared every `interval` traces, and when engines stop.
+    /// Comparing memory after each trace pinpoints the diverging store, but
+    /// is slow for long running programs.
+    pub fn set_memory_check_interval(&mut self, interval: u64) {
+        self.memory_check_interval = interval.max(1);
+    }
+
+    pub fn interpreter_mut(&mut self) -> &mut DefaultMachine<Inner> {
+        &mut self.interpreter
+    }
+
+    pub fn trace_mut(&mut self) -> &mut TraceMachine<Inner> {
+        &mut self.trace
+    }
+
+    #[cfg(has_asm)]
+    pub fn asm_mut(&mut self) -> Option<&mut crate::machine::asm::AsmMachine> {
+        self.asm.as_mut().map(|(machine, _)| machine)
+    }
+
+    /// Loads the program into all engines, after which they can be run or
+    /// stepped.
+    pub fn load_program(&mut self, program: &Bytes, args: &[Bytes]) -> Result<u64, Error> {
+        let bytes = self.interpreter.load_program(program, args)?;
+        self.trace.load_program(program, args)?;
+        #[cfg(has_asm)]
+        if let Some((machine, _)) = &mut self.asm {
+            machine.load_program(program, args)?;
+        }
+        self.set_running();
+        Ok(bytes)
+    }
+
+    fn set_running(&mut self) {
+        self.interpreter.set_running(true);
+        self.trace.machine.set_running(true);
+        #[cfg(has_asm)]
+        if let Some((machine, _)) = &mut self.asm {
+            machine.machine.set_running(true);
+        }
+    }
+
+    /// Runs all engines to the end. The result agreed by all engines is
+    /// returned, or the first divergence.
+    pub fn run(&mut self) -> Result<Result<i8, Error>, Box<Divergence>> {
+        self.set_running();
+        loop {
+            if let Some(result) = self.step()? {
+                return Ok(result);
+            }
+        }
+    }
+
+    /// Runs one trace on all engines and compares them, the agreed result is
+    /// returned once engines stop.
+    pub fn step(&mut self) -> Result<Option<Result<i8, Error>>, Box<Divergence>> {
+        let (instructions, interpreter_result) = self.step_interpreter();
+        let expected = State::capture(&self.interpreter, interpreter_result);
+        let check_memory =
+            expected.result.is_some() || (self.traces + 1) % self.memory_check_interval == 0;
+
+        let result = self.trace.step_trace(&mut self.trace_decoder);
+        let result = stopped(&self.trace.machine, result);
+        let actual = State::capture(&self.trace.machine, result);
+        self.compare(Engine::Trace, &instructions, &expected, actual, |d| {
+            check_memory
+                .then(|| compare_memory(d.interpreter.memory_mut(), d.trace.machine.memory_mut()))
+                .flatten()
+        })?;
+
+        #[cfg(has_asm)]
+        if let Some((machine, decoder)) = &mut self.asm {
+            let result = machine.step_trace(decoder);
+            let result = stopped(&machine.machine, result);
+            let actual = State::capture(&machine.machine, result);
+            self.compare(Engine::Asm, &instructions, &expected, actual, |d| {
+                let (machine, _) = d.asm.as_mut().unwrap();
+                check_memory
+                    .then(|| {
+                        compare_memory(d.interpreter.memory_mut(), machine.machine.memory_mut())
+                    })
+                    .flatten()
+            })?;
+        }
+
+        self.traces += 1;
+        Ok(expected.result)
+    }
+
+    // Runs the instructions of one trace on the interpreter, returning them
+    // along with the result if the interpreter stops.
+    fn step_interpreter(&mut self) -> (Vec<(u64, Instruction)>, Stopped) {
+        if self.interpreter.reset_signal() {
+            if let Err(e) = self.interpreter_decoder.reset_instructions_cache() {
+                return (vec![], Some(Err(e)));
+            }
+        }
+        let mut instructions = vec![];
+        let mut pc = self.interpreter.pc().to_u64();
+        let mut complete = false;
+        while instructions.len() < TRACE_ITEM_LENGTH {
+            match self
+                .interpreter_decoder
+                .decode(self.interpreter.memory_mut(), pc)
+            {
+                Ok(instruction) => {
+                    instructions.push((pc, instruction));
+                    pc = pc.wrapping_add(u64::from(instruction_length(instruction)));
+                    if is_basic_block_end_instruction(instruction) {
+                        complete = true;
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+        complete = complete || instructions.len() == TRACE_ITEM_LENGTH;
+        // When decoding fails, the failing instruction is stepped as well to
+        // get the error.
+        let steps = instructions.len() + usize::from(!complete);
+        for _ in 0..steps {
+            let result = self.interpreter.step(&mut self.interpreter_decoder);
+            if let Some(result) = stopped(&self.interpreter, result) {
+                return (instructions, Some(result));
+            }
+        }
+        (instructions, None)
+    }
+
+    fn compare<F>(
+        &mut self,
+        engine: Engine,
+        instructions: &[(u64, Instruction)],
+        expected: &State,
+        actual: State,
+        memory: F,
+    ) -> Result<(), Box<Divergence>>
+    where
+        F: FnOnce(&mut Self) -> Option<Mismatch>,
+    {
+        let mismatch = if let (Some(Err(a)), Some(Err(b))) = (&expected.result, &actual.result) {
+            if discriminant(a) == discriminant(b) {
+                None
+            } else {
+                Some(Mismatch::Result)
+            }
+        } else if expected.result != actual.result {
+            Some(Mismatch::Result)
+        } else if expected.pc != actual.pc {
+            Some(Mismatch::Pc)
+        } else if let Some(i) = (0..RISCV_GENERAL_REGISTER_NUMBER)
+            .find(|i| expected.registers[*i] != actual.registers[*i])
+        {
+            Some(Mismatch::Register(i))
+        } else if let Some(i) = (0..RISCV_FLOAT_REGISTER_NUMBER)
+            .find(|i| expected.fregisters[*i] != actual.fregisters[*i])
+        {
+            Some(Mismatch::FloatRegister(i))
+        } else if expected.fcsr != actual.fcsr {
+            Some(Mismatch::Fcsr)
+        } else if let Some(i) = (0..RISCV_VECTOR_REGISTER_NUMBER).find(|i| {
+            let range = i * RISCV_VLENB..(i + 1) * RISCV_VLENB;
+            expected.vregisters[range.clone()] != actual.vregisters[range]
+        }) {
+            Some(Mismatch::VectorRegister(i))
+        } else if expected.vl != actual.vl || expected.vtype != actual.vtype {
+            Some(Mismatch::VectorConfig)
+        } else if expected.cycles != actual.cycles {
+            Some(Mismatch::Cycles)
+        } else {
+            memory(self)
+        };
+        match mismatch {
+            Some(mismatch) => Err(Box::new(Divergence {
+                engine,
+                trace: self.traces,
+                instructions: instructions.to_vec(),
+                mismatch,
+                expected: expected.clone(),
+                actual,
+            })),
+            None => Ok(()),
+        }
+    }
+}
+
+// Converts the result of running a trace into the result of the program, if
+// the program stops.
+fn stopped<M: SupportMachine>(
+    machine: &DefaultMachine<M>,
+    result: Result<(), Error>,
+) -> Option<Result<i8, Error>> {
+    match result {
+        Err(e) => Some(Err(e)),
+        Ok(()) if !machine.running() => Some(Ok(machine.exit_code())),
+        Ok(()) => None,
+    }
+}
+
+fn compare_memory<A: Memory, B: Memory>(expected: &mut A, actual: &mut B) -> Option<Mismatch> {
+    let pages = expected.memory_pages().max(actual.memory_pages()) as u64;
+    for page in 0..pages {
+        let (expected_flag, actual_flag) =
+            match (expected.fetch_flag(page), actual.fetch_flag(page)) {
+                (Ok(a), Ok(b)) => (a, b),
+                (Err(_), Err(_)) => continue,
+                (a, b) => {
+                    return Some(Mismatch::MemoryFlag {
+                        page,
+                        expected: a.unwrap_or(0),
+                        actual: b.unwrap_or(0),
+                    })
+                }
+            };
+        if expected_flag != actual_flag {
+            return Some(Mismatch::MemoryFlag {
+                page,
+                expected: expected_flag,
+                actual: actual_flag,
+            });
+        }
+        if expected_flag & crate::memory::FLAG_DIRTY == 0 {
+            continue;
+        }
+        let address = page * RISCV_PAGESIZE as u64;
+        let a = expected.load_bytes(address, RISCV_PAGESIZE as u64);
+        let b = actual.load_bytes(address, RISCV_PAGESIZE as u64);
+        if let (Ok(a), Ok(b)) = (a, b) {
+            if let Some(i) = (0..a.len()).find(|i| a[*i] != b[*i]) {
+                return Some(Mismatch::Memory {
+                    address: address + i as u64,
+                    expected: a[i],
+                    actual: b[i],
+                });
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,446 @@
+// A disassembler showing the instructions the way CKB-VM decodes them. The
+// executable segments of a program are loaded into memory, then decoded with
+// the same decoder the machines use, so the listing reflects the instructions
+// the VM actually runs, including the macro-op fused forms when fusion is
+// enabled. Listings follow the layout of objdump -d: one line per
+// instruction, with symbols and branch targets annotated.
+use crate::{
+    decoder::{build_decoder, Decoder},
+    elf::{parse_elf, Symbols},
+    instructions::{
+        custom::CustomInstruction, extract_opcode, instruction_length, insts,
+        tagged::TaggedInstruction, Instruction, Rtype, Stype, Utype, REGISTER_ABI_NAMES,
+    },
+    machine::VERSION0,
+    memory::{sparse::SparseMemory, Memory, FLAG_EXECUTABLE},
+    Bytes, Error,
+};
+use std::convert::TryFrom;
+use std::io::Write;
+
+/// A range of executable code, named after its ELF section when section
+/// headers are available, otherwise after the segment.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub name: String,
+    pub start: u64,
+    pub end: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DisassembledInstruction {
+    pub address: u64,
+    // Bytes covered by the instruction, a fused macro-op covers all the
+    // instructions it replaces.
+    pub bytes: Vec<u8>,
+    // None when the bytes cannot be decoded.
+    pub instruction: Option<Instruction>,
+}
+
+pub struct Disassembler {
+    decoder: Decoder,
+    memory: SparseMemory<u64>,
+    regions: Vec<Region>,
+    symbols: Symbols,
+    version: u32,
+    fusion: bool,
+    // Names of the custom instructions, in the order they are added.
+    custom_names: Vec<String>,
+}
+
+impl Disassembler {
+    /// Loads the executable segments of a RV64 program, memory_size should
+    /// be the memory size of the machine running the program.
+    pub fn new(program: &Bytes, isa: u8, version: u32, memory_size: usize) -> Result<Self, Error> {
+        let metadata = parse_elf::<u64>(program, version)?;
+        let mut memory = SparseMemory::new_with_memory(memory_size);
+        let mut segments = vec![];
+        for action in metadata
+            .actions
+            .iter()
+            .filter(|a| a.flags & FLAG_EXECUTABLE != 0)
+        {
+            memory.init_pages(
+                action.addr,
+                action.size,
+                action.flags,
+                Some(program.slice(action.source.start as usize..action.source.end as usize)),
+                action.offset_from_addr,
+            )?;
+            let start = action.addr + action.offset_from_addr;
+            segments.push(Region {
+                name: format!("segment{}", segments.len()),
+                start,
+                end: start + (action.source.end - action.source.start),
+            });
+        }
+        let regions = executable_sections(program)
+            .into_iter()
+            .filter(|s| {
+                segments
+                    .iter()
+                    .any(|r| r.start <= s.start && s.end <= r.end)
+            })
+            .collect::<Vec<_>>();
+        Ok(Self {
+            decoder: build_decoder::<u64>(isa, version),
+            memory,
+            regions: if regions.is_empty() {
+                segments
+            } else {
+                regions
+            },
+            symbols: Symbols::parse_labels(program).unwrap_or_default(),
+            version,
+            fusion: false,
+            custom_names: vec![],
+        })
+    }
+
+    /// Decodes and names a custom instruction, custom instructions should be
+    /// added in the order they are registered on the machine.
+    pub fn add_custom_instruction<Mac>(&mut self, custom: &CustomInstruction<Mac>) {
+        self.decoder
+            .add_custom_instruction_factory(custom.decode_func());
+        self.custom_names.push(custom.name().to_string());
+    }
+
+    /// Shows the macro-op fused forms, which the VM uses when ISA_MOP is
+    /// enabled. Fusion is not available on version 0.
+    pub fn set_fusion(&mut self, fusion: bool) -> Result<(), Error> {
+        if fusion && self.version == VERSION0 {
+            return Err(Error::InvalidVersion);
+        }
+        self.fusion = fusion;
+        Ok(())
+    }
+
+    pub fn regions(&self) -> &[Region] {
+        &self.regions
+    }
+
+    pub fn symbols(&self) -> &Symbols {
+        &self.symbols
+    }
+
+    pub fn disassemble_region(&mut self, region: &Region) -> Vec<DisassembledInstruction> {
+        let mut result = vec![];
+        let mut address = region.start;
+        while address < region.end {
+            let decoded = if self.fusion {
+                self.decoder.decode_mop(&mut self.memory, address)
+            } else {
+                self.decoder.decode_raw(&mut self.memory, address)
+            };
+            let (instruction, length) = match decoded {
+                Ok(i) if address + u64::from(instruction_length(i)) <= region.end => {
+                    (Some(i), u64::from(instruction_length(i)))
+                }
+                // Undecodable bytes are skipped in the size given by the
+                // lowest 2 bits, like the decoder does.
+                _ => match self.memory.load8(&address) {
+                    Ok(b) if b & 0b11 == 0b11 && address + 4 <= region.end => (None, 4),
+                    _ => (None, 2),
+                },
+            };
+            let length = length.min(region.end - address);
+            let bytes = self
+                .memory
+                .load_bytes(address, length)
+                .map(|b| b.to_vec())
+                .unwrap_or_default();
+            result.push(DisassembledInstruction {
+                address,
+                bytes,
+                instruction,
+            });
+            address += length;
+        }
+        result
+    }
+
+    /// Formats an instruction as objdump does, the mnemonic and the operands
+    /// are separated by a tab, branch targets are followed by the symbol
+    /// they fall in.
+    pub fn format(&self, inst: &DisassembledInstruction) -> String {
+        let instruction = match inst.instruction {
+            Some(i) => i,
+            None if inst.bytes.len() == 4 => {
+                let bits = u32::from_le_bytes([
+                    inst.bytes[0],
+                    inst.bytes[1],
+                    inst.bytes[2],
+                    inst.bytes[3],
+                ]);
+                return format!(".4byte\t0x{:x}", bits);
+            }
+            None => {
+                let bits = inst
+                    .bytes
+                    .iter()
+                    .rev()
+                    .fold(0u32, |a, b| (a << 8) | u32::from(*b));
+                return format!(".2byte\t0x{:x}", bits);
+            }
+        };
+        let text = disassemble_with_custom_names(instruction, inst.address, &self.custom_names);
+        match branch_target(instruction, inst.address) {
+            Some(target) => text + &self.annotation(target),
+            None => text,
+        }
+    }
+
+    // The symbol an address falls in, as " <symbol+0x10>".
+    fn annotation(&self, address: u64) -> String {
+        match self.symbols.lookup(address) {
+            Some(s) if s.address == address => format!(" <{}>", s.name),
+            Some(s) => format!(" <{}+0x{:x}>", s.name, address - s.address),
+            None => String::new(),
+        }
+    }
+
+    /// Writes a listing in the format of objdump -d.
+    pub fn write_objdump<W: Write>(&mut self, file_name: &str, w: &mut W) -> Result<(), Error> {
+        writeln!(w)?;
+        writeln!(w, "{}:     file format elf64-littleriscv", file_name)?;
+        writeln!(w)?;
+        for region in self.regions.clone() {
+            writeln!(w)?;
+            writeln!(w, "Disassembly of section {}:", region.name)?;
+            for inst in self.disassemble_region(&region) {
+                if let Some(symbol) = self.symbols.lookup(inst.address) {
+                    if symbol.address == inst.address {
+                        writeln!(w)?;
+                        writeln!(w, "{:016x} <{}>:", inst.address, symbol.name)?;
+                    }
+                }
+                writeln!(
+                    w,
+                    "{:>8x}:\t{:<18}\t{}",
+                    inst.address,
+                    raw_bytes(&inst.bytes),
+                    self.format(&inst)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Sections holding code, taken from the section headers.
+fn executable_sections(program: &[u8]) -> Vec<Region> {
+    let elf = match goblin_v040::elf::Elf::parse(program) {
+        Ok(elf) => elf,
+        Err(_) => return vec![],
+    };
+    elf.section_headers
+        .iter()
+        .filter(|s| s.is_executable() && s.sh_size > 0 && s.sh_addr > 0)
+        .filter_map(|s| {
+            Some(Region {
+                name: elf.shdr_strtab.get(s.sh_name)?.ok()?.to_string(),
+                start: s.sh_addr,
+                end: s.sh_addr.checked_add(s.sh_size)?,
+            })
+        })
+        .collect()
+}
+
+// Bytes as the little endian 16 or 32 bits instructions they form, the way
+// objdump shows them.
+fn raw_bytes(bytes: &[u8]) -> String {
+    let mut parts = vec![];
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        let length = if rest[0] & 0b11 == 0b11 && rest.len() >= 4 {
+            4
+        } else {
+            rest.len().min(2)
+        };
+        let value = rest[..length]
+            .iter()
+            .rev()
+            .fold(0u32, |a, b| (a << 8) | u32::from(*b));
+        parts.push(format!("{:0width$x}", value, width = length * 2));
+        rest = &rest[length..];
+    }
+    parts.join(" ")
+}
+
+/// Formats a decoded instruction located at address as objdump does, without
+/// the symbol annotations.
+pub fn disassemble(instruction: Instruction, address: u64) -> String {
+    disassemble_with_custom_names::<&str>(instruction, address, &[])
+}
+
+/// Same as disassemble, custom instructions are named after custom_names,
+/// which are in the order the custom instructions are registered.
+pub fn disassemble_with_custom_names<S: AsRef<str>>(
+    instruction: Instruction,
+    address: u64,
+    custom_names: &[S],
+) -> String {
+    let op = extract_opcode(instruction);
+    let name = match custom_names.get((op >> 8) as usize) {
+        Some(name) if insts::is_custom_opcode(op) => name.as_ref().to_string(),
+        _ => mnemonic(op),
+    };
+    if let Some(target) = branch_target(instruction, address) {
+        let operands = match op {
+            insts::OP_JAL | insts::OP_FAR_JUMP_REL | insts::OP_FAR_JUMP_ABS => {
+                format!("{},", REGISTER_ABI_NAMES[Utype(instruction).rd()])
+            }
+            _ => {
+                let i = Stype(instruction);
+                format!(
+                    "{},{},",
+                    REGISTER_ABI_NAMES[i.rs1()],
+                    REGISTER_ABI_NAMES[i.rs2()]
+                )
+            }
+        };
+        return format!("{}\t{}{:x}", name, operands, target);
+    }
+    match operands(instruction) {
+        Some(operands) if operands.is_empty() => name,
+        Some(operands) => format!("{}\t{}", name, operands),
+        None => name,
+    }
+}
+
+// Fused macro-ops only exist in CKB-VM, they keep the opcode name as is.
+fn is_fused(op: u16) -> bool {
+    (insts::OP_WIDE_MUL..=insts::OP_CUSTOM_LOAD_IMM).contains(&op)
+        || op == insts::OP_FAR_JUMP_REL
+        || op == insts::OP_FAR_JUMP_ABS
+}
+
+fn mnemonic(op: u16) -> String {
+    let special = match op {
+        insts::OP_ADDUW => "add.uw",
+        insts::OP_SH1ADDUW => "sh1add.uw",
+        insts::OP_SH2ADDUW => "sh2add.uw",
+        insts::OP_SH3ADDUW => "sh3add.uw",
+        insts::OP_SLLIUW => "slli.uw",
+        insts::OP_ORCB => "orc.b",
+        insts::OP_SEXTB => "sext.b",
+        insts::OP_SEXTH => "sext.h",
+        insts::OP_ZEXTH => "zext.h",
+        insts::OP_FENCEI => "fence.i",
+        _ => "",
+    };
+    if !special.is_empty() {
+        return special.to_string();
+    }
+    let name = insts::instruction_opcode_name(op).to_lowercase();
+    let name = name
+        .trim_end_matches("_version0")
+        .trim_end_matches("_version1");
+    if is_fused(op) {
+        name.to_string()
+    } else {
+        name.replace('_', ".")
+    }
+}
+
+// The address jumped to by a pc relative or absolute jump.
+fn branch_target(inst: Instruction, address: u64) -> Option<u64> {
+    match extract_opcode(inst) {
+        insts::OP_BEQ
+        | insts::OP_BNE
+        | insts::OP_BLT
+        | insts::OP_BGE
+        | insts::OP_BLTU
+        | insts::OP_BGEU => Some(address.wrapping_add(i64::from(Stype(inst).immediate_s()) as u64)),
+        insts::OP_JAL | insts::OP_FAR_JUMP_REL => {
+            Some(address.wrapping_add(i64::from(Utype(inst).immediate_s()) as u64) & !1)
+        }
+        insts::OP_FAR_JUMP_ABS => Some(i64::from(Utype(inst).immediate_s()) as u64 & !1),
+        _ => None,
+    }
+}
+
+// Operands of the instructions not jumping to a fixed target, None when the
+// instruction has no textual form.
+fn operands(inst: Instruction) -> Option<String> {
+    let op = extract_opcode(inst);
+    let r = |i: usize| REGISTER_ABI_NAMES[i];
+    match op {
+        insts::OP_ECALL | insts::OP_EBREAK | insts::OP_FENCE | insts::OP_FENCEI => {
+            return Some(String::new())
+        }
+        insts::OP_LUI | insts::OP_AUIPC => {
+            let i = Utype(inst);
+            return Some(format!(
+                "{},0x{:x}",
+                r(i.rd()),
+                (i.immediate_s() as u32) >> 12
+            ));
+        }
+        insts::OP_CLZ
+        | insts::OP_CLZW
+        | insts::OP_CTZ
+        | insts::OP_CTZW
+        | insts::OP_CPOP
+        | insts::OP_CPOPW
+        | insts::OP_ORCB
+        | insts::OP_REV8
+        | insts::OP_SEXTB
+        | insts::OP_SEXTH
+        | insts::OP_ZEXTH
+        | insts::OP_BREV8
+        | insts::OP_SHA256SIG0
+        | insts::OP_SHA256SIG1
+        | insts::OP_SHA256SUM0
+        | insts::OP_SHA256SUM1
+        | insts::OP_SHA512SIG0
+        | insts::OP_SHA512SIG1
+        | insts::OP_SHA512SUM0
+        | insts::OP_SHA512SUM1
+        | insts::OP_SM3P0
+        | insts::OP_SM3P1 => {
+            let i = Rtype(inst);
+            return Some(format!("{},{}", r(i.rd()), r(i.rs1())));
+        }
+        _ => (),
+    }
+    let tagged = TaggedInstruction::try_from(inst).ok()?;
+    Some(match tagged {
+        // Loads and jalr keep the offset(base) form, other I-type
+        // instructions take the base register first.
+        TaggedInstruction::Itype(i) if !is_load(op) => {
+            format!("{},{},{}", r(i.rd()), r(i.rs1()), i.immediate_s())
+        }
+        _ => {
+            let text = tagged.to_string();
+            match text.split_once(' ') {
+                Some((_, operands)) => operands.to_string(),
+                None => String::new(),
+            }
+        }
+    })
+}
+
+fn is_load(op: u16) -> bool {
+    matches!(
+        op,
+        insts::OP_LB_VERSION0
+            | insts::OP_LB_VERSION1
+            | insts::OP_LH_VERSION0
+            | insts::OP_LH_VERSION1
+            | insts::OP_LW_VERSION0
+            | insts::OP_LW_VERSION1
+            | insts::OP_LD_VERSION0
+            | insts::OP_LD_VERSION1
+            | insts::OP_LBU_VERSION0
+            | insts::OP_LBU_VERSION1
+            | insts::OP_LHU_VERSION0
+            | insts::OP_LHU_VERSION1
+            | insts::OP_LWU_VERSION0
+            | insts::OP_LWU_VERSION1
+            | insts::OP_JALR_VERSION0
+            | insts::OP_JALR_VERSION1
+            | insts::OP_FLW
+            | insts::OP_FLD
+    )
+}
@@ -0,0 +1,982 @@
+// A minimal reader for the DWARF line number program(.debug_line), covering
+// DWARF versions 2 to 5, and for the call frame information(.debug_frame and
+// .eh_frame). Only what is needed to map an address back to a source line and
+// to unwind the stack is decoded, this is meant for debugging tools and not
+// used when running programs.
+use crate::Error;
+use std::collections::HashMap;
+
+const DW_LNS_COPY: u8 = 1;
+const DW_LNS_ADVANCE_PC: u8 = 2;
+const DW_LNS_ADVANCE_LINE: u8 = 3;
+const DW_LNS_SET_FILE: u8 = 4;
+const DW_LNS_CONST_ADD_PC: u8 = 8;
+const DW_LNS_FIXED_ADVANCE_PC: u8 = 9;
+
+const DW_LNE_END_SEQUENCE: u8 = 1;
+const DW_LNE_SET_ADDRESS: u8 = 2;
+const DW_LNE_DEFINE_FILE: u8 = 3;
+
+const DW_LNCT_PATH: u64 = 1;
+const DW_LNCT_DIRECTORY_INDEX: u64 = 2;
+
+const DW_FORM_BLOCK2: u64 = 0x03;
+const DW_FORM_BLOCK4: u64 = 0x04;
+const DW_FORM_DATA2: u64 = 0x05;
+const DW_FORM_DATA4: u64 = 0x06;
+const DW_FORM_DATA8: u64 = 0x07;
+const DW_FORM_STRING: u64 = 0x08;
+const DW_FORM_BLOCK: u64 = 0x09;
+const DW_FORM_BLOCK1: u64 = 0x0a;
+const DW_FORM_DATA1: u64 = 0x0b;
+const DW_FORM_SDATA: u64 = 0x0d;
+const DW_FORM_STRP: u64 = 0x0e;
+const DW_FORM_UDATA: u64 = 0x0f;
+const DW_FORM_DATA16: u64 = 0x1e;
+const DW_FORM_LINE_STRP: u64 = 0x1f;
+
+const DW_CFA_ADVANCE_LOC: u8 = 0x40;
+const DW_CFA_OFFSET: u8 = 0x80;
+const DW_CFA_RESTORE: u8 = 0xc0;
+const DW_CFA_NOP: u8 = 0x00;
+const DW_CFA_SET_LOC: u8 = 0x01;
+const DW_CFA_ADVANCE_LOC1: u8 = 0x02;
+const DW_CFA_ADVANCE_LOC2: u8 = 0x03;
+const DW_CFA_ADVANCE_LOC4: u8 = 0x04;
+const DW_CFA_OFFSET_EXTENDED: u8 = 0x05;
+const DW_CFA_RESTORE_EXTENDED: u8 = 0x06;
+const DW_CFA_UNDEFINED: u8 = 0x07;
+const DW_CFA_SAME_VALUE: u8 = 0x08;
+const DW_CFA_REGISTER: u8 = 0x09;
+const DW_CFA_REMEMBER_STATE: u8 = 0x0a;
+const DW_CFA_RESTORE_STATE: u8 = 0x0b;
+const DW_CFA_DEF_CFA: u8 = 0x0c;
+const DW_CFA_DEF_CFA_REGISTER: u8 = 0x0d;
+const DW_CFA_DEF_CFA_OFFSET: u8 = 0x0e;
+const DW_CFA_DEF_CFA_EXPRESSION: u8 = 0x0f;
+const DW_CFA_EXPRESSION: u8 = 0x10;
+const DW_CFA_OFFSET_EXTENDED_SF: u8 = 0x11;
+const DW_CFA_DEF_CFA_SF: u8 = 0x12;
+const DW_CFA_DEF_CFA_OFFSET_SF: u8 = 0x13;
+const DW_CFA_VAL_OFFSET: u8 = 0x14;
+const DW_CFA_VAL_OFFSET_SF: u8 = 0x15;
+const DW_CFA_VAL_EXPRESSION: u8 = 0x16;
+const DW_CFA_GNU_ARGS_SIZE: u8 = 0x2e;
+const DW_CFA_GNU_NEGATIVE_OFFSET_EXTENDED: u8 = 0x2f;
+
+const DW_EH_PE_ABSPTR: u8 = 0x00;
+const DW_EH_PE_PCREL: u8 = 0x10;
+const DW_EH_PE_OMIT: u8 = 0xff;
+
+/// A range of addresses generated from the same source line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LineRange {
+    pub start: u64,
+    pub end: u64,
+    /// Index into LineTable::files
+    pub file: usize,
+    pub line: u64,
+}
+
+/// Address to source line mapping of a program, built from .debug_line.
+#[derive(Clone, Debug, Default)]
+pub struct LineTable {
+    files: Vec<String>,
+    ranges: Vec<LineRange>,
+}
+
+impl LineTable {
+    /// Parses the line table of an ELF program. A program without
+    /// .debug_line gives an empty table.
+    pub fn parse(program: &[u8]) -> Result<Self, Error> {
+        let elf = goblin_v040::elf::Elf::parse(program)?;
+        let sections = Sections {
+            line: elf_section(&elf, program, ".debug_line")?.0,
+            line_str: elf_section(&elf, program, ".debug_line_str")?.0,
+            str: elf_section(&elf, program, ".debug_str")?.0,
+        };
+        Self::parse_sections(&sections)
+    }
+
+    fn parse_sections(sections: &Sections) -> Result<Self, Error> {
+        let mut table = LineTable::default();
+        let mut reader = Reader::new(sections.line);
+        while !reader.is_empty() {
+            table.parse_unit(&mut reader, sections)?;
+        }
+        table.ranges.sort_by_key(|r| (r.start, r.end));
+        Ok(table)
+    }
+
+    fn parse_unit(&mut self, reader: &mut Reader, sections: &Sections) -> Result<(), Error> {
+        let (unit_length, offset_size) = reader.initial_length()?;
+        let mut unit = Reader::new(reader.bytes(unit_length)?);
+        let version = unit.u16()?;
+        if !(2..=5).contains(&version) {
+            return Err(malformed(&format!("unsupported version {}", version)));
+        }
+        if version >= 5 {
+            // address_size and segment_selector_size, DW_LNE_set_address
+            // carries its own length so they are not needed.
+            unit.u8()?;
+            unit.u8()?;
+        }
+        let header_length = unit.offset(offset_size)?;
+        let mut program = unit.clone();
+        program.skip(header_length)?;
+        let minimum_instruction_length = u64::from(unit.u8()?);
+        if version >= 4 {
+            // maximum_operations_per_instruction, only useful for VLIW.
+            unit.u8()?;
+        }
+        // default_is_stmt, all rows are kept regardless of is_stmt.
+        unit.u8()?;
+        let line_base = unit.u8()? as i8;
+        let line_range = unit.u8()?;
+        let opcode_base = unit.u8()?;
+        if line_range == 0 || opcode_base == 0 {
+            return Err(malformed("invalid line program header"));
+        }
+        let mut standard_opcode_lengths = vec![];
+        for _ in 1..opcode_base {
+            standard_opcode_lengths.push(unit.u8()?);
+        }
+
+        // Maps file numbers used by the line program to indices in self.files.
+        // Before DWARF 5, file numbers start from 1.
+        let mut files = vec![];
+        if version >= 5 {
+            let directories = unit.entries(offset_size, sections)?;
+            let directories: Vec<String> = directories.into_iter().map(|(p, _)| p).collect();
+            for (path, directory) in unit.entries(offset_size, sections)? {
+                let directory = directories.get(directory as usize).map(|d| d.as_str());
+                files.push(self.add_file(directory, &path));
+            }
+        } else {
+            let mut directories = vec![];
+            loop {
+                let directory = unit.string()?;
+                if directory.is_empty() {
+                    break;
+                }
+                directories.push(directory);
+            }
+            files.push(usize::max_value());
+            loop {
+                let path = unit.string()?;
+                if path.is_empty() {
+                    break;
+                }
+                let directory = unit.uleb128()?;
+                // Modification time and length
+                unit.uleb128()?;
+                unit.uleb128()?;
+                let directory = directory
+                    .checked_sub(1)
+                    .and_then(|d| directories.get(d as usize))
+                    .map(|d| d.as_str());
+                files.push(self.add_file(directory, &path));
+            }
+        }
+
+        let mut state = LineState::new();
+        // Rows of the current sequence as (address, file, line)
+        let mut rows: Vec<(u64, u64, u64)> = vec![];
+        while !program.is_empty() {
+            let opcode = program.u8()?;
+            if opcode >= opcode_base {
+                let adjusted = opcode - opcode_base;
+                state.address = state.address.wrapping_add(
+                    u64::from(adjusted / line_range).wrapping_mul(minimum_instruction_length),
+                );
+                state.line = state
+                    .line
+                    .wrapping_add((i64::from(line_base) + i64::from(adjusted % line_range)) as u64);
+                rows.push((state.address, state.file, state.line));
+                continue;
+            }
+            match opcode {
+                0 => {
+                    let length = program.uleb128()? as usize;
+                    let mut extended = Reader::new(program.bytes(length)?);
+                    match extended.u8()? {
+                        DW_LNE_END_SEQUENCE => {
+                            self.add_sequence(&rows, state.address, &files);
+                            rows.clear();
+                            state = LineState::new();
+                        }
+                        DW_LNE_SET_ADDRESS => {
+                            state.address = extended.address(length - 1)?;
+                        }
+                        DW_LNE_DEFINE_FILE => {
+                            let path = extended.string()?;
+                            files.push(self.add_file(None, &path));
+                        }
+                        _ => (),
+                    }
+                }
+                DW_LNS_COPY => rows.push((state.address, state.file, state.line)),
+                DW_LNS_ADVANCE_PC => {
+                    state.address = state
+                        .address
+                        .wrapping_add(program.uleb128()?.wrapping_mul(minimum_instruction_length));
+                }
+                DW_LNS_ADVANCE_LINE => {
+                    state.line = state.line.wrapping_add(program.sleb128()? as u64);
+                }
+                DW_LNS_SET_FILE => state.file = program.uleb128()?,
+                DW_LNS_CONST_ADD_PC => {
+                    let adjusted = 255 - opcode_base;
+                    state.address = state.address.wrapping_add(
+                        u64::from(adjusted / line_range).wrapping_mul(minimum_instruction_length),
+                    );
+                }
+                DW_LNS_FIXED_ADVANCE_PC => {
+                    state.address = state.address.wrapping_add(u64::from(program.u16()?));
+                }
+                _ => {
+                    // Opcodes we are not interested in, including the ones
+                    // unknown to us, are skipped with their ULEB128 operands.
+                    for _ in 0..standard_opcode_lengths[opcode as usize - 1] {
+                        program.uleb128()?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn add_file(&mut self, directory: Option<&str>, path: &str) -> usize {
+        let path = match directory {
+            Some(directory) if !path.starts_with('/') && !directory.is_empty() => {
+                format!("{}/{}", directory.trim_end_matches('/'), path)
+            }
+            _ => path.to_string(),
+        };
+        match self.files.iter().position(|f| *f == path) {
+            Some(i) => i,
+            None => {
+                self.files.push(path);
+                self.files.len() - 1
+            }
+        }
+    }
+
+    fn add_sequence(&mut self, rows: &[(u64, u64, u64)], end: u64, files: &[usize]) {
+        for (i, (start, file, line)) in rows.iter().enumerate() {
+            let next = rows.get(i + 1).map(|r| r.0).unwrap_or(end);
+            // Code removed by the linker is usually left at address 0.
+            if next <= *start || *start == 0 {
+                continue;
+            }
+            if let Some(file) = files
+                .get(*file as usize)
+                .filter(|f| **f != usize::max_value())
+            {
+                self.ranges.push(LineRange {
+                    start: *start,
+                    end: next,
+                    file: *file,
+                    line: *line,
+                });
+            }
+        }
+    }
+
+    /// Finds the source file and line of an address.
+    pub fn lookup(&self, address: u64) -> Option<(&str, u64)> {
+        let i = self.ranges.partition_point(|r| r.start <= address);
+        let range = self.ranges.get(i.checked_sub(1)?)?;
+        if address < range.end {
+            Some((&self.files[range.file], range.line))
+        } else {
+            None
+        }
+    }
+
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+
+    /// All address ranges sorted by start address.
+    pub fn ranges(&self) -> &[LineRange] {
+        &self.ranges
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+}
+
+/// How to recover the value a register had in the caller.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegisterRule {
+    Undefined,
+    SameValue,
+    /// Saved at CFA + offset.
+    Offset(i64),
+    /// The value is CFA + offset.
+    ValOffset(i64),
+    /// Held in another register.
+    Register(u16),
+    /// Described by a DWARF expression, which is not supported.
+    Expression,
+}
+
+/// Unwinding rules at an address. The CFA is the value of the stack pointer
+/// at the call site in the caller.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrameRow {
+    /// The register and offset giving the CFA, None when the CFA is described
+    /// by a DWARF expression.
+    pub cfa: Option<(u16, i64)>,
+    /// The column holding the return address.
+    pub return_address: u16,
+    rules: Vec<(u16, RegisterRule)>,
+}
+
+impl FrameRow {
+    /// Registers without a rule keep their value, this is what compilers
+    /// assume for callee saved registers they never touch.
+    pub fn rule(&self, register: u16) -> RegisterRule {
+        self.rules
+            .iter()
+            .find(|(r, _)| *r == register)
+            .map(|(_, rule)| *rule)
+            .unwrap_or(RegisterRule::SameValue)
+    }
+
+    fn set_rule(&mut self, register: u16, rule: RegisterRule) {
+        self.rules.retain(|(r, _)| *r != register);
+        self.rules.push((register, rule));
+    }
+}
+
+#[derive(Clone, Debug)]
+struct CommonInformation {
+    code_alignment: u64,
+    data_alignment: i64,
+    return_address: u16,
+    pointer_encoding: u8,
+    augmented: bool,
+    instructions: Vec<u8>,
+}
+
+#[derive(Clone, Debug)]
+struct FrameDescription {
+    start: u64,
+    end: u64,
+    cie: usize,
+    instructions: Vec<u8>,
+}
+
+/// Call frame information of a program, built from .debug_frame and
+/// .eh_frame.
+#[derive(Clone, Debug, Default)]
+pub struct CallFrameTable {
+    address_size: usize,
+    cies: Vec<CommonInformation>,
+    fdes: Vec<FrameDescription>,
+}
+
+impl CallFrameTable {
+    /// Parses the call frame information of an ELF program. A program
+    /// without .debug_frame or .eh_frame gives an empty table.
+    pub fn parse(program: &[u8]) -> Result<Self, Error> {
+        let elf = goblin_v040::elf::Elf::parse(program)?;
+        let mut table = CallFrameTable {
+            address_size: if elf.is_64 { 8 } else { 4 },
+            ..Default::default()
+        };
+        let (debug_frame, _) = elf_section(&elf, program, ".debug_frame")?;
+        table.parse_section(debug_frame, 0, false)?;
+        let (eh_frame, eh_frame_address) = elf_section(&elf, program, ".eh_frame")?;
+        table.parse_section(eh_frame, eh_frame_address, true)?;
+        table.fdes.sort_by_key(|f| (f.start, f.end));
+        Ok(table)
+    }
+
+    fn parse_section(&mut self, section: &[u8], address: u64, eh: bool) -> Result<(), Error> {
+        // CIEs are referenced by their offsets in the section.
+        let mut cies = HashMap::new();
+        let mut fdes = vec![];
+        let mut reader = Reader::new(section);
+        while !reader.is_empty() {
+            let offset = reader.position(section);
+            let (length, offset_size) = reader.initial_length()?;
+            if length == 0 {
+                // The terminator of .eh_frame
+                if eh {
+                    break;
+                }
+                continue;
+            }
+            let mut entry = Reader::new(reader.bytes(length)?);
+            let id_position = entry.position(section);
+            let id = entry.offset(offset_size)?;
+            let is_cie = if eh {
+                id == 0
+            } else {
+                id == u64::max_value() >> (64 - offset_size * 8)
+            };
+            if is_cie {
+                if let Some(cie) = self.parse_cie(&mut entry, eh)? {
+                    cies.insert(offset, self.cies.len());
+                    self.cies.push(cie);
+                }
+            } else {
+                let cie_offset = if eh {
+                    id_position.wrapping_sub(id as usize)
+                } else {
+                    id as usize
+                };
+                fdes.push((cie_offset, entry));
+            }
+        }
+        for (cie_offset, mut entry) in fdes {
+            // FDEs of a CIE with an unknown augmentation are skipped.
+            let index = match cies.get(&cie_offset) {
+                Some(index) => *index,
+                None => continue,
+            };
+            let cie = &self.cies[index];
+            let base = address.wrapping_add(entry.position(section) as u64);
+            let start = entry.pointer(cie.pointer_encoding, self.address_size, base)?;
+            let range = entry.pointer(cie.pointer_encoding & 0x0f, self.address_size, 0)?;
+            if cie.augmented {
+                let length = entry.uleb128()?;
+                entry.skip(length)?;
+            }
+            // Code removed by the linker is usually left at address 0.
+            if start == 0 || range == 0 {
+                continue;
+            }
+            self.fdes.push(FrameDescription {
+                start,
+                end: start.wrapping_add(range),
+                cie: index,
+                instructions: entry.data.to_vec(),
+            });
+        }
+        Ok(())
+    }
+
+    fn parse_cie(&self, entry: &mut Reader, eh: bool) -> Result<Option<CommonInformation>, Error> {
+        let version = entry.u8()?;
+        let augmentation = entry.string()?;
+        if version >= 4 {
+            // address_size and segment_selector_size
+            entry.u8()?;
+            entry.u8()?;
+        }
+        let code_alignment = entry.uleb128()?;
+        let data_alignment = entry.sleb128()?;
+        let return_address = if version == 1 {
+            u16::from(entry.u8()?)
+        } else {
+            entry.uleb128()? as u16
+        };
+        let mut cie = CommonInformation {
+            code_alignment,
+            data_alignment,
+            return_address,
+            pointer_encoding: DW_EH_PE_ABSPTR,
+            augmented: augmentation.starts_with('z'),
+            instructions: vec![],
+        };
+        if cie.augmented {
+            let length = entry.uleb128()? as usize;
+            let mut data = Reader::new(entry.bytes(length)?);
+            for c in augmentation.chars().skip(1) {
+                match c {
+                    'R' => cie.pointer_encoding = data.u8()?,
+                    'P' => {
+                        let encoding = data.u8()?;
+                        // The personality routine is not used, only the
+                        // size of the pointer matters.
+                        data.pointer(encoding & 0x0f, self.address_size, 0)?;
+                    }
+                    'L' => {
+                        data.u8()?;
+                    }
+                    'S' | 'B' => (),
+                    _ => break,
+                }
+            }
+        } else if !augmentation.is_empty() || (eh && version != 1 && version != 3) {
+            return Ok(None);
+        }
+        cie.instructions = entry.data.to_vec();
+        Ok(Some(cie))
+    }
+
+    /// Computes the unwinding rules at an address, None when the address is
+    /// not covered by any FDE or the rules cannot be decoded.
+    pub fn find(&self, address: u64) -> Option<FrameRow> {
+        let i = self.fdes.partition_point(|f| f.start <= address);
+        let fde = self.fdes.get(i.checked_sub(1)?)?;
+        if address >= fde.end {
+            return None;
+        }
+        let cie = &self.cies[fde.cie];
+        let mut row = FrameRow {
+            cfa: None,
+            return_address: cie.return_address,
+            rules: vec![],
+        };
+        let mut location = fde.start;
+        let empty = row.clone();
+        self.execute(
+            cie,
+            &cie.instructions,
+            &mut row,
+            &empty,
+            &mut location,
+            address,
+        )
+        .ok()?;
+        let initial = row.clone();
+        self.execute(
+            cie,
+            &fde.instructions,
+            &mut row,
+            &initial,
+            &mut location,
+            address,
+        )
+        .ok()?;
+        Some(row)
+    }
+
+    // Runs call frame instructions until the location passes the address.
+    fn execute(
+        &self,
+        cie: &CommonInformation,
+        instructions: &[u8],
+        row: &mut FrameRow,
+        initial: &FrameRow,
+        location: &mut u64,
+        address: u64,
+    ) -> Result<(), Error> {
+        let mut reader = Reader::new(instructions);
+        let mut stack = vec![];
+        while !reader.is_empty() {
+            let opcode = reader.u8()?;
+            let mut next_location = None;
+            match opcode & 0xc0 {
+                DW_CFA_ADVANCE_LOC => {
+                    next_location =
+                        Some(location.wrapping_add(
+                            u64::from(opcode & 0x3f).wrapping_mul(cie.code_alignment),
+                        ));
+                }
+                DW_CFA_OFFSET => {
+                    let offset = (reader.uleb128()? as i64).wrapping_mul(cie.data_alignment);
+                    row.set_rule(u16::from(opcode & 0x3f), RegisterRule::Offset(offset));
+                }
+                DW_CFA_RESTORE => {
+                    let register = u16::from(opcode & 0x3f);
+                    row.set_rule(register, initial.rule(register));
+                }
+                _ => match opcode {
+                    DW_CFA_NOP => (),
+                    DW_CFA_SET_LOC => {
+                        next_location = Some(reader.pointer(
+                            cie.pointer_encoding & 0x0f,
+                            self.address_size,
+                            0,
+                        )?);
+                    }
+                    DW_CFA_ADVANCE_LOC1 | DW_CFA_ADVANCE_LOC2 | DW_CFA_ADVANCE_LOC4 => {
+                        let size = 1 << (opcode - DW_CFA_ADVANCE_LOC1);
+                        next_location =
+                            Some(location.wrapping_add(
+                                reader.address(size)?.wrapping_mul(cie.code_alignment),
+                            ));
+                    }
+                    DW_CFA_OFFSET_EXTENDED | DW_CFA_OFFSET_EXTENDED_SF => {
+                        let register = reader.uleb128()? as u16;
+                        let offset = if opcode == DW_CFA_OFFSET_EXTENDED {
+                            reader.uleb128()? as i64
+                        } else {
+                            reader.sleb128()?
+                        };
+                        let offset = offset.wrapping_mul(cie.data_alignment);
+                        row.set_rule(register, RegisterRule::Offset(offset));
+                    }
+                    DW_CFA_GNU_NEGATIVE_OFFSET_EXTENDED => {
+                        let register = reader.uleb128()? as u16;
+                        let offset = (reader.uleb128()? as i64).wrapping_mul(cie.data_alignment);
+                        row.set_rule(register, RegisterRule::Offset(offset.wrapping_neg()));
+                    }
+                    DW_CFA_VAL_OFFSET | DW_CFA_VAL_OFFSET_SF => {
+                        let register = reader.uleb128()? as u16;
+                        let offset = if opcode == DW_CFA_VAL_OFFSET {
+                            reader.uleb128()? as i64
+                        } else {
+                            reader.sleb128()?
+                        };
+                        let offset = offset.wrapping_mul(cie.data_alignment);
+                        row.set_rule(register, RegisterRule::ValOffset(offset));
+                    }
+                    DW_CFA_RESTORE_EXTENDED => {
+                        let register = reader.uleb128()? as u16;
+                        row.set_rule(register, initial.rule(register));
+                    }
+                    DW_CFA_UNDEFINED => {
+                        row.set_rule(reader.uleb128()? as u16, RegisterRule::Undefined);
+                    }
+                    DW_CFA_SAME_VALUE => {
+                        row.set_rule(reader.uleb128()? as u16, RegisterRule::SameValue);
+                    }
+                    DW_CFA_REGISTER => {
+                        let register = reader.uleb128()? as u16;
+                        let other = reader.uleb128()? as u16;
+                        row.set_rule(register, RegisterRule::Register(other));
+                    }
+                    DW_CFA_REMEMBER_STATE => stack.push(row.clone()),
+                    DW_CFA_RESTORE_STATE => {
+                        *row = stack
+                            .pop()
+                            .ok_or_else(|| malformed("unbalanced DW_CFA_restore_state"))?;
+                    }
+                    DW_CFA_DEF_CFA | DW_CFA_DEF_CFA_SF => {
+                        let register = reader.uleb128()? as u16;
+                        let offset = if opcode == DW_CFA_DEF_CFA {
+                            reader.uleb128()? as i64
+                        } else {
+                            reader.sleb128()?.wrapping_mul(cie.data_alignment)
+                        };
+                        row.cfa = Some((register, offset));
+                    }
+                    DW_CFA_DEF_CFA_REGISTER => {
+                        let register = reader.uleb128()? as u16;
+                        row.cfa = Some((register, row.cfa.map(|c| c.1).unwrap_or(0)));
+                    }
+                    DW_CFA_DEF_CFA_OFFSET | DW_CFA_DEF_CFA_OFFSET_SF => {
+                        let offset = if opcode == DW_CFA_DEF_CFA_OFFSET {
+                            reader.uleb128()? as i64
+                        } else {
+                            reader.sleb128()?.wrapping_mul(cie.data_alignment)
+                        };
+                        let (register, _) = row
+                            .cfa
+                            .ok_or_else(|| malformed("CFA offset without a register"))?;
+                        row.cfa = Some((register, offset));
+                    }
+                    DW_CFA_DEF_CFA_EXPRESSION => {
+                        let length = reader.uleb128()?;
+                        reader.skip(length)?;
+                        row.cfa = None;
+                    }
+                    DW_CFA_EXPRESSION | DW_CFA_VAL_EXPRESSION => {
+                        let register = reader.uleb128()? as u16;
+                        let length = reader.uleb128()?;
+                        reader.skip(length)?;
+                        row.set_rule(register, RegisterRule::Expression);
+                    }
+                    DW_CFA_GNU_ARGS_SIZE => {
+                        reader.uleb128()?;
+                    }
+                    _ => {
+                        return Err(malformed(&format!(
+                            "unsupported call frame instruction 0x{:x}",
+                            opcode
+                        )))
+                    }
+                },
+            }
+            if let Some(next_location) = next_location {
+                if next_location > address {
+                    return Ok(());
+                }
+                *location = next_location;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fdes.is_empty()
+    }
+}
+
+struct Sections<'a> {
+    line: &'a [u8],
+    line_str: &'a [u8],
+    str: &'a [u8],
+}
+
+struct LineState {
+    address: u64,
+    file: u64,
+    line: u64,
+}
+
+impl LineState {
+    fn new() -> Self {
+        Self {
+            address: 0,
+            file: 1,
+            line: 1,
+        }
+    }
+}
+
+// Returns the contents and the address of a section, a missing section is
+// returned as empty.
+fn elf_section<'a>(
+    elf: &goblin_v040::elf::Elf,
+    program: &'a [u8],
+    name: &str,
+) -> Result<(&'a [u8], u64), Error> {
+    for header in &elf.section_headers {
+        if elf.shdr_strtab.get(header.sh_name).and_then(|n| n.ok()) == Some(name) {
+            let start = header.sh_offset as usize;
+            let end = start.wrapping_add(header.sh_size as usize);
+            return program
+                .get(start..end)
+                .map(|data| (data, header.sh_addr))
+                .ok_or_else(|| malformed(&format!("section {} out of bound", name)));
+        }
+    }
+    Ok((&[], 0))
+}
+
+fn malformed(message: &str) -> Error {
+    Error::ElfParseError(format!("malformed DWARF data: {}", message))
+}
+
+#[derive(Clone)]
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8], Error> {
+        if length > self.data.len() {
+            return Err(malformed("unexpected end of data"));
+        }
+        let (head, tail) = self.data.split_at(length);
+        self.data = tail;
+        Ok(head)
+    }
+
+    // Offset of the reader into the section it reads from.
+    fn position(&self, section: &[u8]) -> usize {
+        self.data.as_ptr() as usize - section.as_ptr() as usize
+    }
+
+    fn skip(&mut self, length: u64) -> Result<(), Error> {
+        self.bytes(length as usize).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        Ok(self.address(2)? as u16)
+    }
+
+    // Reads a little endian unsigned integer of the given size
+    fn address(&mut self, size: usize) -> Result<u64, Error> {
+        if size > 8 {
+            return Err(malformed("integer too large"));
+        }
+        let bytes = self.bytes(size)?;
+        Ok(bytes
+            .iter()
+            .rev()
+            .fold(0u64, |value, b| (value << 8) | u64::from(*b)))
+    }
+
+    fn offset(&mut self, offset_size: usize) -> Result<u64, Error> {
+        self.address(offset_size)
+    }
+
+    // Returns the unit length and the size of offsets in the unit
+    fn initial_length(&mut self) -> Result<(usize, usize), Error> {
+        let length = self.address(4)?;
+        if length == 0xffff_ffff {
+            Ok((self.address(8)? as usize, 8))
+        } else {
+            Ok((length as usize, 4))
+        }
+    }
+
+    // Reads a pointer in the .eh_frame encoding, base is the address of the
+    // pointer, used by PC relative pointers.
+    fn pointer(&mut self, encoding: u8, address_size: usize, base: u64) -> Result<u64, Error> {
+        if encoding == DW_EH_PE_OMIT {
+            return Ok(0);
+        }
+        let value = match encoding & 0x0f {
+            0x00 => self.address(address_size)?,
+            0x01 => self.uleb128()?,
+            0x02 => self.address(2)?,
+            0x03 => self.address(4)?,
+            0x04 | 0x0c => self.address(8)?,
+            0x09 => self.sleb128()? as u64,
+            0x0a => self.address(2)? as i16 as u64,
+            0x0b => self.address(4)? as i32 as u64,
+            _ => return Err(malformed(&format!("unsupported encoding 0x{:x}", encoding))),
+        };
+        match encoding & 0xf0 {
+            DW_EH_PE_ABSPTR => Ok(value),
+            DW_EH_PE_PCREL => Ok(base.wrapping_add(value)),
+            _ => Err(malformed(&format!("unsupported encoding 0x{:x}", encoding))),
+        }
+    }
+
+    fn uleb128(&mut self) -> Result<u64, Error> {
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.u8()?;
+            if shift < 64 {
+                value |= u64::from(byte & 0x7f) << shift;
+            }
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn sleb128(&mut self) -> Result<i64, Error> {
+        let mut value = 0i64;
+        let mut shift = 0;
+        loop {
+            let byte = self.u8()?;
+            if shift < 64 {
+                value |= i64::from(byte & 0x7f) << shift;
+            }
+            shift += 7;
+            if byte & 0x80 == 0 {
+                if shift < 64 && byte & 0x40 != 0 {
+                    value |= -1 << shift;
+                }
+                return Ok(value);
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        let end = self
+            .data
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or_else(|| malformed("unterminated string"))?;
+        let s = String::from_utf8_lossy(&self.data[..end]).into_owned();
+        self.data = &self.data[end + 1..];
+        Ok(s)
+    }
+
+    // Reads a DWARF 5 directory or file name table, returning the path and
+    // directory index of each entry.
+    fn entries(
+        &mut self,
+        offset_size: usize,
+        sections: &Sections,
+    ) -> Result<Vec<(String, u64)>, Error> {
+        let format_count = self.u8()?;
+        let mut formats = vec![];
+        for _ in 0..format_count {
+            formats.push((self.uleb128()?, self.uleb128()?));
+        }
+        let count = self.uleb128()?;
+        let mut entries = vec![];
+        for _ in 0..count {
+            let mut path = String::new();
+            let mut directory = 0;
+            for (content, form) in &formats {
+                let value = self.attribute(*form, offset_size, sections)?;
+                match (*content, value) {
+                    (DW_LNCT_PATH, Attribute::String(s)) => path = s,
+                    (DW_LNCT_DIRECTORY_INDEX, Attribute::Unsigned(d)) => directory = d,
+                    _ => (),
+                }
+            }
+            entries.push((path, directory));
+        }
+        Ok(entries)
+    }
+
+    fn attribute(
+        &mut self,
+        form: u64,
+        offset_size: usize,
+        sections: &Sections,
+    ) -> Result<Attribute, Error> {
+        let value = match form {
+            DW_FORM_STRING => Attribute::String(self.string()?),
+            DW_FORM_LINE_STRP | DW_FORM_STRP => {
+                let section = if form == DW_FORM_LINE_STRP {
+                    sections.line_str
+                } else {
+                    sections.str
+                };
+                let offset = self.offset(offset_size)? as usize;
+                let mut reader = Reader::new(
+                    section
+                        .get(offset..)
+                        .ok_or_else(|| malformed("string offset out of bound"))?,
+                );
+                Attribute::String(reader.string()?)
+            }
+            DW_FORM_DATA1 => Attribute::Unsigned(self.address(1)?),
+            DW_FORM_DATA2 => Attribute::Unsigned(self.address(2)?),
+            DW_FORM_DATA4 => Attribute::Unsigned(self.address(4)?),
+            DW_FORM_DATA8 => Attribute::Unsigned(self.address(8)?),
+            DW_FORM_UDATA => Attribute::Unsigned(self.uleb128()?),
+            DW_FORM_SDATA => Attribute::Unsigned(self.sleb128()? as u64),
+            DW_FORM_DATA16 => {
+                self.bytes(16)?;
+                Attribute::Skipped
+            }
+            DW_FORM_BLOCK | DW_FORM_BLOCK1 | DW_FORM_BLOCK2 | DW_FORM_BLOCK4 => {
+                let length = match form {
+                    DW_FORM_BLOCK1 => self.address(1)?,
+                    DW_FORM_BLOCK2 => self.address(2)?,
+                    DW_FORM_BLOCK4 => self.address(4)?,
+                    _ => self.uleb128()?,
+                };
+                self.skip(length)?;
+                Attribute::Skipped
+            }
+            _ => return Err(malformed(&format!("unsupported form 0x{:x}", form))),
+        };
+        Ok(value)
+    }
+}
+
+enum Attribute {
+    String(String),
+    Unsigned(u64),
+    Skipped,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_leb128() {
+        let data = [0xe5, 0x8e, 0x26, 0x7f, 0x80, 0x7f];
+        let mut reader = Reader::new(&data);
+        assert_eq!(reader.uleb128().unwrap(), 624485);
+        assert_eq!(reader.sleb128().unwrap(), -1);
+        assert_eq!(reader.sleb128().unwrap(), -128);
+        assert!(reader.is_empty());
+        assert!(reader.uleb128().is_err());
+    }
+}
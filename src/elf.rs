@@ -208,3 +208,138 @@ pub fn parse_elf<R: Register>(program: &Bytes, version: u32) -> Result<ProgramMe
     }
     Ok(ProgramMetadata { actions, entry })
 }
+
+/// Location of the program header table once the program is loaded, as
+/// reported by AT_PHDR, AT_PHENT and AT_PHNUM in the auxiliary vector.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ProgramHeaderTable {
+    /// 0 when no loaded segment covers the table.
+    pub address: u64,
+    pub entry_size: u64,
+    pub count: u64,
+}
+
+pub fn program_header_table(program: &Bytes) -> Result<ProgramHeaderTable, Error> {
+    use goblin_v040::container::Ctx;
+    use goblin_v040::elf::{program_header::ProgramHeader as GoblinProgramHeader, Header};
+    let header = program.pread::<Header>(0)?;
+    let container = header.container().map_err(|_e| Error::ElfBits)?;
+    let endianness = header.endianness().map_err(|_e| Error::ElfBits)?;
+    let program_headers = GoblinProgramHeader::parse(
+        program,
+        header.e_phoff as usize,
+        header.e_phnum as usize,
+        Ctx::new(container, endianness),
+    )?;
+    let address = program_headers
+        .iter()
+        .find(|h| {
+            h.p_type == PT_LOAD
+                && h.p_offset <= header.e_phoff
+                && header.e_phoff < h.p_offset.wrapping_add(h.p_filesz)
+        })
+        .map(|h| h.p_vaddr.wrapping_add(header.e_phoff - h.p_offset))
+        .unwrap_or(0);
+    Ok(ProgramHeaderTable {
+        address,
+        entry_size: u64::from(header.e_phentsize),
+        count: u64::from(header.e_phnum),
+    })
+}
+
+/// The initial program break, the first page above all loaded segments.
+pub fn program_break(program: &Bytes) -> Result<u64, Error> {
+    use goblin_v040::container::Ctx;
+    use goblin_v040::elf::{program_header::ProgramHeader as GoblinProgramHeader, Header};
+    let header = program.pread::<Header>(0)?;
+    let container = header.container().map_err(|_e| Error::ElfBits)?;
+    let endianness = header.endianness().map_err(|_e| Error::ElfBits)?;
+    let program_headers = GoblinProgramHeader::parse(
+        program,
+        header.e_phoff as usize,
+        header.e_phnum as usize,
+        Ctx::new(container, endianness),
+    )?;
+    let end = program_headers
+        .iter()
+        .filter(|h| h.p_type == PT_LOAD)
+        .map(|h| h.p_vaddr.saturating_add(h.p_memsz))
+        .max()
+        .unwrap_or(0);
+    Ok(round_page_up(end))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub address: u64,
+    pub size: u64,
+}
+
+/// Function symbols of a program sorted by address. This is only meant for
+/// debugging tools, unlike parse_elf, goblin's full ELF parser is used here.
+#[derive(Clone, Debug, Default)]
+pub struct Symbols {
+    symbols: Vec<Symbol>,
+}
+
+impl Symbols {
+    pub fn parse(program: &[u8]) -> Result<Self, Error> {
+        Self::parse_with(program, |sym| sym.is_function())
+    }
+
+    /// Like parse, but also keeps the defined symbols without a type, such as
+    /// labels in assembly programs.
+    pub fn parse_labels(program: &[u8]) -> Result<Self, Error> {
+        Self::parse_with(program, |sym| {
+            sym.is_function()
+                || (sym.st_type() == goblin_v040::elf::sym::STT_NOTYPE && sym.st_shndx != 0)
+        })
+    }
+
+    fn parse_with<F: Fn(&goblin_v040::elf::Sym) -> bool>(
+        program: &[u8],
+        filter: F,
+    ) -> Result<Self, Error> {
+        let elf = goblin_v040::elf::Elf::parse(program)?;
+        let mut symbols: Vec<Symbol> = elf
+            .syms
+            .iter()
+            .filter(|sym| filter(sym) && sym.st_value != 0)
+            .filter_map(|sym| {
+                let name = elf.strtab.get(sym.st_name)?.ok()?;
+                // Mapping symbols such as $x only mark the kind of contents.
+                if name.is_empty() || name.starts_with('$') {
+                    return None;
+                }
+                Some(Symbol {
+                    name: name.to_string(),
+                    address: sym.st_value,
+                    size: sym.st_size,
+                })
+            })
+            .collect();
+        symbols.sort_by_key(|s| s.address);
+        symbols.dedup_by_key(|s| s.address);
+        Ok(Self { symbols })
+    }
+
+    /// Finds the function containing the address.
+    pub fn lookup(&self, address: u64) -> Option<&Symbol> {
+        let i = self.symbols.partition_point(|s| s.address <= address);
+        let symbol = self.symbols.get(i.checked_sub(1)?)?;
+        if symbol.size == 0 || address < symbol.address.wrapping_add(symbol.size) {
+            Some(symbol)
+        } else {
+            None
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Symbol> {
+        self.symbols.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+}
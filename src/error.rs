@@ -1,11 +1,18 @@
+use crate::instructions::Instruction;
+use serde::{Deserialize, Serialize};
+
 #[derive(Debug, PartialEq, Clone, Eq, Display)]
 pub enum Error {
     #[display(fmt = "asm error: {}", "_0")]
     Asm(u8),
+    #[display(fmt = "breakpoint pc=0x{:x}", "_0")]
+    Breakpoint(u64),
     #[display(fmt = "cycles error: max cycles exceeded")]
     CyclesExceeded,
     #[display(fmt = "cycles error: overflow")]
     CyclesOverflow,
+    #[display(fmt = "scheduler error: every process is blocked")]
+    Deadlock,
     #[display(fmt = "elf error: bits")]
     ElfBits,
     #[display(fmt = "elf error: {}", "_0")]
@@ -52,8 +59,27 @@ pub enum Error {
     MemWriteOnFreezedPage(u64),
     #[display(fmt = "pause")]
     Pause,
+    #[display(fmt = "stack overflow pc=0x{:x} sp=0x{:x}", "pc", "sp")]
+    StackOverflow { pc: u64, sp: u64 },
+    #[display(fmt = "uninitialized read pc=0x{:x} addr=0x{:x}", "pc", "address")]
+    UninitializedRead { pc: u64, address: u64 },
     #[display(fmt = "unexpected error")]
     Unexpected(String),
+    #[display(
+        fmt = "watchpoint pc=0x{:x} addr=0x{:x}, kind={:?}",
+        "pc",
+        "address",
+        "kind"
+    )]
+    Watchpoint {
+        pc: u64,
+        address: u64,
+        kind: WatchpointKind,
+    },
+    // Returned by a syscall to give control back to the caller of run, such
+    // as a scheduler, the machine is resumed by calling run again.
+    #[display(fmt = "yield")]
+    Yield,
 }
 
 #[derive(Debug, PartialEq, Clone, Eq, Display)]
@@ -62,8 +88,52 @@ pub enum OutOfBoundKind {
     ExternalData,
 }
 
+#[derive(Debug, PartialEq, Clone, Copy, Eq, Display, Serialize, Deserialize)]
+pub enum WatchpointKind {
+    Read,
+    Write,
+    Access,
+}
+
 impl std::error::Error for Error {}
 
+/// The context of a failed instruction, recorded by machines built with error
+/// reports enabled, see DefaultMachineBuilder::error_report.
+#[derive(Debug, PartialEq, Clone, Eq, Serialize, Deserialize)]
+pub struct ErrorReport {
+    /// The error returned by the machine.
+    pub error: String,
+    /// PC of the failed instruction.
+    pub pc: u64,
+    /// The decoded instruction, None when the failure happened before the
+    /// instruction is decoded.
+    pub instruction: Option<Instruction>,
+    pub disassembly: Option<String>,
+    pub registers: Vec<u64>,
+    /// Cycles consumed when the instruction failed.
+    pub cycles: u64,
+    /// The memory accessed by the instruction, if any.
+    pub access: Option<MemoryAccess>,
+}
+
+#[derive(Debug, PartialEq, Clone, Eq, Serialize, Deserialize)]
+pub struct MemoryAccess {
+    pub address: u64,
+    pub size: u64,
+    pub kind: WatchpointKind,
+    /// Flags of the accessed page at the time of the failure, None when the
+    /// address is out of the memory.
+    pub page_flags: Option<u8>,
+}
+
+impl ErrorReport {
+    /// Serializes the report to a JSON object, which can be read back with
+    /// serde.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("an error report serializes to JSON")
+    }
+}
+
 impl From<std::io::Error> for Error {
     fn from(error: std::io::Error) -> Self {
         Error::IO {
@@ -12,6 +12,7 @@ pub enum ActionOp1 {
     Cpop,
     Orcb,
     Rev8,
+    Brev8,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -30,6 +31,8 @@ pub enum ActionOp2 {
     Clmulr,
     Rol,
     Ror,
+    Xperm4,
+    Xperm8,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -331,6 +334,35 @@ impl Register for Value {
         Value::Op1(ActionOp1::Rev8, Rc::new(self.clone()))
     }
 
+    fn brev8(&self) -> Self {
+        if let Value::Imm(imm1) = self {
+            return Value::Imm(imm1.brev8());
+        }
+        Value::Op1(ActionOp1::Brev8, Rc::new(self.clone()))
+    }
+
+    fn xperm4(&self, rhs: &Value) -> Value {
+        if let (Value::Imm(imm1), Value::Imm(imm2)) = (self, rhs) {
+            return Value::Imm(imm1.xperm4(imm2));
+        }
+        Value::Op2(
+            ActionOp2::Xperm4,
+            Rc::new(self.clone()),
+            Rc::new(rhs.clone()),
+        )
+    }
+
+    fn xperm8(&self, rhs: &Value) -> Value {
+        if let (Value::Imm(imm1), Value::Imm(imm2)) = (self, rhs) {
+            return Value::Imm(imm1.xperm8(imm2));
+        }
+        Value::Op2(
+            ActionOp2::Xperm8,
+            Rc::new(self.clone()),
+            Rc::new(rhs.clone()),
+        )
+    }
+
     fn rol(&self, rhs: &Value) -> Value {
         if let (Value::Imm(imm1), Value::Imm(imm2)) = (self, rhs) {
             return Value::Imm(imm1.rotate_left(*imm2 as u32));
@@ -0,0 +1,64 @@
+// Custom instructions live in the custom-0 and custom-1 major opcodes, which
+// RISC-V leaves to non-standard extensions. They are registered on a machine
+// at runtime, so new instructions can be prototyped without forking the VM.
+use super::{Error, Instruction};
+
+pub const CUSTOM_0: u32 = 0b_0001011;
+pub const CUSTOM_1: u32 = 0b_0101011;
+
+// Decodes the bits of a 32 bits instruction in the custom-0 or custom-1 major
+// opcode. The operands are packed the same way as the built-in instructions,
+// e.g. Rtype::new(0, rd, rs1, rs2).0, the opcode passed in is ignored, the
+// decoder fills in the opcode and the length.
+pub type CustomDecodeFunc = fn(instruction_bits: u32) -> Option<Instruction>;
+
+// Executes a decoded custom instruction. The pc already points to the next
+// instruction, call update_pc to jump elsewhere.
+pub type CustomExecuteFunc<Mac> = dyn Fn(&mut Mac, Instruction) -> Result<(), Error> + Send + Sync;
+
+pub struct CustomInstruction<Mac> {
+    name: String,
+    cycles: u64,
+    decode: CustomDecodeFunc,
+    execute: Box<CustomExecuteFunc<Mac>>,
+}
+
+impl<Mac> CustomInstruction<Mac> {
+    pub fn new(
+        name: &str,
+        cycles: u64,
+        decode: CustomDecodeFunc,
+        execute: Box<CustomExecuteFunc<Mac>>,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            cycles,
+            decode,
+            execute,
+        }
+    }
+
+    // Name used when disassembling the instruction.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // Cycles charged for each execution, they replace the value returned by
+    // the instruction cycle function of the machine.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    pub fn decode_func(&self) -> CustomDecodeFunc {
+        self.decode
+    }
+
+    pub fn execute(&self, machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+        (self.execute)(machine, inst)
+    }
+}
+
+pub fn is_custom_major_opcode(instruction_bits: u32) -> bool {
+    let opcode = instruction_bits & 0b_1111111;
+    opcode == CUSTOM_0 || opcode == CUSTOM_1
+}
@@ -0,0 +1,164 @@
+use ckb_vm_definitions::{instructions as insts, registers::SP};
+
+use super::f::{fmt, fused, rounded};
+use super::rvc::{fld_uimmediate, fldsp_uimmediate, fsdsp_uimmediate};
+use super::utils::{funct3, funct7, itype_immediate, opcode, rd, rs1, rs2, stype_immediate, x};
+use super::{
+    set_instruction_length_2, set_instruction_length_4, Instruction, Itype, Register, Rtype, Stype,
+};
+use crate::machine::VERSION3;
+
+// C.FLD, C.FSD, C.FLDSP and C.FSDSP, RV32DC and RV64DC share the encodings.
+fn compressed(instruction_bits: u32) -> Option<Instruction> {
+    let compact_register_number = |lower| (x(instruction_bits, lower, 3, 0) + 8) as usize;
+    match instruction_bits & 0b_111_00000000000_11 {
+        0b_001_00000000000_00 => Some(
+            Itype::new_u(
+                insts::OP_FLD,
+                compact_register_number(2),
+                compact_register_number(7),
+                fld_uimmediate(instruction_bits),
+            )
+            .0,
+        ),
+        0b_101_00000000000_00 => Some(
+            Stype::new_u(
+                insts::OP_FSD,
+                fld_uimmediate(instruction_bits),
+                compact_register_number(7),
+                compact_register_number(2),
+            )
+            .0,
+        ),
+        0b_001_00000000000_10 => Some(
+            Itype::new_u(
+                insts::OP_FLD,
+                rd(instruction_bits),
+                SP,
+                fldsp_uimmediate(instruction_bits),
+            )
+            .0,
+        ),
+        0b_101_00000000000_10 => Some(
+            Stype::new_u(
+                insts::OP_FSD,
+                fsdsp_uimmediate(instruction_bits),
+                SP,
+                x(instruction_bits, 2, 5, 0) as usize,
+            )
+            .0,
+        ),
+        _ => None,
+    }
+    .map(set_instruction_length_2)
+}
+
+pub fn factory<R: Register>(instruction_bits: u32, version: u32) -> Option<Instruction> {
+    let bit_length = R::BITS;
+    if bit_length != 32 && bit_length != 64 {
+        return None;
+    }
+    if version < VERSION3 {
+        return None;
+    }
+    if instruction_bits & 0b11 != 0b11 {
+        return compressed(instruction_bits);
+    }
+    let rv64 = bit_length == 64;
+    let rm_op = |op| rounded(op, instruction_bits);
+    let r_op = |op| {
+        Some(
+            Rtype::new(
+                op,
+                rd(instruction_bits),
+                rs1(instruction_bits),
+                rs2(instruction_bits),
+            )
+            .0,
+        )
+    };
+    let inst = match opcode(instruction_bits) {
+        0b_0000111 if funct3(instruction_bits) == 0b011 => Some(
+            Itype::new_s(
+                insts::OP_FLD,
+                rd(instruction_bits),
+                rs1(instruction_bits),
+                itype_immediate(instruction_bits),
+            )
+            .0,
+        ),
+        0b_0100111 if funct3(instruction_bits) == 0b011 => Some(
+            Stype::new_s(
+                insts::OP_FSD,
+                stype_immediate(instruction_bits),
+                rs1(instruction_bits),
+                rs2(instruction_bits),
+            )
+            .0,
+        ),
+        0b_1000011 | 0b_1000111 | 0b_1001011 | 0b_1001111 if fmt(instruction_bits) == 0b01 => {
+            fused(
+                instruction_bits,
+                [
+                    insts::OP_FMADD_D,
+                    insts::OP_FMSUB_D,
+                    insts::OP_FNMSUB_D,
+                    insts::OP_FNMADD_D,
+                ],
+            )
+        }
+        0b_1010011 => {
+            let funct3 = funct3(instruction_bits);
+            let rs2 = rs2(instruction_bits);
+            match funct7(instruction_bits) {
+                0b_0000001 => rm_op(insts::OP_FADD_D),
+                0b_0000101 => rm_op(insts::OP_FSUB_D),
+                0b_0001001 => rm_op(insts::OP_FMUL_D),
+                0b_0001101 => rm_op(insts::OP_FDIV_D),
+                0b_0101101 if rs2 == 0 => rm_op(insts::OP_FSQRT_D),
+                0b_0010001 => match funct3 {
+                    0b000 => r_op(insts::OP_FSGNJ_D),
+                    0b001 => r_op(insts::OP_FSGNJN_D),
+                    0b010 => r_op(insts::OP_FSGNJX_D),
+                    _ => None,
+                },
+                0b_0010101 => match funct3 {
+                    0b000 => r_op(insts::OP_FMIN_D),
+                    0b001 => r_op(insts::OP_FMAX_D),
+                    _ => None,
+                },
+                0b_0100000 if rs2 == 0b00001 => rm_op(insts::OP_FCVT_S_D),
+                0b_0100001 if rs2 == 0b00000 => rm_op(insts::OP_FCVT_D_S),
+                0b_1100001 => match rs2 {
+                    0b00000 => rm_op(insts::OP_FCVT_W_D),
+                    0b00001 => rm_op(insts::OP_FCVT_WU_D),
+                    0b00010 if rv64 => rm_op(insts::OP_FCVT_L_D),
+                    0b00011 if rv64 => rm_op(insts::OP_FCVT_LU_D),
+                    _ => None,
+                },
+                0b_1110001 if rs2 == 0 => match funct3 {
+                    0b000 if rv64 => r_op(insts::OP_FMV_X_D),
+                    0b001 => r_op(insts::OP_FCLASS_D),
+                    _ => None,
+                },
+                0b_1010001 => match funct3 {
+                    0b000 => r_op(insts::OP_FLE_D),
+                    0b001 => r_op(insts::OP_FLT_D),
+                    0b010 => r_op(insts::OP_FEQ_D),
+                    _ => None,
+                },
+                0b_1101001 => match rs2 {
+                    0b00000 => rm_op(insts::OP_FCVT_D_W),
+                    0b00001 => rm_op(insts::OP_FCVT_D_WU),
+                    0b00010 if rv64 => rm_op(insts::OP_FCVT_D_L),
+                    0b00011 if rv64 => rm_op(insts::OP_FCVT_D_LU),
+                    _ => None,
+                },
+                0b_1111001 if rv64 && rs2 == 0 && funct3 == 0b000 => r_op(insts::OP_FMV_D_X),
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+    inst.map(set_instruction_length_4)
+}
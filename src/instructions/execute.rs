@@ -1,14 +1,18 @@
 use super::{
     super::{machine::Machine, Error},
-    common, extract_opcode, instruction_length,
+    common, extract_opcode, f, instruction_length, rvc,
+    softfloat::{Format, RoundingMode, SoftFloat, F32, F64},
     utils::update_register,
-    Instruction, InstructionOpcode, Itype, R4type, R5type, Register, Rtype, Stype, Utype,
+    v,
+    vector::{self, VType},
+    Instruction, InstructionOpcode, Itype, R4type, R5type, Register, RegisterIndex, Rtype, Stype,
+    Utype,
 };
-use crate::memory::Memory;
+use crate::{memory::Memory, RISCV_VLENB};
 use ckb_vm_definitions::{
-    for_each_inst_array1, for_each_inst_match2,
+    for_each_inst_array1, for_each_inst_match2, for_each_slowpath_inst_match2,
     instructions::{self as insts, paste},
-    registers::RA,
+    registers::{A0, A1, RA, SP},
 };
 
 pub fn handle_sub<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
@@ -1522,6 +1526,2361 @@ pub fn handle_custom_load_imm<Mac: Machine>(
     Ok(())
 }
 
+pub fn handle_brev8<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    let i = Rtype(inst);
+    let rs1_value = &machine.registers()[i.rs1()];
+    let value = rs1_value.brev8();
+    update_register(machine, i.rd(), value);
+    Ok(())
+}
+
+pub fn handle_pack<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    let i = Rtype(inst);
+    let rs1_value = &machine.registers()[i.rs1()];
+    let rs2_value = &machine.registers()[i.rs2()];
+    let half = Mac::REG::from_u8(Mac::REG::BITS / 2);
+    let value = rs1_value.zero_extend(&half) | (rs2_value.clone() << half);
+    update_register(machine, i.rd(), value);
+    Ok(())
+}
+
+pub fn handle_packh<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    let i = Rtype(inst);
+    let rs1_value = &machine.registers()[i.rs1()];
+    let rs2_value = &machine.registers()[i.rs2()];
+    let byte = Mac::REG::from_u8(8);
+    let value = rs1_value.zero_extend(&byte) | (rs2_value.zero_extend(&byte) << byte);
+    update_register(machine, i.rd(), value);
+    Ok(())
+}
+
+pub fn handle_packw<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    let i = Rtype(inst);
+    let rs1_value = &machine.registers()[i.rs1()];
+    let rs2_value = &machine.registers()[i.rs2()];
+    let half = Mac::REG::from_u8(16);
+    let value = rs1_value.zero_extend(&half) | (rs2_value.zero_extend(&half) << half);
+    update_register(machine, i.rd(), value.sign_extend(&Mac::REG::from_u8(32)));
+    Ok(())
+}
+
+// SHA-256 and SM3 work on the low 32 bits of rs1. Rotating the value repeated
+// twice in 64 bits gives the 32 bits rotation in the low half, the same way as
+// rolw and rorw.
+fn twins_32<R: Register>(value: &R) -> R {
+    value.overflowing_mul(&R::from_u64(0x_0000_0001_0000_0001))
+}
+
+fn handle_sha256<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+    rotations: [u8; 2],
+    last: u8,
+    shift: bool,
+) -> Result<(), Error> {
+    let i = Rtype(inst);
+    let x = machine.registers()[i.rs1()].zero_extend(&Mac::REG::from_u8(32));
+    let twins = twins_32(&x);
+    let last = if shift {
+        x >> Mac::REG::from_u8(last)
+    } else {
+        twins.ror(&Mac::REG::from_u8(last))
+    };
+    let value = twins.ror(&Mac::REG::from_u8(rotations[0]))
+        ^ twins.ror(&Mac::REG::from_u8(rotations[1]))
+        ^ last;
+    update_register(machine, i.rd(), value.sign_extend(&Mac::REG::from_u8(32)));
+    Ok(())
+}
+
+pub fn handle_sha256sig0<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    handle_sha256(machine, inst, [7, 18], 3, true)
+}
+
+pub fn handle_sha256sig1<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    handle_sha256(machine, inst, [17, 19], 10, true)
+}
+
+pub fn handle_sha256sum0<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    handle_sha256(machine, inst, [2, 13], 22, false)
+}
+
+pub fn handle_sha256sum1<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    handle_sha256(machine, inst, [6, 11], 25, false)
+}
+
+fn handle_sha512<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+    rotations: [u8; 2],
+    last: u8,
+    shift: bool,
+) -> Result<(), Error> {
+    let i = Rtype(inst);
+    let x = &machine.registers()[i.rs1()];
+    let last = if shift {
+        x.clone() >> Mac::REG::from_u8(last)
+    } else {
+        x.ror(&Mac::REG::from_u8(last))
+    };
+    let value =
+        x.ror(&Mac::REG::from_u8(rotations[0])) ^ x.ror(&Mac::REG::from_u8(rotations[1])) ^ last;
+    update_register(machine, i.rd(), value);
+    Ok(())
+}
+
+pub fn handle_sha512sig0<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    handle_sha512(machine, inst, [1, 8], 7, true)
+}
+
+pub fn handle_sha512sig1<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    handle_sha512(machine, inst, [19, 61], 6, true)
+}
+
+pub fn handle_sha512sum0<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    handle_sha512(machine, inst, [28, 34], 39, false)
+}
+
+pub fn handle_sha512sum1<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    handle_sha512(machine, inst, [14, 18], 41, false)
+}
+
+fn handle_sm3<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+    rotations: [u8; 2],
+) -> Result<(), Error> {
+    let i = Rtype(inst);
+    let x = machine.registers()[i.rs1()].zero_extend(&Mac::REG::from_u8(32));
+    let twins = twins_32(&x);
+    let value = x
+        ^ twins.rol(&Mac::REG::from_u8(rotations[0]))
+        ^ twins.rol(&Mac::REG::from_u8(rotations[1]));
+    update_register(machine, i.rd(), value.sign_extend(&Mac::REG::from_u8(32)));
+    Ok(())
+}
+
+pub fn handle_sm3p0<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    handle_sm3(machine, inst, [9, 17])
+}
+
+pub fn handle_sm3p1<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    handle_sm3(machine, inst, [15, 23])
+}
+
+pub fn handle_xperm4<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    let i = Rtype(inst);
+    let rs1_value = &machine.registers()[i.rs1()];
+    let rs2_value = &machine.registers()[i.rs2()];
+    let value = rs1_value.xperm4(rs2_value);
+    update_register(machine, i.rd(), value);
+    Ok(())
+}
+
+pub fn handle_xperm8<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    let i = Rtype(inst);
+    let rs1_value = &machine.registers()[i.rs1()];
+    let rs2_value = &machine.registers()[i.rs2()];
+    let value = rs1_value.xperm8(rs2_value);
+    update_register(machine, i.rd(), value);
+    Ok(())
+}
+
+pub fn handle_czero_eqz<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    let i = Rtype(inst);
+    let rs1_value = &machine.registers()[i.rs1()];
+    let rs2_value = &machine.registers()[i.rs2()];
+    let value = rs2_value
+        .eq(&Mac::REG::zero())
+        .cond(&Mac::REG::zero(), rs1_value);
+    update_register(machine, i.rd(), value);
+    Ok(())
+}
+
+pub fn handle_czero_nez<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    let i = Rtype(inst);
+    let rs1_value = &machine.registers()[i.rs1()];
+    let rs2_value = &machine.registers()[i.rs2()];
+    let value = rs2_value
+        .eq(&Mac::REG::zero())
+        .cond(rs1_value, &Mac::REG::zero());
+    update_register(machine, i.rd(), value);
+    Ok(())
+}
+
+pub fn handle_cm_mva01s<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    let i = Rtype(inst);
+    let rs1_value = machine.registers()[i.rs1()].clone();
+    let rs2_value = machine.registers()[i.rs2()].clone();
+    update_register(machine, A0, rs1_value);
+    update_register(machine, A1, rs2_value);
+    Ok(())
+}
+
+pub fn handle_cm_mvsa01<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    let i = Rtype(inst);
+    let a0_value = machine.registers()[A0].clone();
+    let a1_value = machine.registers()[A1].clone();
+    update_register(machine, i.rs1(), a0_value);
+    update_register(machine, i.rs2(), a1_value);
+    Ok(())
+}
+
+// Single precision values are NaN-boxed in the 64 bits floating-point
+// registers, reading a value that is not properly boxed gives the canonical
+// NaN.
+fn read_float<Mac: Machine>(machine: &Mac, format: Format, index: RegisterIndex) -> u64 {
+    let value = machine.fregisters()[index];
+    if format != F32 {
+        value
+    } else if value >> 32 == 0xffff_ffff {
+        value & 0xffff_ffff
+    } else {
+        F32.canonical_nan()
+    }
+}
+
+fn write_float<Mac: Machine>(
+    machine: &mut Mac,
+    format: Format,
+    index: RegisterIndex,
+    value: u64,
+) -> Result<(), Error> {
+    if format == F32 {
+        machine.set_fregister(index, value | 0xffff_ffff_0000_0000)
+    } else {
+        machine.set_fregister(index, value)
+    }
+}
+
+// The rounding mode is either static, or the dynamic one in frm. An invalid
+// rounding mode is an illegal instruction.
+fn soft_float<Mac: Machine>(machine: &Mac, inst: Instruction) -> Result<SoftFloat, Error> {
+    let mut rm = f::rounding_mode(inst);
+    if rm == 0b111 {
+        rm = (machine.fcsr() >> 5) & 0b111;
+    }
+    RoundingMode::from_bits(rm)
+        .map(SoftFloat::new)
+        .ok_or_else(|| Error::InvalidOp(extract_opcode(inst)))
+}
+
+fn accrue_flags<Mac: Machine>(machine: &mut Mac, flags: u32) -> Result<(), Error> {
+    machine.set_fcsr(machine.fcsr() | flags)
+}
+
+fn float_load<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+    format: Format,
+) -> Result<(), Error> {
+    let i = Itype(inst);
+    let address =
+        machine.registers()[i.rs1()].overflowing_add(&Mac::REG::from_i32(i.immediate_s()));
+    let value = if format == F32 {
+        machine.memory_mut().load32(&address)?.to_u64() & 0xffff_ffff
+    } else if Mac::REG::BITS == 64 {
+        machine.memory_mut().load64(&address)?.to_u64()
+    } else {
+        let high_address = address.overflowing_add(&Mac::REG::from_u8(4));
+        let low = machine.memory_mut().load32(&address)?.to_u64();
+        let high = machine.memory_mut().load32(&high_address)?.to_u64();
+        (high << 32) | low
+    };
+    write_float(machine, format, i.rd(), value)?;
+    Ok(())
+}
+
+fn float_store<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+    format: Format,
+) -> Result<(), Error> {
+    let i = Stype(inst);
+    let address =
+        machine.registers()[i.rs1()].overflowing_add(&Mac::REG::from_i32(i.immediate_s()));
+    // Stores keep the bits as they are, without checking NaN-boxing
+    let value = machine.fregisters()[i.rs2()];
+    if format == F32 {
+        machine
+            .memory_mut()
+            .store32(&address, &Mac::REG::from_u64(value))
+    } else if Mac::REG::BITS == 64 {
+        machine
+            .memory_mut()
+            .store64(&address, &Mac::REG::from_u64(value))
+    } else {
+        let high_address = address.overflowing_add(&Mac::REG::from_u8(4));
+        machine
+            .memory_mut()
+            .store32(&address, &Mac::REG::from_u64(value))?;
+        machine
+            .memory_mut()
+            .store32(&high_address, &Mac::REG::from_u64(value >> 32))
+    }
+}
+
+fn float_fused<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+    format: Format,
+    negate_product: bool,
+    negate_addend: bool,
+) -> Result<(), Error> {
+    let i = R4type(inst);
+    let mut sf = soft_float(machine, inst)?;
+    let a = read_float(machine, format, i.rs1());
+    let b = read_float(machine, format, i.rs2());
+    let c = read_float(machine, format, i.rs3());
+    let value = sf.fused_mul_add(format, a, b, c, negate_product, negate_addend);
+    write_float(machine, format, i.rd(), value)?;
+    accrue_flags(machine, sf.flags)?;
+    Ok(())
+}
+
+fn float_binary<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+    format: Format,
+    op: fn(&mut SoftFloat, Format, u64, u64) -> u64,
+) -> Result<(), Error> {
+    let i = Rtype(inst);
+    let mut sf = soft_float(machine, inst)?;
+    let a = read_float(machine, format, i.rs1());
+    let b = read_float(machine, format, i.rs2());
+    let value = op(&mut sf, format, a, b);
+    write_float(machine, format, i.rd(), value)?;
+    accrue_flags(machine, sf.flags)?;
+    Ok(())
+}
+
+fn float_sqrt<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+    format: Format,
+) -> Result<(), Error> {
+    let i = Rtype(inst);
+    let mut sf = soft_float(machine, inst)?;
+    let value = sf.sqrt(format, read_float(machine, format, i.rs1()));
+    write_float(machine, format, i.rd(), value)?;
+    accrue_flags(machine, sf.flags)?;
+    Ok(())
+}
+
+// Sign injection takes the sign from the result of `sign(rs1 sign, rs2 sign)`
+fn float_sign_inject<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+    format: Format,
+    sign: fn(bool, bool) -> bool,
+) -> Result<(), Error> {
+    let i = Rtype(inst);
+    let a = read_float(machine, format, i.rs1());
+    let b = read_float(machine, format, i.rs2());
+    let value = if sign(format.sign(a), format.sign(b)) != format.sign(a) {
+        format.negate(a)
+    } else {
+        a
+    };
+    write_float(machine, format, i.rd(), value)?;
+    Ok(())
+}
+
+fn float_min_max<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+    format: Format,
+    max: bool,
+) -> Result<(), Error> {
+    let i = Rtype(inst);
+    let mut sf = SoftFloat::new(RoundingMode::NearestEven);
+    let a = read_float(machine, format, i.rs1());
+    let b = read_float(machine, format, i.rs2());
+    let value = sf.min_max(format, a, b, max);
+    write_float(machine, format, i.rd(), value)?;
+    accrue_flags(machine, sf.flags)?;
+    Ok(())
+}
+
+fn float_compare<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+    format: Format,
+    compare: fn(&mut SoftFloat, Format, u64, u64) -> bool,
+) -> Result<(), Error> {
+    let i = Rtype(inst);
+    let mut sf = SoftFloat::new(RoundingMode::NearestEven);
+    let a = read_float(machine, format, i.rs1());
+    let b = read_float(machine, format, i.rs2());
+    let value = compare(&mut sf, format, a, b);
+    update_register(machine, i.rd(), Mac::REG::from_u8(value as u8));
+    accrue_flags(machine, sf.flags)?;
+    Ok(())
+}
+
+fn float_classify<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+    format: Format,
+) -> Result<(), Error> {
+    let i = Rtype(inst);
+    let value = SoftFloat::classify(format, read_float(machine, format, i.rs1()));
+    update_register(machine, i.rd(), Mac::REG::from_u64(value));
+    Ok(())
+}
+
+fn float_to_int<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+    format: Format,
+    signed: bool,
+    bits: u32,
+) -> Result<(), Error> {
+    let i = Rtype(inst);
+    let mut sf = soft_float(machine, inst)?;
+    let value = sf.convert_to_int(format, read_float(machine, format, i.rs1()), signed, bits);
+    update_register(machine, i.rd(), Mac::REG::from_u64(value));
+    accrue_flags(machine, sf.flags)?;
+    Ok(())
+}
+
+fn float_from_int<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+    format: Format,
+    signed: bool,
+    bits: u32,
+) -> Result<(), Error> {
+    let i = Rtype(inst);
+    let mut sf = soft_float(machine, inst)?;
+    let value = sf.convert_from_int(format, machine.registers()[i.rs1()].to_u64(), signed, bits);
+    write_float(machine, format, i.rd(), value)?;
+    accrue_flags(machine, sf.flags)?;
+    Ok(())
+}
+
+fn float_convert<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+    from: Format,
+    to: Format,
+) -> Result<(), Error> {
+    let i = Rtype(inst);
+    let mut sf = soft_float(machine, inst)?;
+    let value = sf.convert(from, to, read_float(machine, from, i.rs1()));
+    write_float(machine, to, i.rd(), value)?;
+    accrue_flags(machine, sf.flags)?;
+    Ok(())
+}
+
+pub fn handle_flw<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_load(machine, inst, F32)
+}
+
+pub fn handle_fsw<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_store(machine, inst, F32)
+}
+
+pub fn handle_fmadd_s<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_fused(machine, inst, F32, false, false)
+}
+
+pub fn handle_fmsub_s<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_fused(machine, inst, F32, false, true)
+}
+
+pub fn handle_fnmsub_s<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_fused(machine, inst, F32, true, false)
+}
+
+pub fn handle_fnmadd_s<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_fused(machine, inst, F32, true, true)
+}
+
+pub fn handle_fadd_s<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_binary(machine, inst, F32, SoftFloat::add)
+}
+
+pub fn handle_fsub_s<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_binary(machine, inst, F32, SoftFloat::sub)
+}
+
+pub fn handle_fmul_s<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_binary(machine, inst, F32, SoftFloat::mul)
+}
+
+pub fn handle_fdiv_s<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_binary(machine, inst, F32, SoftFloat::div)
+}
+
+pub fn handle_fsqrt_s<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_sqrt(machine, inst, F32)
+}
+
+pub fn handle_fsgnj_s<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_sign_inject(machine, inst, F32, |_, b| b)
+}
+
+pub fn handle_fsgnjn_s<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_sign_inject(machine, inst, F32, |_, b| !b)
+}
+
+pub fn handle_fsgnjx_s<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_sign_inject(machine, inst, F32, |a, b| a ^ b)
+}
+
+pub fn handle_fmin_s<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_min_max(machine, inst, F32, false)
+}
+
+pub fn handle_fmax_s<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_min_max(machine, inst, F32, true)
+}
+
+pub fn handle_fcvt_w_s<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_to_int(machine, inst, F32, true, 32)
+}
+
+pub fn handle_fcvt_wu_s<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_to_int(machine, inst, F32, false, 32)
+}
+
+pub fn handle_fcvt_l_s<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_to_int(machine, inst, F32, true, 64)
+}
+
+pub fn handle_fcvt_lu_s<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_to_int(machine, inst, F32, false, 64)
+}
+
+pub fn handle_fmv_x_w<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    let i = Rtype(inst);
+    let value = machine.fregisters()[i.rs1()] as u32 as i32 as i64 as u64;
+    update_register(machine, i.rd(), Mac::REG::from_u64(value));
+    Ok(())
+}
+
+pub fn handle_feq_s<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_compare(machine, inst, F32, SoftFloat::equal)
+}
+
+pub fn handle_flt_s<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_compare(machine, inst, F32, SoftFloat::less_than)
+}
+
+pub fn handle_fle_s<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_compare(machine, inst, F32, SoftFloat::less_or_equal)
+}
+
+pub fn handle_fclass_s<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_classify(machine, inst, F32)
+}
+
+pub fn handle_fcvt_s_w<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_from_int(machine, inst, F32, true, 32)
+}
+
+pub fn handle_fcvt_s_wu<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_from_int(machine, inst, F32, false, 32)
+}
+
+pub fn handle_fcvt_s_l<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_from_int(machine, inst, F32, true, 64)
+}
+
+pub fn handle_fcvt_s_lu<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_from_int(machine, inst, F32, false, 64)
+}
+
+pub fn handle_fmv_w_x<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    let i = Rtype(inst);
+    let value = machine.registers()[i.rs1()].to_u64() & 0xffff_ffff;
+    write_float(machine, F32, i.rd(), value)?;
+    Ok(())
+}
+
+pub fn handle_fld<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_load(machine, inst, F64)
+}
+
+pub fn handle_fsd<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_store(machine, inst, F64)
+}
+
+pub fn handle_fmadd_d<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_fused(machine, inst, F64, false, false)
+}
+
+pub fn handle_fmsub_d<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_fused(machine, inst, F64, false, true)
+}
+
+pub fn handle_fnmsub_d<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_fused(machine, inst, F64, true, false)
+}
+
+pub fn handle_fnmadd_d<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_fused(machine, inst, F64, true, true)
+}
+
+pub fn handle_fadd_d<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_binary(machine, inst, F64, SoftFloat::add)
+}
+
+pub fn handle_fsub_d<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_binary(machine, inst, F64, SoftFloat::sub)
+}
+
+pub fn handle_fmul_d<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_binary(machine, inst, F64, SoftFloat::mul)
+}
+
+pub fn handle_fdiv_d<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_binary(machine, inst, F64, SoftFloat::div)
+}
+
+pub fn handle_fsqrt_d<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_sqrt(machine, inst, F64)
+}
+
+pub fn handle_fsgnj_d<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_sign_inject(machine, inst, F64, |_, b| b)
+}
+
+pub fn handle_fsgnjn_d<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_sign_inject(machine, inst, F64, |_, b| !b)
+}
+
+pub fn handle_fsgnjx_d<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_sign_inject(machine, inst, F64, |a, b| a ^ b)
+}
+
+pub fn handle_fmin_d<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_min_max(machine, inst, F64, false)
+}
+
+pub fn handle_fmax_d<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_min_max(machine, inst, F64, true)
+}
+
+pub fn handle_fcvt_s_d<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_convert(machine, inst, F64, F32)
+}
+
+pub fn handle_fcvt_d_s<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_convert(machine, inst, F32, F64)
+}
+
+pub fn handle_fcvt_w_d<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_to_int(machine, inst, F64, true, 32)
+}
+
+pub fn handle_fcvt_wu_d<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_to_int(machine, inst, F64, false, 32)
+}
+
+pub fn handle_fcvt_l_d<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_to_int(machine, inst, F64, true, 64)
+}
+
+pub fn handle_fcvt_lu_d<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_to_int(machine, inst, F64, false, 64)
+}
+
+pub fn handle_fmv_x_d<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    let i = Rtype(inst);
+    let value = machine.fregisters()[i.rs1()];
+    update_register(machine, i.rd(), Mac::REG::from_u64(value));
+    Ok(())
+}
+
+pub fn handle_feq_d<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_compare(machine, inst, F64, SoftFloat::equal)
+}
+
+pub fn handle_flt_d<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_compare(machine, inst, F64, SoftFloat::less_than)
+}
+
+pub fn handle_fle_d<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_compare(machine, inst, F64, SoftFloat::less_or_equal)
+}
+
+pub fn handle_fclass_d<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_classify(machine, inst, F64)
+}
+
+pub fn handle_fcvt_d_w<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_from_int(machine, inst, F64, true, 32)
+}
+
+pub fn handle_fcvt_d_wu<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_from_int(machine, inst, F64, false, 32)
+}
+
+pub fn handle_fcvt_d_l<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_from_int(machine, inst, F64, true, 64)
+}
+
+pub fn handle_fcvt_d_lu<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    float_from_int(machine, inst, F64, false, 64)
+}
+
+pub fn handle_fmv_d_x<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    let i = Rtype(inst);
+    let value = machine.registers()[i.rs1()].to_u64();
+    write_float(machine, F64, i.rd(), value)?;
+    Ok(())
+}
+
+fn read_csr<Mac: Machine>(machine: &Mac, csr: u32) -> u64 {
+    if let Some(value) = read_vector_csr(machine, csr) {
+        return value;
+    }
+    let fcsr = u64::from(machine.fcsr());
+    match csr {
+        f::CSR_FFLAGS => fcsr & 0x1f,
+        f::CSR_FRM => (fcsr >> 5) & 0b111,
+        _ => fcsr & 0xff,
+    }
+}
+
+fn write_csr<Mac: Machine>(machine: &mut Mac, csr: u32, value: u64) -> Result<(), Error> {
+    let fcsr = machine.fcsr();
+    let value = value as u32;
+    let fcsr = match csr {
+        f::CSR_FFLAGS => (fcsr & !0x1f) | (value & 0x1f),
+        f::CSR_FRM => (fcsr & !0xe0) | ((value & 0b111) << 5),
+        _ => value & 0xff,
+    };
+    machine.set_fcsr(fcsr)
+}
+
+// CSRRS and CSRRC, as well as their immediate variants, do not write the CSR
+// when rs1 is x0 or the immediate is zero.
+fn csr_access<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+    immediate: bool,
+    set_or_clear: bool,
+    update: fn(u64, u64) -> u64,
+) -> Result<(), Error> {
+    let i = Itype(inst);
+    let csr = i.immediate_u();
+    let operand = if immediate {
+        i.rs1() as u64
+    } else {
+        machine.registers()[i.rs1()].to_u64()
+    };
+    let value = read_csr(machine, csr);
+    if !set_or_clear || i.rs1() != 0 {
+        write_csr(machine, csr, update(value, operand))?;
+    }
+    update_register(machine, i.rd(), Mac::REG::from_u64(value));
+    Ok(())
+}
+
+pub fn handle_csrrw<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    csr_access(machine, inst, false, false, |_, operand| operand)
+}
+
+pub fn handle_csrrs<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    csr_access(machine, inst, false, true, |value, operand| value | operand)
+}
+
+pub fn handle_csrrc<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    csr_access(machine, inst, false, true, |value, operand| {
+        value & !operand
+    })
+}
+
+pub fn handle_csrrwi<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    csr_access(machine, inst, true, false, |_, operand| operand)
+}
+
+pub fn handle_csrrsi<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    csr_access(machine, inst, true, true, |value, operand| value | operand)
+}
+
+pub fn handle_csrrci<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    csr_access(machine, inst, true, true, |value, operand| value & !operand)
+}
+
+fn read_vector_csr<Mac: Machine>(machine: &Mac, csr: u32) -> Option<u64> {
+    match csr {
+        v::CSR_VL => Some(machine.vl()),
+        // vill is the highest bit of XLEN.
+        v::CSR_VTYPE if VType(machine.vtype()).vill() => Some(1 << (Mac::REG::BITS - 1)),
+        v::CSR_VTYPE => Some(machine.vtype()),
+        v::CSR_VLENB => Some(RISCV_VLENB as u64),
+        _ => None,
+    }
+}
+
+fn vector_set_vl<Mac: Machine>(
+    machine: &mut Mac,
+    rd: RegisterIndex,
+    avl: u64,
+    vtype: u64,
+) -> Result<(), Error> {
+    let vtype = VType::new(vtype);
+    let vl = if vtype.vill() {
+        0
+    } else {
+        std::cmp::min(avl, vtype.vlmax() as u64)
+    };
+    machine.set_vl(vl, vtype.0)?;
+    update_register(machine, rd, Mac::REG::from_u64(vl));
+    Ok(())
+}
+
+// AVL of vsetvli and vsetvl. x0 as rs1 requests VLMAX, or keeps the current
+// vl when rd is x0 as well.
+fn vector_avl<Mac: Machine>(machine: &Mac, rd: RegisterIndex, rs1: RegisterIndex) -> u64 {
+    if rs1 != 0 {
+        machine.registers()[rs1].to_u64()
+    } else if rd != 0 {
+        u64::MAX
+    } else {
+        machine.vl()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum VectorOperand {
+    Vector,
+    Scalar,
+    Immediate,
+    UnsignedImmediate,
+}
+
+struct VectorConfig {
+    sew: usize,
+    lmul8: usize,
+    vl: usize,
+    vlmax: usize,
+}
+
+fn vector_config<Mac: Machine>(machine: &Mac, inst: Instruction) -> Result<VectorConfig, Error> {
+    let vtype = VType(machine.vtype());
+    if vtype.vill() {
+        return Err(Error::InvalidOp(extract_opcode(inst)));
+    }
+    Ok(VectorConfig {
+        sew: vtype.sew(),
+        lmul8: vtype.lmul8(),
+        vl: machine.vl() as usize,
+        vlmax: vtype.vlmax(),
+    })
+}
+
+// Checks the alignment of register groups, EMUL is in eighths of a register.
+fn vector_groups(inst: Instruction, groups: &[(RegisterIndex, usize)]) -> Result<(), Error> {
+    if groups
+        .iter()
+        .all(|(register, emul8)| vector::valid_group(*register, *emul8))
+    {
+        Ok(())
+    } else {
+        Err(Error::InvalidOp(extract_opcode(inst)))
+    }
+}
+
+// The destination of a masked instruction can not overlap v0, unless the
+// destination is a mask register itself.
+fn vector_destination(inst: Instruction, emul8: usize) -> Result<(), Error> {
+    let i = R4type(inst);
+    if i.rs3() == 0 && i.rd() == 0 {
+        return Err(Error::InvalidOp(extract_opcode(inst)));
+    }
+    vector_groups(inst, &[(i.rd(), emul8)])
+}
+
+fn vector_active(vregisters: &[u8], inst: Instruction, index: usize) -> bool {
+    R4type(inst).rs3() != 0 || vector::mask_bit(vregisters, 0, index)
+}
+
+// Scalar operands are sign extended from XLEN, then truncated to SEW.
+fn vector_scalar<Mac: Machine>(machine: &Mac, inst: Instruction, operand: VectorOperand) -> u64 {
+    let rs1 = R4type(inst).rs1();
+    match operand {
+        VectorOperand::Vector => 0,
+        VectorOperand::Scalar => machine.registers()[rs1].to_i64() as u64,
+        VectorOperand::Immediate => ((rs1 as i64) << 59 >> 59) as u64,
+        VectorOperand::UnsignedImmediate => rs1 as u64,
+    }
+}
+
+fn vector_operand(
+    vregisters: &[u8],
+    inst: Instruction,
+    operand: VectorOperand,
+    scalar: u64,
+    sew: usize,
+    index: usize,
+) -> u64 {
+    if operand == VectorOperand::Vector {
+        vector::read_element(vregisters, R4type(inst).rs1(), sew, index)
+    } else {
+        vector::truncate(scalar, sew)
+    }
+}
+
+fn vector_sources(inst: Instruction, operand: VectorOperand, emul8: usize) -> Result<(), Error> {
+    let i = R4type(inst);
+    vector_groups(inst, &[(i.rs2(), emul8)])?;
+    if operand == VectorOperand::Vector {
+        vector_groups(inst, &[(i.rs1(), emul8)])?;
+    }
+    Ok(())
+}
+
+// vd[i] = op(vs2[i], operand[i]) for active elements.
+fn vector_binary<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+    operand: VectorOperand,
+    op: fn(u64, u64, usize) -> u64,
+) -> Result<(), Error> {
+    let c = vector_config(machine, inst)?;
+    let i = R4type(inst);
+    vector_destination(inst, c.lmul8)?;
+    vector_sources(inst, operand, c.lmul8)?;
+    let scalar = vector_scalar(machine, inst, operand);
+    let v = machine.vregisters_mut();
+    for index in 0..c.vl {
+        if !vector_active(v, inst, index) {
+            continue;
+        }
+        let a = vector::read_element(v, i.rs2(), c.sew, index);
+        let b = vector_operand(v, inst, operand, scalar, c.sew, index);
+        vector::write_element(v, i.rd(), c.sew, index, op(a, b, c.sew));
+    }
+    Ok(())
+}
+
+// Writes op(vs2[i], operand[i]) to bit i of mask register vd. Bit i of vd
+// never lives in a byte of a later source element, so vd can overlap the
+// sources.
+fn vector_compare<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+    operand: VectorOperand,
+    op: fn(u64, u64, usize) -> bool,
+) -> Result<(), Error> {
+    let c = vector_config(machine, inst)?;
+    let i = R4type(inst);
+    vector_sources(inst, operand, c.lmul8)?;
+    let scalar = vector_scalar(machine, inst, operand);
+    let v = machine.vregisters_mut();
+    for index in 0..c.vl {
+        if !vector_active(v, inst, index) {
+            continue;
+        }
+        let a = vector::read_element(v, i.rs2(), c.sew, index);
+        let b = vector_operand(v, inst, operand, scalar, c.sew, index);
+        vector::set_mask_bit(v, i.rd(), index, op(a, b, c.sew));
+    }
+    Ok(())
+}
+
+// vadc and vsbc, the carry or borrow comes from v0.
+fn vector_carry<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+    operand: VectorOperand,
+    subtract: bool,
+) -> Result<(), Error> {
+    let c = vector_config(machine, inst)?;
+    let i = R4type(inst);
+    vector_destination(inst, c.lmul8)?;
+    vector_sources(inst, operand, c.lmul8)?;
+    let scalar = vector_scalar(machine, inst, operand);
+    let v = machine.vregisters_mut();
+    for index in 0..c.vl {
+        let a = vector::read_element(v, i.rs2(), c.sew, index);
+        let b = vector_operand(v, inst, operand, scalar, c.sew, index);
+        let carry = vector::mask_bit(v, 0, index) as u64;
+        let value = if subtract {
+            a.wrapping_sub(b).wrapping_sub(carry)
+        } else {
+            a.wrapping_add(b).wrapping_add(carry)
+        };
+        vector::write_element(v, i.rd(), c.sew, index, value);
+    }
+    Ok(())
+}
+
+// vmadc and vmsbc, which take the carry or borrow from v0 when masked.
+fn vector_carry_out<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+    operand: VectorOperand,
+    subtract: bool,
+) -> Result<(), Error> {
+    let c = vector_config(machine, inst)?;
+    let i = R4type(inst);
+    vector_sources(inst, operand, c.lmul8)?;
+    let scalar = vector_scalar(machine, inst, operand);
+    let v = machine.vregisters_mut();
+    for index in 0..c.vl {
+        let a = u128::from(vector::read_element(v, i.rs2(), c.sew, index));
+        let b = u128::from(vector_operand(v, inst, operand, scalar, c.sew, index));
+        let carry = (i.rs3() == 0 && vector::mask_bit(v, 0, index)) as u128;
+        let out = if subtract {
+            a < b + carry
+        } else {
+            (a + b + carry) >> c.sew != 0
+        };
+        vector::set_mask_bit(v, i.rd(), index, out);
+    }
+    Ok(())
+}
+
+// vmerge when masked, vmv.v otherwise.
+fn vector_merge<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+    operand: VectorOperand,
+) -> Result<(), Error> {
+    let c = vector_config(machine, inst)?;
+    let i = R4type(inst);
+    vector_destination(inst, c.lmul8)?;
+    vector_sources(inst, operand, c.lmul8)?;
+    let scalar = vector_scalar(machine, inst, operand);
+    let v = machine.vregisters_mut();
+    for index in 0..c.vl {
+        let value = if vector_active(v, inst, index) {
+            vector_operand(v, inst, operand, scalar, c.sew, index)
+        } else {
+            vector::read_element(v, i.rs2(), c.sew, index)
+        };
+        vector::write_element(v, i.rd(), c.sew, index, value);
+    }
+    Ok(())
+}
+
+// vd[i] = op(vd[i], operand[i], vs2[i]) for active elements.
+fn vector_multiply_add<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+    operand: VectorOperand,
+    op: fn(u64, u64, u64) -> u64,
+) -> Result<(), Error> {
+    let c = vector_config(machine, inst)?;
+    let i = R4type(inst);
+    vector_destination(inst, c.lmul8)?;
+    vector_sources(inst, operand, c.lmul8)?;
+    let scalar = vector_scalar(machine, inst, operand);
+    let v = machine.vregisters_mut();
+    for index in 0..c.vl {
+        if !vector_active(v, inst, index) {
+            continue;
+        }
+        let d = vector::read_element(v, i.rd(), c.sew, index);
+        let a = vector::read_element(v, i.rs2(), c.sew, index);
+        let b = vector_operand(v, inst, operand, scalar, c.sew, index);
+        vector::write_element(v, i.rd(), c.sew, index, op(d, b, a));
+    }
+    Ok(())
+}
+
+// Widening instructions write 2 * SEW bits elements to a vd group of
+// 2 * LMUL registers: vd[i] = op(vd[i], vs2[i], operand[i], SEW). The results
+// are computed before being written back, since vd may overlap the sources.
+fn vector_widening<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+    operand: VectorOperand,
+    op: fn(u64, u64, u64, usize) -> u64,
+) -> Result<(), Error> {
+    let c = vector_config(machine, inst)?;
+    let i = R4type(inst);
+    if c.sew * 2 > vector::ELEN {
+        return Err(Error::InvalidOp(extract_opcode(inst)));
+    }
+    vector_destination(inst, c.lmul8 * 2)?;
+    vector_sources(inst, operand, c.lmul8)?;
+    let scalar = vector_scalar(machine, inst, operand);
+    let v = machine.vregisters_mut();
+    let results: Vec<(usize, u64)> = (0..c.vl)
+        .filter(|index| vector_active(v, inst, *index))
+        .map(|index| {
+            let d = vector::read_element(v, i.rd(), c.sew * 2, index);
+            let a = vector::read_element(v, i.rs2(), c.sew, index);
+            let b = vector_operand(v, inst, operand, scalar, c.sew, index);
+            (index, op(d, a, b, c.sew))
+        })
+        .collect();
+    for (index, value) in results {
+        vector::write_element(v, i.rd(), c.sew * 2, index, value);
+    }
+    Ok(())
+}
+
+// vd[0] = op(...op(vs1[0], vs2[0])..., vs2[vl - 1]) over active elements.
+fn vector_reduction<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+    op: fn(u64, u64, usize) -> u64,
+) -> Result<(), Error> {
+    let c = vector_config(machine, inst)?;
+    let i = R4type(inst);
+    vector_groups(inst, &[(i.rs2(), c.lmul8)])?;
+    if c.vl == 0 {
+        return Ok(());
+    }
+    let v = machine.vregisters_mut();
+    let mut result = vector::read_element(v, i.rs1(), c.sew, 0);
+    for index in 0..c.vl {
+        if vector_active(v, inst, index) {
+            result = op(
+                result,
+                vector::read_element(v, i.rs2(), c.sew, index),
+                c.sew,
+            );
+        }
+    }
+    vector::write_element(v, i.rd(), c.sew, 0, result);
+    Ok(())
+}
+
+// Bit i of vd = op(bit i of vs2, bit i of vs1).
+fn vector_mask_logical<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+    op: fn(bool, bool) -> bool,
+) -> Result<(), Error> {
+    let c = vector_config(machine, inst)?;
+    let i = R4type(inst);
+    let v = machine.vregisters_mut();
+    for index in 0..c.vl {
+        let a = vector::mask_bit(v, i.rs2(), index);
+        let b = vector::mask_bit(v, i.rs1(), index);
+        vector::set_mask_bit(v, i.rd(), index, op(a, b));
+    }
+    Ok(())
+}
+
+// vzext and vsext, the source elements are SEW / factor bits wide.
+fn vector_extend<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+    factor: usize,
+    signed: bool,
+) -> Result<(), Error> {
+    let c = vector_config(machine, inst)?;
+    let i = R4type(inst);
+    let eew = c.sew / factor;
+    if eew < 8 {
+        return Err(Error::InvalidOp(extract_opcode(inst)));
+    }
+    vector_destination(inst, c.lmul8)?;
+    vector_groups(inst, &[(i.rs2(), c.lmul8 / factor)])?;
+    let v = machine.vregisters_mut();
+    let results: Vec<(usize, u64)> = (0..c.vl)
+        .filter(|index| vector_active(v, inst, *index))
+        .map(|index| {
+            let value = vector::read_element(v, i.rs2(), eew, index);
+            if signed {
+                (index, vector::sign_extend(value, eew) as u64)
+            } else {
+                (index, value)
+            }
+        })
+        .collect();
+    for (index, value) in results {
+        vector::write_element(v, i.rd(), c.sew, index, value);
+    }
+    Ok(())
+}
+
+// vslideup and vslidedown, the offset is an unsigned XLEN value.
+fn vector_slide<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+    operand: VectorOperand,
+    up: bool,
+) -> Result<(), Error> {
+    let c = vector_config(machine, inst)?;
+    let i = R4type(inst);
+    vector_destination(inst, c.lmul8)?;
+    vector_groups(inst, &[(i.rs2(), c.lmul8)])?;
+    let offset = if operand == VectorOperand::Scalar {
+        machine.registers()[i.rs1()].to_u64()
+    } else {
+        i.rs1() as u64
+    };
+    let source = machine.vregisters().to_vec();
+    let v = machine.vregisters_mut();
+    for index in 0..c.vl {
+        if !vector_active(v, inst, index) {
+            continue;
+        }
+        let value = if up {
+            if (index as u64) < offset {
+                continue;
+            }
+            vector::read_element(&source, i.rs2(), c.sew, index - offset as usize)
+        } else {
+            match (index as u64).checked_add(offset) {
+                Some(from) if from < c.vlmax as u64 => {
+                    vector::read_element(&source, i.rs2(), c.sew, from as usize)
+                }
+                _ => 0,
+            }
+        };
+        vector::write_element(v, i.rd(), c.sew, index, value);
+    }
+    Ok(())
+}
+
+// vslide1up and vslide1down, which slide by one and insert x[rs1].
+fn vector_slide1<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+    up: bool,
+) -> Result<(), Error> {
+    let c = vector_config(machine, inst)?;
+    let i = R4type(inst);
+    vector_destination(inst, c.lmul8)?;
+    vector_groups(inst, &[(i.rs2(), c.lmul8)])?;
+    let scalar = vector_scalar(machine, inst, VectorOperand::Scalar);
+    let source = machine.vregisters().to_vec();
+    let v = machine.vregisters_mut();
+    for index in 0..c.vl {
+        if !vector_active(v, inst, index) {
+            continue;
+        }
+        let value = if (up && index == 0) || (!up && index + 1 == c.vl) {
+            scalar
+        } else if up {
+            vector::read_element(&source, i.rs2(), c.sew, index - 1)
+        } else {
+            vector::read_element(&source, i.rs2(), c.sew, index + 1)
+        };
+        vector::write_element(v, i.rd(), c.sew, index, value);
+    }
+    Ok(())
+}
+
+fn vector_load_element<Mac: Machine>(
+    machine: &mut Mac,
+    address: &Mac::REG,
+    bytes: usize,
+) -> Result<u64, Error> {
+    let memory = machine.memory_mut();
+    Ok(match bytes {
+        1 => memory.load8(address)?.to_u64(),
+        2 => memory.load16(address)?.to_u64(),
+        4 => memory.load32(address)?.to_u64() & 0xffff_ffff,
+        _ if Mac::REG::BITS == 64 => memory.load64(address)?.to_u64(),
+        _ => {
+            let high_address = address.overflowing_add(&Mac::REG::from_u8(4));
+            let low = memory.load32(address)?.to_u64();
+            let high = memory.load32(&high_address)?.to_u64();
+            (high << 32) | low
+        }
+    })
+}
+
+fn vector_store_element<Mac: Machine>(
+    machine: &mut Mac,
+    address: &Mac::REG,
+    bytes: usize,
+    value: u64,
+) -> Result<(), Error> {
+    let memory = machine.memory_mut();
+    match bytes {
+        1 => memory.store8(address, &Mac::REG::from_u64(value)),
+        2 => memory.store16(address, &Mac::REG::from_u64(value)),
+        4 => memory.store32(address, &Mac::REG::from_u64(value)),
+        _ if Mac::REG::BITS == 64 => memory.store64(address, &Mac::REG::from_u64(value)),
+        _ => {
+            let high_address = address.overflowing_add(&Mac::REG::from_u8(4));
+            memory.store32(address, &Mac::REG::from_u64(value))?;
+            memory.store32(&high_address, &Mac::REG::from_u64(value >> 32))
+        }
+    }
+}
+
+// Unit-stride and strided loads and stores of EEW bits elements, the stride
+// comes from rs2 for strided ones. The register group holds EMUL = EEW / SEW * LMUL
+// registers.
+fn vector_load_store<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+    eew: usize,
+    strided: bool,
+    store: bool,
+) -> Result<(), Error> {
+    let c = vector_config(machine, inst)?;
+    let i = R4type(inst);
+    let emul8 = eew * c.lmul8 / c.sew;
+    if store {
+        vector_groups(inst, &[(i.rd(), emul8)])?;
+    } else {
+        vector_destination(inst, emul8)?;
+    }
+    let base = machine.registers()[i.rs1()].clone();
+    let stride = if strided {
+        machine.registers()[i.rs2()].clone()
+    } else {
+        Mac::REG::from_u64((eew / 8) as u64)
+    };
+    for index in 0..c.vl {
+        if !vector_active(machine.vregisters(), inst, index) {
+            continue;
+        }
+        let address =
+            base.overflowing_add(&stride.overflowing_mul(&Mac::REG::from_u64(index as u64)));
+        if store {
+            let value = vector::read_element(machine.vregisters(), i.rd(), eew, index);
+            vector_store_element(machine, &address, eew / 8, value)?;
+        } else {
+            let value = vector_load_element(machine, &address, eew / 8)?;
+            vector::write_element(machine.vregisters_mut(), i.rd(), eew, index, value);
+        }
+    }
+    Ok(())
+}
+
+// Loads and stores of `bytes` bytes starting at vd, which are not affected by
+// vtype nor masking. They are used by the mask and whole register loads and
+// stores.
+fn vector_load_store_bytes<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+    bytes: usize,
+    store: bool,
+) -> Result<(), Error> {
+    let i = R4type(inst);
+    let base = machine.registers()[i.rs1()].clone();
+    for index in 0..bytes {
+        let address = base.overflowing_add(&Mac::REG::from_u64(index as u64));
+        if store {
+            let value = vector::read_element(machine.vregisters(), i.rd(), 8, index);
+            vector_store_element(machine, &address, 1, value)?;
+        } else {
+            let value = vector_load_element(machine, &address, 1)?;
+            vector::write_element(machine.vregisters_mut(), i.rd(), 8, index, value);
+        }
+    }
+    Ok(())
+}
+
+fn vector_mask_load_store<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+    store: bool,
+) -> Result<(), Error> {
+    let c = vector_config(machine, inst)?;
+    vector_load_store_bytes(machine, inst, (c.vl + 7) / 8, store)
+}
+
+// The number of registers is kept in rs2.
+fn vector_whole_load_store<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+    store: bool,
+) -> Result<(), Error> {
+    let i = R4type(inst);
+    vector_groups(inst, &[(i.rd(), i.rs2() * 8)])?;
+    vector_load_store_bytes(machine, inst, i.rs2() * RISCV_VLENB, store)
+}
+
+pub fn handle_vsetvli<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    let i = Itype(inst);
+    let avl = vector_avl(machine, i.rd(), i.rs1());
+    vector_set_vl(machine, i.rd(), avl, u64::from(i.immediate_u()))
+}
+
+pub fn handle_vsetivli<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    let i = Itype(inst);
+    vector_set_vl(machine, i.rd(), i.rs1() as u64, u64::from(i.immediate_u()))
+}
+
+pub fn handle_vsetvl<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    let i = Rtype(inst);
+    let avl = vector_avl(machine, i.rd(), i.rs1());
+    let vtype = machine.registers()[i.rs2()].to_u64();
+    vector_set_vl(machine, i.rd(), avl, vtype)
+}
+
+pub fn handle_vle8_v<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_load_store(machine, inst, 8, false, false)
+}
+
+pub fn handle_vle16_v<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_load_store(machine, inst, 16, false, false)
+}
+
+pub fn handle_vle32_v<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_load_store(machine, inst, 32, false, false)
+}
+
+pub fn handle_vle64_v<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_load_store(machine, inst, 64, false, false)
+}
+
+pub fn handle_vse8_v<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_load_store(machine, inst, 8, false, true)
+}
+
+pub fn handle_vse16_v<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_load_store(machine, inst, 16, false, true)
+}
+
+pub fn handle_vse32_v<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_load_store(machine, inst, 32, false, true)
+}
+
+pub fn handle_vse64_v<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_load_store(machine, inst, 64, false, true)
+}
+
+pub fn handle_vlse8_v<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_load_store(machine, inst, 8, true, false)
+}
+
+pub fn handle_vlse16_v<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_load_store(machine, inst, 16, true, false)
+}
+
+pub fn handle_vlse32_v<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_load_store(machine, inst, 32, true, false)
+}
+
+pub fn handle_vlse64_v<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_load_store(machine, inst, 64, true, false)
+}
+
+pub fn handle_vsse8_v<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_load_store(machine, inst, 8, true, true)
+}
+
+pub fn handle_vsse16_v<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_load_store(machine, inst, 16, true, true)
+}
+
+pub fn handle_vsse32_v<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_load_store(machine, inst, 32, true, true)
+}
+
+pub fn handle_vsse64_v<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_load_store(machine, inst, 64, true, true)
+}
+
+pub fn handle_vlm_v<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_mask_load_store(machine, inst, false)
+}
+
+pub fn handle_vsm_v<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_mask_load_store(machine, inst, true)
+}
+
+pub fn handle_vlr_v<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_whole_load_store(machine, inst, false)
+}
+
+pub fn handle_vsr_v<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_whole_load_store(machine, inst, true)
+}
+
+pub fn handle_vadd_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Vector, |a, b, _| {
+        a.wrapping_add(b)
+    })
+}
+
+pub fn handle_vadd_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Scalar, |a, b, _| {
+        a.wrapping_add(b)
+    })
+}
+
+pub fn handle_vadd_vi<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Immediate, |a, b, _| {
+        a.wrapping_add(b)
+    })
+}
+
+pub fn handle_vsub_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Vector, |a, b, _| {
+        a.wrapping_sub(b)
+    })
+}
+
+pub fn handle_vsub_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Scalar, |a, b, _| {
+        a.wrapping_sub(b)
+    })
+}
+
+pub fn handle_vrsub_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Scalar, |a, b, _| {
+        b.wrapping_sub(a)
+    })
+}
+
+pub fn handle_vrsub_vi<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Immediate, |a, b, _| {
+        b.wrapping_sub(a)
+    })
+}
+
+pub fn handle_vminu_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Vector, |a, b, _| a.min(b))
+}
+
+pub fn handle_vminu_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Scalar, |a, b, _| a.min(b))
+}
+
+pub fn handle_vmin_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Vector, |a, b, sew| {
+        if vector::sign_extend(a, sew) < vector::sign_extend(b, sew) {
+            a
+        } else {
+            b
+        }
+    })
+}
+
+pub fn handle_vmin_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Scalar, |a, b, sew| {
+        if vector::sign_extend(a, sew) < vector::sign_extend(b, sew) {
+            a
+        } else {
+            b
+        }
+    })
+}
+
+pub fn handle_vmaxu_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Vector, |a, b, _| a.max(b))
+}
+
+pub fn handle_vmaxu_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Scalar, |a, b, _| a.max(b))
+}
+
+pub fn handle_vmax_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Vector, |a, b, sew| {
+        if vector::sign_extend(a, sew) > vector::sign_extend(b, sew) {
+            a
+        } else {
+            b
+        }
+    })
+}
+
+pub fn handle_vmax_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Scalar, |a, b, sew| {
+        if vector::sign_extend(a, sew) > vector::sign_extend(b, sew) {
+            a
+        } else {
+            b
+        }
+    })
+}
+
+pub fn handle_vand_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Vector, |a, b, _| a & b)
+}
+
+pub fn handle_vand_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Scalar, |a, b, _| a & b)
+}
+
+pub fn handle_vand_vi<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Immediate, |a, b, _| a & b)
+}
+
+pub fn handle_vor_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Vector, |a, b, _| a | b)
+}
+
+pub fn handle_vor_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Scalar, |a, b, _| a | b)
+}
+
+pub fn handle_vor_vi<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Immediate, |a, b, _| a | b)
+}
+
+pub fn handle_vxor_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Vector, |a, b, _| a ^ b)
+}
+
+pub fn handle_vxor_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Scalar, |a, b, _| a ^ b)
+}
+
+pub fn handle_vxor_vi<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Immediate, |a, b, _| a ^ b)
+}
+
+pub fn handle_vadc_vvm<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_carry(machine, inst, VectorOperand::Vector, false)
+}
+
+pub fn handle_vmadc_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_carry_out(machine, inst, VectorOperand::Vector, false)
+}
+
+pub fn handle_vadc_vxm<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_carry(machine, inst, VectorOperand::Scalar, false)
+}
+
+pub fn handle_vmadc_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_carry_out(machine, inst, VectorOperand::Scalar, false)
+}
+
+pub fn handle_vadc_vim<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_carry(machine, inst, VectorOperand::Immediate, false)
+}
+
+pub fn handle_vmadc_vi<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_carry_out(machine, inst, VectorOperand::Immediate, false)
+}
+
+pub fn handle_vsbc_vvm<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_carry(machine, inst, VectorOperand::Vector, true)
+}
+
+pub fn handle_vmsbc_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_carry_out(machine, inst, VectorOperand::Vector, true)
+}
+
+pub fn handle_vsbc_vxm<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_carry(machine, inst, VectorOperand::Scalar, true)
+}
+
+pub fn handle_vmsbc_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_carry_out(machine, inst, VectorOperand::Scalar, true)
+}
+
+pub fn handle_vmerge_vvm<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_merge(machine, inst, VectorOperand::Vector)
+}
+
+pub fn handle_vmerge_vxm<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_merge(machine, inst, VectorOperand::Scalar)
+}
+
+pub fn handle_vmerge_vim<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_merge(machine, inst, VectorOperand::Immediate)
+}
+
+pub fn handle_vmv_v_v<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_merge(machine, inst, VectorOperand::Vector)
+}
+
+pub fn handle_vmv_v_x<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_merge(machine, inst, VectorOperand::Scalar)
+}
+
+pub fn handle_vmv_v_i<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_merge(machine, inst, VectorOperand::Immediate)
+}
+
+pub fn handle_vmseq_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_compare(machine, inst, VectorOperand::Vector, |a, b, _| a == b)
+}
+
+pub fn handle_vmseq_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_compare(machine, inst, VectorOperand::Scalar, |a, b, _| a == b)
+}
+
+pub fn handle_vmseq_vi<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_compare(machine, inst, VectorOperand::Immediate, |a, b, _| a == b)
+}
+
+pub fn handle_vmsne_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_compare(machine, inst, VectorOperand::Vector, |a, b, _| a != b)
+}
+
+pub fn handle_vmsne_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_compare(machine, inst, VectorOperand::Scalar, |a, b, _| a != b)
+}
+
+pub fn handle_vmsne_vi<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_compare(machine, inst, VectorOperand::Immediate, |a, b, _| a != b)
+}
+
+pub fn handle_vmsltu_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_compare(machine, inst, VectorOperand::Vector, |a, b, _| a < b)
+}
+
+pub fn handle_vmsltu_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_compare(machine, inst, VectorOperand::Scalar, |a, b, _| a < b)
+}
+
+pub fn handle_vmslt_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_compare(machine, inst, VectorOperand::Vector, |a, b, sew| {
+        vector::sign_extend(a, sew) < vector::sign_extend(b, sew)
+    })
+}
+
+pub fn handle_vmslt_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_compare(machine, inst, VectorOperand::Scalar, |a, b, sew| {
+        vector::sign_extend(a, sew) < vector::sign_extend(b, sew)
+    })
+}
+
+pub fn handle_vmsleu_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_compare(machine, inst, VectorOperand::Vector, |a, b, _| a <= b)
+}
+
+pub fn handle_vmsleu_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_compare(machine, inst, VectorOperand::Scalar, |a, b, _| a <= b)
+}
+
+pub fn handle_vmsleu_vi<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_compare(machine, inst, VectorOperand::Immediate, |a, b, _| a <= b)
+}
+
+pub fn handle_vmsle_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_compare(machine, inst, VectorOperand::Vector, |a, b, sew| {
+        vector::sign_extend(a, sew) <= vector::sign_extend(b, sew)
+    })
+}
+
+pub fn handle_vmsle_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_compare(machine, inst, VectorOperand::Scalar, |a, b, sew| {
+        vector::sign_extend(a, sew) <= vector::sign_extend(b, sew)
+    })
+}
+
+pub fn handle_vmsle_vi<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_compare(machine, inst, VectorOperand::Immediate, |a, b, sew| {
+        vector::sign_extend(a, sew) <= vector::sign_extend(b, sew)
+    })
+}
+
+pub fn handle_vmsgtu_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_compare(machine, inst, VectorOperand::Scalar, |a, b, _| a > b)
+}
+
+pub fn handle_vmsgtu_vi<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_compare(machine, inst, VectorOperand::Immediate, |a, b, _| a > b)
+}
+
+pub fn handle_vmsgt_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_compare(machine, inst, VectorOperand::Scalar, |a, b, sew| {
+        vector::sign_extend(a, sew) > vector::sign_extend(b, sew)
+    })
+}
+
+pub fn handle_vmsgt_vi<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_compare(machine, inst, VectorOperand::Immediate, |a, b, sew| {
+        vector::sign_extend(a, sew) > vector::sign_extend(b, sew)
+    })
+}
+
+pub fn handle_vsll_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Vector, |a, b, sew| {
+        a << (b & (sew as u64 - 1))
+    })
+}
+
+pub fn handle_vsll_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Scalar, |a, b, sew| {
+        a << (b & (sew as u64 - 1))
+    })
+}
+
+pub fn handle_vsll_vi<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(
+        machine,
+        inst,
+        VectorOperand::UnsignedImmediate,
+        |a, b, sew| a << (b & (sew as u64 - 1)),
+    )
+}
+
+pub fn handle_vsrl_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Vector, |a, b, sew| {
+        a >> (b & (sew as u64 - 1))
+    })
+}
+
+pub fn handle_vsrl_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Scalar, |a, b, sew| {
+        a >> (b & (sew as u64 - 1))
+    })
+}
+
+pub fn handle_vsrl_vi<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(
+        machine,
+        inst,
+        VectorOperand::UnsignedImmediate,
+        |a, b, sew| a >> (b & (sew as u64 - 1)),
+    )
+}
+
+pub fn handle_vsra_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Vector, |a, b, sew| {
+        (vector::sign_extend(a, sew) >> (b & (sew as u64 - 1))) as u64
+    })
+}
+
+pub fn handle_vsra_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Scalar, |a, b, sew| {
+        (vector::sign_extend(a, sew) >> (b & (sew as u64 - 1))) as u64
+    })
+}
+
+pub fn handle_vsra_vi<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(
+        machine,
+        inst,
+        VectorOperand::UnsignedImmediate,
+        |a, b, sew| (vector::sign_extend(a, sew) >> (b & (sew as u64 - 1))) as u64,
+    )
+}
+
+pub fn handle_vslideup_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_slide(machine, inst, VectorOperand::Scalar, true)
+}
+
+pub fn handle_vslideup_vi<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_slide(machine, inst, VectorOperand::UnsignedImmediate, true)
+}
+
+pub fn handle_vslidedown_vx<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+) -> Result<(), Error> {
+    vector_slide(machine, inst, VectorOperand::Scalar, false)
+}
+
+pub fn handle_vslidedown_vi<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+) -> Result<(), Error> {
+    vector_slide(machine, inst, VectorOperand::UnsignedImmediate, false)
+}
+
+// vmv<nr>r.v copies nr registers regardless of vtype, nr - 1 is kept in rs1.
+pub fn handle_vmvnr_v<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    let i = R4type(inst);
+    let registers = i.rs1() + 1;
+    vector_groups(inst, &[(i.rd(), registers * 8), (i.rs2(), registers * 8)])?;
+    machine.vregisters_mut().copy_within(
+        i.rs2() * RISCV_VLENB..(i.rs2() + registers) * RISCV_VLENB,
+        i.rd() * RISCV_VLENB,
+    );
+    Ok(())
+}
+
+pub fn handle_vmul_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Vector, |a, b, _| {
+        a.wrapping_mul(b)
+    })
+}
+
+pub fn handle_vmul_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Scalar, |a, b, _| {
+        a.wrapping_mul(b)
+    })
+}
+
+pub fn handle_vmulh_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Vector, |a, b, sew| {
+        ((i128::from(vector::sign_extend(a, sew)) * i128::from(vector::sign_extend(b, sew))) >> sew)
+            as u64
+    })
+}
+
+pub fn handle_vmulh_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Scalar, |a, b, sew| {
+        ((i128::from(vector::sign_extend(a, sew)) * i128::from(vector::sign_extend(b, sew))) >> sew)
+            as u64
+    })
+}
+
+pub fn handle_vmulhu_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Vector, |a, b, sew| {
+        ((u128::from(a) * u128::from(b)) >> sew) as u64
+    })
+}
+
+pub fn handle_vmulhu_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Scalar, |a, b, sew| {
+        ((u128::from(a) * u128::from(b)) >> sew) as u64
+    })
+}
+
+pub fn handle_vmulhsu_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Vector, |a, b, sew| {
+        ((i128::from(vector::sign_extend(a, sew)) * i128::from(b)) >> sew) as u64
+    })
+}
+
+pub fn handle_vmulhsu_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Scalar, |a, b, sew| {
+        ((i128::from(vector::sign_extend(a, sew)) * i128::from(b)) >> sew) as u64
+    })
+}
+
+pub fn handle_vdivu_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Vector, |a, b, _| {
+        if b == 0 {
+            u64::MAX
+        } else {
+            a / b
+        }
+    })
+}
+
+pub fn handle_vdivu_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Scalar, |a, b, _| {
+        if b == 0 {
+            u64::MAX
+        } else {
+            a / b
+        }
+    })
+}
+
+pub fn handle_vdiv_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Vector, |a, b, sew| {
+        let (a, b) = (vector::sign_extend(a, sew), vector::sign_extend(b, sew));
+        if b == 0 {
+            u64::MAX
+        } else {
+            a.wrapping_div(b) as u64
+        }
+    })
+}
+
+pub fn handle_vdiv_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Scalar, |a, b, sew| {
+        let (a, b) = (vector::sign_extend(a, sew), vector::sign_extend(b, sew));
+        if b == 0 {
+            u64::MAX
+        } else {
+            a.wrapping_div(b) as u64
+        }
+    })
+}
+
+pub fn handle_vremu_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Vector, |a, b, _| {
+        if b == 0 {
+            a
+        } else {
+            a % b
+        }
+    })
+}
+
+pub fn handle_vremu_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Scalar, |a, b, _| {
+        if b == 0 {
+            a
+        } else {
+            a % b
+        }
+    })
+}
+
+pub fn handle_vrem_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Vector, |a, b, sew| {
+        let (sa, sb) = (vector::sign_extend(a, sew), vector::sign_extend(b, sew));
+        if sb == 0 {
+            a
+        } else {
+            sa.wrapping_rem(sb) as u64
+        }
+    })
+}
+
+pub fn handle_vrem_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_binary(machine, inst, VectorOperand::Scalar, |a, b, sew| {
+        let (sa, sb) = (vector::sign_extend(a, sew), vector::sign_extend(b, sew));
+        if sb == 0 {
+            a
+        } else {
+            sa.wrapping_rem(sb) as u64
+        }
+    })
+}
+
+pub fn handle_vmacc_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_multiply_add(machine, inst, VectorOperand::Vector, |d, b, a| {
+        d.wrapping_add(b.wrapping_mul(a))
+    })
+}
+
+pub fn handle_vmacc_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_multiply_add(machine, inst, VectorOperand::Scalar, |d, b, a| {
+        d.wrapping_add(b.wrapping_mul(a))
+    })
+}
+
+pub fn handle_vnmsac_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_multiply_add(machine, inst, VectorOperand::Vector, |d, b, a| {
+        d.wrapping_sub(b.wrapping_mul(a))
+    })
+}
+
+pub fn handle_vnmsac_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_multiply_add(machine, inst, VectorOperand::Scalar, |d, b, a| {
+        d.wrapping_sub(b.wrapping_mul(a))
+    })
+}
+
+pub fn handle_vmadd_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_multiply_add(machine, inst, VectorOperand::Vector, |d, b, a| {
+        b.wrapping_mul(d).wrapping_add(a)
+    })
+}
+
+pub fn handle_vmadd_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_multiply_add(machine, inst, VectorOperand::Scalar, |d, b, a| {
+        b.wrapping_mul(d).wrapping_add(a)
+    })
+}
+
+pub fn handle_vnmsub_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_multiply_add(machine, inst, VectorOperand::Vector, |d, b, a| {
+        a.wrapping_sub(b.wrapping_mul(d))
+    })
+}
+
+pub fn handle_vnmsub_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_multiply_add(machine, inst, VectorOperand::Scalar, |d, b, a| {
+        a.wrapping_sub(b.wrapping_mul(d))
+    })
+}
+
+pub fn handle_vwaddu_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_widening(machine, inst, VectorOperand::Vector, |_, a, b, _| a + b)
+}
+
+pub fn handle_vwaddu_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_widening(machine, inst, VectorOperand::Scalar, |_, a, b, _| a + b)
+}
+
+pub fn handle_vwadd_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_widening(machine, inst, VectorOperand::Vector, |_, a, b, sew| {
+        (vector::sign_extend(a, sew) + vector::sign_extend(b, sew)) as u64
+    })
+}
+
+pub fn handle_vwadd_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_widening(machine, inst, VectorOperand::Scalar, |_, a, b, sew| {
+        (vector::sign_extend(a, sew) + vector::sign_extend(b, sew)) as u64
+    })
+}
+
+pub fn handle_vwsubu_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_widening(machine, inst, VectorOperand::Vector, |_, a, b, _| {
+        a.wrapping_sub(b)
+    })
+}
+
+pub fn handle_vwsubu_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_widening(machine, inst, VectorOperand::Scalar, |_, a, b, _| {
+        a.wrapping_sub(b)
+    })
+}
+
+pub fn handle_vwsub_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_widening(machine, inst, VectorOperand::Vector, |_, a, b, sew| {
+        (vector::sign_extend(a, sew) - vector::sign_extend(b, sew)) as u64
+    })
+}
+
+pub fn handle_vwsub_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_widening(machine, inst, VectorOperand::Scalar, |_, a, b, sew| {
+        (vector::sign_extend(a, sew) - vector::sign_extend(b, sew)) as u64
+    })
+}
+
+pub fn handle_vwmulu_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_widening(machine, inst, VectorOperand::Vector, |_, a, b, _| a * b)
+}
+
+pub fn handle_vwmulu_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_widening(machine, inst, VectorOperand::Scalar, |_, a, b, _| a * b)
+}
+
+pub fn handle_vwmul_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_widening(machine, inst, VectorOperand::Vector, |_, a, b, sew| {
+        (vector::sign_extend(a, sew) * vector::sign_extend(b, sew)) as u64
+    })
+}
+
+pub fn handle_vwmul_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_widening(machine, inst, VectorOperand::Scalar, |_, a, b, sew| {
+        (vector::sign_extend(a, sew) * vector::sign_extend(b, sew)) as u64
+    })
+}
+
+pub fn handle_vwmulsu_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_widening(machine, inst, VectorOperand::Vector, |_, a, b, sew| {
+        (vector::sign_extend(a, sew) * b as i64) as u64
+    })
+}
+
+pub fn handle_vwmulsu_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_widening(machine, inst, VectorOperand::Scalar, |_, a, b, sew| {
+        (vector::sign_extend(a, sew) * b as i64) as u64
+    })
+}
+
+pub fn handle_vwmaccu_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_widening(machine, inst, VectorOperand::Vector, |d, a, b, _| {
+        d.wrapping_add(a * b)
+    })
+}
+
+pub fn handle_vwmaccu_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_widening(machine, inst, VectorOperand::Scalar, |d, a, b, _| {
+        d.wrapping_add(a * b)
+    })
+}
+
+pub fn handle_vwmacc_vv<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_widening(machine, inst, VectorOperand::Vector, |d, a, b, sew| {
+        d.wrapping_add((vector::sign_extend(a, sew) * vector::sign_extend(b, sew)) as u64)
+    })
+}
+
+pub fn handle_vwmacc_vx<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_widening(machine, inst, VectorOperand::Scalar, |d, a, b, sew| {
+        d.wrapping_add((vector::sign_extend(a, sew) * vector::sign_extend(b, sew)) as u64)
+    })
+}
+
+pub fn handle_vredsum_vs<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_reduction(machine, inst, |acc, x, _| acc.wrapping_add(x))
+}
+
+pub fn handle_vredand_vs<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_reduction(machine, inst, |acc, x, _| acc & x)
+}
+
+pub fn handle_vredor_vs<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_reduction(machine, inst, |acc, x, _| acc | x)
+}
+
+pub fn handle_vredxor_vs<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_reduction(machine, inst, |acc, x, _| acc ^ x)
+}
+
+pub fn handle_vredminu_vs<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_reduction(machine, inst, |acc, x, _| acc.min(x))
+}
+
+pub fn handle_vredmin_vs<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_reduction(machine, inst, |acc, x, sew| {
+        if vector::sign_extend(x, sew) < vector::sign_extend(acc, sew) {
+            x
+        } else {
+            acc
+        }
+    })
+}
+
+pub fn handle_vredmaxu_vs<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_reduction(machine, inst, |acc, x, _| acc.max(x))
+}
+
+pub fn handle_vredmax_vs<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_reduction(machine, inst, |acc, x, sew| {
+        if vector::sign_extend(x, sew) > vector::sign_extend(acc, sew) {
+            x
+        } else {
+            acc
+        }
+    })
+}
+
+pub fn handle_vmand_mm<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_mask_logical(machine, inst, |a, b| a & b)
+}
+
+pub fn handle_vmnand_mm<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_mask_logical(machine, inst, |a, b| !(a & b))
+}
+
+pub fn handle_vmandn_mm<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_mask_logical(machine, inst, |a, b| a & !b)
+}
+
+pub fn handle_vmxor_mm<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_mask_logical(machine, inst, |a, b| a ^ b)
+}
+
+pub fn handle_vmor_mm<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_mask_logical(machine, inst, |a, b| a | b)
+}
+
+pub fn handle_vmnor_mm<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_mask_logical(machine, inst, |a, b| !(a | b))
+}
+
+pub fn handle_vmorn_mm<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_mask_logical(machine, inst, |a, b| a | !b)
+}
+
+pub fn handle_vmxnor_mm<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_mask_logical(machine, inst, |a, b| !(a ^ b))
+}
+
+pub fn handle_vmv_x_s<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    let c = vector_config(machine, inst)?;
+    let i = R4type(inst);
+    let value = vector::read_element(machine.vregisters(), i.rs2(), c.sew, 0);
+    let value = vector::sign_extend(value, c.sew) as u64;
+    update_register(machine, i.rd(), Mac::REG::from_u64(value));
+    Ok(())
+}
+
+pub fn handle_vmv_s_x<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    let c = vector_config(machine, inst)?;
+    let i = R4type(inst);
+    let value = vector_scalar(machine, inst, VectorOperand::Scalar);
+    if c.vl > 0 {
+        vector::write_element(machine.vregisters_mut(), i.rd(), c.sew, 0, value);
+    }
+    Ok(())
+}
+
+pub fn handle_vcpop_m<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    let c = vector_config(machine, inst)?;
+    let i = R4type(inst);
+    let v = machine.vregisters();
+    let count = (0..c.vl)
+        .filter(|index| vector_active(v, inst, *index) && vector::mask_bit(v, i.rs2(), *index))
+        .count();
+    update_register(machine, i.rd(), Mac::REG::from_u64(count as u64));
+    Ok(())
+}
+
+pub fn handle_vfirst_m<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    let c = vector_config(machine, inst)?;
+    let i = R4type(inst);
+    let v = machine.vregisters();
+    let first = (0..c.vl)
+        .find(|index| vector_active(v, inst, *index) && vector::mask_bit(v, i.rs2(), *index))
+        .map(|index| index as i64)
+        .unwrap_or(-1);
+    update_register(machine, i.rd(), Mac::REG::from_i64(first));
+    Ok(())
+}
+
+pub fn handle_vid_v<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    let c = vector_config(machine, inst)?;
+    let i = R4type(inst);
+    vector_destination(inst, c.lmul8)?;
+    let v = machine.vregisters_mut();
+    for index in 0..c.vl {
+        if vector_active(v, inst, index) {
+            vector::write_element(v, i.rd(), c.sew, index, index as u64);
+        }
+    }
+    Ok(())
+}
+
+pub fn handle_vzext_vf2<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_extend(machine, inst, 2, false)
+}
+
+pub fn handle_vzext_vf4<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_extend(machine, inst, 4, false)
+}
+
+pub fn handle_vzext_vf8<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_extend(machine, inst, 8, false)
+}
+
+pub fn handle_vsext_vf2<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_extend(machine, inst, 2, true)
+}
+
+pub fn handle_vsext_vf4<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_extend(machine, inst, 4, true)
+}
+
+pub fn handle_vsext_vf8<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    vector_extend(machine, inst, 8, true)
+}
+
+pub fn handle_vslide1up_vx<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+) -> Result<(), Error> {
+    vector_slide1(machine, inst, true)
+}
+
+pub fn handle_vslide1down_vx<Mac: Machine>(
+    machine: &mut Mac,
+    inst: Instruction,
+) -> Result<(), Error> {
+    vector_slide1(machine, inst, false)
+}
+
+// Registers are stored below sp from the last one of the list, so ra ends up
+// at the lowest address, then sp moves down by the stack adjustment.
+pub fn handle_cm_push<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    let i = Itype(inst);
+    let bytes = i32::from(Mac::REG::BITS / 8);
+    let mut offset = 0;
+    for register in rvc::zcmp_registers(i.rs1()).iter().rev() {
+        offset -= bytes;
+        if bytes == 8 {
+            common::sd(machine, SP, *register, offset)?;
+        } else {
+            common::sw(machine, SP, *register, offset)?;
+        }
+    }
+    let sp = machine.registers()[SP].overflowing_sub(&Mac::REG::from_u32(i.immediate_u()));
+    update_register(machine, SP, sp);
+    Ok(())
+}
+
+fn cm_pop<Mac: Machine>(machine: &mut Mac, i: Itype) -> Result<(), Error> {
+    let bytes = i32::from(Mac::REG::BITS / 8);
+    let mut offset = i.immediate_u() as i32;
+    for register in rvc::zcmp_registers(i.rs1()).iter().rev() {
+        offset -= bytes;
+        if bytes == 8 {
+            common::ld(machine, *register, SP, offset, false)?;
+        } else {
+            common::lw(machine, *register, SP, offset, false)?;
+        }
+    }
+    let sp = machine.registers()[SP].overflowing_add(&Mac::REG::from_u32(i.immediate_u()));
+    update_register(machine, SP, sp);
+    Ok(())
+}
+
+pub fn handle_cm_pop<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    cm_pop(machine, Itype(inst))
+}
+
+pub fn handle_cm_popret<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    cm_pop(machine, Itype(inst))?;
+    let next_pc = machine.registers()[RA].clone() & (!Mac::REG::one());
+    machine.update_pc(next_pc);
+    Ok(())
+}
+
+pub fn handle_cm_popretz<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    cm_pop(machine, Itype(inst))?;
+    update_register(machine, A0, Mac::REG::zero());
+    let next_pc = machine.registers()[RA].clone() & (!Mac::REG::one());
+    machine.update_pc(next_pc);
+    Ok(())
+}
+
 pub fn handle_unloaded<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
     handle_invalid_op(machine, inst)
 }
@@ -1557,6 +3916,18 @@ pub fn execute_instruction<Mac: Machine>(
     machine: &mut Mac,
 ) -> Result<(), Error> {
     let op = extract_opcode(inst);
+    if (op as u8 as u16) < insts::MINIMAL_OPCODE {
+        if insts::is_custom_opcode(op) {
+            return machine.custom(inst);
+        }
+        return for_each_slowpath_inst_match2!(
+            handle_single_opcode,
+            op,
+            handle_invalid_op(machine, inst),
+            machine,
+            inst
+        );
+    }
     for_each_inst_match2!(
         handle_single_opcode,
         op,
@@ -1594,10 +3965,14 @@ pub fn execute_with_thread<Mac: Machine>(
 
 pub type Thread<Mac> = fn(&mut Mac, Instruction) -> Result<(), Error>;
 
+fn handle_slowpath<Mac: Machine>(machine: &mut Mac, inst: Instruction) -> Result<(), Error> {
+    execute_instruction(inst, machine)
+}
+
 pub struct ThreadFactory<Mac: Machine> {
-    // Right now we are only dealing with fastpath opcodes, later we might
-    // (or might not?) expand this with some opcodes in the slowpath category.
     threads: Vec<Thread<Mac>>,
+    // Slowpath opcodes share one thread dispatching on the opcode again.
+    slowpath: Thread<Mac>,
 }
 
 macro_rules! thread_func_item {
@@ -1613,10 +3988,14 @@ impl<Mac: Machine> ThreadFactory<Mac> {
         let threads = for_each_inst_array1!(thread_func_item, Mac);
         Self {
             threads: Vec::from(threads),
+            slowpath: handle_slowpath::<Mac>,
         }
     }
 
     pub fn get(&self, op: InstructionOpcode) -> Option<&Thread<Mac>> {
+        if (op as u8 as u16) < insts::MINIMAL_OPCODE {
+            return Some(&self.slowpath);
+        }
         self.threads
             .get((op as usize).wrapping_sub(insts::MINIMAL_OPCODE as usize))
     }
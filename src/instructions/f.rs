@@ -0,0 +1,207 @@
+use ckb_vm_definitions::instructions::{self as insts, InstructionOpcode};
+
+use super::utils::{funct3, funct7, itype_immediate, opcode, rd, rs1, rs2, stype_immediate, x};
+use super::{set_instruction_length_4, Instruction, Itype, R4type, Register, Rtype, Stype};
+use crate::machine::VERSION3;
+
+// CSR numbers of the floating-point CSRs.
+pub const CSR_FFLAGS: u32 = 0x001;
+pub const CSR_FRM: u32 = 0x002;
+pub const CSR_FCSR: u32 = 0x003;
+
+// The rounding mode of an instruction is kept in the highest byte, which is
+// not used by Rtype and R4type instructions. 0b111 selects the dynamic
+// rounding mode in frm.
+pub fn rounding_mode(instruction: Instruction) -> u32 {
+    (instruction >> 56) as u32
+}
+
+pub(crate) fn set_rounding_mode(instruction: Instruction, rm: u32) -> Instruction {
+    instruction | (u64::from(rm) << 56)
+}
+
+// The rm field of instruction bits, 0b101 and 0b110 are reserved.
+pub(crate) fn valid_rounding_mode(instruction_bits: u32) -> Option<u32> {
+    match funct3(instruction_bits) {
+        0b101 | 0b110 => None,
+        rm => Some(rm),
+    }
+}
+
+pub(crate) fn rs3(instruction_bits: u32) -> usize {
+    x(instruction_bits, 27, 5, 0) as usize
+}
+
+// The fmt field of R4-type instructions, 0b00 for single and 0b01 for double
+// precision.
+pub(crate) fn fmt(instruction_bits: u32) -> u32 {
+    x(instruction_bits, 25, 2, 0)
+}
+
+pub(crate) fn fused(instruction_bits: u32, ops: [InstructionOpcode; 4]) -> Option<Instruction> {
+    let op = match opcode(instruction_bits) {
+        0b_1000011 => ops[0],
+        0b_1000111 => ops[1],
+        0b_1001011 => ops[2],
+        0b_1001111 => ops[3],
+        _ => return None,
+    };
+    let rm = valid_rounding_mode(instruction_bits)?;
+    Some(set_rounding_mode(
+        R4type::new(
+            op,
+            rd(instruction_bits),
+            rs1(instruction_bits),
+            rs2(instruction_bits),
+            rs3(instruction_bits),
+        )
+        .0,
+        rm,
+    ))
+}
+
+pub(crate) fn rounded(op: InstructionOpcode, instruction_bits: u32) -> Option<Instruction> {
+    let rm = valid_rounding_mode(instruction_bits)?;
+    Some(set_rounding_mode(
+        Rtype::new(
+            op,
+            rd(instruction_bits),
+            rs1(instruction_bits),
+            rs2(instruction_bits),
+        )
+        .0,
+        rm,
+    ))
+}
+
+pub(crate) fn csr_number(instruction_bits: u32) -> u32 {
+    itype_immediate(instruction_bits) as u32 & 0xfff
+}
+
+pub(crate) fn csr(instruction_bits: u32) -> Option<Instruction> {
+    let csr = csr_number(instruction_bits);
+    // The immediate variants keep their 5 bits unsigned immediate in rs1.
+    let op = match funct3(instruction_bits) {
+        0b001 => insts::OP_CSRRW,
+        0b010 => insts::OP_CSRRS,
+        0b011 => insts::OP_CSRRC,
+        0b101 => insts::OP_CSRRWI,
+        0b110 => insts::OP_CSRRSI,
+        0b111 => insts::OP_CSRRCI,
+        _ => return None,
+    };
+    Some(Itype::new_u(op, rd(instruction_bits), rs1(instruction_bits), csr).0)
+}
+
+pub fn factory<R: Register>(instruction_bits: u32, version: u32) -> Option<Instruction> {
+    let bit_length = R::BITS;
+    if bit_length != 32 && bit_length != 64 {
+        return None;
+    }
+    if version < VERSION3 {
+        return None;
+    }
+    let rv64 = bit_length == 64;
+    let rm_op = |op| rounded(op, instruction_bits);
+    let r_op = |op| {
+        Some(
+            Rtype::new(
+                op,
+                rd(instruction_bits),
+                rs1(instruction_bits),
+                rs2(instruction_bits),
+            )
+            .0,
+        )
+    };
+    let inst = match opcode(instruction_bits) {
+        0b_0000111 if funct3(instruction_bits) == 0b010 => Some(
+            Itype::new_s(
+                insts::OP_FLW,
+                rd(instruction_bits),
+                rs1(instruction_bits),
+                itype_immediate(instruction_bits),
+            )
+            .0,
+        ),
+        0b_0100111 if funct3(instruction_bits) == 0b010 => Some(
+            Stype::new_s(
+                insts::OP_FSW,
+                stype_immediate(instruction_bits),
+                rs1(instruction_bits),
+                rs2(instruction_bits),
+            )
+            .0,
+        ),
+        0b_1000011 | 0b_1000111 | 0b_1001011 | 0b_1001111 if fmt(instruction_bits) == 0b00 => {
+            fused(
+                instruction_bits,
+                [
+                    insts::OP_FMADD_S,
+                    insts::OP_FMSUB_S,
+                    insts::OP_FNMSUB_S,
+                    insts::OP_FNMADD_S,
+                ],
+            )
+        }
+        0b_1010011 => {
+            let funct3 = funct3(instruction_bits);
+            let rs2 = rs2(instruction_bits);
+            match funct7(instruction_bits) {
+                0b_0000000 => rm_op(insts::OP_FADD_S),
+                0b_0000100 => rm_op(insts::OP_FSUB_S),
+                0b_0001000 => rm_op(insts::OP_FMUL_S),
+                0b_0001100 => rm_op(insts::OP_FDIV_S),
+                0b_0101100 if rs2 == 0 => rm_op(insts::OP_FSQRT_S),
+                0b_0010000 => match funct3 {
+                    0b000 => r_op(insts::OP_FSGNJ_S),
+                    0b001 => r_op(insts::OP_FSGNJN_S),
+                    0b010 => r_op(insts::OP_FSGNJX_S),
+                    _ => None,
+                },
+                0b_0010100 => match funct3 {
+                    0b000 => r_op(insts::OP_FMIN_S),
+                    0b001 => r_op(insts::OP_FMAX_S),
+                    _ => None,
+                },
+                0b_1100000 => match rs2 {
+                    0b00000 => rm_op(insts::OP_FCVT_W_S),
+                    0b00001 => rm_op(insts::OP_FCVT_WU_S),
+                    0b00010 if rv64 => rm_op(insts::OP_FCVT_L_S),
+                    0b00011 if rv64 => rm_op(insts::OP_FCVT_LU_S),
+                    _ => None,
+                },
+                0b_1110000 if rs2 == 0 => match funct3 {
+                    0b000 => r_op(insts::OP_FMV_X_W),
+                    0b001 => r_op(insts::OP_FCLASS_S),
+                    _ => None,
+                },
+                0b_1010000 => match funct3 {
+                    0b000 => r_op(insts::OP_FLE_S),
+                    0b001 => r_op(insts::OP_FLT_S),
+                    0b010 => r_op(insts::OP_FEQ_S),
+                    _ => None,
+                },
+                0b_1101000 => match rs2 {
+                    0b00000 => rm_op(insts::OP_FCVT_S_W),
+                    0b00001 => rm_op(insts::OP_FCVT_S_WU),
+                    0b00010 if rv64 => rm_op(insts::OP_FCVT_S_L),
+                    0b00011 if rv64 => rm_op(insts::OP_FCVT_S_LU),
+                    _ => None,
+                },
+                0b_1111000 if rs2 == 0 && funct3 == 0b000 => r_op(insts::OP_FMV_W_X),
+                _ => None,
+            }
+        }
+        0b_1110011
+            if matches!(
+                csr_number(instruction_bits),
+                CSR_FFLAGS | CSR_FRM | CSR_FCSR
+            ) =>
+        {
+            csr(instruction_bits)
+        }
+        _ => None,
+    };
+    inst.map(set_instruction_length_4)
+}
@@ -0,0 +1,129 @@
+// RISC-V Scalar Cryptography Extension, covering Zbkb, Zbkc, Zbkx, Zknh and
+// Zksh. The instructions only available on RV32(zip, unzip and the split
+// sha512 ones) are not supported.
+// See https://github.com/riscv/riscv-crypto/releases/download/v1.0.1-scalar/riscv-crypto-spec-scalar-v1.0.1.pdf
+
+use ckb_vm_definitions::instructions as insts;
+
+use super::utils::{self, funct3, funct7, opcode, rd, rs1, rs2};
+use super::{set_instruction_length_4, Instruction, Itype, Register, Rtype};
+use crate::machine::VERSION3;
+
+pub fn factory<R: Register>(instruction_bits: u32, version: u32) -> Option<Instruction> {
+    let bit_length = R::BITS;
+    if bit_length != 32 && bit_length != 64 {
+        return None;
+    }
+    if version < VERSION3 {
+        return None;
+    }
+    let rv64 = bit_length == 64;
+    let inst = match opcode(instruction_bits) {
+        0b_0110011 => {
+            let inst_opt = match (funct3(instruction_bits), funct7(instruction_bits)) {
+                // Zbkb, the bit manipulation instructions shared with B
+                // decode to the same opcodes.
+                (0b_111, 0b_0100000) => Some(insts::OP_ANDN),
+                (0b_110, 0b_0100000) => Some(insts::OP_ORN),
+                (0b_100, 0b_0100000) => Some(insts::OP_XNOR),
+                (0b_001, 0b_0110000) => Some(insts::OP_ROL),
+                (0b_101, 0b_0110000) => Some(insts::OP_ROR),
+                (0b_100, 0b_0000100) => Some(insts::OP_PACK),
+                (0b_111, 0b_0000100) => Some(insts::OP_PACKH),
+                // Zbkc
+                (0b_001, 0b_0000101) => Some(insts::OP_CLMUL),
+                (0b_011, 0b_0000101) => Some(insts::OP_CLMULH),
+                // Zbkx
+                (0b_010, 0b_0010100) => Some(insts::OP_XPERM4),
+                (0b_100, 0b_0010100) => Some(insts::OP_XPERM8),
+                _ => None,
+            };
+            inst_opt.map(|inst| {
+                Rtype::new(
+                    inst,
+                    rd(instruction_bits),
+                    rs1(instruction_bits),
+                    rs2(instruction_bits),
+                )
+                .0
+            })
+        }
+        0b_0111011 if rv64 => {
+            let inst_opt = match (funct3(instruction_bits), funct7(instruction_bits)) {
+                (0b_001, 0b_0110000) => Some(insts::OP_ROLW),
+                (0b_101, 0b_0110000) => Some(insts::OP_RORW),
+                (0b_100, 0b_0000100) => Some(insts::OP_PACKW),
+                _ => None,
+            };
+            inst_opt.map(|inst| {
+                Rtype::new(
+                    inst,
+                    rd(instruction_bits),
+                    rs1(instruction_bits),
+                    rs2(instruction_bits),
+                )
+                .0
+            })
+        }
+        0b_0010011 => {
+            let funct3_value = funct3(instruction_bits);
+            let funct7_value = funct7(instruction_bits);
+            let rs2_value = rs2(instruction_bits);
+            let inst_opt = match (funct7_value, funct3_value, rs2_value) {
+                (0b_0110101, 0b_101, 0b_11000) if rv64 => Some(insts::OP_REV8),
+                (0b_0110100, 0b_101, 0b_11000) if !rv64 => Some(insts::OP_REV8),
+                (0b_0110100, 0b_101, 0b_00111) => Some(insts::OP_BREV8),
+                // Zknh
+                (0b_0001000, 0b_001, 0b_00000) => Some(insts::OP_SHA256SUM0),
+                (0b_0001000, 0b_001, 0b_00001) => Some(insts::OP_SHA256SUM1),
+                (0b_0001000, 0b_001, 0b_00010) => Some(insts::OP_SHA256SIG0),
+                (0b_0001000, 0b_001, 0b_00011) => Some(insts::OP_SHA256SIG1),
+                (0b_0001000, 0b_001, 0b_00100) if rv64 => Some(insts::OP_SHA512SUM0),
+                (0b_0001000, 0b_001, 0b_00101) if rv64 => Some(insts::OP_SHA512SUM1),
+                (0b_0001000, 0b_001, 0b_00110) if rv64 => Some(insts::OP_SHA512SIG0),
+                (0b_0001000, 0b_001, 0b_00111) if rv64 => Some(insts::OP_SHA512SIG1),
+                // Zksh
+                (0b_0001000, 0b_001, 0b_01000) => Some(insts::OP_SM3P0),
+                (0b_0001000, 0b_001, 0b_01001) => Some(insts::OP_SM3P1),
+                _ => None,
+            };
+            if let Some(inst) = inst_opt {
+                Some(Rtype::new(inst, rd(instruction_bits), rs1(instruction_bits), 0).0)
+            } else if funct7_value >> 1 == 0b_011000 && funct3_value == 0b_101 {
+                // RV32 only has 5 bits of shift amount.
+                if !rv64 && funct7_value & 1 != 0 {
+                    return None;
+                }
+                Some(
+                    Itype::new_u(
+                        insts::OP_RORI,
+                        rd(instruction_bits),
+                        rs1(instruction_bits),
+                        utils::x(instruction_bits, 20, 6, 0),
+                    )
+                    .0,
+                )
+            } else {
+                None
+            }
+        }
+        0b_0011011 if rv64 => {
+            if funct7(instruction_bits) == 0b_0110000 && funct3(instruction_bits) == 0b_101 {
+                Some(
+                    Itype::new_u(
+                        insts::OP_RORIW,
+                        rd(instruction_bits),
+                        rs1(instruction_bits),
+                        utils::x(instruction_bits, 20, 5, 0),
+                    )
+                    .0,
+                )
+            } else {
+                None
+            }
+        }
+        _ => None,
+    };
+
+    inst.map(set_instruction_length_4)
+}
@@ -1,15 +1,23 @@
 mod common;
 mod execute;
 mod register;
+mod softfloat;
 mod utils;
+pub(crate) mod vector;
 
 pub mod a;
 pub mod ast;
 pub mod b;
+pub mod custom;
+pub mod d;
+pub mod f;
 pub mod i;
+pub mod k;
 pub mod m;
 pub mod rvc;
 pub mod tagged;
+pub mod v;
+pub mod zicond;
 
 pub use self::register::Register;
 use super::Error;
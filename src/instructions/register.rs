@@ -71,6 +71,12 @@ pub trait Register:
     fn clmulr(&self, rhs: &Self) -> Self;
     fn orcb(&self) -> Self;
     fn rev8(&self) -> Self;
+    // Reverses the bits in each byte.
+    fn brev8(&self) -> Self;
+    // Crossbar permutation, each nibble or byte of rhs selects a nibble or byte
+    // of self, an out of range index selects 0.
+    fn xperm4(&self, rhs: &Self) -> Self;
+    fn xperm8(&self, rhs: &Self) -> Self;
 
     fn signed_shl(&self, rhs: &Self) -> Self;
     fn signed_shr(&self, rhs: &Self) -> Self;
@@ -337,6 +343,32 @@ impl Register for u32 {
         r
     }
 
+    fn brev8(&self) -> u32 {
+        self.reverse_bits().swap_bytes()
+    }
+
+    fn xperm4(&self, rhs: &u32) -> u32 {
+        let mut r = 0;
+        for i in (0..32).step_by(4) {
+            let index = (rhs >> i) & 0xf;
+            if index < 8 {
+                r |= ((self >> (index * 4)) & 0xf) << i;
+            }
+        }
+        r
+    }
+
+    fn xperm8(&self, rhs: &u32) -> u32 {
+        let mut r = 0;
+        for i in (0..32).step_by(8) {
+            let index = (rhs >> i) & 0xff;
+            if index < 4 {
+                r |= ((self >> (index * 8)) & 0xff) << i;
+            }
+        }
+        r
+    }
+
     fn rol(&self, rhs: &u32) -> u32 {
         (*self as u32).rotate_left(*rhs) as u32
     }
@@ -642,6 +674,32 @@ impl Register for u64 {
         r
     }
 
+    fn brev8(&self) -> u64 {
+        self.reverse_bits().swap_bytes()
+    }
+
+    fn xperm4(&self, rhs: &u64) -> u64 {
+        let mut r = 0;
+        for i in (0..64).step_by(4) {
+            let index = (rhs >> i) & 0xf;
+            if index < 16 {
+                r |= ((self >> (index * 4)) & 0xf) << i;
+            }
+        }
+        r
+    }
+
+    fn xperm8(&self, rhs: &u64) -> u64 {
+        let mut r = 0;
+        for i in (0..64).step_by(8) {
+            let index = (rhs >> i) & 0xff;
+            if index < 8 {
+                r |= ((self >> (index * 8)) & 0xff) << i;
+            }
+        }
+        r
+    }
+
     fn rol(&self, rhs: &u64) -> u64 {
         (*self as u64).rotate_left((*rhs) as u32) as u64
     }
@@ -1,10 +1,11 @@
 use ckb_vm_definitions::instructions::{self as insts};
-use ckb_vm_definitions::registers::SP;
+use ckb_vm_definitions::registers::{RA, S0, S1, SP};
 
 use super::i::nop;
 use super::register::Register;
-use super::utils::{jalr, ld, lw, rd, x, xs};
+use super::utils::{jalr, lbu, ld, lh, lhu, lw, rd, x, xs};
 use super::{blank_instruction, set_instruction_length_2, Instruction, Itype, Rtype, Stype, Utype};
+use crate::machine::VERSION3;
 
 // Notice the location of rs2 in RVC encoding is different from full encoding
 #[inline(always)]
@@ -47,7 +48,7 @@ fn j_immediate(instruction_bits: u32) -> i32 {
 
 // [12:10] => uimm[5:3]
 // [6:5]   => uimm[7:6]
-fn fld_uimmediate(instruction_bits: u32) -> u32 {
+pub(super) fn fld_uimmediate(instruction_bits: u32) -> u32 {
     x(instruction_bits, 10, 3, 3) | x(instruction_bits, 5, 2, 6)
 }
 
@@ -65,12 +66,12 @@ fn lwsp_uimmediate(instruction_bits: u32) -> u32 {
 
 // [12]  => uimm[5]
 // [6:2] => uimm[4:3|8:6]
-fn fldsp_uimmediate(instruction_bits: u32) -> u32 {
+pub(super) fn fldsp_uimmediate(instruction_bits: u32) -> u32 {
     x(instruction_bits, 5, 2, 3) | x(instruction_bits, 12, 1, 5) | x(instruction_bits, 2, 3, 6)
 }
 
 // [12:7] => uimm[5:3|8:6]
-fn fsdsp_uimmediate(instruction_bits: u32) -> u32 {
+pub(super) fn fsdsp_uimmediate(instruction_bits: u32) -> u32 {
     x(instruction_bits, 10, 3, 3) | x(instruction_bits, 7, 3, 6)
 }
 
@@ -498,3 +499,126 @@ pub fn factory<R: Register>(instruction_bits: u32, version: u32) -> Option<Instr
     }
     .map(set_instruction_length_2)
 }
+
+// Registers saved and restored by the Zcmp push and pop instructions, a
+// register list encoding of 4 to 15 selects ra, then ra and s0, up to ra and
+// s0 - s11. s10 is never saved alone, so 15 selects 2 more registers.
+const ZCMP_REGISTERS: [usize; 13] = [RA, S0, S1, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27];
+
+pub fn zcmp_registers(rlist: usize) -> &'static [usize] {
+    match rlist {
+        4..=14 => &ZCMP_REGISTERS[..rlist - 3],
+        15 => &ZCMP_REGISTERS[..],
+        _ => &[],
+    }
+}
+
+// The s register pairs of cm.mvsa01 and cm.mva01s are s0 - s7, they are
+// encoded in 3 bits as x8, x9, then x18 - x23.
+fn zcmp_sreg_number(instruction_bits: u32, least_bit: usize) -> usize {
+    match x(instruction_bits, least_bit, 3, 0) as usize {
+        r @ 0..=1 => r + 8,
+        r => r + 16,
+    }
+}
+
+// Zcb and Zcmp, code size reduction instructions reusing the reserved RVC
+// encodings. Zcmp overlaps with c.fsdsp, when D is also enabled, the D
+// factory takes those encodings first.
+pub fn zc_factory<R: Register>(instruction_bits: u32, version: u32) -> Option<Instruction> {
+    let bit_length = R::BITS;
+    if bit_length != 32 && bit_length != 64 {
+        return None;
+    }
+    if version < VERSION3 {
+        return None;
+    }
+    let rv64 = bit_length == 64;
+    match instruction_bits & 0b_111_00000000000_11 {
+        0b_100_00000000000_00 => {
+            let rs1 = compact_register_number(instruction_bits, 7);
+            let r = compact_register_number(instruction_bits, 2);
+            // [5]   => uimm[1]
+            // [6]   => uimm[0] for bytes, 0 or the sign for halves
+            let uimm = x(instruction_bits, 5, 1, 1);
+            let byte_uimm = uimm | x(instruction_bits, 6, 1, 0);
+            let signed = instruction_bits & 0b_1000000 != 0;
+            match x(instruction_bits, 10, 3, 0) {
+                // C.LBU
+                0b_000 => Some(Itype::new_u(lbu(version), r, rs1, byte_uimm).0),
+                // C.LHU
+                0b_001 if !signed => Some(Itype::new_u(lhu(version), r, rs1, uimm).0),
+                // C.LH
+                0b_001 => Some(Itype::new_u(lh(version), r, rs1, uimm).0),
+                // C.SB
+                0b_010 => Some(Stype::new_u(insts::OP_SB, byte_uimm, rs1, r).0),
+                // C.SH
+                0b_011 if !signed => Some(Stype::new_u(insts::OP_SH, uimm, rs1, r).0),
+                _ => None,
+            }
+        }
+        0b_100_00000000000_01 => {
+            let rd = compact_register_number(instruction_bits, 7);
+            match instruction_bits & 0b_1_11_000_11111_00 {
+                // C.ZEXT.B
+                0b_1_11_000_11000_00 => Some(Itype::new_u(insts::OP_ANDI, rd, rd, 0xff).0),
+                // C.SEXT.B
+                0b_1_11_000_11001_00 => Some(Rtype::new(insts::OP_SEXTB, rd, rd, 0).0),
+                // C.ZEXT.H
+                0b_1_11_000_11010_00 => Some(Rtype::new(insts::OP_ZEXTH, rd, rd, 0).0),
+                // C.SEXT.H
+                0b_1_11_000_11011_00 => Some(Rtype::new(insts::OP_SEXTH, rd, rd, 0).0),
+                // C.ZEXT.W
+                0b_1_11_000_11100_00 if rv64 => Some(Rtype::new(insts::OP_ADDUW, rd, rd, 0).0),
+                // C.NOT
+                0b_1_11_000_11101_00 => Some(Itype::new_s(insts::OP_XORI, rd, rd, -1).0),
+                // C.MUL
+                op if op & 0b_1_11_000_11000_00 == 0b_1_11_000_10000_00 => Some(
+                    Rtype::new(
+                        insts::OP_MUL,
+                        rd,
+                        rd,
+                        compact_register_number(instruction_bits, 2),
+                    )
+                    .0,
+                ),
+                _ => None,
+            }
+        }
+        0b_101_00000000000_10 => match x(instruction_bits, 8, 5, 0) {
+            0b_11000 | 0b_11010 | 0b_11100 | 0b_11110 => {
+                let rlist = x(instruction_bits, 4, 4, 0) as usize;
+                let registers = zcmp_registers(rlist);
+                if registers.is_empty() {
+                    return None;
+                }
+                // The stack adjustment covers the saved registers rounded up
+                // to 16 bytes, plus the additional spimm[5:4].
+                let bytes = registers.len() as u32 * u32::from(bit_length / 8);
+                let stack_adj = ((bytes + 15) & !15) + x(instruction_bits, 2, 2, 4);
+                let op = match x(instruction_bits, 8, 5, 0) {
+                    0b_11000 => insts::OP_CM_PUSH,
+                    0b_11010 => insts::OP_CM_POP,
+                    0b_11100 => insts::OP_CM_POPRETZ,
+                    _ => insts::OP_CM_POPRET,
+                };
+                Some(Itype::new_u(op, 0, rlist, stack_adj).0)
+            }
+            _ => {
+                let r1s = zcmp_sreg_number(instruction_bits, 7);
+                let r2s = zcmp_sreg_number(instruction_bits, 2);
+                match instruction_bits & 0b_111_000_11_000_00 {
+                    // CM.MVSA01
+                    0b_011_000_01_000_00 if r1s != r2s => {
+                        Some(Rtype::new(insts::OP_CM_MVSA01, 0, r1s, r2s).0)
+                    }
+                    // CM.MVA01S
+                    0b_011_000_11_000_00 => Some(Rtype::new(insts::OP_CM_MVA01S, 0, r1s, r2s).0),
+                    _ => None,
+                }
+            }
+        },
+        _ => None,
+    }
+    .map(set_instruction_length_2)
+}
@@ -0,0 +1,977 @@
+// Software implementation of IEEE 754 binary32 and binary64 arithmetic used by
+// the F and D extensions. Host floating-point instructions are never used, so
+// results, including NaN encodings and exception flags, are bit-exact on every
+// host. Behaviors left open by IEEE 754 follow the RISC-V ISA manual: NaN
+// results are always the canonical NaN, and tininess is detected after
+// rounding.
+use std::cmp::Ordering;
+
+pub const FLAG_INEXACT: u32 = 0b00001;
+pub const FLAG_UNDERFLOW: u32 = 0b00010;
+pub const FLAG_OVERFLOW: u32 = 0b00100;
+pub const FLAG_DIVIDE_BY_ZERO: u32 = 0b01000;
+pub const FLAG_INVALID: u32 = 0b10000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoundingMode {
+    NearestEven,
+    TowardZero,
+    Down,
+    Up,
+    NearestMaxMagnitude,
+}
+
+impl RoundingMode {
+    pub fn from_bits(bits: u32) -> Option<Self> {
+        match bits {
+            0b000 => Some(RoundingMode::NearestEven),
+            0b001 => Some(RoundingMode::TowardZero),
+            0b010 => Some(RoundingMode::Down),
+            0b011 => Some(RoundingMode::Up),
+            0b100 => Some(RoundingMode::NearestMaxMagnitude),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Format {
+    exp_bits: u32,
+    frac_bits: u32,
+}
+
+pub const F32: Format = Format {
+    exp_bits: 8,
+    frac_bits: 23,
+};
+pub const F64: Format = Format {
+    exp_bits: 11,
+    frac_bits: 52,
+};
+
+// A floating-point value split into its class, finite values are
+// `significand * 2^exponent`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Unpacked {
+    Zero,
+    Finite(i32, u64),
+    Infinity,
+    NaN,
+}
+
+impl Format {
+    fn bias(self) -> i32 {
+        (1 << (self.exp_bits - 1)) - 1
+    }
+
+    fn max_biased_exp(self) -> u64 {
+        (1 << self.exp_bits) - 1
+    }
+
+    fn frac_mask(self) -> u64 {
+        (1 << self.frac_bits) - 1
+    }
+
+    fn quiet_bit(self) -> u64 {
+        1 << (self.frac_bits - 1)
+    }
+
+    // Exponent of the lowest bit of subnormal numbers
+    fn min_lsb_exp(self) -> i32 {
+        1 - self.bias() - self.frac_bits as i32
+    }
+
+    fn pack(self, sign: bool, biased_exp: u64, frac: u64) -> u64 {
+        (u64::from(sign) << (self.exp_bits + self.frac_bits))
+            | (biased_exp << self.frac_bits)
+            | frac
+    }
+
+    pub fn sign(self, a: u64) -> bool {
+        (a >> (self.exp_bits + self.frac_bits)) & 1 != 0
+    }
+
+    pub fn negate(self, a: u64) -> u64 {
+        a ^ (1 << (self.exp_bits + self.frac_bits))
+    }
+
+    fn biased_exp(self, a: u64) -> u64 {
+        (a >> self.frac_bits) & self.max_biased_exp()
+    }
+
+    pub fn canonical_nan(self) -> u64 {
+        self.pack(false, self.max_biased_exp(), self.quiet_bit())
+    }
+
+    fn infinity(self, sign: bool) -> u64 {
+        self.pack(sign, self.max_biased_exp(), 0)
+    }
+
+    fn zero(self, sign: bool) -> u64 {
+        self.pack(sign, 0, 0)
+    }
+
+    fn max_finite(self, sign: bool) -> u64 {
+        self.pack(sign, self.max_biased_exp() - 1, self.frac_mask())
+    }
+
+    pub fn is_nan(self, a: u64) -> bool {
+        self.biased_exp(a) == self.max_biased_exp() && a & self.frac_mask() != 0
+    }
+
+    pub fn is_signaling_nan(self, a: u64) -> bool {
+        self.is_nan(a) && a & self.quiet_bit() == 0
+    }
+
+    fn unpack(self, a: u64) -> (bool, Unpacked) {
+        let sign = self.sign(a);
+        let biased_exp = self.biased_exp(a);
+        let frac = a & self.frac_mask();
+        let unpacked = if biased_exp == self.max_biased_exp() {
+            if frac == 0 {
+                Unpacked::Infinity
+            } else {
+                Unpacked::NaN
+            }
+        } else if biased_exp == 0 {
+            if frac == 0 {
+                Unpacked::Zero
+            } else {
+                Unpacked::Finite(self.min_lsb_exp(), frac)
+            }
+        } else {
+            Unpacked::Finite(
+                biased_exp as i32 - self.bias() - self.frac_bits as i32,
+                frac | (1 << self.frac_bits),
+            )
+        };
+        (sign, unpacked)
+    }
+}
+
+// Shifts the significand so it has its highest bit at bit 63.
+fn normalize(exp: i32, sig: u64) -> (i32, u64) {
+    let shift = sig.leading_zeros();
+    (exp - shift as i32, sig << shift)
+}
+
+fn bit_length(value: u128) -> i32 {
+    128 - value.leading_zeros() as i32
+}
+
+fn isqrt(n: u128) -> u128 {
+    let mut remainder = n;
+    let mut root = 0u128;
+    let mut bit = 1u128 << 126;
+    while bit > n {
+        bit >>= 2;
+    }
+    while bit != 0 {
+        if remainder >= root + bit {
+            remainder -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+        bit >>= 2;
+    }
+    root
+}
+
+/// Floating-point environment of a single operation: the rounding mode to
+/// use, and the exception flags raised.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SoftFloat {
+    pub rounding_mode: RoundingMode,
+    pub flags: u32,
+}
+
+impl SoftFloat {
+    pub fn new(rounding_mode: RoundingMode) -> Self {
+        Self {
+            rounding_mode,
+            flags: 0,
+        }
+    }
+
+    // Drops `shift` low bits of `sig`, rounding the result to an integer.
+    // `sticky` stands for a positive amount below the lowest bit of `sig`.
+    // Returns the rounded value and whether it is inexact.
+    fn shift_round(&self, sign: bool, sig: u128, sticky: bool, shift: i32) -> (u128, bool) {
+        let (kept, remainder) = if shift <= 0 {
+            (sig << -shift, Ordering::Less)
+        } else if shift > 127 {
+            (0, Ordering::Less)
+        } else {
+            let dropped = sig & ((1 << shift) - 1);
+            let remainder = match dropped.cmp(&(1 << (shift - 1))) {
+                Ordering::Equal if sticky => Ordering::Greater,
+                ordering => ordering,
+            };
+            (sig >> shift, remainder)
+        };
+        let inexact = sticky || (shift > 0 && (shift > 127 || sig & ((1 << shift) - 1) != 0));
+        let increment = match self.rounding_mode {
+            RoundingMode::NearestEven => {
+                remainder == Ordering::Greater || (remainder == Ordering::Equal && kept & 1 != 0)
+            }
+            RoundingMode::NearestMaxMagnitude => remainder != Ordering::Less,
+            RoundingMode::TowardZero => false,
+            RoundingMode::Down => inexact && sign,
+            RoundingMode::Up => inexact && !sign,
+        };
+        (kept + u128::from(increment), inexact)
+    }
+
+    // Rounds `sig * 2^exp`, plus a positive amount below the lowest bit of
+    // sig when `sticky` is set, to the format.
+    fn round_pack(&mut self, fmt: Format, sign: bool, exp: i32, sig: u128, sticky: bool) -> u64 {
+        if sig == 0 && !sticky {
+            return fmt.zero(sign);
+        }
+        debug_assert!(sig != 0);
+        let precision = fmt.frac_bits as i32 + 1;
+        let emin = 1 - fmt.bias();
+        // The value is in [2^e, 2^(e + 1))
+        let e = exp + bit_length(sig) - 1;
+        let mut lsb = (e - (precision - 1)).max(fmt.min_lsb_exp());
+        let (mut kept, inexact) = self.shift_round(sign, sig, sticky, lsb - exp);
+        if kept >> precision != 0 {
+            kept >>= 1;
+            lsb += 1;
+        }
+        if inexact {
+            self.flags |= FLAG_INEXACT;
+            // Tininess is detected after rounding: the value rounded with an
+            // unbounded exponent range is below the smallest normal number.
+            let tiny = e < emin - 1
+                || (e == emin - 1 && {
+                    let (unbounded, _) =
+                        self.shift_round(sign, sig, sticky, e - (precision - 1) - exp);
+                    unbounded >> precision == 0
+                });
+            if tiny {
+                self.flags |= FLAG_UNDERFLOW;
+            }
+        }
+        if kept >> (precision - 1) == 0 {
+            // Subnormal, lsb is the lowest exponent here
+            return fmt.pack(sign, 0, kept as u64);
+        }
+        let biased_exp = (lsb + precision - 1 + fmt.bias()) as u64;
+        if biased_exp >= fmt.max_biased_exp() {
+            self.flags |= FLAG_OVERFLOW | FLAG_INEXACT;
+            let infinite = match self.rounding_mode {
+                RoundingMode::NearestEven | RoundingMode::NearestMaxMagnitude => true,
+                RoundingMode::TowardZero => false,
+                RoundingMode::Down => sign,
+                RoundingMode::Up => !sign,
+            };
+            return if infinite {
+                fmt.infinity(sign)
+            } else {
+                fmt.max_finite(sign)
+            };
+        }
+        fmt.pack(sign, biased_exp, kept as u64 & fmt.frac_mask())
+    }
+
+    // The result of an operation with NaN operands
+    fn propagate_nan(&mut self, fmt: Format, operands: &[u64]) -> u64 {
+        if operands.iter().any(|a| fmt.is_signaling_nan(*a)) {
+            self.flags |= FLAG_INVALID;
+        }
+        fmt.canonical_nan()
+    }
+
+    fn invalid(&mut self, fmt: Format) -> u64 {
+        self.flags |= FLAG_INVALID;
+        fmt.canonical_nan()
+    }
+
+    // Sign of an exact zero sum of operands with different signs
+    fn zero_sum_sign(&self) -> bool {
+        self.rounding_mode == RoundingMode::Down
+    }
+
+    pub fn add(&mut self, fmt: Format, a: u64, b: u64) -> u64 {
+        let (sa, ua) = fmt.unpack(a);
+        let (sb, ub) = fmt.unpack(b);
+        match (ua, ub) {
+            (Unpacked::NaN, _) | (_, Unpacked::NaN) => self.propagate_nan(fmt, &[a, b]),
+            (Unpacked::Infinity, Unpacked::Infinity) if sa != sb => self.invalid(fmt),
+            (Unpacked::Infinity, _) => a,
+            (_, Unpacked::Infinity) => b,
+            (Unpacked::Zero, Unpacked::Zero) if sa != sb => fmt.zero(self.zero_sum_sign()),
+            (Unpacked::Zero, _) => b,
+            (_, Unpacked::Zero) => a,
+            (Unpacked::Finite(ea, ma), Unpacked::Finite(eb, mb)) => {
+                let ((sx, ex, mx), (sy, ey, my)) = if ea >= eb {
+                    ((sa, ea, ma), (sb, eb, mb))
+                } else {
+                    ((sb, eb, mb), (sa, ea, ma))
+                };
+                let d = ex - ey;
+                // When the exponents are far apart, the smaller operand is
+                // far below the rounding position and only matters as a
+                // sticky bit.
+                let (exp, x, y, sticky) = if d <= 64 {
+                    (ey, u128::from(mx) << d, u128::from(my), false)
+                } else {
+                    (ex - 3, u128::from(mx) << 3, 0, true)
+                };
+                if sx == sy {
+                    self.round_pack(fmt, sx, exp, x + y, sticky)
+                } else if sticky {
+                    self.round_pack(fmt, sx, exp, x - 1, true)
+                } else {
+                    match x.cmp(&y) {
+                        Ordering::Greater => self.round_pack(fmt, sx, exp, x - y, false),
+                        Ordering::Less => self.round_pack(fmt, sy, exp, y - x, false),
+                        Ordering::Equal => fmt.zero(self.zero_sum_sign()),
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn sub(&mut self, fmt: Format, a: u64, b: u64) -> u64 {
+        self.add(fmt, a, fmt.negate(b))
+    }
+
+    pub fn mul(&mut self, fmt: Format, a: u64, b: u64) -> u64 {
+        let (sa, ua) = fmt.unpack(a);
+        let (sb, ub) = fmt.unpack(b);
+        let sign = sa ^ sb;
+        match (ua, ub) {
+            (Unpacked::NaN, _) | (_, Unpacked::NaN) => self.propagate_nan(fmt, &[a, b]),
+            (Unpacked::Infinity, Unpacked::Zero) | (Unpacked::Zero, Unpacked::Infinity) => {
+                self.invalid(fmt)
+            }
+            (Unpacked::Infinity, _) | (_, Unpacked::Infinity) => fmt.infinity(sign),
+            (Unpacked::Zero, _) | (_, Unpacked::Zero) => fmt.zero(sign),
+            (Unpacked::Finite(ea, ma), Unpacked::Finite(eb, mb)) => {
+                self.round_pack(fmt, sign, ea + eb, u128::from(ma) * u128::from(mb), false)
+            }
+        }
+    }
+
+    pub fn div(&mut self, fmt: Format, a: u64, b: u64) -> u64 {
+        let (sa, ua) = fmt.unpack(a);
+        let (sb, ub) = fmt.unpack(b);
+        let sign = sa ^ sb;
+        match (ua, ub) {
+            (Unpacked::NaN, _) | (_, Unpacked::NaN) => self.propagate_nan(fmt, &[a, b]),
+            (Unpacked::Infinity, Unpacked::Infinity) | (Unpacked::Zero, Unpacked::Zero) => {
+                self.invalid(fmt)
+            }
+            (Unpacked::Infinity, _) => fmt.infinity(sign),
+            (_, Unpacked::Infinity) | (Unpacked::Zero, _) => fmt.zero(sign),
+            (_, Unpacked::Zero) => {
+                self.flags |= FLAG_DIVIDE_BY_ZERO;
+                fmt.infinity(sign)
+            }
+            (Unpacked::Finite(ea, ma), Unpacked::Finite(eb, mb)) => {
+                let (ea, ma) = normalize(ea, ma);
+                let (eb, mb) = normalize(eb, mb);
+                let dividend = u128::from(ma) << 64;
+                let quotient = dividend / u128::from(mb);
+                let sticky = dividend % u128::from(mb) != 0;
+                self.round_pack(fmt, sign, ea - eb - 64, quotient, sticky)
+            }
+        }
+    }
+
+    pub fn sqrt(&mut self, fmt: Format, a: u64) -> u64 {
+        let (sign, ua) = fmt.unpack(a);
+        match ua {
+            Unpacked::NaN => self.propagate_nan(fmt, &[a]),
+            Unpacked::Zero => a,
+            _ if sign => self.invalid(fmt),
+            Unpacked::Infinity => a,
+            Unpacked::Finite(e, m) => {
+                let (e, m) = normalize(e, m);
+                let mut n = u128::from(m) << 62;
+                let mut exp = e - 62;
+                if exp % 2 != 0 {
+                    n <<= 1;
+                    exp -= 1;
+                }
+                let root = isqrt(n);
+                self.round_pack(fmt, false, exp / 2, root, root * root != n)
+            }
+        }
+    }
+
+    /// Computes `(±a * b) ± c` with a single rounding.
+    pub fn fused_mul_add(
+        &mut self,
+        fmt: Format,
+        a: u64,
+        b: u64,
+        c: u64,
+        negate_product: bool,
+        negate_addend: bool,
+    ) -> u64 {
+        let (sa, ua) = fmt.unpack(a);
+        let (sb, ub) = fmt.unpack(b);
+        let (sc, uc) = fmt.unpack(c);
+        let sp = sa ^ sb ^ negate_product;
+        let sc = sc ^ negate_addend;
+        // Multiplying infinity by zero is invalid even with a quiet NaN addend
+        if matches!(
+            (ua, ub),
+            (Unpacked::Infinity, Unpacked::Zero) | (Unpacked::Zero, Unpacked::Infinity)
+        ) {
+            self.flags |= FLAG_INVALID;
+            return fmt.canonical_nan();
+        }
+        if ua == Unpacked::NaN || ub == Unpacked::NaN || uc == Unpacked::NaN {
+            return self.propagate_nan(fmt, &[a, b, c]);
+        }
+        let product = match (ua, ub) {
+            (Unpacked::Infinity, _) | (_, Unpacked::Infinity) => Unpacked::Infinity,
+            (Unpacked::Zero, _) | (_, Unpacked::Zero) => Unpacked::Zero,
+            (Unpacked::Finite(ea, _), Unpacked::Finite(eb, _)) => Unpacked::Finite(ea + eb, 0),
+            _ => unreachable!(),
+        };
+        match (product, uc) {
+            (Unpacked::Infinity, Unpacked::Infinity) if sp != sc => self.invalid(fmt),
+            (Unpacked::Infinity, _) => fmt.infinity(sp),
+            (_, Unpacked::Infinity) => fmt.infinity(sc),
+            (Unpacked::Zero, Unpacked::Zero) => {
+                fmt.zero(if sp == sc { sp } else { self.zero_sum_sign() })
+            }
+            (Unpacked::Zero, _) => {
+                if fmt.sign(c) == sc {
+                    c
+                } else {
+                    fmt.negate(c)
+                }
+            }
+            (Unpacked::Finite(ep, _), uc) => {
+                let mp = match (ua, ub) {
+                    (Unpacked::Finite(_, ma), Unpacked::Finite(_, mb)) => {
+                        u128::from(ma) * u128::from(mb)
+                    }
+                    _ => unreachable!(),
+                };
+                let (ec, mc) = match uc {
+                    Unpacked::Finite(ec, mc) => (ec, u128::from(mc)),
+                    _ => return self.round_pack(fmt, sp, ep, mp, false),
+                };
+                // Both operands are aligned to a common lowest exponent, with
+                // 125 bits below the highest bit. Only the smaller operand
+                // can lose bits, which then only matter as a sticky bit.
+                let top = (ep + bit_length(mp)).max(ec + bit_length(mc));
+                let lsb = top - 125;
+                let align = |e: i32, m: u128| -> (u128, bool) {
+                    if e >= lsb {
+                        (m << (e - lsb), false)
+                    } else if lsb - e >= 128 {
+                        (0, true)
+                    } else {
+                        let shift = lsb - e;
+                        (m >> shift, m & ((1 << shift) - 1) != 0)
+                    }
+                };
+                let (p, sticky_p) = align(ep, mp);
+                let (q, sticky_q) = align(ec, mc);
+                if sp == sc {
+                    self.round_pack(fmt, sp, lsb, p + q, sticky_p || sticky_q)
+                } else if sticky_q {
+                    self.round_pack(fmt, sp, lsb, p - q - 1, true)
+                } else if sticky_p {
+                    self.round_pack(fmt, sc, lsb, q - p - 1, true)
+                } else {
+                    match p.cmp(&q) {
+                        Ordering::Greater => self.round_pack(fmt, sp, lsb, p - q, false),
+                        Ordering::Less => self.round_pack(fmt, sc, lsb, q - p, false),
+                        Ordering::Equal => fmt.zero(self.zero_sum_sign()),
+                    }
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    // Orders non-NaN values, with -0 below +0.
+    fn less(fmt: Format, a: u64, b: u64) -> bool {
+        let (sa, sb) = (fmt.sign(a), fmt.sign(b));
+        let magnitude_mask = (1 << (fmt.exp_bits + fmt.frac_bits)) - 1;
+        let (ma, mb) = (a & magnitude_mask, b & magnitude_mask);
+        if sa != sb {
+            sa
+        } else if sa {
+            ma > mb
+        } else {
+            ma < mb
+        }
+    }
+
+    fn both_zero(fmt: Format, a: u64, b: u64) -> bool {
+        let magnitude_mask = (1 << (fmt.exp_bits + fmt.frac_bits)) - 1;
+        (a | b) & magnitude_mask == 0
+    }
+
+    /// Minimum or maximum as defined by IEEE 754-2019 minimumNumber and
+    /// maximumNumber.
+    pub fn min_max(&mut self, fmt: Format, a: u64, b: u64, max: bool) -> u64 {
+        if fmt.is_signaling_nan(a) || fmt.is_signaling_nan(b) {
+            self.flags |= FLAG_INVALID;
+        }
+        match (fmt.is_nan(a), fmt.is_nan(b)) {
+            (true, true) => fmt.canonical_nan(),
+            (true, false) => b,
+            (false, true) => a,
+            (false, false) => {
+                if Self::less(fmt, a, b) != max {
+                    a
+                } else {
+                    b
+                }
+            }
+        }
+    }
+
+    /// Quiet equality comparison.
+    pub fn equal(&mut self, fmt: Format, a: u64, b: u64) -> bool {
+        if fmt.is_nan(a) || fmt.is_nan(b) {
+            if fmt.is_signaling_nan(a) || fmt.is_signaling_nan(b) {
+                self.flags |= FLAG_INVALID;
+            }
+            return false;
+        }
+        a == b || Self::both_zero(fmt, a, b)
+    }
+
+    /// Signaling less than comparison.
+    pub fn less_than(&mut self, fmt: Format, a: u64, b: u64) -> bool {
+        if fmt.is_nan(a) || fmt.is_nan(b) {
+            self.flags |= FLAG_INVALID;
+            return false;
+        }
+        !Self::both_zero(fmt, a, b) && Self::less(fmt, a, b)
+    }
+
+    /// Signaling less than or equal comparison.
+    pub fn less_or_equal(&mut self, fmt: Format, a: u64, b: u64) -> bool {
+        if fmt.is_nan(a) || fmt.is_nan(b) {
+            self.flags |= FLAG_INVALID;
+            return false;
+        }
+        a == b || Self::both_zero(fmt, a, b) || Self::less(fmt, a, b)
+    }
+
+    /// The class mask of FCLASS.S and FCLASS.D.
+    pub fn classify(fmt: Format, a: u64) -> u64 {
+        let (sign, unpacked) = fmt.unpack(a);
+        let bit = match unpacked {
+            Unpacked::Infinity => {
+                if sign {
+                    0
+                } else {
+                    7
+                }
+            }
+            Unpacked::Finite(..) => {
+                let subnormal = fmt.biased_exp(a) == 0;
+                match (sign, subnormal) {
+                    (true, false) => 1,
+                    (true, true) => 2,
+                    (false, true) => 5,
+                    (false, false) => 6,
+                }
+            }
+            Unpacked::Zero => {
+                if sign {
+                    3
+                } else {
+                    4
+                }
+            }
+            Unpacked::NaN => {
+                if fmt.is_signaling_nan(a) {
+                    8
+                } else {
+                    9
+                }
+            }
+        };
+        1 << bit
+    }
+
+    /// Converts to a `bits` wide signed or unsigned integer, the result is
+    /// sign extended to 64 bits. Out of range values and NaNs saturate.
+    pub fn convert_to_int(&mut self, fmt: Format, a: u64, signed: bool, bits: u32) -> u64 {
+        let (min, max): (i128, i128) = if signed {
+            (-(1 << (bits - 1)), (1 << (bits - 1)) - 1)
+        } else {
+            (0, (1 << bits) - 1)
+        };
+        let (sign, unpacked) = fmt.unpack(a);
+        let value = match unpacked {
+            Unpacked::NaN => {
+                self.flags |= FLAG_INVALID;
+                max
+            }
+            Unpacked::Infinity => {
+                self.flags |= FLAG_INVALID;
+                if sign {
+                    min
+                } else {
+                    max
+                }
+            }
+            Unpacked::Zero => 0,
+            Unpacked::Finite(e, m) => {
+                let (magnitude, inexact) = if e > 64 {
+                    // Far out of range for any integer
+                    (1 << 72, false)
+                } else {
+                    self.shift_round(sign, u128::from(m), false, -e)
+                };
+                let value = if sign {
+                    -(magnitude as i128)
+                } else {
+                    magnitude as i128
+                };
+                if value < min || value > max {
+                    self.flags |= FLAG_INVALID;
+                    if sign {
+                        min
+                    } else {
+                        max
+                    }
+                } else {
+                    if inexact {
+                        self.flags |= FLAG_INEXACT;
+                    }
+                    value
+                }
+            }
+        };
+        if bits == 32 {
+            value as u32 as i32 as i64 as u64
+        } else {
+            value as u64
+        }
+    }
+
+    /// Converts the lowest `bits` bits of a signed or unsigned integer.
+    pub fn convert_from_int(&mut self, fmt: Format, value: u64, signed: bool, bits: u32) -> u64 {
+        let (sign, magnitude) = if signed {
+            let value = if bits == 32 {
+                i64::from(value as i32)
+            } else {
+                value as i64
+            };
+            (value < 0, value.unsigned_abs())
+        } else if bits == 32 {
+            (false, u64::from(value as u32))
+        } else {
+            (false, value)
+        };
+        self.round_pack(fmt, sign, 0, u128::from(magnitude), false)
+    }
+
+    /// Converts between formats.
+    pub fn convert(&mut self, from: Format, to: Format, a: u64) -> u64 {
+        let (sign, unpacked) = from.unpack(a);
+        match unpacked {
+            Unpacked::NaN => {
+                if from.is_signaling_nan(a) {
+                    self.flags |= FLAG_INVALID;
+                }
+                to.canonical_nan()
+            }
+            Unpacked::Infinity => to.infinity(sign),
+            Unpacked::Zero => to.zero(sign),
+            Unpacked::Finite(e, m) => self.round_pack(to, sign, e, u128::from(m), false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Both results match, comparing NaNs by class since host NaN encodings
+    // are not canonical.
+    fn same(fmt: Format, expected: u64, actual: u64) -> bool {
+        if fmt.is_nan(expected) {
+            actual == fmt.canonical_nan()
+        } else {
+            expected == actual
+        }
+    }
+
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        // Random values biased towards special values and nearby exponents
+        fn value(&mut self, fmt: Format) -> u64 {
+            let r = self.next();
+            let mask = u64::MAX >> (63 - fmt.exp_bits - fmt.frac_bits);
+            match r % 8 {
+                0 => fmt.pack(r & 1 != 0, 0, (r >> 8) & fmt.frac_mask()),
+                1 => fmt.pack(r & 1 != 0, fmt.max_biased_exp(), (r >> 8) % 3),
+                2 => fmt.pack(
+                    r & 1 != 0,
+                    fmt.max_biased_exp() - 1 - (r >> 60),
+                    r >> 8 & fmt.frac_mask(),
+                ),
+                3 => fmt.pack(
+                    r & 1 != 0,
+                    fmt.bias() as u64 + (r >> 4) % 4,
+                    (r >> 8) & fmt.frac_mask(),
+                ),
+                _ => r & mask,
+            }
+        }
+    }
+
+    #[test]
+    fn test_f64_arithmetic_matches_host() {
+        let mut rng = Rng(0x2545f4914f6cdd1d);
+        for _ in 0..200_000 {
+            let (a, b, c) = (rng.value(F64), rng.value(F64), rng.value(F64));
+            let (x, y, z) = (f64::from_bits(a), f64::from_bits(b), f64::from_bits(c));
+            let mut sf = SoftFloat::new(RoundingMode::NearestEven);
+            assert!(
+                same(F64, (x + y).to_bits(), sf.add(F64, a, b)),
+                "{:x} + {:x}",
+                a,
+                b
+            );
+            assert!(
+                same(F64, (x - y).to_bits(), sf.sub(F64, a, b)),
+                "{:x} - {:x}",
+                a,
+                b
+            );
+            assert!(
+                same(F64, (x * y).to_bits(), sf.mul(F64, a, b)),
+                "{:x} * {:x}",
+                a,
+                b
+            );
+            assert!(
+                same(F64, (x / y).to_bits(), sf.div(F64, a, b)),
+                "{:x} / {:x}",
+                a,
+                b
+            );
+            assert!(
+                same(F64, x.sqrt().to_bits(), sf.sqrt(F64, a)),
+                "sqrt {:x}",
+                a
+            );
+            assert!(
+                same(
+                    F64,
+                    x.mul_add(y, z).to_bits(),
+                    sf.fused_mul_add(F64, a, b, c, false, false)
+                ),
+                "{:x} * {:x} + {:x}",
+                a,
+                b,
+                c
+            );
+            assert!(same(
+                F32,
+                (x as f32).to_bits() as u64,
+                sf.convert(F64, F32, a)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_f32_arithmetic_matches_host() {
+        let mut rng = Rng(0x9e3779b97f4a7c15);
+        for _ in 0..200_000 {
+            let (a, b, c) = (rng.value(F32), rng.value(F32), rng.value(F32));
+            let (x, y, z) = (
+                f32::from_bits(a as u32),
+                f32::from_bits(b as u32),
+                f32::from_bits(c as u32),
+            );
+            let mut sf = SoftFloat::new(RoundingMode::NearestEven);
+            assert!(same(F32, (x + y).to_bits() as u64, sf.add(F32, a, b)));
+            assert!(same(F32, (x * y).to_bits() as u64, sf.mul(F32, a, b)));
+            assert!(same(F32, (x / y).to_bits() as u64, sf.div(F32, a, b)));
+            assert!(same(F32, x.sqrt().to_bits() as u64, sf.sqrt(F32, a)));
+            assert!(same(
+                F32,
+                x.mul_add(y, z).to_bits() as u64,
+                sf.fused_mul_add(F32, a, b, c, false, false)
+            ));
+            assert!(
+                same(F64, (x as f64).to_bits(), sf.convert(F32, F64, a)),
+                "{:x}",
+                a
+            );
+            let i = rng.next();
+            assert_eq!(
+                (i as i64 as f32).to_bits() as u64,
+                sf.convert_from_int(F32, i, true, 64)
+            );
+            assert_eq!(
+                (i as u32 as f32).to_bits() as u64,
+                sf.convert_from_int(F32, i, false, 32)
+            );
+        }
+    }
+
+    #[test]
+    fn test_flags() {
+        let mut sf = SoftFloat::new(RoundingMode::NearestEven);
+        let one = 1f64.to_bits();
+        let three = 3f64.to_bits();
+        sf.div(F64, one, three);
+        assert_eq!(sf.flags, FLAG_INEXACT);
+
+        let mut sf = SoftFloat::new(RoundingMode::NearestEven);
+        sf.div(F64, one, 0);
+        assert_eq!(sf.flags, FLAG_DIVIDE_BY_ZERO);
+
+        let mut sf = SoftFloat::new(RoundingMode::NearestEven);
+        assert_eq!(
+            sf.mul(F64, f64::MAX.to_bits(), 2f64.to_bits()),
+            F64.infinity(false)
+        );
+        assert_eq!(sf.flags, FLAG_OVERFLOW | FLAG_INEXACT);
+
+        let mut sf = SoftFloat::new(RoundingMode::TowardZero);
+        assert_eq!(
+            sf.mul(F64, f64::MAX.to_bits(), 2f64.to_bits()),
+            f64::MAX.to_bits()
+        );
+
+        // The smallest subnormal halved rounds to zero
+        let mut sf = SoftFloat::new(RoundingMode::NearestEven);
+        assert_eq!(sf.mul(F64, 1, 0.5f64.to_bits()), 0);
+        assert_eq!(sf.flags, FLAG_UNDERFLOW | FLAG_INEXACT);
+
+        // Rounds up to the smallest normal number, which is not tiny after
+        // rounding
+        let below_min_normal = f64::MIN_POSITIVE.to_bits() - 1;
+        let mut sf = SoftFloat::new(RoundingMode::Up);
+        assert_eq!(
+            sf.mul(F64, below_min_normal, (1.0 + f64::EPSILON).to_bits()),
+            f64::MIN_POSITIVE.to_bits()
+        );
+        assert_eq!(sf.flags, FLAG_INEXACT);
+
+        let mut sf = SoftFloat::new(RoundingMode::NearestEven);
+        assert_eq!(sf.sqrt(F64, (-1f64).to_bits()), F64.canonical_nan());
+        assert_eq!(sf.flags, FLAG_INVALID);
+
+        let mut sf = SoftFloat::new(RoundingMode::NearestEven);
+        let snan = F64.pack(false, F64.max_biased_exp(), 1);
+        assert!(!sf.equal(F64, snan, one));
+        assert_eq!(sf.flags, FLAG_INVALID);
+        let mut sf = SoftFloat::new(RoundingMode::NearestEven);
+        assert!(!sf.equal(F64, F64.canonical_nan(), one));
+        assert_eq!(sf.flags, 0);
+        assert!(!sf.less_than(F64, F64.canonical_nan(), one));
+        assert_eq!(sf.flags, FLAG_INVALID);
+
+        let mut sf = SoftFloat::new(RoundingMode::NearestEven);
+        assert_eq!(
+            sf.fused_mul_add(
+                F64,
+                F64.infinity(false),
+                0,
+                F64.canonical_nan(),
+                false,
+                false
+            ),
+            F64.canonical_nan()
+        );
+        assert_eq!(sf.flags, FLAG_INVALID);
+    }
+
+    #[test]
+    fn test_rounding_modes() {
+        let x = 2.5f64.to_bits();
+        let cases = [
+            (RoundingMode::NearestEven, 2, -2),
+            (RoundingMode::TowardZero, 2, -2),
+            (RoundingMode::Down, 2, -3),
+            (RoundingMode::Up, 3, -2),
+            (RoundingMode::NearestMaxMagnitude, 3, -3),
+        ];
+        for (mode, positive, negative) in cases {
+            let mut sf = SoftFloat::new(mode);
+            assert_eq!(sf.convert_to_int(F64, x, true, 64), positive as u64);
+            assert_eq!(
+                sf.convert_to_int(F64, F64.negate(x), true, 64),
+                negative as u64
+            );
+            assert_eq!(sf.flags, FLAG_INEXACT);
+        }
+        let mut sf = SoftFloat::new(RoundingMode::NearestEven);
+        let one = 1f64.to_bits();
+        assert_eq!(sf.sub(F64, one, one), 0);
+        let mut sf = SoftFloat::new(RoundingMode::Down);
+        assert_eq!(sf.sub(F64, one, one), F64.zero(true));
+    }
+
+    #[test]
+    fn test_conversions() {
+        let mut sf = SoftFloat::new(RoundingMode::NearestEven);
+        assert_eq!(
+            sf.convert_to_int(F64, F64.canonical_nan(), true, 32),
+            0x7fffffff
+        );
+        assert_eq!(
+            sf.convert_to_int(F64, F64.canonical_nan(), false, 32),
+            u64::MAX
+        );
+        assert_eq!(sf.convert_to_int(F64, F64.infinity(true), false, 64), 0);
+        assert_eq!(
+            sf.convert_to_int(F64, (-1f64).to_bits(), true, 32),
+            u64::MAX
+        );
+        assert_eq!(
+            sf.convert_to_int(F64, 1e30f64.to_bits(), true, 64),
+            i64::MAX as u64
+        );
+        assert_eq!(sf.flags, FLAG_INVALID);
+
+        let mut sf = SoftFloat::new(RoundingMode::NearestEven);
+        assert_eq!(sf.convert_to_int(F64, (-0.3f64).to_bits(), false, 64), 0);
+        assert_eq!(sf.flags, FLAG_INEXACT);
+        assert_eq!(
+            sf.convert_to_int(F64, (-2147483648f64).to_bits(), true, 32),
+            i32::MIN as i64 as u64
+        );
+
+        assert_eq!(SoftFloat::classify(F64, F64.zero(true)), 1 << 3);
+        assert_eq!(SoftFloat::classify(F64, 1), 1 << 5);
+        assert_eq!(SoftFloat::classify(F32, F32.canonical_nan()), 1 << 9);
+        assert_eq!(SoftFloat::classify(F32, F32.infinity(true)), 1);
+    }
+
+    #[test]
+    fn test_min_max() {
+        let mut sf = SoftFloat::new(RoundingMode::NearestEven);
+        let one = 1f32.to_bits() as u64;
+        assert_eq!(sf.min_max(F32, F32.zero(true), 0, false), F32.zero(true));
+        assert_eq!(sf.min_max(F32, F32.zero(true), 0, true), 0);
+        assert_eq!(sf.min_max(F32, F32.canonical_nan(), one, false), one);
+        assert_eq!(
+            sf.min_max(F32, F32.canonical_nan(), F32.canonical_nan(), true),
+            F32.canonical_nan()
+        );
+        assert_eq!(sf.flags, 0);
+    }
+}
@@ -245,6 +245,267 @@ impl TryFrom<Instruction> for TaggedInstruction {
             insts::OP_ADD3C => R5type(i).into(),
             insts::OP_CUSTOM_LOAD_UIMM => Utype(i).into(),
             insts::OP_CUSTOM_LOAD_IMM => Utype(i).into(),
+            insts::OP_BREV8 => Rtype(i).into(),
+            insts::OP_PACK => Rtype(i).into(),
+            insts::OP_PACKH => Rtype(i).into(),
+            insts::OP_PACKW => Rtype(i).into(),
+            insts::OP_SHA256SIG0 => Rtype(i).into(),
+            insts::OP_SHA256SIG1 => Rtype(i).into(),
+            insts::OP_SHA256SUM0 => Rtype(i).into(),
+            insts::OP_SHA256SUM1 => Rtype(i).into(),
+            insts::OP_SHA512SIG0 => Rtype(i).into(),
+            insts::OP_SHA512SIG1 => Rtype(i).into(),
+            insts::OP_SHA512SUM0 => Rtype(i).into(),
+            insts::OP_SHA512SUM1 => Rtype(i).into(),
+            insts::OP_SM3P0 => Rtype(i).into(),
+            insts::OP_SM3P1 => Rtype(i).into(),
+            insts::OP_XPERM4 => Rtype(i).into(),
+            insts::OP_XPERM8 => Rtype(i).into(),
+            insts::OP_CZERO_EQZ => Rtype(i).into(),
+            insts::OP_CZERO_NEZ => Rtype(i).into(),
+            insts::OP_CM_MVA01S => Rtype(i).into(),
+            insts::OP_CM_MVSA01 => Rtype(i).into(),
+            insts::OP_FLW => Itype(i).into(),
+            insts::OP_FSW => Stype(i).into(),
+            insts::OP_FMADD_S => R4type(i).into(),
+            insts::OP_FMSUB_S => R4type(i).into(),
+            insts::OP_FNMSUB_S => R4type(i).into(),
+            insts::OP_FNMADD_S => R4type(i).into(),
+            insts::OP_FADD_S => Rtype(i).into(),
+            insts::OP_FSUB_S => Rtype(i).into(),
+            insts::OP_FMUL_S => Rtype(i).into(),
+            insts::OP_FDIV_S => Rtype(i).into(),
+            insts::OP_FSQRT_S => Rtype(i).into(),
+            insts::OP_FSGNJ_S => Rtype(i).into(),
+            insts::OP_FSGNJN_S => Rtype(i).into(),
+            insts::OP_FSGNJX_S => Rtype(i).into(),
+            insts::OP_FMIN_S => Rtype(i).into(),
+            insts::OP_FMAX_S => Rtype(i).into(),
+            insts::OP_FCVT_W_S => Rtype(i).into(),
+            insts::OP_FCVT_WU_S => Rtype(i).into(),
+            insts::OP_FCVT_L_S => Rtype(i).into(),
+            insts::OP_FCVT_LU_S => Rtype(i).into(),
+            insts::OP_FMV_X_W => Rtype(i).into(),
+            insts::OP_FEQ_S => Rtype(i).into(),
+            insts::OP_FLT_S => Rtype(i).into(),
+            insts::OP_FLE_S => Rtype(i).into(),
+            insts::OP_FCLASS_S => Rtype(i).into(),
+            insts::OP_FCVT_S_W => Rtype(i).into(),
+            insts::OP_FCVT_S_WU => Rtype(i).into(),
+            insts::OP_FCVT_S_L => Rtype(i).into(),
+            insts::OP_FCVT_S_LU => Rtype(i).into(),
+            insts::OP_FMV_W_X => Rtype(i).into(),
+            insts::OP_FLD => Itype(i).into(),
+            insts::OP_FSD => Stype(i).into(),
+            insts::OP_FMADD_D => R4type(i).into(),
+            insts::OP_FMSUB_D => R4type(i).into(),
+            insts::OP_FNMSUB_D => R4type(i).into(),
+            insts::OP_FNMADD_D => R4type(i).into(),
+            insts::OP_FADD_D => Rtype(i).into(),
+            insts::OP_FSUB_D => Rtype(i).into(),
+            insts::OP_FMUL_D => Rtype(i).into(),
+            insts::OP_FDIV_D => Rtype(i).into(),
+            insts::OP_FSQRT_D => Rtype(i).into(),
+            insts::OP_FSGNJ_D => Rtype(i).into(),
+            insts::OP_FSGNJN_D => Rtype(i).into(),
+            insts::OP_FSGNJX_D => Rtype(i).into(),
+            insts::OP_FMIN_D => Rtype(i).into(),
+            insts::OP_FMAX_D => Rtype(i).into(),
+            insts::OP_FCVT_S_D => Rtype(i).into(),
+            insts::OP_FCVT_D_S => Rtype(i).into(),
+            insts::OP_FCVT_W_D => Rtype(i).into(),
+            insts::OP_FCVT_WU_D => Rtype(i).into(),
+            insts::OP_FCVT_L_D => Rtype(i).into(),
+            insts::OP_FCVT_LU_D => Rtype(i).into(),
+            insts::OP_FMV_X_D => Rtype(i).into(),
+            insts::OP_FEQ_D => Rtype(i).into(),
+            insts::OP_FLT_D => Rtype(i).into(),
+            insts::OP_FLE_D => Rtype(i).into(),
+            insts::OP_FCLASS_D => Rtype(i).into(),
+            insts::OP_FCVT_D_W => Rtype(i).into(),
+            insts::OP_FCVT_D_WU => Rtype(i).into(),
+            insts::OP_FCVT_D_L => Rtype(i).into(),
+            insts::OP_FCVT_D_LU => Rtype(i).into(),
+            insts::OP_FMV_D_X => Rtype(i).into(),
+            insts::OP_CSRRW => Itype(i).into(),
+            insts::OP_CSRRS => Itype(i).into(),
+            insts::OP_CSRRC => Itype(i).into(),
+            insts::OP_CSRRWI => Itype(i).into(),
+            insts::OP_CSRRSI => Itype(i).into(),
+            insts::OP_CSRRCI => Itype(i).into(),
+            // V
+            insts::OP_VSETVLI => Itype(i).into(),
+            insts::OP_VSETIVLI => Itype(i).into(),
+            insts::OP_VSETVL => Rtype(i).into(),
+            insts::OP_VLE8_V => R4type(i).into(),
+            insts::OP_VLE16_V => R4type(i).into(),
+            insts::OP_VLE32_V => R4type(i).into(),
+            insts::OP_VLE64_V => R4type(i).into(),
+            insts::OP_VSE8_V => R4type(i).into(),
+            insts::OP_VSE16_V => R4type(i).into(),
+            insts::OP_VSE32_V => R4type(i).into(),
+            insts::OP_VSE64_V => R4type(i).into(),
+            insts::OP_VLSE8_V => R4type(i).into(),
+            insts::OP_VLSE16_V => R4type(i).into(),
+            insts::OP_VLSE32_V => R4type(i).into(),
+            insts::OP_VLSE64_V => R4type(i).into(),
+            insts::OP_VSSE8_V => R4type(i).into(),
+            insts::OP_VSSE16_V => R4type(i).into(),
+            insts::OP_VSSE32_V => R4type(i).into(),
+            insts::OP_VSSE64_V => R4type(i).into(),
+            insts::OP_VLM_V => R4type(i).into(),
+            insts::OP_VSM_V => R4type(i).into(),
+            insts::OP_VLR_V => R4type(i).into(),
+            insts::OP_VSR_V => R4type(i).into(),
+            insts::OP_VADD_VV => R4type(i).into(),
+            insts::OP_VADD_VX => R4type(i).into(),
+            insts::OP_VADD_VI => R4type(i).into(),
+            insts::OP_VSUB_VV => R4type(i).into(),
+            insts::OP_VSUB_VX => R4type(i).into(),
+            insts::OP_VRSUB_VX => R4type(i).into(),
+            insts::OP_VRSUB_VI => R4type(i).into(),
+            insts::OP_VMINU_VV => R4type(i).into(),
+            insts::OP_VMINU_VX => R4type(i).into(),
+            insts::OP_VMIN_VV => R4type(i).into(),
+            insts::OP_VMIN_VX => R4type(i).into(),
+            insts::OP_VMAXU_VV => R4type(i).into(),
+            insts::OP_VMAXU_VX => R4type(i).into(),
+            insts::OP_VMAX_VV => R4type(i).into(),
+            insts::OP_VMAX_VX => R4type(i).into(),
+            insts::OP_VAND_VV => R4type(i).into(),
+            insts::OP_VAND_VX => R4type(i).into(),
+            insts::OP_VAND_VI => R4type(i).into(),
+            insts::OP_VOR_VV => R4type(i).into(),
+            insts::OP_VOR_VX => R4type(i).into(),
+            insts::OP_VOR_VI => R4type(i).into(),
+            insts::OP_VXOR_VV => R4type(i).into(),
+            insts::OP_VXOR_VX => R4type(i).into(),
+            insts::OP_VXOR_VI => R4type(i).into(),
+            insts::OP_VADC_VVM => R4type(i).into(),
+            insts::OP_VADC_VXM => R4type(i).into(),
+            insts::OP_VADC_VIM => R4type(i).into(),
+            insts::OP_VMADC_VV => R4type(i).into(),
+            insts::OP_VMADC_VX => R4type(i).into(),
+            insts::OP_VMADC_VI => R4type(i).into(),
+            insts::OP_VSBC_VVM => R4type(i).into(),
+            insts::OP_VSBC_VXM => R4type(i).into(),
+            insts::OP_VMSBC_VV => R4type(i).into(),
+            insts::OP_VMSBC_VX => R4type(i).into(),
+            insts::OP_VMERGE_VVM => R4type(i).into(),
+            insts::OP_VMERGE_VXM => R4type(i).into(),
+            insts::OP_VMERGE_VIM => R4type(i).into(),
+            insts::OP_VMV_V_V => R4type(i).into(),
+            insts::OP_VMV_V_X => R4type(i).into(),
+            insts::OP_VMV_V_I => R4type(i).into(),
+            insts::OP_VMSEQ_VV => R4type(i).into(),
+            insts::OP_VMSEQ_VX => R4type(i).into(),
+            insts::OP_VMSEQ_VI => R4type(i).into(),
+            insts::OP_VMSNE_VV => R4type(i).into(),
+            insts::OP_VMSNE_VX => R4type(i).into(),
+            insts::OP_VMSNE_VI => R4type(i).into(),
+            insts::OP_VMSLTU_VV => R4type(i).into(),
+            insts::OP_VMSLTU_VX => R4type(i).into(),
+            insts::OP_VMSLT_VV => R4type(i).into(),
+            insts::OP_VMSLT_VX => R4type(i).into(),
+            insts::OP_VMSLEU_VV => R4type(i).into(),
+            insts::OP_VMSLEU_VX => R4type(i).into(),
+            insts::OP_VMSLEU_VI => R4type(i).into(),
+            insts::OP_VMSLE_VV => R4type(i).into(),
+            insts::OP_VMSLE_VX => R4type(i).into(),
+            insts::OP_VMSLE_VI => R4type(i).into(),
+            insts::OP_VMSGTU_VX => R4type(i).into(),
+            insts::OP_VMSGTU_VI => R4type(i).into(),
+            insts::OP_VMSGT_VX => R4type(i).into(),
+            insts::OP_VMSGT_VI => R4type(i).into(),
+            insts::OP_VSLL_VV => R4type(i).into(),
+            insts::OP_VSLL_VX => R4type(i).into(),
+            insts::OP_VSLL_VI => R4type(i).into(),
+            insts::OP_VSRL_VV => R4type(i).into(),
+            insts::OP_VSRL_VX => R4type(i).into(),
+            insts::OP_VSRL_VI => R4type(i).into(),
+            insts::OP_VSRA_VV => R4type(i).into(),
+            insts::OP_VSRA_VX => R4type(i).into(),
+            insts::OP_VSRA_VI => R4type(i).into(),
+            insts::OP_VSLIDEUP_VX => R4type(i).into(),
+            insts::OP_VSLIDEUP_VI => R4type(i).into(),
+            insts::OP_VSLIDEDOWN_VX => R4type(i).into(),
+            insts::OP_VSLIDEDOWN_VI => R4type(i).into(),
+            insts::OP_VMVNR_V => R4type(i).into(),
+            insts::OP_VMUL_VV => R4type(i).into(),
+            insts::OP_VMUL_VX => R4type(i).into(),
+            insts::OP_VMULH_VV => R4type(i).into(),
+            insts::OP_VMULH_VX => R4type(i).into(),
+            insts::OP_VMULHU_VV => R4type(i).into(),
+            insts::OP_VMULHU_VX => R4type(i).into(),
+            insts::OP_VMULHSU_VV => R4type(i).into(),
+            insts::OP_VMULHSU_VX => R4type(i).into(),
+            insts::OP_VDIVU_VV => R4type(i).into(),
+            insts::OP_VDIVU_VX => R4type(i).into(),
+            insts::OP_VDIV_VV => R4type(i).into(),
+            insts::OP_VDIV_VX => R4type(i).into(),
+            insts::OP_VREMU_VV => R4type(i).into(),
+            insts::OP_VREMU_VX => R4type(i).into(),
+            insts::OP_VREM_VV => R4type(i).into(),
+            insts::OP_VREM_VX => R4type(i).into(),
+            insts::OP_VWADDU_VV => R4type(i).into(),
+            insts::OP_VWADDU_VX => R4type(i).into(),
+            insts::OP_VWADD_VV => R4type(i).into(),
+            insts::OP_VWADD_VX => R4type(i).into(),
+            insts::OP_VWSUBU_VV => R4type(i).into(),
+            insts::OP_VWSUBU_VX => R4type(i).into(),
+            insts::OP_VWSUB_VV => R4type(i).into(),
+            insts::OP_VWSUB_VX => R4type(i).into(),
+            insts::OP_VWMULU_VV => R4type(i).into(),
+            insts::OP_VWMULU_VX => R4type(i).into(),
+            insts::OP_VWMUL_VV => R4type(i).into(),
+            insts::OP_VWMUL_VX => R4type(i).into(),
+            insts::OP_VWMULSU_VV => R4type(i).into(),
+            insts::OP_VWMULSU_VX => R4type(i).into(),
+            insts::OP_VMACC_VV => R4type(i).into(),
+            insts::OP_VMACC_VX => R4type(i).into(),
+            insts::OP_VNMSAC_VV => R4type(i).into(),
+            insts::OP_VNMSAC_VX => R4type(i).into(),
+            insts::OP_VMADD_VV => R4type(i).into(),
+            insts::OP_VMADD_VX => R4type(i).into(),
+            insts::OP_VNMSUB_VV => R4type(i).into(),
+            insts::OP_VNMSUB_VX => R4type(i).into(),
+            insts::OP_VWMACCU_VV => R4type(i).into(),
+            insts::OP_VWMACCU_VX => R4type(i).into(),
+            insts::OP_VWMACC_VV => R4type(i).into(),
+            insts::OP_VWMACC_VX => R4type(i).into(),
+            insts::OP_VREDSUM_VS => R4type(i).into(),
+            insts::OP_VREDAND_VS => R4type(i).into(),
+            insts::OP_VREDOR_VS => R4type(i).into(),
+            insts::OP_VREDXOR_VS => R4type(i).into(),
+            insts::OP_VREDMINU_VS => R4type(i).into(),
+            insts::OP_VREDMIN_VS => R4type(i).into(),
+            insts::OP_VREDMAXU_VS => R4type(i).into(),
+            insts::OP_VREDMAX_VS => R4type(i).into(),
+            insts::OP_VMAND_MM => R4type(i).into(),
+            insts::OP_VMNAND_MM => R4type(i).into(),
+            insts::OP_VMANDN_MM => R4type(i).into(),
+            insts::OP_VMXOR_MM => R4type(i).into(),
+            insts::OP_VMOR_MM => R4type(i).into(),
+            insts::OP_VMNOR_MM => R4type(i).into(),
+            insts::OP_VMORN_MM => R4type(i).into(),
+            insts::OP_VMXNOR_MM => R4type(i).into(),
+            insts::OP_VMV_X_S => R4type(i).into(),
+            insts::OP_VMV_S_X => R4type(i).into(),
+            insts::OP_VCPOP_M => R4type(i).into(),
+            insts::OP_VFIRST_M => R4type(i).into(),
+            insts::OP_VID_V => R4type(i).into(),
+            insts::OP_VZEXT_VF2 => R4type(i).into(),
+            insts::OP_VZEXT_VF4 => R4type(i).into(),
+            insts::OP_VZEXT_VF8 => R4type(i).into(),
+            insts::OP_VSEXT_VF2 => R4type(i).into(),
+            insts::OP_VSEXT_VF4 => R4type(i).into(),
+            insts::OP_VSEXT_VF8 => R4type(i).into(),
+            insts::OP_VSLIDE1UP_VX => R4type(i).into(),
+            insts::OP_VSLIDE1DOWN_VX => R4type(i).into(),
+            insts::OP_CM_PUSH => Itype(i).into(),
+            insts::OP_CM_POP => Itype(i).into(),
+            insts::OP_CM_POPRET => Itype(i).into(),
+            insts::OP_CM_POPRETZ => Itype(i).into(),
             _ => return Err(Error::InvalidOp(op)),
         };
         Ok(tagged_inst)
@@ -255,6 +516,7 @@ impl TryFrom<Instruction> for TaggedInstruction {
 mod tests {
     use super::*;
     use crate::instructions::{blank_instruction, instruction_opcode_name};
+    use ckb_vm_definitions::for_each_slowpath_inst;
 
     #[test]
     fn test_all_valid_opcodes_convert_to_tagged_instruction() {
@@ -268,5 +530,11 @@ mod tests {
                 instruction_opcode_name(i)
             );
         }
+        macro_rules! assert_slowpath_opcode {
+            ($name:ident, $real_name:ident, $code:expr) => {
+                assert!(TaggedInstruction::try_from(blank_instruction(insts::$name)).is_ok());
+            };
+        }
+        for_each_slowpath_inst!(assert_slowpath_opcode);
     }
 }
@@ -0,0 +1,292 @@
+use ckb_vm_definitions::instructions::{self as insts, InstructionOpcode};
+
+use super::f::{csr, csr_number};
+use super::utils::{funct3, opcode, rd, rs1, rs2, x};
+use super::{set_instruction_length_4, Instruction, Itype, R4type, Register, Rtype};
+use crate::machine::VERSION3;
+
+// CSR numbers of the vector CSRs, they are read only. vstart, vxrm, vxsat
+// and vcsr are not supported since no instruction traps halfway nor uses
+// fixed-point rounding.
+pub const CSR_VL: u32 = 0xc20;
+pub const CSR_VTYPE: u32 = 0xc21;
+pub const CSR_VLENB: u32 = 0xc22;
+
+// Arithmetic, load and store instructions are R4type instructions: rd is vd
+// (or vs3 of stores), rs1 is vs1, rs1 or the raw 5 bits immediate, rs2 is vs2
+// (or the stride, or the number of registers of whole register loads and
+// stores) and rs3 is the vm bit, 1 means unmasked.
+fn vector(
+    op: InstructionOpcode,
+    vd: usize,
+    rs1: usize,
+    vs2: usize,
+    vm: u32,
+) -> Option<Instruction> {
+    Some(R4type::new(op, vd, rs1, vs2, vm as usize).0)
+}
+
+fn funct6(instruction_bits: u32) -> u32 {
+    x(instruction_bits, 26, 6, 0)
+}
+
+fn vm(instruction_bits: u32) -> u32 {
+    x(instruction_bits, 25, 1, 0)
+}
+
+// EEW of the width field of vector loads and stores.
+fn eew(instruction_bits: u32) -> Option<usize> {
+    match funct3(instruction_bits) {
+        0b000 => Some(8),
+        0b101 => Some(16),
+        0b110 => Some(32),
+        0b111 => Some(64),
+        _ => None,
+    }
+}
+
+fn load_store(instruction_bits: u32, store: bool) -> Option<Instruction> {
+    let eew = eew(instruction_bits)?;
+    let nf = x(instruction_bits, 29, 3, 0);
+    let mew = x(instruction_bits, 28, 1, 0);
+    let mop = x(instruction_bits, 26, 2, 0);
+    let vm = vm(instruction_bits);
+    if mew != 0 {
+        return None;
+    }
+    let pick = |ops: [InstructionOpcode; 4]| match eew {
+        8 => ops[0],
+        16 => ops[1],
+        32 => ops[2],
+        _ => ops[3],
+    };
+    let (unit, strided, mask, whole) = if store {
+        (
+            pick([
+                insts::OP_VSE8_V,
+                insts::OP_VSE16_V,
+                insts::OP_VSE32_V,
+                insts::OP_VSE64_V,
+            ]),
+            pick([
+                insts::OP_VSSE8_V,
+                insts::OP_VSSE16_V,
+                insts::OP_VSSE32_V,
+                insts::OP_VSSE64_V,
+            ]),
+            insts::OP_VSM_V,
+            insts::OP_VSR_V,
+        )
+    } else {
+        (
+            pick([
+                insts::OP_VLE8_V,
+                insts::OP_VLE16_V,
+                insts::OP_VLE32_V,
+                insts::OP_VLE64_V,
+            ]),
+            pick([
+                insts::OP_VLSE8_V,
+                insts::OP_VLSE16_V,
+                insts::OP_VLSE32_V,
+                insts::OP_VLSE64_V,
+            ]),
+            insts::OP_VLM_V,
+            insts::OP_VLR_V,
+        )
+    };
+    let vd = rd(instruction_bits);
+    let rs1 = rs1(instruction_bits);
+    // Segment and indexed accesses are not supported.
+    match (mop, rs2(instruction_bits)) {
+        (0b00, 0b00000) if nf == 0 => vector(unit, vd, rs1, 0, vm),
+        // Whole register stores only have the 8 bits encoding, the hint of
+        // whole register loads makes no difference here.
+        (0b00, 0b01000) if vm == 1 && (!store || eew == 8) => match nf {
+            0 | 1 | 3 | 7 => vector(whole, vd, rs1, nf as usize + 1, vm),
+            _ => None,
+        },
+        (0b00, 0b01011) if vm == 1 && nf == 0 && eew == 8 => vector(mask, vd, rs1, 0, vm),
+        (0b10, rs2) if nf == 0 => vector(strided, vd, rs1, rs2, vm),
+        _ => None,
+    }
+}
+
+// OPIVV, OPIVX and OPIVI instructions, ops are the vector-vector,
+// vector-scalar and vector-immediate variants.
+fn opi(instruction_bits: u32) -> Option<Instruction> {
+    let variant = match funct3(instruction_bits) {
+        0b000 => 0,
+        0b100 => 1,
+        _ => 2,
+    };
+    let vm = vm(instruction_bits);
+    let vs2 = rs2(instruction_bits);
+    let none = insts::OP_UNLOADED;
+    let ops = match funct6(instruction_bits) {
+        0b000000 => [insts::OP_VADD_VV, insts::OP_VADD_VX, insts::OP_VADD_VI],
+        0b000010 => [insts::OP_VSUB_VV, insts::OP_VSUB_VX, none],
+        0b000011 => [none, insts::OP_VRSUB_VX, insts::OP_VRSUB_VI],
+        0b000100 => [insts::OP_VMINU_VV, insts::OP_VMINU_VX, none],
+        0b000101 => [insts::OP_VMIN_VV, insts::OP_VMIN_VX, none],
+        0b000110 => [insts::OP_VMAXU_VV, insts::OP_VMAXU_VX, none],
+        0b000111 => [insts::OP_VMAX_VV, insts::OP_VMAX_VX, none],
+        0b001001 => [insts::OP_VAND_VV, insts::OP_VAND_VX, insts::OP_VAND_VI],
+        0b001010 => [insts::OP_VOR_VV, insts::OP_VOR_VX, insts::OP_VOR_VI],
+        0b001011 => [insts::OP_VXOR_VV, insts::OP_VXOR_VX, insts::OP_VXOR_VI],
+        0b001110 => [none, insts::OP_VSLIDEUP_VX, insts::OP_VSLIDEUP_VI],
+        0b001111 => [none, insts::OP_VSLIDEDOWN_VX, insts::OP_VSLIDEDOWN_VI],
+        0b010000 if vm == 0 => [insts::OP_VADC_VVM, insts::OP_VADC_VXM, insts::OP_VADC_VIM],
+        0b010001 => [insts::OP_VMADC_VV, insts::OP_VMADC_VX, insts::OP_VMADC_VI],
+        0b010010 if vm == 0 => [insts::OP_VSBC_VVM, insts::OP_VSBC_VXM, none],
+        0b010011 => [insts::OP_VMSBC_VV, insts::OP_VMSBC_VX, none],
+        0b010111 if vm == 0 => [
+            insts::OP_VMERGE_VVM,
+            insts::OP_VMERGE_VXM,
+            insts::OP_VMERGE_VIM,
+        ],
+        0b010111 if vs2 == 0 => [insts::OP_VMV_V_V, insts::OP_VMV_V_X, insts::OP_VMV_V_I],
+        0b011000 => [insts::OP_VMSEQ_VV, insts::OP_VMSEQ_VX, insts::OP_VMSEQ_VI],
+        0b011001 => [insts::OP_VMSNE_VV, insts::OP_VMSNE_VX, insts::OP_VMSNE_VI],
+        0b011010 => [insts::OP_VMSLTU_VV, insts::OP_VMSLTU_VX, none],
+        0b011011 => [insts::OP_VMSLT_VV, insts::OP_VMSLT_VX, none],
+        0b011100 => [
+            insts::OP_VMSLEU_VV,
+            insts::OP_VMSLEU_VX,
+            insts::OP_VMSLEU_VI,
+        ],
+        0b011101 => [insts::OP_VMSLE_VV, insts::OP_VMSLE_VX, insts::OP_VMSLE_VI],
+        0b011110 => [none, insts::OP_VMSGTU_VX, insts::OP_VMSGTU_VI],
+        0b011111 => [none, insts::OP_VMSGT_VX, insts::OP_VMSGT_VI],
+        0b100101 => [insts::OP_VSLL_VV, insts::OP_VSLL_VX, insts::OP_VSLL_VI],
+        // vmv<nr>r.v keeps nr - 1 in the immediate.
+        0b100111 if vm == 1 && matches!(rs1(instruction_bits), 0 | 1 | 3 | 7) => {
+            [none, none, insts::OP_VMVNR_V]
+        }
+        0b101000 => [insts::OP_VSRL_VV, insts::OP_VSRL_VX, insts::OP_VSRL_VI],
+        0b101001 => [insts::OP_VSRA_VV, insts::OP_VSRA_VX, insts::OP_VSRA_VI],
+        _ => return None,
+    };
+    let op = ops[variant];
+    if op == none {
+        return None;
+    }
+    vector(op, rd(instruction_bits), rs1(instruction_bits), vs2, vm)
+}
+
+// OPMVV and OPMVX instructions.
+fn opm(instruction_bits: u32) -> Option<Instruction> {
+    let scalar = funct3(instruction_bits) == 0b110;
+    let vm = vm(instruction_bits);
+    let vd = rd(instruction_bits);
+    let vs1 = rs1(instruction_bits);
+    let vs2 = rs2(instruction_bits);
+    let pair = |vv, vx| if scalar { vx } else { vv };
+    let op = match funct6(instruction_bits) {
+        0b000000 if !scalar => insts::OP_VREDSUM_VS,
+        0b000001 if !scalar => insts::OP_VREDAND_VS,
+        0b000010 if !scalar => insts::OP_VREDOR_VS,
+        0b000011 if !scalar => insts::OP_VREDXOR_VS,
+        0b000100 if !scalar => insts::OP_VREDMINU_VS,
+        0b000101 if !scalar => insts::OP_VREDMIN_VS,
+        0b000110 if !scalar => insts::OP_VREDMAXU_VS,
+        0b000111 if !scalar => insts::OP_VREDMAX_VS,
+        0b001110 if scalar => insts::OP_VSLIDE1UP_VX,
+        0b001111 if scalar => insts::OP_VSLIDE1DOWN_VX,
+        0b010000 if scalar && vm == 1 && vs2 == 0 => insts::OP_VMV_S_X,
+        0b010000 if !scalar => match vs1 {
+            0b00000 if vm == 1 => insts::OP_VMV_X_S,
+            0b10000 => insts::OP_VCPOP_M,
+            0b10001 => insts::OP_VFIRST_M,
+            _ => return None,
+        },
+        0b010010 if !scalar => match vs1 {
+            0b00010 => insts::OP_VZEXT_VF8,
+            0b00011 => insts::OP_VSEXT_VF8,
+            0b00100 => insts::OP_VZEXT_VF4,
+            0b00101 => insts::OP_VSEXT_VF4,
+            0b00110 => insts::OP_VZEXT_VF2,
+            0b00111 => insts::OP_VSEXT_VF2,
+            _ => return None,
+        },
+        0b010100 if !scalar && vs1 == 0b10001 && vs2 == 0 => insts::OP_VID_V,
+        0b011000 if !scalar && vm == 1 => insts::OP_VMANDN_MM,
+        0b011001 if !scalar && vm == 1 => insts::OP_VMAND_MM,
+        0b011010 if !scalar && vm == 1 => insts::OP_VMOR_MM,
+        0b011011 if !scalar && vm == 1 => insts::OP_VMXOR_MM,
+        0b011100 if !scalar && vm == 1 => insts::OP_VMORN_MM,
+        0b011101 if !scalar && vm == 1 => insts::OP_VMNAND_MM,
+        0b011110 if !scalar && vm == 1 => insts::OP_VMNOR_MM,
+        0b011111 if !scalar && vm == 1 => insts::OP_VMXNOR_MM,
+        0b100000 => pair(insts::OP_VDIVU_VV, insts::OP_VDIVU_VX),
+        0b100001 => pair(insts::OP_VDIV_VV, insts::OP_VDIV_VX),
+        0b100010 => pair(insts::OP_VREMU_VV, insts::OP_VREMU_VX),
+        0b100011 => pair(insts::OP_VREM_VV, insts::OP_VREM_VX),
+        0b100100 => pair(insts::OP_VMULHU_VV, insts::OP_VMULHU_VX),
+        0b100101 => pair(insts::OP_VMUL_VV, insts::OP_VMUL_VX),
+        0b100110 => pair(insts::OP_VMULHSU_VV, insts::OP_VMULHSU_VX),
+        0b100111 => pair(insts::OP_VMULH_VV, insts::OP_VMULH_VX),
+        0b101001 => pair(insts::OP_VMADD_VV, insts::OP_VMADD_VX),
+        0b101011 => pair(insts::OP_VNMSUB_VV, insts::OP_VNMSUB_VX),
+        0b101101 => pair(insts::OP_VMACC_VV, insts::OP_VMACC_VX),
+        0b101111 => pair(insts::OP_VNMSAC_VV, insts::OP_VNMSAC_VX),
+        0b110000 => pair(insts::OP_VWADDU_VV, insts::OP_VWADDU_VX),
+        0b110001 => pair(insts::OP_VWADD_VV, insts::OP_VWADD_VX),
+        0b110010 => pair(insts::OP_VWSUBU_VV, insts::OP_VWSUBU_VX),
+        0b110011 => pair(insts::OP_VWSUB_VV, insts::OP_VWSUB_VX),
+        0b111000 => pair(insts::OP_VWMULU_VV, insts::OP_VWMULU_VX),
+        0b111010 => pair(insts::OP_VWMULSU_VV, insts::OP_VWMULSU_VX),
+        0b111011 => pair(insts::OP_VWMUL_VV, insts::OP_VWMUL_VX),
+        0b111100 => pair(insts::OP_VWMACCU_VV, insts::OP_VWMACCU_VX),
+        0b111101 => pair(insts::OP_VWMACC_VV, insts::OP_VWMACC_VX),
+        _ => return None,
+    };
+    vector(op, vd, vs1, vs2, vm)
+}
+
+fn config(instruction_bits: u32) -> Option<Instruction> {
+    let rd = rd(instruction_bits);
+    let rs1 = rs1(instruction_bits);
+    if instruction_bits >> 31 == 0 {
+        Some(Itype::new_u(insts::OP_VSETVLI, rd, rs1, x(instruction_bits, 20, 11, 0)).0)
+    } else if instruction_bits >> 30 == 0b11 {
+        // The AVL immediate is kept in rs1.
+        Some(Itype::new_u(insts::OP_VSETIVLI, rd, rs1, x(instruction_bits, 20, 10, 0)).0)
+    } else if instruction_bits >> 25 == 0b1000000 {
+        Some(Rtype::new(insts::OP_VSETVL, rd, rs1, rs2(instruction_bits)).0)
+    } else {
+        None
+    }
+}
+
+pub fn factory<R: Register>(instruction_bits: u32, version: u32) -> Option<Instruction> {
+    let bit_length = R::BITS;
+    if bit_length != 32 && bit_length != 64 {
+        return None;
+    }
+    if version < VERSION3 {
+        return None;
+    }
+    let inst = match opcode(instruction_bits) {
+        0b_0000111 => load_store(instruction_bits, false),
+        0b_0100111 => load_store(instruction_bits, true),
+        0b_1010111 => match funct3(instruction_bits) {
+            0b000 | 0b011 | 0b100 => opi(instruction_bits),
+            0b010 | 0b110 => opm(instruction_bits),
+            0b111 => config(instruction_bits),
+            // Floating-point vector instructions are not supported.
+            _ => None,
+        },
+        // Vector CSRs are read only, which leaves only reads by CSRRS and
+        // CSRRC with x0 or a zero immediate.
+        0b_1110011
+            if matches!(csr_number(instruction_bits), CSR_VL | CSR_VTYPE | CSR_VLENB)
+                && matches!(funct3(instruction_bits), 0b010 | 0b011 | 0b110 | 0b111)
+                && rs1(instruction_bits) == 0 =>
+        {
+            csr(instruction_bits)
+        }
+        _ => None,
+    };
+    inst.map(set_instruction_length_4)
+}
@@ -0,0 +1,158 @@
+// Register file helpers of the V extension.
+//
+// Vector registers are stored one after another as little endian bytes, so
+// element i of a register group starting at register r with SEW bits elements
+// always starts at byte r * VLENB + i * SEW / 8, no matter how many registers
+// the group spans.
+use crate::{RISCV_VECTOR_REGISTER_NUMBER, RISCV_VLEN, RISCV_VLENB, RISCV_VTYPE_VILL};
+
+// The widest element supported, CKB-VM implements Zve64x.
+pub const ELEN: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VType(pub u64);
+
+impl VType {
+    // Validates the vtype requested by vsetvl and friends, an unsupported
+    // setting results in vill being set with all other bits cleared.
+    pub fn new(bits: u64) -> Self {
+        let vtype = VType(bits);
+        // Only vlmul, vsew, vta and vma are defined.
+        if bits >> 8 != 0 || vtype.lmul8() == 0 || vtype.sew() > ELEN {
+            return VType(RISCV_VTYPE_VILL);
+        }
+        // SEW must not be larger than LMUL * ELEN for fractional LMUL.
+        if vtype.sew() * 8 > vtype.lmul8() * ELEN {
+            return VType(RISCV_VTYPE_VILL);
+        }
+        vtype
+    }
+
+    pub fn vill(self) -> bool {
+        self.0 & RISCV_VTYPE_VILL != 0
+    }
+
+    pub fn sew(self) -> usize {
+        8 << ((self.0 >> 3) & 0b111)
+    }
+
+    // LMUL in eighths of a register, fractional LMUL is below 8. 0 means the
+    // reserved vlmul encoding.
+    pub fn lmul8(self) -> usize {
+        match self.0 & 0b111 {
+            0b000 => 8,
+            0b001 => 16,
+            0b010 => 32,
+            0b011 => 64,
+            0b101 => 1,
+            0b110 => 2,
+            0b111 => 4,
+            _ => 0,
+        }
+    }
+
+    pub fn vlmax(self) -> usize {
+        RISCV_VLEN * self.lmul8() / 8 / self.sew()
+    }
+}
+
+// Number of registers occupied by a group of EMUL(in eighths) registers, None
+// when EMUL is out of the 1/8 to 8 range.
+pub fn group_size(emul8: usize) -> Option<usize> {
+    if emul8 == 0 || emul8 > 64 {
+        return None;
+    }
+    Some(std::cmp::max(1, emul8 / 8))
+}
+
+// A register group must start at a register number aligned to its size and
+// fit in the register file.
+pub fn valid_group(register: usize, emul8: usize) -> bool {
+    match group_size(emul8) {
+        Some(size) => register % size == 0 && register + size <= RISCV_VECTOR_REGISTER_NUMBER,
+        None => false,
+    }
+}
+
+pub fn read_element(vregisters: &[u8], register: usize, sew: usize, index: usize) -> u64 {
+    let bytes = sew / 8;
+    let offset = register * RISCV_VLENB + index * bytes;
+    let mut buf = [0u8; 8];
+    buf[..bytes].copy_from_slice(&vregisters[offset..offset + bytes]);
+    u64::from_le_bytes(buf)
+}
+
+pub fn write_element(vregisters: &mut [u8], register: usize, sew: usize, index: usize, value: u64) {
+    let bytes = sew / 8;
+    let offset = register * RISCV_VLENB + index * bytes;
+    vregisters[offset..offset + bytes].copy_from_slice(&value.to_le_bytes()[..bytes]);
+}
+
+pub fn mask_bit(vregisters: &[u8], register: usize, index: usize) -> bool {
+    vregisters[register * RISCV_VLENB + index / 8] >> (index % 8) & 1 != 0
+}
+
+pub fn set_mask_bit(vregisters: &mut [u8], register: usize, index: usize, bit: bool) {
+    let byte = &mut vregisters[register * RISCV_VLENB + index / 8];
+    *byte = (*byte & !(1 << (index % 8))) | ((bit as u8) << (index % 8));
+}
+
+pub fn truncate(value: u64, sew: usize) -> u64 {
+    if sew >= 64 {
+        value
+    } else {
+        value & ((1 << sew) - 1)
+    }
+}
+
+pub fn sign_extend(value: u64, sew: usize) -> i64 {
+    ((value << (64 - sew)) as i64) >> (64 - sew)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vtype() {
+        // e8, m1
+        let vtype = VType::new(0b000_000);
+        assert!(!vtype.vill());
+        assert_eq!(vtype.sew(), 8);
+        assert_eq!(vtype.vlmax(), RISCV_VLEN / 8);
+        // e64, m8
+        let vtype = VType::new(0b011_011);
+        assert_eq!(vtype.vlmax(), RISCV_VLEN / 8);
+        // e32, mf2
+        let vtype = VType::new(0b010_111);
+        assert_eq!(vtype.vlmax(), RISCV_VLEN / 64);
+        // e64, mf2 needs ELEN of 128
+        assert!(VType::new(0b011_111).vill());
+        // e128 and the reserved vlmul
+        assert!(VType::new(0b100_000).vill());
+        assert!(VType::new(0b000_100).vill());
+        assert!(VType::new(1 << 8).vill());
+        assert_eq!(VType::new(1 << 8).0, RISCV_VTYPE_VILL);
+    }
+
+    #[test]
+    fn test_elements() {
+        let mut v = [0u8; RISCV_VLENB * RISCV_VECTOR_REGISTER_NUMBER];
+        write_element(&mut v, 2, 32, 9, 0x1122_3344_5566_7788);
+        assert_eq!(read_element(&v, 2, 32, 9), 0x5566_7788);
+        // Element 9 of the group starting at v2 is element 1 of v3.
+        assert_eq!(read_element(&v, 3, 32, 9 - RISCV_VLENB / 4), 0x5566_7788);
+        assert_eq!(read_element(&v, 2, 8, 36), 0x88);
+        set_mask_bit(&mut v, 0, 13, true);
+        assert!(mask_bit(&v, 0, 13));
+        assert_eq!(v[1], 0b0010_0000);
+        set_mask_bit(&mut v, 0, 13, false);
+        assert_eq!(v[1], 0);
+        assert_eq!(sign_extend(0x80, 8), -128);
+        assert_eq!(truncate(u64::MAX, 16), 0xffff);
+        assert!(valid_group(4, 32));
+        assert!(!valid_group(2, 32));
+        assert!(!valid_group(31, 16));
+        assert!(valid_group(31, 2));
+    }
+}
@@ -0,0 +1,35 @@
+// RISC-V Integer Conditional Operations Extension
+// See https://github.com/riscv/riscv-zicond/releases/download/v1.0.1/riscv-zicond_1.0.1.pdf
+
+use ckb_vm_definitions::instructions as insts;
+
+use super::utils::{funct3, funct7, opcode, rd, rs1, rs2};
+use super::{set_instruction_length_4, Instruction, Register, Rtype};
+use crate::machine::VERSION3;
+
+pub fn factory<R: Register>(instruction_bits: u32, version: u32) -> Option<Instruction> {
+    let bit_length = R::BITS;
+    if bit_length != 32 && bit_length != 64 {
+        return None;
+    }
+    if version < VERSION3 {
+        return None;
+    }
+    if opcode(instruction_bits) != 0b_0110011 || funct7(instruction_bits) != 0b_0000111 {
+        return None;
+    }
+    let inst = match funct3(instruction_bits) {
+        0b_101 => insts::OP_CZERO_EQZ,
+        0b_111 => insts::OP_CZERO_NEZ,
+        _ => return None,
+    };
+    Some(set_instruction_length_4(
+        Rtype::new(
+            inst,
+            rd(instruction_bits),
+            rs1(instruction_bits),
+            rs2(instruction_bits),
+        )
+        .0,
+    ))
+}
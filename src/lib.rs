@@ -1,15 +1,25 @@
 #[macro_use]
 extern crate derive_more;
 
+pub mod backtrace;
 pub mod bits;
+pub mod compliance;
+pub mod coredump;
 pub mod cost_model;
+pub mod coverage;
 pub mod debugger;
 pub mod decoder;
+pub mod differential;
+pub mod disasm;
+pub mod dwarf;
 pub mod elf;
 pub mod error;
 pub mod instructions;
 pub mod machine;
 pub mod memory;
+pub mod profiler;
+pub mod record;
+pub mod scheduler;
 pub mod snapshot;
 pub mod snapshot2;
 pub mod syscalls;
@@ -19,22 +29,27 @@ pub use ckb_vm_definitions;
 
 pub use crate::{
     debugger::Debugger,
-    instructions::{Instruction, Register},
+    instructions::{custom::CustomInstruction, Instruction, Register},
     machine::{
         trace::TraceMachine, CoreMachine, DefaultCoreMachine, DefaultMachine,
         DefaultMachineBuilder, InstructionCycleFunc, Machine, SupportMachine,
     },
-    memory::{flat::FlatMemory, sparse::SparseMemory, wxorx::WXorXMemory, Memory},
-    syscalls::Syscalls,
+    memory::{
+        flat::FlatMemory, memcheck::MemcheckMemory, sparse::SparseMemory,
+        watchpoint::WatchpointMemory, wxorx::WXorXMemory, Memory,
+    },
+    syscalls::{SyscallInfo, SyscallSchema, SyscallTable, SyscallTrace, Syscalls},
 };
 pub use bytes::Bytes;
 
 pub use ckb_vm_definitions::{
-    registers, DEFAULT_MEMORY_SIZE, ISA_A, ISA_B, ISA_IMC, ISA_MOP, MEMORY_FRAMESIZE,
-    MEMORY_FRAME_SHIFTS, RISCV_GENERAL_REGISTER_NUMBER, RISCV_PAGESIZE, RISCV_PAGE_SHIFTS,
+    registers, DEFAULT_MEMORY_SIZE, ISA_A, ISA_B, ISA_D, ISA_F, ISA_IMC, ISA_K, ISA_MOP, ISA_V,
+    ISA_ZC, MEMORY_FRAMESIZE, MEMORY_FRAME_SHIFTS, RISCV_FLOAT_REGISTER_NUMBER,
+    RISCV_GENERAL_REGISTER_NUMBER, RISCV_PAGESIZE, RISCV_PAGE_SHIFTS, RISCV_VECTOR_REGISTER_NUMBER,
+    RISCV_VLEN, RISCV_VLENB, RISCV_VTYPE_VILL,
 };
 
-pub use error::Error;
+pub use error::{Error, ErrorReport};
 
 pub fn run<R: Register, M: Memory<REG = R> + Default>(
     program: &Bytes,
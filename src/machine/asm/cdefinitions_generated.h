@@ -17,9 +17,12 @@
 #define CKB_VM_ASM_RET_INVALID_PERMISSION 8
 #define CKB_VM_ASM_RET_SLOWPATH 9
 #define CKB_VM_ASM_RET_PAUSE 10
+#define CKB_VM_ASM_RET_STACK_OVERFLOW 11
 
 #define CKB_VM_ASM_REGISTER_RA 1
 #define CKB_VM_ASM_REGISTER_SP 2
+#define CKB_VM_ASM_REGISTER_A0 10
+#define CKB_VM_ASM_REGISTER_A1 11
 
 #define CKB_VM_ASM_MEMORY_FLAG_FREEZED 1
 #define CKB_VM_ASM_MEMORY_FLAG_EXECUTABLE 2
@@ -45,14 +48,17 @@
 #define CKB_VM_ASM_ASM_CORE_MACHINE_OFFSET_CHAOS_SEED 300
 #define CKB_VM_ASM_ASM_CORE_MACHINE_OFFSET_LOAD_RESERVATION_ADDRESS 304
 #define CKB_VM_ASM_ASM_CORE_MACHINE_OFFSET_ERROR_ARG0 320
-#define CKB_VM_ASM_ASM_CORE_MACHINE_OFFSET_MEMORY_SIZE 328
-#define CKB_VM_ASM_ASM_CORE_MACHINE_OFFSET_FRAMES_SIZE 336
-#define CKB_VM_ASM_ASM_CORE_MACHINE_OFFSET_FLAGS_SIZE 344
-#define CKB_VM_ASM_ASM_CORE_MACHINE_OFFSET_LAST_READ_FRAME 352
-#define CKB_VM_ASM_ASM_CORE_MACHINE_OFFSET_LAST_WRITE_PAGE 360
-#define CKB_VM_ASM_ASM_CORE_MACHINE_OFFSET_MEMORY_PTR 368
-#define CKB_VM_ASM_ASM_CORE_MACHINE_OFFSET_FLAGS_PTR 376
-#define CKB_VM_ASM_ASM_CORE_MACHINE_OFFSET_FRAMES_PTR 384
+#define CKB_VM_ASM_ASM_CORE_MACHINE_OFFSET_ERROR_ARG1 328
+#define CKB_VM_ASM_ASM_CORE_MACHINE_OFFSET_MEMORY_SIZE 336
+#define CKB_VM_ASM_ASM_CORE_MACHINE_OFFSET_FRAMES_SIZE 344
+#define CKB_VM_ASM_ASM_CORE_MACHINE_OFFSET_FLAGS_SIZE 352
+#define CKB_VM_ASM_ASM_CORE_MACHINE_OFFSET_STACK_GUARD_START 360
+#define CKB_VM_ASM_ASM_CORE_MACHINE_OFFSET_STACK_GUARD_END 368
+#define CKB_VM_ASM_ASM_CORE_MACHINE_OFFSET_LAST_READ_FRAME 376
+#define CKB_VM_ASM_ASM_CORE_MACHINE_OFFSET_LAST_WRITE_PAGE 384
+#define CKB_VM_ASM_ASM_CORE_MACHINE_OFFSET_MEMORY_PTR 392
+#define CKB_VM_ASM_ASM_CORE_MACHINE_OFFSET_FLAGS_PTR 400
+#define CKB_VM_ASM_ASM_CORE_MACHINE_OFFSET_FRAMES_PTR 408
 
 #define CKB_VM_ASM_OP_UNLOADED 16
 #define CKB_VM_ASM_OP_ADD 17
@@ -194,23 +200,43 @@
 #define CKB_VM_ASM_OP_ADD3C 153
 #define CKB_VM_ASM_OP_CUSTOM_LOAD_UIMM 154
 #define CKB_VM_ASM_OP_CUSTOM_LOAD_IMM 155
-#define CKB_VM_ASM_OP_AUIPC 156
-#define CKB_VM_ASM_OP_BEQ 157
-#define CKB_VM_ASM_OP_BGE 158
-#define CKB_VM_ASM_OP_BGEU 159
-#define CKB_VM_ASM_OP_BLT 160
-#define CKB_VM_ASM_OP_BLTU 161
-#define CKB_VM_ASM_OP_BNE 162
-#define CKB_VM_ASM_OP_EBREAK 163
-#define CKB_VM_ASM_OP_ECALL 164
-#define CKB_VM_ASM_OP_FENCE 165
-#define CKB_VM_ASM_OP_FENCEI 166
-#define CKB_VM_ASM_OP_JAL 167
-#define CKB_VM_ASM_OP_JALR_VERSION0 168
-#define CKB_VM_ASM_OP_JALR_VERSION1 169
-#define CKB_VM_ASM_OP_FAR_JUMP_REL 170
-#define CKB_VM_ASM_OP_FAR_JUMP_ABS 171
-#define CKB_VM_ASM_OP_CUSTOM_ASM_TRACE_JUMP 172
+#define CKB_VM_ASM_OP_BREV8 156
+#define CKB_VM_ASM_OP_PACK 157
+#define CKB_VM_ASM_OP_PACKH 158
+#define CKB_VM_ASM_OP_PACKW 159
+#define CKB_VM_ASM_OP_SHA256SIG0 160
+#define CKB_VM_ASM_OP_SHA256SIG1 161
+#define CKB_VM_ASM_OP_SHA256SUM0 162
+#define CKB_VM_ASM_OP_SHA256SUM1 163
+#define CKB_VM_ASM_OP_SHA512SIG0 164
+#define CKB_VM_ASM_OP_SHA512SIG1 165
+#define CKB_VM_ASM_OP_SHA512SUM0 166
+#define CKB_VM_ASM_OP_SHA512SUM1 167
+#define CKB_VM_ASM_OP_SM3P0 168
+#define CKB_VM_ASM_OP_SM3P1 169
+#define CKB_VM_ASM_OP_XPERM4 170
+#define CKB_VM_ASM_OP_XPERM8 171
+#define CKB_VM_ASM_OP_CZERO_EQZ 172
+#define CKB_VM_ASM_OP_CZERO_NEZ 173
+#define CKB_VM_ASM_OP_CM_MVA01S 174
+#define CKB_VM_ASM_OP_CM_MVSA01 175
+#define CKB_VM_ASM_OP_AUIPC 176
+#define CKB_VM_ASM_OP_BEQ 177
+#define CKB_VM_ASM_OP_BGE 178
+#define CKB_VM_ASM_OP_BGEU 179
+#define CKB_VM_ASM_OP_BLT 180
+#define CKB_VM_ASM_OP_BLTU 181
+#define CKB_VM_ASM_OP_BNE 182
+#define CKB_VM_ASM_OP_EBREAK 183
+#define CKB_VM_ASM_OP_ECALL 184
+#define CKB_VM_ASM_OP_FENCE 185
+#define CKB_VM_ASM_OP_FENCEI 186
+#define CKB_VM_ASM_OP_JAL 187
+#define CKB_VM_ASM_OP_JALR_VERSION0 188
+#define CKB_VM_ASM_OP_JALR_VERSION1 189
+#define CKB_VM_ASM_OP_FAR_JUMP_REL 190
+#define CKB_VM_ASM_OP_FAR_JUMP_ABS 191
+#define CKB_VM_ASM_OP_CUSTOM_ASM_TRACE_JUMP 192
 
 #ifdef CKB_VM_ASM_GENERATE_LABEL_TABLES
 #ifdef __APPLE__
@@ -377,6 +403,26 @@ ckb_vm_asm_labels:
 	.long	.CKB_VM_ASM_LABEL_OP_ADD3C - .CKB_VM_ASM_LABEL_TABLE
 	.long	.CKB_VM_ASM_LABEL_OP_CUSTOM_LOAD_UIMM - .CKB_VM_ASM_LABEL_TABLE
 	.long	.CKB_VM_ASM_LABEL_OP_CUSTOM_LOAD_IMM - .CKB_VM_ASM_LABEL_TABLE
+	.long	.CKB_VM_ASM_LABEL_OP_BREV8 - .CKB_VM_ASM_LABEL_TABLE
+	.long	.CKB_VM_ASM_LABEL_OP_PACK - .CKB_VM_ASM_LABEL_TABLE
+	.long	.CKB_VM_ASM_LABEL_OP_PACKH - .CKB_VM_ASM_LABEL_TABLE
+	.long	.CKB_VM_ASM_LABEL_OP_PACKW - .CKB_VM_ASM_LABEL_TABLE
+	.long	.CKB_VM_ASM_LABEL_OP_SHA256SIG0 - .CKB_VM_ASM_LABEL_TABLE
+	.long	.CKB_VM_ASM_LABEL_OP_SHA256SIG1 - .CKB_VM_ASM_LABEL_TABLE
+	.long	.CKB_VM_ASM_LABEL_OP_SHA256SUM0 - .CKB_VM_ASM_LABEL_TABLE
+	.long	.CKB_VM_ASM_LABEL_OP_SHA256SUM1 - .CKB_VM_ASM_LABEL_TABLE
+	.long	.CKB_VM_ASM_LABEL_OP_SHA512SIG0 - .CKB_VM_ASM_LABEL_TABLE
+	.long	.CKB_VM_ASM_LABEL_OP_SHA512SIG1 - .CKB_VM_ASM_LABEL_TABLE
+	.long	.CKB_VM_ASM_LABEL_OP_SHA512SUM0 - .CKB_VM_ASM_LABEL_TABLE
+	.long	.CKB_VM_ASM_LABEL_OP_SHA512SUM1 - .CKB_VM_ASM_LABEL_TABLE
+	.long	.CKB_VM_ASM_LABEL_OP_SM3P0 - .CKB_VM_ASM_LABEL_TABLE
+	.long	.CKB_VM_ASM_LABEL_OP_SM3P1 - .CKB_VM_ASM_LABEL_TABLE
+	.long	.CKB_VM_ASM_LABEL_OP_XPERM4 - .CKB_VM_ASM_LABEL_TABLE
+	.long	.CKB_VM_ASM_LABEL_OP_XPERM8 - .CKB_VM_ASM_LABEL_TABLE
+	.long	.CKB_VM_ASM_LABEL_OP_CZERO_EQZ - .CKB_VM_ASM_LABEL_TABLE
+	.long	.CKB_VM_ASM_LABEL_OP_CZERO_NEZ - .CKB_VM_ASM_LABEL_TABLE
+	.long	.CKB_VM_ASM_LABEL_OP_CM_MVA01S - .CKB_VM_ASM_LABEL_TABLE
+	.long	.CKB_VM_ASM_LABEL_OP_CM_MVSA01 - .CKB_VM_ASM_LABEL_TABLE
 	.long	.CKB_VM_ASM_LABEL_OP_AUIPC - .CKB_VM_ASM_LABEL_TABLE
 	.long	.CKB_VM_ASM_LABEL_OP_BEQ - .CKB_VM_ASM_LABEL_TABLE
 	.long	.CKB_VM_ASM_LABEL_OP_BGE - .CKB_VM_ASM_LABEL_TABLE
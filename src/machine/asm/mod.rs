@@ -7,25 +7,29 @@ use ckb_vm_definitions::{
     asm::{
         FixedTrace, InvokeData, RET_CYCLES_OVERFLOW, RET_DECODE_TRACE, RET_DYNAMIC_JUMP,
         RET_EBREAK, RET_ECALL, RET_INVALID_PERMISSION, RET_MAX_CYCLES_EXCEEDED, RET_OUT_OF_BOUND,
-        RET_PAUSE, RET_SLOWPATH,
+        RET_PAUSE, RET_SLOWPATH, RET_STACK_OVERFLOW,
     },
-    ISA_MOP, MEMORY_FRAME_PAGE_SHIFTS, RISCV_GENERAL_REGISTER_NUMBER, RISCV_PAGE_SHIFTS,
+    ISA_MOP, MEMORY_FRAME_PAGE_SHIFTS, RISCV_FLOAT_REGISTER_NUMBER, RISCV_GENERAL_REGISTER_NUMBER,
+    RISCV_PAGE_SHIFTS, RISCV_VECTOR_REGISTER_NUMBER, RISCV_VLENB, RISCV_VTYPE_VILL,
 };
 use rand::{prelude::RngCore, SeedableRng};
 use std::os::raw::c_uchar;
 
 use crate::{
-    decoder::{build_decoder, InstDecoder},
+    decoder::InstDecoder,
     elf::ProgramMetadata,
-    error::OutOfBoundKind,
-    instructions::execute_instruction,
+    error::{OutOfBoundKind, WatchpointKind},
+    instructions::{execute_instruction, instruction_length, Instruction},
     machine::{
         asm::traces::{decode_fixed_trace, SimpleFixedTraceDecoder, TraceDecoder},
         VERSION0,
     },
     memory::{
-        check_no_overflow, fill_page_data, get_page_indices, memset, round_page_down,
-        round_page_up, FLAG_DIRTY, FLAG_EXECUTABLE, FLAG_FREEZED, FLAG_WRITABLE, FLAG_WXORX_BIT,
+        check_no_overflow, check_stack_guard, fill_page_data, get_page_indices,
+        memcheck::{find_undefined, mark_defined},
+        memset, round_page_down, round_page_up,
+        watchpoint::{instruction_memory_access, Watchpoints},
+        FLAG_DIRTY, FLAG_EXECUTABLE, FLAG_FREEZED, FLAG_WRITABLE, FLAG_WXORX_BIT,
     },
     CoreMachine, DefaultMachine, Error, Machine, Memory, SupportMachine, MEMORY_FRAME_SHIFTS,
     RISCV_PAGESIZE,
@@ -63,6 +67,46 @@ impl CoreMachine for Box<AsmCoreMachine> {
         self.registers[idx] = value;
     }
 
+    fn fregisters(&self) -> &[u64] {
+        &self.fregisters
+    }
+
+    fn set_fregister(&mut self, idx: usize, value: u64) -> Result<(), Error> {
+        self.fregisters[idx] = value;
+        Ok(())
+    }
+
+    fn fcsr(&self) -> u32 {
+        self.fcsr
+    }
+
+    fn set_fcsr(&mut self, value: u32) -> Result<(), Error> {
+        self.fcsr = value;
+        Ok(())
+    }
+
+    fn vregisters(&self) -> &[u8] {
+        &self.vregisters
+    }
+
+    fn vregisters_mut(&mut self) -> &mut [u8] {
+        &mut self.vregisters
+    }
+
+    fn vl(&self) -> u64 {
+        self.vl
+    }
+
+    fn vtype(&self) -> u64 {
+        self.vtype
+    }
+
+    fn set_vl(&mut self, vl: u64, vtype: u64) -> Result<(), Error> {
+        self.vl = vl;
+        self.vtype = vtype;
+        Ok(())
+    }
+
     fn isa(&self) -> u8 {
         self.isa
     }
@@ -178,6 +222,16 @@ fn check_memory_executable(
     Ok(())
 }
 
+// Marks the bytes written from Rust as defined when memcheck is enabled,
+// stores of the assembly code are marked by AsmMachine::run_single_step.
+fn memcheck_mark(machine: &mut Box<AsmCoreMachine>, addr: u64, size: u64) {
+    if machine.memcheck_ptr != 0 {
+        let shadow =
+            machine.cast_ptr_to_slice_mut(machine.memcheck_ptr, 0, machine.memcheck_size());
+        mark_defined(shadow, addr, size);
+    }
+}
+
 // check whether a memory address is initialized, `size` should be 1, 2, 4 or 8
 fn check_memory_inited(
     machine: &mut Box<AsmCoreMachine>,
@@ -368,6 +422,12 @@ impl Memory for Box<AsmCoreMachine> {
         self.load_reservation_address = u64::MAX;
         self.last_read_frame = u64::max_value();
         self.last_write_page = u64::max_value();
+        self.stack_guard_start = 0;
+        self.stack_guard_end = 0;
+        if self.memcheck_ptr != 0 {
+            let slice = self.cast_ptr_to_slice_mut(self.memcheck_ptr, 0, self.memcheck_size());
+            memset(slice, 0);
+        }
         Ok(())
     }
 
@@ -420,6 +480,7 @@ impl Memory for Box<AsmCoreMachine> {
             self.set_flag(page, flags)?;
             current_addr += RISCV_PAGESIZE as u64;
         }
+        memcheck_mark(self, addr, size);
         // Clear last read/write page cache
         self.last_read_frame = u64::max_value();
         self.last_write_page = u64::max_value();
@@ -485,6 +546,7 @@ impl Memory for Box<AsmCoreMachine> {
         }
         let slice = self.cast_ptr_to_slice_mut(self.memory_ptr, addr as usize, value.len());
         slice.copy_from_slice(value);
+        memcheck_mark(self, addr, value.len() as u64);
         Ok(())
     }
 
@@ -501,6 +563,7 @@ impl Memory for Box<AsmCoreMachine> {
         }
         let slice = self.cast_ptr_to_slice_mut(self.memory_ptr, addr as usize, size as usize);
         memset(slice, value);
+        memcheck_mark(self, addr, size);
         Ok(())
     }
 
@@ -522,12 +585,14 @@ impl Memory for Box<AsmCoreMachine> {
     }
 
     fn execute_load16(&mut self, addr: u64) -> Result<u16, Error> {
+        check_stack_guard(self.stack_guard(), addr, 2)?;
         check_memory_executable(self, addr, 2)?;
         let slice = self.cast_ptr_to_slice(self.memory_ptr, addr as usize, 2);
         Ok(LittleEndian::read_u16(slice))
     }
 
     fn execute_load32(&mut self, addr: u64) -> Result<u32, Error> {
+        check_stack_guard(self.stack_guard(), addr, 4)?;
         check_memory_executable(self, addr, 4)?;
         let slice = self.cast_ptr_to_slice(self.memory_ptr, addr as usize, 4);
         Ok(LittleEndian::read_u32(slice))
@@ -535,6 +600,7 @@ impl Memory for Box<AsmCoreMachine> {
 
     fn load8(&mut self, addr: &u64) -> Result<u64, Error> {
         let addr = *addr;
+        check_stack_guard(self.stack_guard(), addr, 1)?;
         check_memory_inited(self, addr, 1)?;
         let slice = self.cast_ptr_to_slice(self.memory_ptr, addr as usize, 1);
         Ok(u64::from(slice[0]))
@@ -542,6 +608,7 @@ impl Memory for Box<AsmCoreMachine> {
 
     fn load16(&mut self, addr: &u64) -> Result<u64, Error> {
         let addr = *addr;
+        check_stack_guard(self.stack_guard(), addr, 2)?;
         check_memory_inited(self, addr, 2)?;
         let slice = self.cast_ptr_to_slice(self.memory_ptr, addr as usize, 2);
         Ok(u64::from(LittleEndian::read_u16(slice)))
@@ -549,6 +616,7 @@ impl Memory for Box<AsmCoreMachine> {
 
     fn load32(&mut self, addr: &u64) -> Result<u64, Error> {
         let addr = *addr;
+        check_stack_guard(self.stack_guard(), addr, 4)?;
         check_memory_inited(self, addr, 4)?;
         let slice = self.cast_ptr_to_slice(self.memory_ptr, addr as usize, 4);
         Ok(u64::from(LittleEndian::read_u32(slice)))
@@ -556,6 +624,7 @@ impl Memory for Box<AsmCoreMachine> {
 
     fn load64(&mut self, addr: &u64) -> Result<u64, Error> {
         let addr = *addr;
+        check_stack_guard(self.stack_guard(), addr, 8)?;
         check_memory_inited(self, addr, 8)?;
         let slice = self.cast_ptr_to_slice(self.memory_ptr, addr as usize, 8);
         Ok(LittleEndian::read_u64(slice))
@@ -563,33 +632,41 @@ impl Memory for Box<AsmCoreMachine> {
 
     fn store8(&mut self, addr: &u64, value: &u64) -> Result<(), Error> {
         let addr = *addr;
+        check_stack_guard(self.stack_guard(), addr, 1)?;
         check_memory_writable(self, addr, 1)?;
         let slice = self.cast_ptr_to_slice_mut(self.memory_ptr, addr as usize, 1);
         slice[0] = *value as u8;
+        memcheck_mark(self, addr, 1);
         Ok(())
     }
 
     fn store16(&mut self, addr: &u64, value: &u64) -> Result<(), Error> {
         let addr = *addr;
+        check_stack_guard(self.stack_guard(), addr, 2)?;
         check_memory_writable(self, addr, 2)?;
         let slice = self.cast_ptr_to_slice_mut(self.memory_ptr, addr as usize, 2);
         LittleEndian::write_u16(slice, *value as u16);
+        memcheck_mark(self, addr, 2);
         Ok(())
     }
 
     fn store32(&mut self, addr: &u64, value: &u64) -> Result<(), Error> {
         let addr = *addr;
+        check_stack_guard(self.stack_guard(), addr, 4)?;
         check_memory_writable(self, addr, 4)?;
         let slice = self.cast_ptr_to_slice_mut(self.memory_ptr, addr as usize, 4);
         LittleEndian::write_u32(slice, *value as u32);
+        memcheck_mark(self, addr, 4);
         Ok(())
     }
 
     fn store64(&mut self, addr: &u64, value: &u64) -> Result<(), Error> {
         let addr = *addr;
+        check_stack_guard(self.stack_guard(), addr, 8)?;
         check_memory_writable(self, addr, 8)?;
         let slice = self.cast_ptr_to_slice_mut(self.memory_ptr, addr as usize, 8);
         LittleEndian::write_u64(slice, *value as u64);
+        memcheck_mark(self, addr, 8);
         Ok(())
     }
 
@@ -600,6 +677,16 @@ impl Memory for Box<AsmCoreMachine> {
     fn set_lr(&mut self, value: &Self::REG) {
         self.load_reservation_address = *value;
     }
+
+    fn set_stack_guard(&mut self, start: u64, end: u64) -> Result<(), Error> {
+        self.stack_guard_start = start;
+        self.stack_guard_end = end;
+        Ok(())
+    }
+
+    fn stack_guard(&self) -> (u64, u64) {
+        (self.stack_guard_start, self.stack_guard_end)
+    }
 }
 
 impl SupportMachine for Box<AsmCoreMachine> {
@@ -621,6 +708,11 @@ impl SupportMachine for Box<AsmCoreMachine> {
 
     fn reset(&mut self, max_cycles: u64) -> Result<(), Error> {
         self.registers = [0; RISCV_GENERAL_REGISTER_NUMBER];
+        self.fregisters = [0; RISCV_FLOAT_REGISTER_NUMBER];
+        self.fcsr = 0;
+        self.vregisters = [0; RISCV_VLENB * RISCV_VECTOR_REGISTER_NUMBER];
+        self.vl = 0;
+        self.vtype = RISCV_VTYPE_VILL;
         self.pc = 0;
         self.cycles = 0;
         self.max_cycles = max_cycles;
@@ -657,11 +749,25 @@ extern "C" {
 
 pub struct AsmMachine {
     pub machine: DefaultMachine<Box<AsmCoreMachine>>,
+    // Memory accesses happen in assembly code, so watchpoints are kept here
+    // and checked against decoded instructions in single-step mode.
+    watchpoints: Watchpoints,
 }
 
 impl AsmMachine {
     pub fn new(machine: DefaultMachine<Box<AsmCoreMachine>>) -> Self {
-        Self { machine }
+        Self {
+            machine,
+            watchpoints: Watchpoints::default(),
+        }
+    }
+
+    pub fn watchpoints(&self) -> &Watchpoints {
+        &self.watchpoints
+    }
+
+    pub fn watchpoints_mut(&mut self) -> &mut Watchpoints {
+        &mut self.watchpoints
     }
 
     pub fn set_max_cycles(&mut self, cycles: u64) {
@@ -683,7 +789,7 @@ impl AsmMachine {
     }
 
     pub fn run(&mut self) -> Result<i8, Error> {
-        let decoder = build_decoder::<u64>(self.machine.isa(), self.machine.version());
+        let decoder = self.machine.build_decoder();
         let mut decoder = SimpleFixedTraceDecoder::new(decoder);
         self.run_with_decoder(&mut decoder)
     }
@@ -692,6 +798,12 @@ impl AsmMachine {
         if self.machine.isa() & ISA_MOP != 0 && self.machine.version() == VERSION0 {
             return Err(Error::InvalidVersion);
         }
+        if !self.machine.breakpoints().is_empty()
+            || !self.watchpoints.is_empty()
+            || self.machine.inner.memcheck_ptr != 0
+        {
+            return self.run_single_step(decoder);
+        }
         self.machine.set_running(true);
         while self.machine.running() {
             if self.machine.reset_signal() {
@@ -706,29 +818,26 @@ impl AsmMachine {
                 };
                 ckb_vm_x64_execute(&mut **self.machine.inner_mut(), &data as *const _)
             };
+            let traces = (decoder.fixed_traces(), decoder.fixed_trace_size() as usize);
             match result {
-                RET_DECODE_TRACE => decoder.prepare_traces(&mut self.machine)?,
-                RET_ECALL => self.machine.ecall()?,
-                RET_EBREAK => self.machine.ebreak()?,
+                RET_DECODE_TRACE => {
+                    if let Err(e) = decoder.prepare_traces(&mut self.machine) {
+                        return Err(self.report_decode_error(e));
+                    }
+                }
+                RET_ECALL => self.report_block_end_error(|m| m.ecall_at(m.pc().wrapping_sub(4)))?,
+                RET_EBREAK => self.report_block_end_error(|m| m.ebreak())?,
                 RET_DYNAMIC_JUMP => (),
-                RET_MAX_CYCLES_EXCEEDED => return Err(Error::CyclesExceeded),
-                RET_CYCLES_OVERFLOW => return Err(Error::CyclesOverflow),
-                RET_OUT_OF_BOUND => {
-                    return Err(Error::MemOutOfBound(
-                        self.machine.inner.error_arg0,
-                        OutOfBoundKind::Memory,
-                    ))
+                RET_MAX_CYCLES_EXCEEDED => {
+                    return Err(self.report_trace_error(decoder, Error::CyclesExceeded))
                 }
-                RET_INVALID_PERMISSION => {
-                    return Err(Error::MemWriteOnExecutablePage(
-                        self.machine.inner.error_arg0,
-                    ))
+                RET_CYCLES_OVERFLOW => {
+                    return Err(self.report_trace_error(decoder, Error::CyclesOverflow))
                 }
-                RET_SLOWPATH => {
-                    let pc = *self.machine.pc() - 4;
-                    let instruction = decoder.decode(self.machine.memory_mut(), pc)?;
-                    execute_instruction(instruction, &mut self.machine)?;
+                RET_OUT_OF_BOUND | RET_INVALID_PERMISSION | RET_STACK_OVERFLOW => {
+                    return Err(self.report_memory_error(result, traces))
                 }
+                RET_SLOWPATH => self.execute_slowpath()?,
                 RET_PAUSE => {
                     self.machine.pause.free();
                     return Err(Error::Pause);
@@ -739,43 +848,199 @@ impl AsmMachine {
         Ok(self.machine.exit_code())
     }
 
+    // Breakpoints, watchpoints and memcheck cannot be checked inside traces,
+    // so they are checked here before each instruction is stepped.
+    fn run_single_step<D: TraceDecoder>(&mut self, decoder: &mut D) -> Result<i8, Error> {
+        self.machine.set_running(true);
+        while self.machine.running() {
+            if self.machine.pause.has_interrupted() {
+                self.machine.pause.free();
+                return Err(Error::Pause);
+            }
+            if self.machine.reset_signal() {
+                decoder.reset()?;
+            }
+            if !self.machine.breakpoints().is_empty() {
+                self.machine.check_breakpoint()?;
+            }
+            let pc = *self.machine.pc();
+            let memcheck = self.machine.inner.memcheck_ptr != 0;
+            let access = if self.watchpoints.is_empty() && !memcheck {
+                None
+            } else {
+                let instruction = decoder.decode(self.machine.memory_mut(), pc)?;
+                instruction_memory_access(&self.machine, instruction)
+            };
+            if let Some((address, size, kind)) = access.filter(|_| memcheck) {
+                if kind != WatchpointKind::Write {
+                    let inner = &self.machine.inner;
+                    let shadow =
+                        inner.cast_ptr_to_slice(inner.memcheck_ptr, 0, inner.memcheck_size());
+                    if let Some(address) = find_undefined(shadow, address, size) {
+                        return Err(Error::UninitializedRead { pc, address });
+                    }
+                }
+            }
+            self.step(decoder)?;
+            if let Some((address, size, kind)) = access {
+                if memcheck && kind != WatchpointKind::Read {
+                    memcheck_mark(&mut self.machine.inner, address, size);
+                }
+                if let Some(kind) = self.watchpoints.check(address, size, kind) {
+                    return Err(Error::Watchpoint { pc, address, kind });
+                }
+            }
+        }
+        Ok(self.machine.exit_code())
+    }
+
     pub fn step<D: InstDecoder>(&mut self, decoder: &mut D) -> Result<(), Error> {
         // Decode only one instruction into a trace
-        let (trace, _) = decode_fixed_trace(decoder, &mut self.machine, Some(1))?;
+        self.execute_single_trace(decoder, Some(1))
+    }
 
+    /// Runs one trace, which is a basic block or at most TRACE_ITEM_LENGTH
+    /// instructions of it, the same way traces are run by run_with_decoder.
+    pub fn step_trace<D: InstDecoder>(&mut self, decoder: &mut D) -> Result<(), Error> {
+        if self.machine.reset_signal() {
+            decoder.reset_instructions_cache()?;
+        }
+        self.execute_single_trace(decoder, None)
+    }
+
+    fn execute_single_trace<D: InstDecoder>(
+        &mut self,
+        decoder: &mut D,
+        maximum_insts: Option<usize>,
+    ) -> Result<(), Error> {
+        let (trace, _) = decode_fixed_trace(decoder, &mut self.machine, maximum_insts)?;
+        // The pause flag is checked before entering the next trace once a
+        // jump is taken, a raised flag of our own stops the execution there,
+        // so a trace jumping back to itself is not run twice.
+        let mut pause = 1u8;
         let result = unsafe {
             let data = InvokeData {
-                pause: self.machine.pause.get_raw_ptr(),
+                pause: &mut pause as *mut u8,
                 fixed_traces: &trace as *const FixedTrace,
                 fixed_trace_mask: 0,
             };
             ckb_vm_x64_execute(&mut **self.machine.inner_mut(), &data as *const _)
         };
         match result {
-            RET_DECODE_TRACE => (),
-            RET_ECALL => self.machine.ecall()?,
-            RET_EBREAK => self.machine.ebreak()?,
-            RET_MAX_CYCLES_EXCEEDED => return Err(Error::CyclesExceeded),
-            RET_OUT_OF_BOUND => {
-                return Err(Error::MemOutOfBound(
-                    self.machine.inner.error_arg0,
-                    OutOfBoundKind::Memory,
-                ))
+            RET_DECODE_TRACE | RET_PAUSE => (),
+            RET_ECALL => self.report_block_end_error(|m| m.ecall_at(m.pc().wrapping_sub(4)))?,
+            RET_EBREAK => self.report_block_end_error(|m| m.ebreak())?,
+            RET_MAX_CYCLES_EXCEEDED => {
+                return Err(self.report_trace_error(decoder, Error::CyclesExceeded))
             }
-            RET_INVALID_PERMISSION => {
-                return Err(Error::MemWriteOnExecutablePage(
-                    self.machine.inner.error_arg0,
-                ))
+            RET_CYCLES_OVERFLOW => {
+                return Err(self.report_trace_error(decoder, Error::CyclesOverflow))
             }
-            RET_SLOWPATH => {
-                let pc = *self.machine.pc() - 4;
-                let instruction = decoder.decode(self.machine.memory_mut(), pc)?;
-                execute_instruction(instruction, &mut self.machine)?;
+            RET_OUT_OF_BOUND | RET_INVALID_PERMISSION | RET_STACK_OVERFLOW => {
+                return Err(self.report_memory_error(result, (&trace as *const FixedTrace, 1)))
             }
+            RET_SLOWPATH => self.execute_slowpath()?,
             _ => return Err(Error::Asm(result)),
         }
         Ok(())
     }
+
+    fn execute_slowpath(&mut self) -> Result<(), Error> {
+        // The pc already points to the next instruction, a slowpath
+        // instruction may still jump elsewhere, like cm.popret does.
+        let instruction = self.machine.inner.error_arg0;
+        let pc = *self.machine.pc();
+        // Traces charge a vector instruction for a single register, the
+        // other registers it covers are charged here.
+        let charged = self.machine.instruction_cycle_func()(instruction);
+        let cycles = self.machine.instruction_cycles(instruction) - charged;
+        self.machine.update_pc(pc);
+        if let Err(e) = self
+            .machine
+            .add_cycles(cycles)
+            .and_then(|_| execute_instruction(instruction, &mut self.machine))
+        {
+            let pc = pc.wrapping_sub(u64::from(instruction_length(instruction)));
+            return Err(self.machine.record_error(e, pc, Some(instruction)));
+        }
+        self.machine.commit_pc();
+        Ok(())
+    }
+
+    // Ecall and ebreak end a trace, the pc already points past them when
+    // they are run.
+    fn report_block_end_error<F>(&mut self, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut DefaultMachine<Box<AsmCoreMachine>>) -> Result<(), Error>,
+    {
+        f(&mut self.machine).map_err(|e| {
+            let pc = self.machine.pc().wrapping_sub(4);
+            let instruction = self
+                .machine
+                .build_decoder()
+                .decode_raw(self.machine.memory_mut(), pc);
+            self.machine.record_error(e, pc, instruction.ok())
+        })
+    }
+
+    fn report_decode_error(&mut self, error: Error) -> Error {
+        let pc = match error {
+            Error::InvalidInstruction { pc, .. } => pc,
+            _ => *self.machine.pc(),
+        };
+        self.machine.record_error(error, pc, None)
+    }
+
+    // Cycles are charged before a trace is run, the trace at pc is the one
+    // that could not be run.
+    fn report_trace_error<D: InstDecoder>(&mut self, decoder: &mut D, error: Error) -> Error {
+        let pc = *self.machine.pc();
+        let instruction = decoder.decode(self.machine.memory_mut(), pc).ok();
+        self.machine.record_error(error, pc, instruction)
+    }
+
+    fn report_memory_error(&mut self, result: u8, traces: (*const FixedTrace, usize)) -> Error {
+        let error = match result {
+            RET_OUT_OF_BOUND => {
+                Error::MemOutOfBound(self.machine.inner.error_arg0, OutOfBoundKind::Memory)
+            }
+            RET_STACK_OVERFLOW => Error::MemOutOfStack,
+            _ => Error::MemWriteOnExecutablePage(self.machine.inner.error_arg0),
+        };
+        match failed_instruction(traces.0, traces.1, self.machine.inner.error_arg1) {
+            Some((pc, instruction)) => self.machine.record_error(error, pc, Some(instruction)),
+            None => {
+                let pc = *self.machine.pc();
+                self.machine.record_error(error, pc, None)
+            }
+        }
+    }
+}
+
+// Locates the instruction a trace failed on from the INST_ARGS register saved
+// by the assembly code. Instructions and their threads are interleaved in a
+// trace, and INST_ARGS already points past the running instruction.
+fn failed_instruction(
+    traces: *const FixedTrace,
+    trace_count: usize,
+    inst_args: u64,
+) -> Option<(u64, Instruction)> {
+    let trace_size = std::mem::size_of::<FixedTrace>() as u64;
+    let start = traces as u64;
+    let end = start.checked_add(trace_size.checked_mul(trace_count as u64)?)?;
+    if inst_args < start || inst_args >= end {
+        return None;
+    }
+    let trace = unsafe { &*traces.add(((inst_args - start) / trace_size) as usize) };
+    let threads = trace._threads.as_ptr() as u64;
+    let index = ((inst_args.checked_sub(threads)? / 8).checked_sub(3)? / 2) as usize;
+    let mut pc = trace.address;
+    for i in 0..index {
+        let (instruction, _) = trace.thread(i)?;
+        pc = pc.wrapping_add(u64::from(instruction_length(instruction)));
+    }
+    let (instruction, _) = trace.thread(index)?;
+    Some((pc, instruction))
 }
 
 #[cfg(test)]
@@ -9,7 +9,6 @@ use crate::{
     error::Error,
     instructions::{
         blank_instruction, extract_opcode, instruction_length, is_basic_block_end_instruction,
-        is_slowpath_instruction,
     },
     machine::{
         asm::{ckb_vm_asm_labels, AsmCoreMachine},
@@ -30,8 +29,9 @@ pub trait TraceDecoder: InstDecoder {
     fn reset(&mut self) -> Result<(), Error>;
 }
 
+// Slowpath opcodes map to a label that leaves the trace with the instruction
+// in error_arg0, so it can be executed in Rust.
 pub fn label_from_fastpath_opcode(opcode: InstructionOpcode) -> u64 {
-    debug_assert!(!is_slowpath_instruction(blank_instruction(opcode)));
     unsafe {
         u64::from(*(ckb_vm_asm_labels as *const u32).offset(opcode as u8 as isize))
             + (ckb_vm_asm_labels as *const u32 as u64)
@@ -100,6 +100,14 @@ impl<D: InstDecoder> SimpleFixedTraceDecoder<D> {
             self.traces[i] = FixedTrace::default();
         }
     }
+
+    pub fn decoder(&self) -> &D {
+        &self.decoder
+    }
+
+    pub fn decoder_mut(&mut self) -> &mut D {
+        &mut self.decoder
+    }
 }
 
 impl<D: InstDecoder> TraceDecoder for SimpleFixedTraceDecoder<D> {
@@ -154,6 +162,14 @@ impl<D: InstDecoder> MemoizedFixedTraceDecoder<D> {
     pub fn clear_traces(&mut self) {
         self.inner.clear_traces();
     }
+
+    pub fn decoder(&self) -> &D {
+        self.inner.decoder()
+    }
+
+    pub fn decoder_mut(&mut self) -> &mut D {
+        self.inner.decoder_mut()
+    }
 }
 
 impl<D: InstDecoder> TraceDecoder for MemoizedFixedTraceDecoder<D> {
@@ -298,6 +314,14 @@ impl<D: InstDecoder> MemoizedDynamicTraceDecoder<D> {
         self.inner.clear_traces();
     }
 
+    pub fn decoder(&self) -> &D {
+        self.inner.decoder()
+    }
+
+    pub fn decoder_mut(&mut self) -> &mut D {
+        self.inner.decoder_mut()
+    }
+
     fn find_or_build_dynamic_trace(
         &mut self,
         pc: u64,
@@ -1,22 +1,36 @@
 #[cfg(has_asm)]
 pub mod asm;
+pub mod stack;
 pub mod trace;
 
+use std::collections::HashSet;
 use std::fmt::{self, Display};
 use std::sync::atomic::{AtomicU8, Ordering};
 use std::sync::Arc;
 
 use bytes::Bytes;
 
+use super::cost_model::vector_registers;
 use super::debugger::Debugger;
-use super::decoder::{build_decoder, InstDecoder};
-use super::elf::{parse_elf, LoadingAction, ProgramMetadata};
-use super::instructions::{execute, Instruction, Register};
-use super::memory::Memory;
-use super::syscalls::Syscalls;
+use super::decoder::{build_decoder, Decoder, InstDecoder};
+use super::disasm::disassemble_with_custom_names;
+use super::elf::{parse_elf, program_header_table, LoadingAction, ProgramMetadata};
+use super::error::{ErrorReport, MemoryAccess};
+use super::instructions::{
+    custom::CustomInstruction, execute, extract_opcode, insts, Instruction, Register,
+};
+use super::memory::{watchpoint::instruction_memory_access, Memory};
+use super::syscalls::{
+    strace::{SyscallHandledBy, SyscallRecord},
+    SyscallHandler, SyscallInfo, SyscallTable, SyscallTrace, Syscalls,
+};
 use super::{
-    registers::{A0, A7, REGISTER_ABI_NAMES, SP},
-    Error, ISA_MOP, RISCV_GENERAL_REGISTER_NUMBER,
+    registers::{A0, A1, A2, A3, A4, A5, A7, REGISTER_ABI_NAMES, SP},
+    Error, ISA_MOP, RISCV_FLOAT_REGISTER_NUMBER, RISCV_GENERAL_REGISTER_NUMBER, RISCV_PAGESIZE,
+    RISCV_PAGE_SHIFTS, RISCV_VECTOR_REGISTER_NUMBER, RISCV_VLENB, RISCV_VTYPE_VILL,
+};
+use stack::{
+    initialize_process_stack, StackConfig, AT_ENTRY, AT_PAGESZ, AT_PHDR, AT_PHENT, AT_PHNUM,
 };
 
 // Version 0 is the initial launched CKB VM, it is used in CKB Lina mainnet
@@ -29,6 +43,9 @@ pub const VERSION0: u32 = 0;
 // * https://github.com/nervosnetwork/ckb-vm/issues/106
 pub const VERSION1: u32 = 1;
 pub const VERSION2: u32 = 2;
+// Version 3 adds the F, D, V, scalar cryptography and code size reduction
+// extensions, enabled by ISA_F, ISA_D, ISA_V, ISA_K and ISA_ZC.
+pub const VERSION3: u32 = 3;
 
 /// This is the core part of RISC-V that only deals with data part, it
 /// is extracted from Machine so we can handle lifetime logic in dynamic
@@ -44,6 +61,47 @@ pub trait CoreMachine {
     fn memory_mut(&mut self) -> &mut Self::MEM;
     fn registers(&self) -> &[Self::REG];
     fn set_register(&mut self, idx: usize, value: Self::REG);
+    // Floating-point registers and the fcsr register, they are only used by
+    // the F and D extensions. Registers are FLEN(64) bits wide. A machine
+    // not supporting the extensions keeps the defaults, it has no registers,
+    // fails on writes and must not enable ISA_F or ISA_D.
+    fn fregisters(&self) -> &[u64] {
+        &[]
+    }
+    fn set_fregister(&mut self, _idx: usize, _value: u64) -> Result<(), Error> {
+        Err(Error::Unexpected(String::from(
+            "Floating-point registers are not supported by the machine",
+        )))
+    }
+    fn fcsr(&self) -> u32 {
+        0
+    }
+    fn set_fcsr(&mut self, _value: u32) -> Result<(), Error> {
+        Err(Error::Unexpected(String::from(
+            "Floating-point registers are not supported by the machine",
+        )))
+    }
+    // Vector registers, RISCV_VLENB bytes each and laid out one after another
+    // so a register group is a contiguous slice, and the vl and vtype CSRs.
+    // They are only used by the V extension, a machine not supporting it
+    // keeps the defaults, fails on set_vl and must not enable ISA_V.
+    fn vregisters(&self) -> &[u8] {
+        &[]
+    }
+    fn vregisters_mut(&mut self) -> &mut [u8] {
+        &mut []
+    }
+    fn vl(&self) -> u64 {
+        0
+    }
+    fn vtype(&self) -> u64 {
+        RISCV_VTYPE_VILL
+    }
+    fn set_vl(&mut self, _vl: u64, _vtype: u64) -> Result<(), Error> {
+        Err(Error::Unexpected(String::from(
+            "Vector registers are not supported by the machine",
+        )))
+    }
 
     // Current running machine version, used to support compatible behavior
     // in case of bug fixes.
@@ -56,6 +114,12 @@ pub trait CoreMachine {
 pub trait Machine: CoreMachine {
     fn ecall(&mut self) -> Result<(), Error>;
     fn ebreak(&mut self) -> Result<(), Error>;
+
+    // Executes an instruction decoded by a custom instruction factory, only
+    // machines supporting custom instructions need to implement it.
+    fn custom(&mut self, inst: Instruction) -> Result<(), Error> {
+        Err(Error::InvalidOp(extract_opcode(inst)))
+    }
 }
 
 /// This traits extend on top of CoreMachine by adding additional support
@@ -256,9 +320,13 @@ pub trait SupportMachine: CoreMachine {
     fn code(&self) -> &Bytes;
 }
 
-#[derive(Default)]
 pub struct DefaultCoreMachine<R, M> {
     registers: [R; RISCV_GENERAL_REGISTER_NUMBER],
+    fregisters: [u64; RISCV_FLOAT_REGISTER_NUMBER],
+    fcsr: u32,
+    vregisters: [u8; RISCV_VLENB * RISCV_VECTOR_REGISTER_NUMBER],
+    vl: u64,
+    vtype: u64,
     pc: R,
     next_pc: R,
     reset_signal: bool,
@@ -303,6 +371,46 @@ impl<R: Register, M: Memory<REG = R>> CoreMachine for DefaultCoreMachine<R, M> {
         self.registers[idx] = value;
     }
 
+    fn fregisters(&self) -> &[u64] {
+        &self.fregisters
+    }
+
+    fn set_fregister(&mut self, idx: usize, value: u64) -> Result<(), Error> {
+        self.fregisters[idx] = value;
+        Ok(())
+    }
+
+    fn fcsr(&self) -> u32 {
+        self.fcsr
+    }
+
+    fn set_fcsr(&mut self, value: u32) -> Result<(), Error> {
+        self.fcsr = value;
+        Ok(())
+    }
+
+    fn vregisters(&self) -> &[u8] {
+        &self.vregisters
+    }
+
+    fn vregisters_mut(&mut self) -> &mut [u8] {
+        &mut self.vregisters
+    }
+
+    fn vl(&self) -> u64 {
+        self.vl
+    }
+
+    fn vtype(&self) -> u64 {
+        self.vtype
+    }
+
+    fn set_vl(&mut self, vl: u64, vtype: u64) -> Result<(), Error> {
+        self.vl = vl;
+        self.vtype = vtype;
+        Ok(())
+    }
+
     fn isa(&self) -> u8 {
         self.isa
     }
@@ -331,6 +439,11 @@ impl<R: Register, M: Memory<REG = R>> SupportMachine for DefaultCoreMachine<R, M
 
     fn reset(&mut self, max_cycles: u64) -> Result<(), Error> {
         self.registers = Default::default();
+        self.fregisters = Default::default();
+        self.fcsr = 0;
+        self.vregisters = [0; RISCV_VLENB * RISCV_VECTOR_REGISTER_NUMBER];
+        self.vl = 0;
+        self.vtype = RISCV_VTYPE_VILL;
         self.pc = Default::default();
         self.memory.reset_memory()?;
         self.cycles = 0;
@@ -368,6 +481,13 @@ impl<R: Register, M: Memory<REG = R>> SupportMachine for DefaultCoreMachine<R, M
     }
 }
 
+// The vector registers are too large to derive Default.
+impl<R: Register, M: Memory + Default> Default for DefaultCoreMachine<R, M> {
+    fn default() -> Self {
+        Self::new(0, 0, 0)
+    }
+}
+
 impl<R: Register, M: Memory + Default> DefaultCoreMachine<R, M> {
     pub fn new(isa: u8, version: u32, max_cycles: u64) -> Self {
         Self::new_with_memory(isa, version, max_cycles, M::default())
@@ -378,6 +498,11 @@ impl<R: Register, M: Memory> DefaultCoreMachine<R, M> {
     pub fn new_with_memory(isa: u8, version: u32, max_cycles: u64, memory: M) -> Self {
         Self {
             registers: Default::default(),
+            fregisters: Default::default(),
+            fcsr: 0,
+            vregisters: [0; RISCV_VLENB * RISCV_VECTOR_REGISTER_NUMBER],
+            vl: 0,
+            vtype: RISCV_VTYPE_VILL,
             pc: Default::default(),
             next_pc: Default::default(),
             reset_signal: Default::default(),
@@ -413,8 +538,21 @@ pub struct DefaultMachine<Inner> {
     // we can change to static dispatch.
     instruction_cycle_func: Box<InstructionCycleFunc>,
     debugger: Option<Box<dyn Debugger<Inner>>>,
+    syscall_table: SyscallTable<Inner>,
     syscalls: Vec<Box<dyn Syscalls<Inner>>>,
+    syscall_trace: Option<Box<SyscallTrace>>,
+    custom_instructions: Vec<CustomInstruction<Inner>>,
+    stack: StackConfig,
     exit_code: i8,
+    // Reports of failed instructions are opt-in, as building one copies the
+    // register file.
+    record_error_reports: bool,
+    error_report: Option<ErrorReport>,
+
+    breakpoints: HashSet<u64>,
+    // PC of the last breakpoint hit, so resuming from it runs the
+    // instruction instead of stopping at it again.
+    resumed_breakpoint: Option<u64>,
 }
 
 impl<Inner: CoreMachine> CoreMachine for DefaultMachine<Inner> {
@@ -449,6 +587,42 @@ impl<Inner: CoreMachine> CoreMachine for DefaultMachine<Inner> {
         self.inner.set_register(idx, value)
     }
 
+    fn fregisters(&self) -> &[u64] {
+        self.inner.fregisters()
+    }
+
+    fn set_fregister(&mut self, idx: usize, value: u64) -> Result<(), Error> {
+        self.inner.set_fregister(idx, value)
+    }
+
+    fn fcsr(&self) -> u32 {
+        self.inner.fcsr()
+    }
+
+    fn set_fcsr(&mut self, value: u32) -> Result<(), Error> {
+        self.inner.set_fcsr(value)
+    }
+
+    fn vregisters(&self) -> &[u8] {
+        self.inner.vregisters()
+    }
+
+    fn vregisters_mut(&mut self) -> &mut [u8] {
+        self.inner.vregisters_mut()
+    }
+
+    fn vl(&self) -> u64 {
+        self.inner.vl()
+    }
+
+    fn vtype(&self) -> u64 {
+        self.inner.vtype()
+    }
+
+    fn set_vl(&mut self, vl: u64, vtype: u64) -> Result<(), Error> {
+        self.inner.set_vl(vl, vtype)
+    }
+
     fn isa(&self) -> u8 {
         self.inner.isa()
     }
@@ -499,27 +673,8 @@ impl<Inner: SupportMachine> SupportMachine for DefaultMachine<Inner> {
 
 impl<Inner: SupportMachine> Machine for DefaultMachine<Inner> {
     fn ecall(&mut self) -> Result<(), Error> {
-        let code = self.registers()[A7].to_u64();
-        match code {
-            93 => {
-                // exit
-                self.exit_code = self.registers()[A0].to_i8();
-                self.set_running(false);
-                Ok(())
-            }
-            _ => {
-                for syscall in &mut self.syscalls {
-                    let processed = syscall.ecall(&mut self.inner)?;
-                    if processed {
-                        if self.cycles() > self.max_cycles() {
-                            return Err(Error::CyclesExceeded);
-                        }
-                        return Ok(());
-                    }
-                }
-                Err(Error::InvalidEcall(code))
-            }
-        }
+        let pc = self.pc().to_u64();
+        self.ecall_at(pc)
     }
 
     fn ebreak(&mut self) -> Result<(), Error> {
@@ -531,6 +686,14 @@ impl<Inner: SupportMachine> Machine for DefaultMachine<Inner> {
             Ok(())
         }
     }
+
+    fn custom(&mut self, inst: Instruction) -> Result<(), Error> {
+        let op = extract_opcode(inst);
+        match self.custom_instructions.get((op >> 8) as usize) {
+            Some(custom) => custom.execute(&mut self.inner, inst),
+            None => Err(Error::InvalidOp(op)),
+        }
+    }
 }
 
 impl<Inner: CoreMachine> Display for DefaultMachine<Inner> {
@@ -551,7 +714,7 @@ impl<Inner: CoreMachine> Display for DefaultMachine<Inner> {
 impl<Inner: SupportMachine> DefaultMachine<Inner> {
     pub fn load_program(&mut self, program: &Bytes, args: &[Bytes]) -> Result<u64, Error> {
         let elf_bytes = self.load_elf(program, true)?;
-        let stack_bytes = self.initialize(args)?;
+        let stack_bytes = self.initialize(program, args)?;
         let bytes = elf_bytes.checked_add(stack_bytes).ok_or_else(|| {
             Error::Unexpected(String::from(
                 "The bytes count overflowed on loading program",
@@ -567,7 +730,7 @@ impl<Inner: SupportMachine> DefaultMachine<Inner> {
         args: &[Bytes],
     ) -> Result<u64, Error> {
         let elf_bytes = self.load_binary(program, metadata, true)?;
-        let stack_bytes = self.initialize(args)?;
+        let stack_bytes = self.initialize(program, args)?;
         let bytes = elf_bytes.checked_add(stack_bytes).ok_or_else(|| {
             Error::Unexpected(String::from(
                 "The bytes count overflowed on loading program",
@@ -576,17 +739,34 @@ impl<Inner: SupportMachine> DefaultMachine<Inner> {
         Ok(bytes)
     }
 
-    fn initialize(&mut self, args: &[Bytes]) -> Result<u64, Error> {
+    fn initialize(&mut self, program: &Bytes, args: &[Bytes]) -> Result<u64, Error> {
         for syscall in &mut self.syscalls {
             syscall.initialize(&mut self.inner)?;
         }
         if let Some(debugger) = &mut self.debugger {
             debugger.initialize(&mut self.inner)?;
         }
-        let memory_size = self.memory().memory_size();
-        let stack_size = memory_size / 4;
-        let stack_bytes =
-            self.initialize_stack(args, (memory_size - stack_size) as u64, stack_size as u64)?;
+        let memory_size = self.memory().memory_size() as u64;
+        let (stack_start, stack_size) = self.stack.range(memory_size)?;
+        let stack_bytes = if self.stack.is_plain() {
+            self.initialize_stack(args, stack_start, stack_size)?
+        } else {
+            let table = program_header_table(program)?;
+            let auxv = [
+                (AT_PHDR, table.address),
+                (AT_PHENT, table.entry_size),
+                (AT_PHNUM, table.count),
+                (AT_PAGESZ, RISCV_PAGESIZE as u64),
+                (AT_ENTRY, self.pc().to_u64()),
+            ];
+            let config = self.stack.clone();
+            initialize_process_stack(self, args, &config, &auxv, stack_start, stack_size)?
+        };
+        if self.stack.guard_size > 0 {
+            let guard_start = stack_start.saturating_sub(self.stack.guard_size);
+            self.memory_mut()
+                .set_stack_guard(guard_start, stack_start)?;
+        }
         // Make sure SP is 16 byte aligned
         if self.inner.version() >= VERSION1 {
             debug_assert!(self.registers()[SP].to_u64() % 16 == 0);
@@ -594,6 +774,98 @@ impl<Inner: SupportMachine> DefaultMachine<Inner> {
         Ok(stack_bytes)
     }
 
+    // Handles an ecall made at pc, the asm machine has moved past the ecall
+    // when it calls back into the machine.
+    pub(crate) fn ecall_at(&mut self, pc: u64) -> Result<(), Error> {
+        if self.syscall_trace.is_some() {
+            return self.traced_ecall(pc);
+        }
+        self.dispatch_ecall(&mut EcallHandler::Unhandled)
+    }
+
+    // Kept out of ecall, whose frame is on the host stack of every nested
+    // machine.
+    #[inline(never)]
+    fn traced_ecall(&mut self, pc: u64) -> Result<(), Error> {
+        let arguments = [A0, A1, A2, A3, A4, A5].map(|r| self.registers()[r].to_u64());
+        let number = self.registers()[A7].to_u64();
+        let cycles = self.cycles();
+        let mut handler = EcallHandler::Unhandled;
+        let result = self.dispatch_ecall(&mut handler);
+        let table_name = self.syscall_table.info(number).map(|i| i.name.clone());
+        let handled_by = match handler {
+            EcallHandler::Machine => SyscallHandledBy::Machine,
+            EcallHandler::Table => SyscallHandledBy::Table(table_name.clone().unwrap()),
+            EcallHandler::Module(i) => SyscallHandledBy::Module(self.syscalls[i].name().into()),
+            EcallHandler::Unhandled => SyscallHandledBy::Unhandled,
+        };
+        let record = SyscallRecord {
+            pc,
+            number,
+            name: None,
+            arguments,
+            result: self.registers()[A0].to_i64(),
+            cycles: self.cycles().wrapping_sub(cycles),
+            handled_by,
+            error: result.as_ref().err().map(|e| e.to_string()),
+        };
+        let trace = self.syscall_trace.as_mut().unwrap();
+        trace.push(record, table_name.as_deref())?;
+        result
+    }
+
+    // Runs the handler of the ecall, which is told in handler.
+    fn dispatch_ecall(&mut self, handler: &mut EcallHandler) -> Result<(), Error> {
+        let code = self.registers()[A7].to_u64();
+        if code == 93 {
+            // exit
+            *handler = EcallHandler::Machine;
+            self.exit_code = self.registers()[A0].to_i8();
+            self.set_running(false);
+            return Ok(());
+        }
+        if let Some((info, table_handler)) = self.syscall_table.get_mut(code) {
+            *handler = EcallHandler::Table;
+            self.inner.add_cycles(info.cycles)?;
+            table_handler(&mut self.inner)?;
+            return self.finish_syscall();
+        }
+        for (i, syscall) in self.syscalls.iter_mut().enumerate() {
+            *handler = EcallHandler::Module(i);
+            if syscall.ecall(&mut self.inner)? {
+                if let Some(code) = syscall.exit_code() {
+                    self.exit_code = code;
+                }
+                return self.finish_syscall();
+            }
+        }
+        *handler = EcallHandler::Unhandled;
+        Err(Error::InvalidEcall(code))
+    }
+
+    // Checks cycles charged by a syscall.
+    fn finish_syscall(&mut self) -> Result<(), Error> {
+        if self.cycles() > self.max_cycles() {
+            return Err(Error::CyclesExceeded);
+        }
+        Ok(())
+    }
+
+    /// Syscalls bound to numbers with DefaultMachineBuilder::syscall_handler.
+    pub fn syscall_table(&self) -> &SyscallTable<Inner> {
+        &self.syscall_table
+    }
+
+    /// Syscalls recorded since the machine is built, when tracing is enabled
+    /// with DefaultMachineBuilder::syscall_trace.
+    pub fn syscall_trace(&self) -> Option<&SyscallTrace> {
+        self.syscall_trace.as_deref()
+    }
+
+    pub fn syscall_trace_mut(&mut self) -> Option<&mut SyscallTrace> {
+        self.syscall_trace.as_deref_mut()
+    }
+
     pub fn take_inner(self) -> Inner {
         self.inner
     }
@@ -602,6 +874,11 @@ impl<Inner: SupportMachine> DefaultMachine<Inner> {
         self.pause.clone()
     }
 
+    /// Shares pause with other machines, so they can be paused together.
+    pub fn set_pause(&mut self, pause: Pause) {
+        self.pause = pause;
+    }
+
     pub fn exit_code(&self) -> i8 {
         self.exit_code
     }
@@ -610,16 +887,129 @@ impl<Inner: SupportMachine> DefaultMachine<Inner> {
         &self.instruction_cycle_func
     }
 
+    // Cycles charged for an instruction, vector instructions are charged for
+    // every register holding their elements under the current vl and vtype.
+    pub fn instruction_cycles(&self, instruction: Instruction) -> u64 {
+        let registers = vector_registers(instruction, self.vl(), self.vtype());
+        self.instruction_cycle_func()(instruction).saturating_mul(registers)
+    }
+
     pub fn inner_mut(&mut self) -> &mut Inner {
         &mut self.inner
     }
 
+    pub fn add_breakpoint(&mut self, pc: u64) -> bool {
+        self.breakpoints.insert(pc)
+    }
+
+    pub fn remove_breakpoint(&mut self, pc: u64) -> bool {
+        self.breakpoints.remove(&pc)
+    }
+
+    pub fn breakpoints(&self) -> &HashSet<u64> {
+        &self.breakpoints
+    }
+
+    pub fn custom_instructions(&self) -> &[CustomInstruction<Inner>] {
+        &self.custom_instructions
+    }
+
+    // Builds the decoder for the ISA and version of the machine, with the
+    // registered custom instructions.
+    pub fn build_decoder(&self) -> Decoder {
+        let mut decoder = build_decoder::<Inner::REG>(self.isa(), self.version());
+        for custom in &self.custom_instructions {
+            decoder.add_custom_instruction_factory(custom.decode_func());
+        }
+        decoder
+    }
+
+    /// The report of the last failed instruction, only recorded when the
+    /// machine is built with error reports enabled.
+    pub fn error_report(&self) -> Option<&ErrorReport> {
+        self.error_report.as_ref()
+    }
+
+    /// Records the report of an error raised by the instruction at pc and
+    /// returns the error to stop the machine with, this is called by the
+    /// engines running the machine. Accesses to the stack guard are turned
+    /// into Error::StackOverflow. The report is only recorded when error
+    /// reports are enabled, pausing is not a failure and is ignored. The
+    /// memory access is only reported for memory errors, other errors may
+    /// stop an instruction before its access happens.
+    pub fn record_error(
+        &mut self,
+        error: Error,
+        pc: u64,
+        instruction: Option<Instruction>,
+    ) -> Error {
+        let (guard_start, guard_end) = self.memory().stack_guard();
+        let error = match error {
+            Error::MemOutOfStack if guard_start < guard_end => Error::StackOverflow {
+                pc,
+                sp: self.registers()[SP].to_u64(),
+            },
+            error => error,
+        };
+        if !self.record_error_reports || error == Error::Pause || error == Error::Yield {
+            return error;
+        }
+        let memory_error = matches!(
+            error,
+            Error::MemOutOfBound(..)
+                | Error::MemOutOfStack
+                | Error::StackOverflow { .. }
+                | Error::MemPageUnalignedAccess(_)
+                | Error::MemWriteOnExecutablePage(_)
+                | Error::MemWriteOnFreezedPage(_)
+        );
+        let access = instruction
+            .filter(|_| memory_error)
+            .and_then(|i| instruction_memory_access(self, i))
+            .map(|(address, size, kind)| {
+                let page = address >> RISCV_PAGE_SHIFTS;
+                let page_flags = if address < self.memory().memory_size() as u64 {
+                    self.memory_mut().fetch_flag(page).ok()
+                } else {
+                    None
+                };
+                MemoryAccess {
+                    address,
+                    size,
+                    kind,
+                    page_flags,
+                }
+            });
+        self.error_report = Some(ErrorReport {
+            error: error.to_string(),
+            pc,
+            instruction,
+            disassembly: instruction.map(|i| {
+                let names: Vec<&str> = self.custom_instructions.iter().map(|c| c.name()).collect();
+                disassemble_with_custom_names(i, pc, &names).replace('\t', " ")
+            }),
+            registers: self.registers().iter().map(|r| r.to_u64()).collect(),
+            cycles: self.cycles(),
+            access,
+        });
+        error
+    }
+
+    fn check_breakpoint(&mut self) -> Result<(), Error> {
+        let pc = self.pc().to_u64();
+        if self.resumed_breakpoint.take() != Some(pc) && self.breakpoints.contains(&pc) {
+            self.resumed_breakpoint = Some(pc);
+            return Err(Error::Breakpoint(pc));
+        }
+        Ok(())
+    }
+
     // This is the most naive way of running the VM, it only decodes each
     // instruction and run it, no optimization is performed here. It might
     // not be practical in production, but it serves as a baseline and
     // reference implementation
     pub fn run(&mut self) -> Result<i8, Error> {
-        let mut decoder = build_decoder::<Inner::REG>(self.isa(), self.version());
+        let mut decoder = self.build_decoder();
         self.run_with_decoder(&mut decoder)
     }
 
@@ -628,6 +1018,10 @@ impl<Inner: SupportMachine> DefaultMachine<Inner> {
             return Err(Error::InvalidVersion);
         }
         self.set_running(true);
+        // Discard hits from accesses made outside of instructions, such as
+        // loading the program.
+        self.memory_mut().take_watchpoint_hit();
+        self.memory_mut().take_uninitialized_read();
         while self.running() {
             if self.pause.has_interrupted() {
                 self.pause.free();
@@ -636,28 +1030,53 @@ impl<Inner: SupportMachine> DefaultMachine<Inner> {
             if self.reset_signal() {
                 decoder.reset_instructions_cache()?;
             }
+            if !self.breakpoints.is_empty() {
+                self.check_breakpoint()?;
+            }
+            let pc = self.pc().to_u64();
             self.step(decoder)?;
+            if let Some((address, kind)) = self.memory_mut().take_watchpoint_hit() {
+                return Err(Error::Watchpoint { pc, address, kind });
+            }
+            if let Some(address) = self.memory_mut().take_uninitialized_read() {
+                return Err(Error::UninitializedRead { pc, address });
+            }
         }
         Ok(self.exit_code())
     }
 
     pub fn step<D: InstDecoder>(&mut self, decoder: &mut D) -> Result<(), Error> {
-        let instruction = {
-            let pc = self.pc().to_u64();
-            let memory = self.memory_mut();
-            decoder.decode(memory, pc)?
+        let pc = self.pc().to_u64();
+        let instruction = match decoder.decode(self.memory_mut(), pc) {
+            Ok(instruction) => instruction,
+            Err(e) => return Err(self.record_error(e, pc, None)),
         };
-        let cycles = self.instruction_cycle_func()(instruction);
-        self.add_cycles(cycles)?;
-        execute(instruction, self)
+        let cycles = self.instruction_cycles(instruction);
+        self.add_cycles(cycles)
+            .and_then(|_| execute(instruction, self))
+            .map_err(|e| self.record_error(e, pc, Some(instruction)))
     }
 }
 
+// Where dispatch_ecall handled an ecall, names of handlers are only looked
+// up when the ecall is traced.
+enum EcallHandler {
+    Machine,
+    Table,
+    Module(usize),
+    Unhandled,
+}
+
 pub struct DefaultMachineBuilder<Inner> {
     inner: Inner,
     instruction_cycle_func: Box<InstructionCycleFunc>,
     debugger: Option<Box<dyn Debugger<Inner>>>,
+    syscall_table: SyscallTable<Inner>,
     syscalls: Vec<Box<dyn Syscalls<Inner>>>,
+    syscall_trace: Option<Box<SyscallTrace>>,
+    custom_instructions: Vec<CustomInstruction<Inner>>,
+    stack: StackConfig,
+    record_error_reports: bool,
 }
 
 impl<Inner> DefaultMachineBuilder<Inner> {
@@ -666,7 +1085,12 @@ impl<Inner> DefaultMachineBuilder<Inner> {
             inner,
             instruction_cycle_func: Box::new(|_| 0),
             debugger: None,
+            syscall_table: SyscallTable::default(),
             syscalls: vec![],
+            syscall_trace: None,
+            custom_instructions: vec![],
+            stack: StackConfig::default(),
+            record_error_reports: false,
         }
     }
 
@@ -678,24 +1102,101 @@ impl<Inner> DefaultMachineBuilder<Inner> {
         self
     }
 
+    // Syscalls modules are tried in order for numbers not bound by
+    // syscall_handler.
     pub fn syscall(mut self, syscall: Box<dyn Syscalls<Inner>>) -> Self {
         self.syscalls.push(syscall);
         self
     }
 
+    // Binds a handler to a syscall number, info.cycles are charged on each
+    // call. Binding a number twice, or the exit syscall handled by the
+    // machine, is a mistake and panics.
+    pub fn syscall_handler(
+        mut self,
+        number: u64,
+        info: SyscallInfo,
+        handler: Box<SyscallHandler<Inner>>,
+    ) -> Self {
+        assert!(number != 93, "syscall 93 is handled by the machine");
+        if !self.syscall_table.insert(number, info, handler) {
+            panic!("syscall {} is registered twice", number);
+        }
+        self
+    }
+
+    // Records every ecall of the machine, see DefaultMachine::syscall_trace.
+    pub fn syscall_trace(mut self, trace: SyscallTrace) -> Self {
+        self.syscall_trace = Some(Box::new(trace));
+        self
+    }
+
     pub fn debugger(mut self, debugger: Box<dyn Debugger<Inner>>) -> Self {
         self.debugger = Some(debugger);
         self
     }
 
+    // At most MAXIMUM_CUSTOM_INSTRUCTIONS custom instructions can be
+    // registered, the first one decoding an instruction wins.
+    pub fn custom_instruction(mut self, custom_instruction: CustomInstruction<Inner>) -> Self {
+        assert!(
+            self.custom_instructions.len() < insts::MAXIMUM_CUSTOM_INSTRUCTIONS,
+            "too many custom instructions"
+        );
+        self.custom_instructions.push(custom_instruction);
+        self
+    }
+
+    // Location and contents of the stack set up by load_program, the default
+    // is the top quarter of memory holding argc and argv.
+    pub fn stack(mut self, stack: StackConfig) -> Self {
+        self.stack = stack;
+        self
+    }
+
+    // When enabled, the machine keeps an ErrorReport of the last failed
+    // instruction, see DefaultMachine::error_report.
+    pub fn error_report(mut self, enabled: bool) -> Self {
+        self.record_error_reports = enabled;
+        self
+    }
+
     pub fn build(self) -> DefaultMachine<Inner> {
+        let instruction_cycle_func = if self.custom_instructions.is_empty() {
+            self.instruction_cycle_func
+        } else {
+            // Custom instructions carry their own cycles, the trace and asm
+            // machines only see the instruction cycle function.
+            let cycles: Vec<u64> = self
+                .custom_instructions
+                .iter()
+                .map(|c| c.cycles())
+                .collect();
+            let instruction_cycle_func = self.instruction_cycle_func;
+            Box::new(move |inst: Instruction| {
+                let op = extract_opcode(inst);
+                if insts::is_custom_opcode(op) {
+                    cycles[(op >> 8) as usize]
+                } else {
+                    instruction_cycle_func(inst)
+                }
+            })
+        };
         DefaultMachine {
             inner: self.inner,
             pause: Pause::new(),
-            instruction_cycle_func: self.instruction_cycle_func,
+            instruction_cycle_func,
             debugger: self.debugger,
+            syscall_table: self.syscall_table,
             syscalls: self.syscalls,
+            syscall_trace: self.syscall_trace,
+            custom_instructions: self.custom_instructions,
+            stack: self.stack,
             exit_code: 0,
+            record_error_reports: self.record_error_reports,
+            error_report: None,
+            breakpoints: HashSet::default(),
+            resumed_breakpoint: None,
         }
     }
 }
@@ -0,0 +1,149 @@
+// Stack layout of a program. By default the stack takes the top quarter of
+// memory and only holds argc and argv, as CKB expects. StackConfig moves or
+// resizes the stack, and sets up the layout Linux gives to a new process so
+// newlib and musl startup code can run unmodified:
+//
+//   +-------------------------+ stack_start + stack_size
+//   | argv and envp strings   |
+//   | AT_RANDOM bytes         |
+//   +-------------------------+
+//   | auxv pairs, AT_NULL     |
+//   | envp pointers, NULL     |
+//   | argv pointers, NULL     |
+//   | argc                    |
+//   +-------------------------+ SP, 16 byte aligned
+use super::SupportMachine;
+use crate::{memory::Memory, registers::SP, Bytes, Error, Register};
+
+pub const AT_NULL: u64 = 0;
+pub const AT_PHDR: u64 = 3;
+pub const AT_PHENT: u64 = 4;
+pub const AT_PHNUM: u64 = 5;
+pub const AT_PAGESZ: u64 = 6;
+pub const AT_ENTRY: u64 = 9;
+pub const AT_RANDOM: u64 = 25;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StackConfig {
+    /// Lowest address of the stack, the stack ends at the top of memory when
+    /// not set.
+    pub start: Option<u64>,
+    /// Size of the stack, a quarter of memory when neither start nor size is
+    /// set, otherwise the rest of memory above start.
+    pub size: Option<u64>,
+    /// Environment variables in the form of KEY=VALUE, pointed to by envp.
+    pub envs: Vec<Bytes>,
+    /// Pushes envp and the auxiliary vector after argv.
+    pub auxv: bool,
+    /// AT_RANDOM points to 16 bytes derived from this seed, programs see the
+    /// same bytes on every run.
+    pub random_seed: u64,
+    /// Size of a guard region right below the stack, any load or store of
+    /// the program inside it stops with Error::StackOverflow. No guard when
+    /// zero.
+    pub guard_size: u64,
+}
+
+impl StackConfig {
+    /// Whether the stack only holds argc and argv, the layout CKB uses.
+    pub fn is_plain(&self) -> bool {
+        self.envs.is_empty() && !self.auxv
+    }
+
+    /// Start and size of the stack in a memory of memory_size bytes.
+    pub fn range(&self, memory_size: u64) -> Result<(u64, u64), Error> {
+        let (start, size) = match (self.start, self.size) {
+            (None, None) => (memory_size - memory_size / 4, memory_size / 4),
+            (None, Some(size)) => (memory_size.wrapping_sub(size), size),
+            (Some(start), None) => (start, memory_size.wrapping_sub(start)),
+            (Some(start), Some(size)) => (start, size),
+        };
+        match start.checked_add(size) {
+            Some(end) if size > 0 && end <= memory_size => Ok((start, size)),
+            _ => Err(Error::MemOutOfStack),
+        }
+    }
+
+    /// The bytes AT_RANDOM points to.
+    pub fn random_bytes(&self) -> [u8; 16] {
+        // splitmix64
+        let mut state = self.random_seed;
+        let mut next = || {
+            state = state.wrapping_add(0x9e3779b97f4a7c15);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            z ^ (z >> 31)
+        };
+        let mut bytes = [0u8; 16];
+        bytes[..8].copy_from_slice(&next().to_le_bytes());
+        bytes[8..].copy_from_slice(&next().to_le_bytes());
+        bytes
+    }
+}
+
+// Writes the Linux process stack, auxv holds the (type, value) pairs besides
+// AT_RANDOM and AT_NULL. Returns the bytes used like initialize_stack.
+pub(crate) fn initialize_process_stack<Mac: SupportMachine>(
+    machine: &mut Mac,
+    args: &[Bytes],
+    config: &StackConfig,
+    auxv: &[(u64, u64)],
+    stack_start: u64,
+    stack_size: u64,
+) -> Result<u64, Error> {
+    let stack_end = stack_start + stack_size;
+    let mut sp = stack_end;
+    let mut reserve = |bytes: u64| -> Result<u64, Error> {
+        sp = sp.checked_sub(bytes).ok_or(Error::MemOutOfStack)?;
+        if sp < stack_start {
+            return Err(Error::MemOutOfStack);
+        }
+        Ok(sp)
+    };
+    let mut push_strings = |machine: &mut Mac, strings: &[Bytes]| -> Result<Vec<u64>, Error> {
+        let mut addresses = vec![];
+        for string in strings {
+            let address = reserve(string.len() as u64 + 1)?;
+            machine.memory_mut().store_bytes(address, string)?;
+            machine
+                .memory_mut()
+                .store_byte(address + string.len() as u64, 1, 0)?;
+            addresses.push(address);
+        }
+        Ok(addresses)
+    };
+    let argv = push_strings(machine, args)?;
+    let envp = push_strings(machine, &config.envs)?;
+    let mut values = vec![args.len() as u64];
+    values.extend(&argv);
+    values.push(0);
+    values.extend(&envp);
+    values.push(0);
+    if config.auxv {
+        let random = reserve(16)?;
+        machine
+            .memory_mut()
+            .store_bytes(random, &config.random_bytes())?;
+        for (kind, value) in auxv {
+            values.push(*kind);
+            values.push(*value);
+        }
+        values.extend([AT_RANDOM, random, AT_NULL, 0]);
+    }
+    let word_size = u64::from(Mac::REG::BITS / 8);
+    let unaligned_sp = reserve(word_size * values.len() as u64)?;
+    let aligned_sp = unaligned_sp & !15;
+    reserve(unaligned_sp - aligned_sp)?;
+    for (i, value) in values.iter().enumerate() {
+        let address = Mac::REG::from_u64(aligned_sp + word_size * i as u64);
+        let value = Mac::REG::from_u64(*value);
+        if Mac::REG::BITS == 64 {
+            machine.memory_mut().store64(&address, &value)?;
+        } else {
+            machine.memory_mut().store32(&address, &value)?;
+        }
+    }
+    machine.set_register(SP, Mac::REG::from_u64(aligned_sp));
+    Ok(stack_end - aligned_sp)
+}
@@ -1,11 +1,12 @@
 use super::{
     super::{
-        decoder::{build_decoder, InstDecoder},
+        decoder::InstDecoder,
         elf::ProgramMetadata,
         instructions::{
             execute_with_thread, extract_opcode, handle_invalid_op, instruction_length,
             is_basic_block_end_instruction, Instruction, Register, Thread, ThreadFactory,
         },
+        memory::Memory,
         Error,
     },
     CoreMachine, DefaultMachine, Machine, SupportMachine,
@@ -85,6 +86,42 @@ impl<Inner: SupportMachine> CoreMachine for TraceMachine<Inner> {
         self.machine.set_register(idx, value)
     }
 
+    fn fregisters(&self) -> &[u64] {
+        self.machine.fregisters()
+    }
+
+    fn set_fregister(&mut self, idx: usize, value: u64) -> Result<(), Error> {
+        self.machine.set_fregister(idx, value)
+    }
+
+    fn fcsr(&self) -> u32 {
+        self.machine.fcsr()
+    }
+
+    fn set_fcsr(&mut self, value: u32) -> Result<(), Error> {
+        self.machine.set_fcsr(value)
+    }
+
+    fn vregisters(&self) -> &[u8] {
+        self.machine.vregisters()
+    }
+
+    fn vregisters_mut(&mut self) -> &mut [u8] {
+        self.machine.vregisters_mut()
+    }
+
+    fn vl(&self) -> u64 {
+        self.machine.vl()
+    }
+
+    fn vtype(&self) -> u64 {
+        self.machine.vtype()
+    }
+
+    fn set_vl(&mut self, vl: u64, vtype: u64) -> Result<(), Error> {
+        self.machine.set_vl(vl, vtype)
+    }
+
     fn isa(&self) -> u8 {
         self.machine.isa()
     }
@@ -102,6 +139,10 @@ impl<Inner: SupportMachine> Machine for TraceMachine<Inner> {
     fn ebreak(&mut self) -> Result<(), Error> {
         self.machine.ebreak()
     }
+
+    fn custom(&mut self, inst: Instruction) -> Result<(), Error> {
+        self.machine.custom(inst)
+    }
 }
 
 impl<Inner: SupportMachine> TraceMachine<Inner> {
@@ -128,60 +169,87 @@ impl<Inner: SupportMachine> TraceMachine<Inner> {
     }
 
     pub fn run(&mut self) -> Result<i8, Error> {
-        let mut decoder = build_decoder::<Inner::REG>(self.isa(), self.version());
+        let mut decoder = self.machine.build_decoder();
         self.run_with_decoder(&mut decoder)
     }
 
     pub fn run_with_decoder<D: InstDecoder>(&mut self, decoder: &mut D) -> Result<i8, Error> {
         self.machine.set_running(true);
-        // For current trace size this is acceptable, however we might want
-        // to tweak the code here if we choose to use a larger trace size or
-        // larger trace item length.
-        self.traces.resize_with(TRACE_SIZE, Trace::default);
+        // Discard hits from accesses made outside of instructions, such as
+        // loading the program.
+        self.machine.memory_mut().take_watchpoint_hit();
+        self.machine.memory_mut().take_uninitialized_read();
         while self.machine.running() {
             if self.machine.pause.has_interrupted() {
                 self.machine.pause.free();
                 return Err(Error::Pause);
             }
-            if self.machine.reset_signal() {
-                decoder.reset_instructions_cache()?;
-                for i in self.traces.iter_mut() {
-                    *i = Trace::default()
-                }
+            self.step_trace(decoder)?;
+        }
+        Ok(self.machine.exit_code())
+    }
+
+    /// Runs one trace, which is a basic block or at most TRACE_ITEM_LENGTH
+    /// instructions of it.
+    pub fn step_trace<D: InstDecoder>(&mut self, decoder: &mut D) -> Result<(), Error> {
+        // For current trace size this is acceptable, however we might want
+        // to tweak the code here if we choose to use a larger trace size or
+        // larger trace item length.
+        if self.traces.is_empty() {
+            self.traces.resize_with(TRACE_SIZE, Trace::default);
+        }
+        if self.machine.reset_signal() {
+            decoder.reset_instructions_cache()?;
+            for i in self.traces.iter_mut() {
+                *i = Trace::default()
             }
-            let pc = self.machine.pc().to_u64();
-            let slot = calculate_slot(pc);
-            if pc != self.traces[slot].address || self.traces[slot].instruction_count == 0 {
-                self.traces[slot] = Trace::default();
-                let mut current_pc = pc;
-                let mut i = 0;
-                while i < TRACE_ITEM_LENGTH {
-                    let instruction = decoder.decode(self.machine.memory_mut(), current_pc)?;
-                    let end_instruction = is_basic_block_end_instruction(instruction);
-                    current_pc += u64::from(instruction_length(instruction));
-                    self.traces[slot].instructions[i] = instruction;
-                    self.traces[slot].threads[i] = self.factory[extract_opcode(instruction)];
-                    i += 1;
-                    if end_instruction {
-                        break;
-                    }
+        }
+        let pc = self.machine.pc().to_u64();
+        let slot = calculate_slot(pc);
+        if pc != self.traces[slot].address || self.traces[slot].instruction_count == 0 {
+            self.traces[slot] = Trace::default();
+            let mut current_pc = pc;
+            let mut i = 0;
+            while i < TRACE_ITEM_LENGTH {
+                let instruction = match decoder.decode(self.machine.memory_mut(), current_pc) {
+                    Ok(instruction) => instruction,
+                    Err(e) => return Err(self.machine.record_error(e, current_pc, None)),
+                };
+                let end_instruction = is_basic_block_end_instruction(instruction);
+                current_pc += u64::from(instruction_length(instruction));
+                self.traces[slot].instructions[i] = instruction;
+                self.traces[slot].threads[i] = self.factory[extract_opcode(instruction)];
+                i += 1;
+                if end_instruction {
+                    break;
                 }
-                self.traces[slot].address = pc;
-                self.traces[slot].length = (current_pc - pc) as usize;
-                self.traces[slot].instruction_count = i as u8;
             }
-            for i in 0..self.traces[slot].instruction_count {
-                let inst = self.traces[slot].instructions[i as usize];
-                let cycles = self.machine.instruction_cycle_func()(inst);
-                self.machine.add_cycles(cycles)?;
+            self.traces[slot].address = pc;
+            self.traces[slot].length = (current_pc - pc) as usize;
+            self.traces[slot].instruction_count = i as u8;
+        }
+        for i in 0..self.traces[slot].instruction_count {
+            let inst = self.traces[slot].instructions[i as usize];
+            let pc = self.machine.pc().to_u64();
+            let cycles = self.machine.instruction_cycles(inst);
+            let result = self.machine.add_cycles(cycles).and_then(|_| {
                 execute_with_thread(
                     inst,
                     &mut self.machine,
                     &self.traces[slot].threads[i as usize],
-                )?;
+                )
+            });
+            if let Err(e) = result {
+                return Err(self.machine.record_error(e, pc, Some(inst)));
+            }
+            if let Some((address, kind)) = self.machine.memory_mut().take_watchpoint_hit() {
+                return Err(Error::Watchpoint { pc, address, kind });
+            }
+            if let Some(address) = self.machine.memory_mut().take_uninitialized_read() {
+                return Err(Error::UninitializedRead { pc, address });
             }
         }
-        Ok(self.machine.exit_code())
+        Ok(())
     }
 }
 
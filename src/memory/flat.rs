@@ -1,7 +1,10 @@
 use super::super::{
     error::OutOfBoundKind, Error, Register, DEFAULT_MEMORY_SIZE, RISCV_PAGESIZE, RISCV_PAGE_SHIFTS,
 };
-use super::{check_no_overflow, fill_page_data, get_page_indices, memset, set_dirty, Memory};
+use super::{
+    check_no_overflow, check_stack_guard, fill_page_data, get_page_indices, memset, set_dirty,
+    Memory,
+};
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use bytes::Bytes;
@@ -15,6 +18,7 @@ pub struct FlatMemory<R> {
     memory_size: usize,
     riscv_pages: usize,
     load_reservation_address: R,
+    stack_guard: (u64, u64),
     _inner: PhantomData<R>,
 }
 
@@ -41,6 +45,7 @@ impl<R: Register> FlatMemory<R> {
             memory_size,
             riscv_pages: memory_size / RISCV_PAGESIZE,
             load_reservation_address: R::from_u64(u64::MAX),
+            stack_guard: (0, 0),
             _inner: PhantomData,
         }
     }
@@ -61,6 +66,7 @@ impl<R: Register> Memory for FlatMemory<R> {
         memset(&mut self.data, 0);
         memset(&mut self.flags, 0);
         self.load_reservation_address = R::from_u64(u64::MAX);
+        self.stack_guard = (0, 0);
         Ok(())
     }
 
@@ -124,6 +130,7 @@ impl<R: Register> Memory for FlatMemory<R> {
 
     fn load8(&mut self, addr: &Self::REG) -> Result<Self::REG, Error> {
         let addr = addr.to_u64();
+        check_stack_guard(self.stack_guard, addr, 1)?;
         check_no_overflow(addr, 1, self.memory_size as u64)?;
         let mut reader = Cursor::new(&self.data);
         reader.seek(SeekFrom::Start(addr as u64))?;
@@ -133,6 +140,7 @@ impl<R: Register> Memory for FlatMemory<R> {
 
     fn load16(&mut self, addr: &Self::REG) -> Result<Self::REG, Error> {
         let addr = addr.to_u64();
+        check_stack_guard(self.stack_guard, addr, 2)?;
         check_no_overflow(addr, 2, self.memory_size as u64)?;
         let mut reader = Cursor::new(&self.data);
         reader.seek(SeekFrom::Start(addr as u64))?;
@@ -143,6 +151,7 @@ impl<R: Register> Memory for FlatMemory<R> {
 
     fn load32(&mut self, addr: &Self::REG) -> Result<Self::REG, Error> {
         let addr = addr.to_u64();
+        check_stack_guard(self.stack_guard, addr, 4)?;
         check_no_overflow(addr, 4, self.memory_size as u64)?;
         let mut reader = Cursor::new(&self.data);
         reader.seek(SeekFrom::Start(addr as u64))?;
@@ -153,6 +162,7 @@ impl<R: Register> Memory for FlatMemory<R> {
 
     fn load64(&mut self, addr: &Self::REG) -> Result<Self::REG, Error> {
         let addr = addr.to_u64();
+        check_stack_guard(self.stack_guard, addr, 8)?;
         check_no_overflow(addr, 8, self.memory_size as u64)?;
         let mut reader = Cursor::new(&self.data);
         reader.seek(SeekFrom::Start(addr as u64))?;
@@ -163,6 +173,7 @@ impl<R: Register> Memory for FlatMemory<R> {
 
     fn store8(&mut self, addr: &Self::REG, value: &Self::REG) -> Result<(), Error> {
         let addr = addr.to_u64();
+        check_stack_guard(self.stack_guard, addr, 1)?;
         check_no_overflow(addr, 1, self.memory_size as u64)?;
         let page_indices = get_page_indices(addr, 1);
         set_dirty(self, &page_indices)?;
@@ -174,6 +185,7 @@ impl<R: Register> Memory for FlatMemory<R> {
 
     fn store16(&mut self, addr: &Self::REG, value: &Self::REG) -> Result<(), Error> {
         let addr = addr.to_u64();
+        check_stack_guard(self.stack_guard, addr, 2)?;
         check_no_overflow(addr, 2, self.memory_size as u64)?;
         let page_indices = get_page_indices(addr, 2);
         set_dirty(self, &page_indices)?;
@@ -185,6 +197,7 @@ impl<R: Register> Memory for FlatMemory<R> {
 
     fn store32(&mut self, addr: &Self::REG, value: &Self::REG) -> Result<(), Error> {
         let addr = addr.to_u64();
+        check_stack_guard(self.stack_guard, addr, 4)?;
         check_no_overflow(addr, 4, self.memory_size as u64)?;
         let page_indices = get_page_indices(addr, 4);
         set_dirty(self, &page_indices)?;
@@ -196,6 +209,7 @@ impl<R: Register> Memory for FlatMemory<R> {
 
     fn store64(&mut self, addr: &Self::REG, value: &Self::REG) -> Result<(), Error> {
         let addr = addr.to_u64();
+        check_stack_guard(self.stack_guard, addr, 8)?;
         check_no_overflow(addr, 8, self.memory_size as u64)?;
         let page_indices = get_page_indices(addr, 8);
         set_dirty(self, &page_indices)?;
@@ -246,4 +260,13 @@ impl<R: Register> Memory for FlatMemory<R> {
     fn set_lr(&mut self, value: &Self::REG) {
         self.load_reservation_address = value.clone();
     }
+
+    fn set_stack_guard(&mut self, start: u64, end: u64) -> Result<(), Error> {
+        self.stack_guard = (start, end);
+        Ok(())
+    }
+
+    fn stack_guard(&self) -> (u64, u64) {
+        self.stack_guard
+    }
 }
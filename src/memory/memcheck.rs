@@ -0,0 +1,208 @@
+use super::super::{error::WatchpointKind, Error, Register};
+use super::Memory;
+
+use bytes::Bytes;
+
+// Shadow memory holds one bit per byte of memory, a bit is set once the byte
+// is written by ELF loading, the host or a store of the program. Accesses out
+// of memory are left to the memory itself to reject.
+
+/// Marks size bytes starting from addr as defined.
+pub fn mark_defined(shadow: &mut [u8], addr: u64, size: u64) {
+    let end = addr.saturating_add(size).min(shadow.len() as u64 * 8);
+    let mut addr = addr;
+    while addr < end {
+        if addr % 8 == 0 && end - addr >= 8 {
+            let bytes = ((end - addr) / 8) as usize;
+            let start = (addr / 8) as usize;
+            shadow[start..start + bytes].fill(0xff);
+            addr += bytes as u64 * 8;
+        } else {
+            shadow[(addr / 8) as usize] |= 1 << (addr % 8);
+            addr += 1;
+        }
+    }
+}
+
+/// Returns the first byte of the range that was never defined.
+pub fn find_undefined(shadow: &[u8], addr: u64, size: u64) -> Option<u64> {
+    let end = addr.saturating_add(size).min(shadow.len() as u64 * 8);
+    (addr..end).find(|a| shadow[(a / 8) as usize] & (1 << (a % 8)) == 0)
+}
+
+/// A memory wrapper reporting loads of bytes that were never written, which
+/// makes reads of uninitialized memory deterministic errors instead of
+/// values depending on the memory implementation. Only the loads of
+/// instructions are checked, load_bytes is left to the host. The first
+/// uninitialized read is kept till the machine takes it via
+/// Memory::take_uninitialized_read after the instruction has finished.
+pub struct MemcheckMemory<M: Memory> {
+    inner: M,
+    shadow: Vec<u8>,
+    uninitialized_read: Option<u64>,
+}
+
+impl<M: Memory> MemcheckMemory<M> {
+    pub fn inner_mut(&mut self) -> &mut M {
+        &mut self.inner
+    }
+
+    pub fn new(inner: M) -> Self {
+        let shadow = vec![0; (inner.memory_size() + 7) / 8];
+        Self {
+            inner,
+            shadow,
+            uninitialized_read: None,
+        }
+    }
+
+    /// Whether all size bytes starting from addr are defined.
+    pub fn is_defined(&self, addr: u64, size: u64) -> bool {
+        find_undefined(&self.shadow, addr, size).is_none()
+    }
+
+    #[inline(always)]
+    fn check(&mut self, addr: u64, size: u64) {
+        if self.uninitialized_read.is_none() {
+            self.uninitialized_read = find_undefined(&self.shadow, addr, size);
+        }
+    }
+}
+
+impl<M: Memory + Default> Default for MemcheckMemory<M> {
+    fn default() -> Self {
+        Self::new(M::default())
+    }
+}
+
+impl<M: Memory> Memory for MemcheckMemory<M> {
+    type REG = M::REG;
+
+    fn reset_memory(&mut self) -> Result<(), Error> {
+        self.shadow.fill(0);
+        self.uninitialized_read = None;
+        self.inner.reset_memory()
+    }
+
+    fn init_pages(
+        &mut self,
+        addr: u64,
+        size: u64,
+        flags: u8,
+        source: Option<Bytes>,
+        offset_from_addr: u64,
+    ) -> Result<(), Error> {
+        self.inner
+            .init_pages(addr, size, flags, source, offset_from_addr)?;
+        mark_defined(&mut self.shadow, addr, size);
+        Ok(())
+    }
+
+    fn fetch_flag(&mut self, page: u64) -> Result<u8, Error> {
+        self.inner.fetch_flag(page)
+    }
+
+    fn set_flag(&mut self, page: u64, flag: u8) -> Result<(), Error> {
+        self.inner.set_flag(page, flag)
+    }
+
+    fn clear_flag(&mut self, page: u64, flag: u8) -> Result<(), Error> {
+        self.inner.clear_flag(page, flag)
+    }
+
+    fn memory_size(&self) -> usize {
+        self.inner.memory_size()
+    }
+
+    fn execute_load16(&mut self, addr: u64) -> Result<u16, Error> {
+        self.inner.execute_load16(addr)
+    }
+
+    fn execute_load32(&mut self, addr: u64) -> Result<u32, Error> {
+        self.inner.execute_load32(addr)
+    }
+
+    fn load8(&mut self, addr: &Self::REG) -> Result<Self::REG, Error> {
+        self.check(addr.to_u64(), 1);
+        self.inner.load8(addr)
+    }
+
+    fn load16(&mut self, addr: &Self::REG) -> Result<Self::REG, Error> {
+        self.check(addr.to_u64(), 2);
+        self.inner.load16(addr)
+    }
+
+    fn load32(&mut self, addr: &Self::REG) -> Result<Self::REG, Error> {
+        self.check(addr.to_u64(), 4);
+        self.inner.load32(addr)
+    }
+
+    fn load64(&mut self, addr: &Self::REG) -> Result<Self::REG, Error> {
+        self.check(addr.to_u64(), 8);
+        self.inner.load64(addr)
+    }
+
+    fn store8(&mut self, addr: &Self::REG, value: &Self::REG) -> Result<(), Error> {
+        self.inner.store8(addr, value)?;
+        mark_defined(&mut self.shadow, addr.to_u64(), 1);
+        Ok(())
+    }
+
+    fn store16(&mut self, addr: &Self::REG, value: &Self::REG) -> Result<(), Error> {
+        self.inner.store16(addr, value)?;
+        mark_defined(&mut self.shadow, addr.to_u64(), 2);
+        Ok(())
+    }
+
+    fn store32(&mut self, addr: &Self::REG, value: &Self::REG) -> Result<(), Error> {
+        self.inner.store32(addr, value)?;
+        mark_defined(&mut self.shadow, addr.to_u64(), 4);
+        Ok(())
+    }
+
+    fn store64(&mut self, addr: &Self::REG, value: &Self::REG) -> Result<(), Error> {
+        self.inner.store64(addr, value)?;
+        mark_defined(&mut self.shadow, addr.to_u64(), 8);
+        Ok(())
+    }
+
+    fn store_bytes(&mut self, addr: u64, value: &[u8]) -> Result<(), Error> {
+        self.inner.store_bytes(addr, value)?;
+        mark_defined(&mut self.shadow, addr, value.len() as u64);
+        Ok(())
+    }
+
+    fn store_byte(&mut self, addr: u64, size: u64, value: u8) -> Result<(), Error> {
+        self.inner.store_byte(addr, size, value)?;
+        mark_defined(&mut self.shadow, addr, size);
+        Ok(())
+    }
+
+    fn load_bytes(&mut self, addr: u64, size: u64) -> Result<Bytes, Error> {
+        self.inner.load_bytes(addr, size)
+    }
+
+    fn lr(&self) -> &Self::REG {
+        self.inner.lr()
+    }
+
+    fn set_lr(&mut self, value: &Self::REG) {
+        self.inner.set_lr(value);
+    }
+
+    fn set_stack_guard(&mut self, start: u64, end: u64) -> Result<(), Error> {
+        self.inner.set_stack_guard(start, end)
+    }
+
+    fn stack_guard(&self) -> (u64, u64) {
+        self.inner.stack_guard()
+    }
+
+    fn take_watchpoint_hit(&mut self) -> Option<(u64, WatchpointKind)> {
+        self.inner.take_watchpoint_hit()
+    }
+
+    fn take_uninitialized_read(&mut self) -> Option<u64> {
+        self.uninitialized_read.take()
+    }
+}
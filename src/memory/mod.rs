@@ -1,6 +1,6 @@
 use super::{
     bits::{rounddown, roundup},
-    error::OutOfBoundKind,
+    error::{OutOfBoundKind, WatchpointKind},
     Error, Register, RISCV_PAGESIZE,
 };
 use bytes::Bytes;
@@ -8,7 +8,9 @@ use std::cmp::min;
 use std::ptr;
 
 pub mod flat;
+pub mod memcheck;
 pub mod sparse;
+pub mod watchpoint;
 pub mod wxorx;
 
 pub use ckb_vm_definitions::{
@@ -72,6 +74,37 @@ pub trait Memory {
     // Load reservation address for atomic extension.
     fn lr(&self) -> &Self::REG;
     fn set_lr(&mut self, value: &Self::REG);
+
+    // Returns and clears the first watchpoint hit recorded since the last
+    // call, only memories watching addresses need to implement this.
+    fn take_watchpoint_hit(&mut self) -> Option<(u64, WatchpointKind)> {
+        None
+    }
+
+    // Loads and stores of instructions overlapping [start, end) fail with
+    // Error::MemOutOfStack, which the machines report as a stack overflow.
+    // Bulk accesses such as load_bytes are left unchecked so the host can
+    // still inspect the region. An empty range removes the guard.
+    fn set_stack_guard(&mut self, start: u64, end: u64) -> Result<(), Error> {
+        if start < end {
+            Err(Error::Unexpected(String::from(
+                "Stack guard is not supported by this memory",
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn stack_guard(&self) -> (u64, u64) {
+        (0, 0)
+    }
+
+    // Returns and clears the address of the first load of uninitialized
+    // memory since the last call, only memories tracking initialized bytes
+    // need to implement this.
+    fn take_uninitialized_read(&mut self) -> Option<u64> {
+        None
+    }
 }
 
 #[inline(always)]
@@ -101,6 +134,15 @@ pub fn fill_page_data<M: Memory>(
     Ok(())
 }
 
+#[inline(always)]
+pub fn check_stack_guard(guard: (u64, u64), addr: u64, size: u64) -> Result<(), Error> {
+    if addr < guard.1 && addr.wrapping_add(size) > guard.0 {
+        Err(Error::MemOutOfStack)
+    } else {
+        Ok(())
+    }
+}
+
 pub fn check_no_overflow(addr: u64, size: u64, memory_size: u64) -> Result<(), Error> {
     if addr >= memory_size {
         return Err(Error::MemOutOfBound(addr, OutOfBoundKind::Memory));
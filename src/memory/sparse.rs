@@ -1,7 +1,10 @@
 use super::super::{
     error::OutOfBoundKind, Error, Register, DEFAULT_MEMORY_SIZE, RISCV_PAGESIZE, RISCV_PAGE_SHIFTS,
 };
-use super::{check_no_overflow, fill_page_data, memset, round_page_down, Memory, Page, FLAG_DIRTY};
+use super::{
+    check_no_overflow, check_stack_guard, fill_page_data, memset, round_page_down, Memory, Page,
+    FLAG_DIRTY,
+};
 
 use bytes::Bytes;
 use std::cmp::min;
@@ -22,6 +25,7 @@ pub struct SparseMemory<R> {
     memory_size: usize,
     riscv_pages: usize,
     load_reservation_address: R,
+    stack_guard: (u64, u64),
     _inner: PhantomData<R>,
 }
 
@@ -42,6 +46,7 @@ impl<R: Register> SparseMemory<R> {
 
     fn load(&mut self, addr: u64, bytes: u64) -> Result<u64, Error> {
         debug_assert!(bytes == 1 || bytes == 2 || bytes == 4 || bytes == 8);
+        check_stack_guard(self.stack_guard, addr, bytes)?;
         let page_addr = round_page_down(addr);
         let first_page_bytes = min(bytes, RISCV_PAGESIZE as u64 - (addr - page_addr));
         let mut shift = 0;
@@ -77,6 +82,7 @@ impl<R: Register> SparseMemory<R> {
             memory_size,
             riscv_pages: memory_size / RISCV_PAGESIZE,
             load_reservation_address: R::from_u64(u64::MAX),
+            stack_guard: (0, 0),
             _inner: PhantomData,
         }
     }
@@ -96,6 +102,7 @@ impl<R: Register> Memory for SparseMemory<R> {
         memset(&mut self.flags, 0);
         self.pages.clear();
         self.load_reservation_address = R::from_u64(u64::MAX);
+        self.stack_guard = (0, 0);
         Ok(())
     }
 
@@ -242,16 +249,19 @@ impl<R: Register> Memory for SparseMemory<R> {
     }
 
     fn store8(&mut self, addr: &Self::REG, value: &Self::REG) -> Result<(), Error> {
+        check_stack_guard(self.stack_guard, addr.to_u64(), 1)?;
         self.store_bytes(addr.to_u64(), &[value.to_u8()])
     }
 
     fn store16(&mut self, addr: &Self::REG, value: &Self::REG) -> Result<(), Error> {
+        check_stack_guard(self.stack_guard, addr.to_u64(), 2)?;
         let value = value.to_u16();
         // RISC-V is little-endian by specification
         self.store_bytes(addr.to_u64(), &[(value & 0xFF) as u8, (value >> 8) as u8])
     }
 
     fn store32(&mut self, addr: &Self::REG, value: &Self::REG) -> Result<(), Error> {
+        check_stack_guard(self.stack_guard, addr.to_u64(), 4)?;
         let value = value.to_u32();
         // RISC-V is little-endian by specification
         self.store_bytes(
@@ -266,6 +276,7 @@ impl<R: Register> Memory for SparseMemory<R> {
     }
 
     fn store64(&mut self, addr: &Self::REG, value: &Self::REG) -> Result<(), Error> {
+        check_stack_guard(self.stack_guard, addr.to_u64(), 8)?;
         let value = value.to_u64();
         // RISC-V is little-endian by specification
         self.store_bytes(
@@ -290,4 +301,13 @@ impl<R: Register> Memory for SparseMemory<R> {
     fn set_lr(&mut self, value: &Self::REG) {
         self.load_reservation_address = value.clone();
     }
+
+    fn set_stack_guard(&mut self, start: u64, end: u64) -> Result<(), Error> {
+        self.stack_guard = (start, end);
+        Ok(())
+    }
+
+    fn stack_guard(&self) -> (u64, u64) {
+        self.stack_guard
+    }
 }
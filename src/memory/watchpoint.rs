@@ -0,0 +1,366 @@
+use super::super::{
+    error::WatchpointKind,
+    instructions::{
+        extract_opcode, insts, rvc::zcmp_registers, vector::VType, Instruction, Itype, R4type,
+        Rtype, Stype,
+    },
+    registers::SP,
+    CoreMachine, Error, Register, RISCV_VLENB,
+};
+use super::Memory;
+
+use bytes::Bytes;
+
+#[derive(Debug, PartialEq, Clone, Copy, Eq)]
+pub struct Watchpoint {
+    pub addr: u64,
+    pub size: u64,
+    pub kind: WatchpointKind,
+}
+
+impl Watchpoint {
+    // A watchpoint of kind Access fires on both reads and writes, and an
+    // access of kind Access (such as an AMO instruction) is both a read
+    // and a write.
+    fn matches(&self, addr: u64, size: u64, kind: WatchpointKind) -> bool {
+        let kind_matches = self.kind == kind
+            || self.kind == WatchpointKind::Access
+            || kind == WatchpointKind::Access;
+        kind_matches
+            && addr < self.addr.saturating_add(self.size)
+            && self.addr < addr.saturating_add(size)
+    }
+}
+
+/// A registry of watched address ranges.
+#[derive(Default)]
+pub struct Watchpoints {
+    watchpoints: Vec<Watchpoint>,
+}
+
+impl Watchpoints {
+    pub fn add(&mut self, addr: u64, size: u64, kind: WatchpointKind) {
+        self.watchpoints.push(Watchpoint { addr, size, kind });
+    }
+
+    pub fn remove(&mut self, addr: u64, size: u64, kind: WatchpointKind) -> bool {
+        let length = self.watchpoints.len();
+        self.watchpoints
+            .retain(|w| *w != Watchpoint { addr, size, kind });
+        self.watchpoints.len() != length
+    }
+
+    pub fn clear(&mut self) {
+        self.watchpoints.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.watchpoints.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Watchpoint> {
+        self.watchpoints.iter()
+    }
+
+    /// Checks an access of size bytes starting from addr, returns the kind
+    /// of the first watchpoint it triggers.
+    pub fn check(&self, addr: u64, size: u64, kind: WatchpointKind) -> Option<WatchpointKind> {
+        self.watchpoints
+            .iter()
+            .find(|w| w.matches(addr, size, kind))
+            .map(|w| w.kind)
+    }
+}
+
+/// A memory wrapper that records accesses to watched ranges. Accesses are
+/// never blocked, the first hit is kept till the machine takes it via
+/// Memory::take_watchpoint_hit, which happens after the accessing
+/// instruction has finished.
+pub struct WatchpointMemory<M: Memory> {
+    inner: M,
+    watchpoints: Watchpoints,
+    hit: Option<(u64, WatchpointKind)>,
+}
+
+impl<M: Memory> WatchpointMemory<M> {
+    pub fn inner_mut(&mut self) -> &mut M {
+        &mut self.inner
+    }
+
+    pub fn new(inner: M) -> Self {
+        Self {
+            inner,
+            watchpoints: Watchpoints::default(),
+            hit: None,
+        }
+    }
+
+    pub fn watchpoints(&self) -> &Watchpoints {
+        &self.watchpoints
+    }
+
+    pub fn watchpoints_mut(&mut self) -> &mut Watchpoints {
+        &mut self.watchpoints
+    }
+
+    #[inline(always)]
+    fn watch(&mut self, addr: u64, size: u64, kind: WatchpointKind) {
+        if self.hit.is_none() && !self.watchpoints.is_empty() {
+            if let Some(kind) = self.watchpoints.check(addr, size, kind) {
+                self.hit = Some((addr, kind));
+            }
+        }
+    }
+}
+
+impl<M: Memory + Default> Default for WatchpointMemory<M> {
+    fn default() -> Self {
+        Self::new(M::default())
+    }
+}
+
+impl<M: Memory> Memory for WatchpointMemory<M> {
+    type REG = M::REG;
+
+    fn reset_memory(&mut self) -> Result<(), Error> {
+        self.hit = None;
+        self.inner.reset_memory()
+    }
+
+    fn init_pages(
+        &mut self,
+        addr: u64,
+        size: u64,
+        flags: u8,
+        source: Option<Bytes>,
+        offset_from_addr: u64,
+    ) -> Result<(), Error> {
+        self.inner
+            .init_pages(addr, size, flags, source, offset_from_addr)
+    }
+
+    fn fetch_flag(&mut self, page: u64) -> Result<u8, Error> {
+        self.inner.fetch_flag(page)
+    }
+
+    fn set_flag(&mut self, page: u64, flag: u8) -> Result<(), Error> {
+        self.inner.set_flag(page, flag)
+    }
+
+    fn clear_flag(&mut self, page: u64, flag: u8) -> Result<(), Error> {
+        self.inner.clear_flag(page, flag)
+    }
+
+    fn memory_size(&self) -> usize {
+        self.inner.memory_size()
+    }
+
+    fn execute_load16(&mut self, addr: u64) -> Result<u16, Error> {
+        self.inner.execute_load16(addr)
+    }
+
+    fn execute_load32(&mut self, addr: u64) -> Result<u32, Error> {
+        self.inner.execute_load32(addr)
+    }
+
+    fn load8(&mut self, addr: &Self::REG) -> Result<Self::REG, Error> {
+        self.watch(addr.to_u64(), 1, WatchpointKind::Read);
+        self.inner.load8(addr)
+    }
+
+    fn load16(&mut self, addr: &Self::REG) -> Result<Self::REG, Error> {
+        self.watch(addr.to_u64(), 2, WatchpointKind::Read);
+        self.inner.load16(addr)
+    }
+
+    fn load32(&mut self, addr: &Self::REG) -> Result<Self::REG, Error> {
+        self.watch(addr.to_u64(), 4, WatchpointKind::Read);
+        self.inner.load32(addr)
+    }
+
+    fn load64(&mut self, addr: &Self::REG) -> Result<Self::REG, Error> {
+        self.watch(addr.to_u64(), 8, WatchpointKind::Read);
+        self.inner.load64(addr)
+    }
+
+    fn store8(&mut self, addr: &Self::REG, value: &Self::REG) -> Result<(), Error> {
+        self.watch(addr.to_u64(), 1, WatchpointKind::Write);
+        self.inner.store8(addr, value)
+    }
+
+    fn store16(&mut self, addr: &Self::REG, value: &Self::REG) -> Result<(), Error> {
+        self.watch(addr.to_u64(), 2, WatchpointKind::Write);
+        self.inner.store16(addr, value)
+    }
+
+    fn store32(&mut self, addr: &Self::REG, value: &Self::REG) -> Result<(), Error> {
+        self.watch(addr.to_u64(), 4, WatchpointKind::Write);
+        self.inner.store32(addr, value)
+    }
+
+    fn store64(&mut self, addr: &Self::REG, value: &Self::REG) -> Result<(), Error> {
+        self.watch(addr.to_u64(), 8, WatchpointKind::Write);
+        self.inner.store64(addr, value)
+    }
+
+    fn store_bytes(&mut self, addr: u64, value: &[u8]) -> Result<(), Error> {
+        self.watch(addr, value.len() as u64, WatchpointKind::Write);
+        self.inner.store_bytes(addr, value)
+    }
+
+    fn store_byte(&mut self, addr: u64, size: u64, value: u8) -> Result<(), Error> {
+        self.watch(addr, size, WatchpointKind::Write);
+        self.inner.store_byte(addr, size, value)
+    }
+
+    fn load_bytes(&mut self, addr: u64, size: u64) -> Result<Bytes, Error> {
+        self.watch(addr, size, WatchpointKind::Read);
+        self.inner.load_bytes(addr, size)
+    }
+
+    fn lr(&self) -> &Self::REG {
+        self.inner.lr()
+    }
+
+    fn set_lr(&mut self, value: &Self::REG) {
+        self.inner.set_lr(value);
+    }
+
+    fn set_stack_guard(&mut self, start: u64, end: u64) -> Result<(), Error> {
+        self.inner.set_stack_guard(start, end)
+    }
+
+    fn stack_guard(&self) -> (u64, u64) {
+        self.inner.stack_guard()
+    }
+
+    fn take_uninitialized_read(&mut self) -> Option<u64> {
+        self.inner.take_uninitialized_read()
+    }
+
+    fn take_watchpoint_hit(&mut self) -> Option<(u64, WatchpointKind)> {
+        self.hit.take()
+    }
+}
+
+/// Returns the address, size and kind of the data memory access performed
+/// by an instruction, this is used by engines that cannot intercept memory
+/// accesses directly and must check watchpoints before running each
+/// instruction instead. Strided and masked vector accesses report the whole
+/// range spanned by their elements.
+pub fn instruction_memory_access<Mac: CoreMachine>(
+    machine: &Mac,
+    inst: Instruction,
+) -> Option<(u64, u64, WatchpointKind)> {
+    let load = |size| {
+        let i = Itype(inst);
+        let addr = machine.registers()[i.rs1()].to_u64();
+        Some((
+            addr.wrapping_add(i.immediate_s() as i64 as u64),
+            size,
+            WatchpointKind::Read,
+        ))
+    };
+    let store = |size| {
+        let i = Stype(inst);
+        let addr = machine.registers()[i.rs1()].to_u64();
+        Some((
+            addr.wrapping_add(i.immediate_s() as i64 as u64),
+            size,
+            WatchpointKind::Write,
+        ))
+    };
+    let atomic = |size, kind| {
+        let addr = machine.registers()[Rtype(inst).rs1()].to_u64();
+        Some((addr, size, kind))
+    };
+    // Unit-stride and strided vector loads and stores of vl elements of eew
+    // bits, the stride is the element size for unit-stride ones.
+    let vill = VType(machine.vtype()).vill();
+    let vector = |eew: u64, strided: bool, kind| {
+        let i = R4type(inst);
+        if vill || machine.vl() == 0 {
+            return None;
+        }
+        let base = machine.registers()[i.rs1()].to_u64();
+        let stride = if strided {
+            machine.registers()[i.rs2()].to_i64()
+        } else {
+            eew as i64 / 8
+        };
+        let last = base.wrapping_add(stride.wrapping_mul(machine.vl() as i64 - 1) as u64);
+        let addr = if stride < 0 { last } else { base };
+        let size = last.abs_diff(base).wrapping_add(eew / 8);
+        Some((addr, size, kind))
+    };
+    // Mask and whole register loads and stores, which are not affected by
+    // vtype nor masking.
+    let vector_bytes = |bytes: u64, kind| {
+        let addr = machine.registers()[R4type(inst).rs1()].to_u64();
+        Some((addr, bytes, kind)).filter(|_| bytes > 0)
+    };
+    // Zcmp push stores the registers right below sp, pop loads them right
+    // below sp plus the stack adjustment.
+    let zcmp = |kind| {
+        let i = Itype(inst);
+        let bytes = u64::from(Mac::REG::BITS / 8);
+        let size = zcmp_registers(i.rs1()).len() as u64 * bytes;
+        let mut top = machine.registers()[SP].to_u64();
+        if kind == WatchpointKind::Read {
+            top = top.wrapping_add(u64::from(i.immediate_u()));
+        }
+        Some((top.wrapping_sub(size), size, kind))
+    };
+    let vl_bytes = if vill { 0 } else { (machine.vl() + 7) / 8 };
+    match extract_opcode(inst) {
+        insts::OP_LB_VERSION0 | insts::OP_LB_VERSION1 => load(1),
+        insts::OP_LBU_VERSION0 | insts::OP_LBU_VERSION1 => load(1),
+        insts::OP_LH_VERSION0 | insts::OP_LH_VERSION1 => load(2),
+        insts::OP_LHU_VERSION0 | insts::OP_LHU_VERSION1 => load(2),
+        insts::OP_LW_VERSION0 | insts::OP_LW_VERSION1 => load(4),
+        insts::OP_LWU_VERSION0 | insts::OP_LWU_VERSION1 => load(4),
+        insts::OP_LD_VERSION0 | insts::OP_LD_VERSION1 => load(8),
+        insts::OP_SB => store(1),
+        insts::OP_SH => store(2),
+        insts::OP_SW => store(4),
+        insts::OP_SD => store(8),
+        insts::OP_LR_W => atomic(4, WatchpointKind::Read),
+        insts::OP_LR_D => atomic(8, WatchpointKind::Read),
+        insts::OP_SC_W..=insts::OP_AMOMAXU_W => atomic(4, WatchpointKind::Access),
+        insts::OP_SC_D..=insts::OP_AMOMAXU_D => atomic(8, WatchpointKind::Access),
+        insts::OP_FLW => load(4),
+        insts::OP_FLD => load(8),
+        insts::OP_FSW => store(4),
+        insts::OP_FSD => store(8),
+        insts::OP_VLE8_V => vector(8, false, WatchpointKind::Read),
+        insts::OP_VLE16_V => vector(16, false, WatchpointKind::Read),
+        insts::OP_VLE32_V => vector(32, false, WatchpointKind::Read),
+        insts::OP_VLE64_V => vector(64, false, WatchpointKind::Read),
+        insts::OP_VSE8_V => vector(8, false, WatchpointKind::Write),
+        insts::OP_VSE16_V => vector(16, false, WatchpointKind::Write),
+        insts::OP_VSE32_V => vector(32, false, WatchpointKind::Write),
+        insts::OP_VSE64_V => vector(64, false, WatchpointKind::Write),
+        insts::OP_VLSE8_V => vector(8, true, WatchpointKind::Read),
+        insts::OP_VLSE16_V => vector(16, true, WatchpointKind::Read),
+        insts::OP_VLSE32_V => vector(32, true, WatchpointKind::Read),
+        insts::OP_VLSE64_V => vector(64, true, WatchpointKind::Read),
+        insts::OP_VSSE8_V => vector(8, true, WatchpointKind::Write),
+        insts::OP_VSSE16_V => vector(16, true, WatchpointKind::Write),
+        insts::OP_VSSE32_V => vector(32, true, WatchpointKind::Write),
+        insts::OP_VSSE64_V => vector(64, true, WatchpointKind::Write),
+        insts::OP_VLM_V => vector_bytes(vl_bytes, WatchpointKind::Read),
+        insts::OP_VSM_V => vector_bytes(vl_bytes, WatchpointKind::Write),
+        // The number of registers is kept in rs2.
+        insts::OP_VLR_V => {
+            let bytes = (R4type(inst).rs2() * RISCV_VLENB) as u64;
+            vector_bytes(bytes, WatchpointKind::Read)
+        }
+        insts::OP_VSR_V => {
+            let bytes = (R4type(inst).rs2() * RISCV_VLENB) as u64;
+            vector_bytes(bytes, WatchpointKind::Write)
+        }
+        insts::OP_CM_PUSH => zcmp(WatchpointKind::Write),
+        insts::OP_CM_POP | insts::OP_CM_POPRET | insts::OP_CM_POPRETZ => zcmp(WatchpointKind::Read),
+        _ => None,
+    }
+}
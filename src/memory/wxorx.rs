@@ -1,7 +1,10 @@
-use super::super::{error::OutOfBoundKind, Error, Register, RISCV_PAGESIZE};
+use super::super::{
+    error::{OutOfBoundKind, WatchpointKind},
+    Error, Register, RISCV_PAGESIZE,
+};
 use super::{
-    check_no_overflow, check_permission, get_page_indices, round_page_down, round_page_up, Memory,
-    FLAG_EXECUTABLE, FLAG_FREEZED, FLAG_WRITABLE,
+    check_no_overflow, check_permission, check_stack_guard, get_page_indices, round_page_down,
+    round_page_up, Memory, FLAG_EXECUTABLE, FLAG_FREEZED, FLAG_WRITABLE,
 };
 
 use bytes::Bytes;
@@ -91,6 +94,7 @@ impl<M: Memory> Memory for WXorXMemory<M> {
     }
 
     fn execute_load16(&mut self, addr: u64) -> Result<u16, Error> {
+        check_stack_guard(self.stack_guard(), addr, 2)?;
         check_no_overflow(addr, 2, self.memory_size() as u64)?;
         let page_indices = get_page_indices(addr, 2);
         check_permission(self, &page_indices, FLAG_EXECUTABLE)?;
@@ -98,6 +102,7 @@ impl<M: Memory> Memory for WXorXMemory<M> {
     }
 
     fn execute_load32(&mut self, addr: u64) -> Result<u32, Error> {
+        check_stack_guard(self.stack_guard(), addr, 4)?;
         check_no_overflow(addr, 4, self.memory_size() as u64)?;
         let page_indices = get_page_indices(addr, 4);
         check_permission(self, &page_indices, FLAG_EXECUTABLE)?;
@@ -121,6 +126,7 @@ impl<M: Memory> Memory for WXorXMemory<M> {
     }
 
     fn store8(&mut self, addr: &Self::REG, value: &Self::REG) -> Result<(), Error> {
+        check_stack_guard(self.stack_guard(), addr.to_u64(), 1)?;
         check_no_overflow(addr.to_u64(), 1, self.memory_size() as u64)?;
         let page_indices = get_page_indices(addr.to_u64(), 1);
         check_permission(self, &page_indices, FLAG_WRITABLE)?;
@@ -128,6 +134,7 @@ impl<M: Memory> Memory for WXorXMemory<M> {
     }
 
     fn store16(&mut self, addr: &Self::REG, value: &Self::REG) -> Result<(), Error> {
+        check_stack_guard(self.stack_guard(), addr.to_u64(), 2)?;
         check_no_overflow(addr.to_u64(), 2, self.memory_size() as u64)?;
         let page_indices = get_page_indices(addr.to_u64(), 2);
         check_permission(self, &page_indices, FLAG_WRITABLE)?;
@@ -135,6 +142,7 @@ impl<M: Memory> Memory for WXorXMemory<M> {
     }
 
     fn store32(&mut self, addr: &Self::REG, value: &Self::REG) -> Result<(), Error> {
+        check_stack_guard(self.stack_guard(), addr.to_u64(), 4)?;
         check_no_overflow(addr.to_u64(), 4, self.memory_size() as u64)?;
         let page_indices = get_page_indices(addr.to_u64(), 4);
         check_permission(self, &page_indices, FLAG_WRITABLE)?;
@@ -142,6 +150,7 @@ impl<M: Memory> Memory for WXorXMemory<M> {
     }
 
     fn store64(&mut self, addr: &Self::REG, value: &Self::REG) -> Result<(), Error> {
+        check_stack_guard(self.stack_guard(), addr.to_u64(), 8)?;
         check_no_overflow(addr.to_u64(), 8, self.memory_size() as u64)?;
         let page_indices = get_page_indices(addr.to_u64(), 8);
         check_permission(self, &page_indices, FLAG_WRITABLE)?;
@@ -180,4 +189,20 @@ impl<M: Memory> Memory for WXorXMemory<M> {
     fn set_lr(&mut self, value: &Self::REG) {
         self.inner.set_lr(value);
     }
+
+    fn set_stack_guard(&mut self, start: u64, end: u64) -> Result<(), Error> {
+        self.inner.set_stack_guard(start, end)
+    }
+
+    fn stack_guard(&self) -> (u64, u64) {
+        self.inner.stack_guard()
+    }
+
+    fn take_uninitialized_read(&mut self) -> Option<u64> {
+        self.inner.take_uninitialized_read()
+    }
+
+    fn take_watchpoint_hit(&mut self) -> Option<(u64, WatchpointKind)> {
+        self.inner.take_watchpoint_hit()
+    }
 }
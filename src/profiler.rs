@@ -0,0 +1,330 @@
+// A cycle profiler attributing the cycles charged for each instruction to
+// guest functions. The call stack is tracked following the RISC-V calling
+// convention: a jal / jalr linking to ra(or t0 as the alternate link
+// register) is a call, and a jalr jumping to the link register without
+// linking is a return. Results are kept as a tree of call stacks, which can
+// be exported in folded stack format(used by flamegraph tools), or in
+// callgrind format(used by KCachegrind and friends).
+use crate::{
+    decoder::InstDecoder,
+    elf::Symbols,
+    instructions::{extract_opcode, instruction_length, insts, Instruction, Itype, Utype},
+    machine::{DefaultMachine, VERSION0},
+    registers::{RA, T0},
+    CoreMachine, Error, Register, SupportMachine, ISA_MOP,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+
+// Call stack tree node
+struct Node {
+    parent: usize,
+    // Entry address of the function
+    function: u64,
+    children: HashMap<u64, usize>,
+    calls: u64,
+    self_cycles: u64,
+}
+
+// Self cycles, and callee -> (calls, inclusive cycles) of a function
+type CallgrindFunction = (u64, BTreeMap<u64, (u64, u64)>);
+
+enum Transfer {
+    Call(u64),
+    Return,
+    Jump,
+    Other,
+}
+
+pub struct Profiler {
+    symbols: Symbols,
+    nodes: Vec<Node>,
+    // (node, return address) of each active frame, the first one is the root
+    stack: Vec<(usize, u64)>,
+}
+
+impl Profiler {
+    pub fn new(program: &[u8]) -> Result<Self, Error> {
+        Ok(Self::new_with_symbols(Symbols::parse(program)?))
+    }
+
+    pub fn new_with_symbols(symbols: Symbols) -> Self {
+        Self {
+            symbols,
+            nodes: vec![],
+            stack: vec![],
+        }
+    }
+
+    pub fn symbols(&self) -> &Symbols {
+        &self.symbols
+    }
+
+    pub fn run<Inner: SupportMachine>(
+        &mut self,
+        machine: &mut DefaultMachine<Inner>,
+    ) -> Result<i8, Error> {
+        let mut decoder = machine.build_decoder();
+        self.run_with_decoder(machine, &mut decoder)
+    }
+
+    pub fn run_with_decoder<Inner: SupportMachine, D: InstDecoder>(
+        &mut self,
+        machine: &mut DefaultMachine<Inner>,
+        decoder: &mut D,
+    ) -> Result<i8, Error> {
+        if machine.isa() & ISA_MOP != 0 && machine.version() == VERSION0 {
+            return Err(Error::InvalidVersion);
+        }
+        machine.set_running(true);
+        while machine.running() {
+            if machine.pause().has_interrupted() {
+                machine.pause().free();
+                return Err(Error::Pause);
+            }
+            if machine.reset_signal() {
+                decoder.reset_instructions_cache()?;
+            }
+            let pc = machine.pc().to_u64();
+            let instruction = decoder.decode(machine.memory_mut(), pc)?;
+            let cycles = machine.cycles();
+            let result = machine.step(decoder);
+            self.account(
+                pc,
+                instruction,
+                machine.pc().to_u64(),
+                machine.cycles().wrapping_sub(cycles),
+            );
+            result?;
+        }
+        Ok(machine.exit_code())
+    }
+
+    /// Profiles an asm machine. Instructions are run one at a time, which is
+    /// slower than AsmMachine::run, but the cycles charged stay the same.
+    #[cfg(has_asm)]
+    pub fn run_asm(&mut self, machine: &mut crate::machine::asm::AsmMachine) -> Result<i8, Error> {
+        let mut decoder = machine.machine.build_decoder();
+        if machine.machine.isa() & ISA_MOP != 0 && machine.machine.version() == VERSION0 {
+            return Err(Error::InvalidVersion);
+        }
+        machine.machine.set_running(true);
+        while machine.machine.running() {
+            if machine.machine.pause().has_interrupted() {
+                machine.machine.pause().free();
+                return Err(Error::Pause);
+            }
+            if machine.machine.reset_signal() {
+                decoder.reset_instructions_cache()?;
+            }
+            let pc = *machine.machine.pc();
+            let instruction = decoder.decode(machine.machine.memory_mut(), pc)?;
+            let cycles = machine.machine.cycles();
+            let result = machine.step(&mut decoder);
+            self.account(
+                pc,
+                instruction,
+                *machine.machine.pc(),
+                machine.machine.cycles().wrapping_sub(cycles),
+            );
+            result?;
+        }
+        Ok(machine.machine.exit_code())
+    }
+
+    /// Cycles spent in each function itself, keyed by function name.
+    pub fn self_cycles(&self) -> BTreeMap<String, u64> {
+        let mut result = BTreeMap::new();
+        for node in &self.nodes {
+            *result.entry(self.function_name(node.function)).or_insert(0) += node.self_cycles;
+        }
+        result
+    }
+
+    /// Writes the profile in folded stack format, one line per stack:
+    /// `_start;main;foo 1234`
+    pub fn write_folded<W: Write>(&self, w: &mut W) -> Result<(), Error> {
+        let mut lines: BTreeMap<String, u64> = BTreeMap::new();
+        for (i, node) in self.nodes.iter().enumerate() {
+            if node.self_cycles == 0 {
+                continue;
+            }
+            let mut frames = vec![];
+            let mut current = i;
+            loop {
+                frames.push(self.function_name(self.nodes[current].function));
+                if current == 0 {
+                    break;
+                }
+                current = self.nodes[current].parent;
+            }
+            frames.reverse();
+            *lines.entry(frames.join(";")).or_insert(0) += node.self_cycles;
+        }
+        for (stack, cycles) in lines {
+            writeln!(w, "{} {}", stack, cycles)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the profile in callgrind format. There is no line information,
+    /// all costs are attributed to line 0 of each function.
+    pub fn write_callgrind<W: Write>(&self, w: &mut W) -> Result<(), Error> {
+        let inclusive = self.inclusive_cycles();
+        let mut functions: BTreeMap<u64, CallgrindFunction> = BTreeMap::new();
+        for (i, node) in self.nodes.iter().enumerate() {
+            functions.entry(node.function).or_default().0 += node.self_cycles;
+            if i != 0 {
+                let caller = self.nodes[node.parent].function;
+                let edge = functions
+                    .entry(caller)
+                    .or_default()
+                    .1
+                    .entry(node.function)
+                    .or_default();
+                edge.0 += node.calls;
+                edge.1 += inclusive[i];
+            }
+        }
+        writeln!(w, "# callgrind format")?;
+        writeln!(w, "version: 1")?;
+        writeln!(w, "creator: ckb-vm")?;
+        writeln!(w, "positions: line")?;
+        writeln!(w, "events: Cycles")?;
+        writeln!(w, "summary: {}", inclusive.first().copied().unwrap_or(0))?;
+        for (function, (self_cycles, callees)) in functions {
+            writeln!(w)?;
+            writeln!(w, "fn={}", self.function_name(function))?;
+            writeln!(w, "0 {}", self_cycles)?;
+            for (callee, (calls, cycles)) in callees {
+                writeln!(w, "cfn={}", self.function_name(callee))?;
+                writeln!(w, "calls={} 0", calls)?;
+                writeln!(w, "0 {}", cycles)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn inclusive_cycles(&self) -> Vec<u64> {
+        let mut inclusive: Vec<u64> = self.nodes.iter().map(|n| n.self_cycles).collect();
+        // Children are always created after their parents.
+        for i in (1..self.nodes.len()).rev() {
+            let parent = self.nodes[i].parent;
+            inclusive[parent] += inclusive[i];
+        }
+        inclusive
+    }
+
+    fn function_name(&self, function: u64) -> String {
+        match self.symbols.lookup(function) {
+            Some(symbol) if symbol.address == function => symbol.name.clone(),
+            Some(symbol) => format!("{}+0x{:x}", symbol.name, function - symbol.address),
+            None => format!("0x{:x}", function),
+        }
+    }
+
+    // Maps an address to the entry of the function containing it
+    fn function_of(&self, address: u64) -> u64 {
+        self.symbols
+            .lookup(address)
+            .map(|s| s.address)
+            .unwrap_or(address)
+    }
+
+    fn child(&mut self, parent: usize, function: u64) -> usize {
+        if let Some(i) = self.nodes[parent].children.get(&function) {
+            return *i;
+        }
+        let i = self.nodes.len();
+        self.nodes.push(Node {
+            parent,
+            function,
+            children: HashMap::default(),
+            calls: 0,
+            self_cycles: 0,
+        });
+        self.nodes[parent].children.insert(function, i);
+        i
+    }
+
+    fn account(&mut self, pc: u64, instruction: Instruction, next_pc: u64, cycles: u64) {
+        if self.stack.is_empty() {
+            let function = self.function_of(pc);
+            self.nodes.push(Node {
+                parent: 0,
+                function,
+                children: HashMap::default(),
+                calls: 1,
+                self_cycles: 0,
+            });
+            self.stack.push((0, 0));
+        }
+        let current = self.stack.last().unwrap().0;
+        self.nodes[current].self_cycles += cycles;
+        match transfer(pc, instruction) {
+            Transfer::Call(return_address) => {
+                let function = self.function_of(next_pc);
+                let node = self.child(current, function);
+                self.nodes[node].calls += 1;
+                self.stack.push((node, return_address));
+            }
+            Transfer::Return => {
+                // Unwind to the frame returning to next_pc, this also copes
+                // with frames skipped by longjmp.
+                if let Some(i) = self.stack.iter().rposition(|(_, r)| *r == next_pc) {
+                    if i > 0 {
+                        self.stack.truncate(i);
+                    }
+                } else if self.stack.len() > 1 {
+                    self.stack.pop();
+                }
+            }
+            Transfer::Other => (),
+            Transfer::Jump => {
+                // A jump to the entry of another function is a tail call,
+                // it replaces the current frame.
+                if self.stack.len() > 1 && self.nodes[current].function != self.function_of(next_pc)
+                {
+                    if let Some(symbol) = self.symbols.lookup(next_pc) {
+                        if symbol.address == next_pc {
+                            let (_, return_address) = self.stack.pop().unwrap();
+                            let parent = self.stack.last().unwrap().0;
+                            let node = self.child(parent, next_pc);
+                            self.nodes[node].calls += 1;
+                            self.stack.push((node, return_address));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn is_link_register(r: usize) -> bool {
+    r == RA || r == T0
+}
+
+fn transfer(pc: u64, instruction: Instruction) -> Transfer {
+    let return_address = pc.wrapping_add(u64::from(instruction_length(instruction)));
+    match extract_opcode(instruction) {
+        insts::OP_JAL => {
+            if is_link_register(Utype(instruction).rd()) {
+                Transfer::Call(return_address)
+            } else {
+                Transfer::Jump
+            }
+        }
+        insts::OP_JALR_VERSION0 | insts::OP_JALR_VERSION1 => {
+            let i = Itype(instruction);
+            if is_link_register(i.rd()) {
+                Transfer::Call(return_address)
+            } else if i.rd() == 0 && is_link_register(i.rs1()) {
+                Transfer::Return
+            } else {
+                Transfer::Jump
+            }
+        }
+        insts::OP_FAR_JUMP_REL | insts::OP_FAR_JUMP_ABS => Transfer::Call(return_address),
+        _ => Transfer::Other,
+    }
+}
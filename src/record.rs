@@ -0,0 +1,887 @@
+// Record and replay of machine executions. Recorder runs a DefaultMachine
+// while logging the effect of every instruction: written registers, memory
+// stores, the new PC and consumed cycles, syscalls included. The produced log
+// starts with a snapshot of the initial machine state, so it can be replayed
+// into a fresh machine without the original program or syscall handlers.
+// Replayer then moves back and forth through the log, using periodic
+// snapshot2 checkpoints to implement reverse stepping.
+use crate::{
+    decoder::InstDecoder,
+    error::WatchpointKind,
+    instructions::{extract_opcode, insts},
+    machine::{DefaultMachine, VERSION0, VERSION3},
+    memory::Memory,
+    registers::A7,
+    snapshot::{FloatState, VectorState},
+    snapshot2::{DataSource, Snapshot2, Snapshot2Context},
+    CoreMachine, Error, Register, SupportMachine, ISA_MOP, RISCV_FLOAT_REGISTER_NUMBER,
+    RISCV_GENERAL_REGISTER_NUMBER,
+};
+use bytes::Bytes;
+use std::collections::HashSet;
+use std::io::{ErrorKind, Read, Write};
+
+const LOG_MAGIC: &[u8; 8] = b"CKBVMLOG";
+// Bump this whenever the layout of the log changes. Version 2 added the float
+// and vector state and the pages loaded from a data source to snapshots.
+const LOG_FORMAT_VERSION: u8 = 2;
+
+// Each entry in the log starts with a tag byte. For a step entry the tag is
+// a combination of the flags below, followed by the PC delta and cycles
+// delta, then the optional parts in the same order as the flags.
+const TAG_REGISTERS: u8 = 0x01;
+const TAG_STORES: u8 = 0x02;
+const TAG_SYSCALL: u8 = 0x04;
+// The machine was reset(by a syscall for example), a full snapshot follows.
+const TAG_SNAPSHOT: u8 = 0x40;
+const TAG_EXIT: u8 = 0x80;
+const TAG_ERROR: u8 = 0x81;
+
+pub const DEFAULT_CHECKPOINT_INTERVAL: usize = 100_000;
+
+/// A memory wrapper keeping all stores since the last take_stores call,
+/// Recorder requires the recorded machine to use it.
+pub struct RecordingMemory<M: Memory> {
+    inner: M,
+    stores: Vec<(u64, Vec<u8>)>,
+}
+
+impl<M: Memory> RecordingMemory<M> {
+    pub fn new(inner: M) -> Self {
+        Self {
+            inner,
+            stores: Vec::new(),
+        }
+    }
+
+    pub fn inner_mut(&mut self) -> &mut M {
+        &mut self.inner
+    }
+
+    pub fn take_stores(&mut self) -> Vec<(u64, Vec<u8>)> {
+        std::mem::take(&mut self.stores)
+    }
+
+    fn record(&mut self, addr: u64, value: &M::REG, size: usize) {
+        let bytes = value.to_u64().to_le_bytes();
+        self.stores.push((addr, bytes[..size].to_vec()));
+    }
+}
+
+impl<M: Memory + Default> Default for RecordingMemory<M> {
+    fn default() -> Self {
+        Self::new(M::default())
+    }
+}
+
+impl<M: Memory> Memory for RecordingMemory<M> {
+    type REG = M::REG;
+
+    fn reset_memory(&mut self) -> Result<(), Error> {
+        self.stores.clear();
+        self.inner.reset_memory()
+    }
+
+    fn init_pages(
+        &mut self,
+        addr: u64,
+        size: u64,
+        flags: u8,
+        source: Option<Bytes>,
+        offset_from_addr: u64,
+    ) -> Result<(), Error> {
+        self.inner
+            .init_pages(addr, size, flags, source.clone(), offset_from_addr)?;
+        // Pages initialized at runtime still need to be recreated on replay.
+        let mut data = vec![0u8; size as usize];
+        if let Some(source) = source {
+            let start = (offset_from_addr as usize).min(data.len());
+            let length = source.len().min(data.len() - start);
+            data[start..start + length].copy_from_slice(&source[..length]);
+        }
+        self.stores.push((addr, data));
+        Ok(())
+    }
+
+    fn fetch_flag(&mut self, page: u64) -> Result<u8, Error> {
+        self.inner.fetch_flag(page)
+    }
+
+    fn set_flag(&mut self, page: u64, flag: u8) -> Result<(), Error> {
+        self.inner.set_flag(page, flag)
+    }
+
+    fn clear_flag(&mut self, page: u64, flag: u8) -> Result<(), Error> {
+        self.inner.clear_flag(page, flag)
+    }
+
+    fn memory_size(&self) -> usize {
+        self.inner.memory_size()
+    }
+
+    fn execute_load16(&mut self, addr: u64) -> Result<u16, Error> {
+        self.inner.execute_load16(addr)
+    }
+
+    fn execute_load32(&mut self, addr: u64) -> Result<u32, Error> {
+        self.inner.execute_load32(addr)
+    }
+
+    fn load8(&mut self, addr: &Self::REG) -> Result<Self::REG, Error> {
+        self.inner.load8(addr)
+    }
+
+    fn load16(&mut self, addr: &Self::REG) -> Result<Self::REG, Error> {
+        self.inner.load16(addr)
+    }
+
+    fn load32(&mut self, addr: &Self::REG) -> Result<Self::REG, Error> {
+        self.inner.load32(addr)
+    }
+
+    fn load64(&mut self, addr: &Self::REG) -> Result<Self::REG, Error> {
+        self.inner.load64(addr)
+    }
+
+    fn store8(&mut self, addr: &Self::REG, value: &Self::REG) -> Result<(), Error> {
+        self.inner.store8(addr, value)?;
+        self.record(addr.to_u64(), value, 1);
+        Ok(())
+    }
+
+    fn store16(&mut self, addr: &Self::REG, value: &Self::REG) -> Result<(), Error> {
+        self.inner.store16(addr, value)?;
+        self.record(addr.to_u64(), value, 2);
+        Ok(())
+    }
+
+    fn store32(&mut self, addr: &Self::REG, value: &Self::REG) -> Result<(), Error> {
+        self.inner.store32(addr, value)?;
+        self.record(addr.to_u64(), value, 4);
+        Ok(())
+    }
+
+    fn store64(&mut self, addr: &Self::REG, value: &Self::REG) -> Result<(), Error> {
+        self.inner.store64(addr, value)?;
+        self.record(addr.to_u64(), value, 8);
+        Ok(())
+    }
+
+    fn store_bytes(&mut self, addr: u64, value: &[u8]) -> Result<(), Error> {
+        self.inner.store_bytes(addr, value)?;
+        if !value.is_empty() {
+            self.stores.push((addr, value.to_vec()));
+        }
+        Ok(())
+    }
+
+    fn store_byte(&mut self, addr: u64, size: u64, value: u8) -> Result<(), Error> {
+        self.inner.store_byte(addr, size, value)?;
+        if size > 0 {
+            self.stores.push((addr, vec![value; size as usize]));
+        }
+        Ok(())
+    }
+
+    fn load_bytes(&mut self, addr: u64, size: u64) -> Result<Bytes, Error> {
+        self.inner.load_bytes(addr, size)
+    }
+
+    fn lr(&self) -> &Self::REG {
+        self.inner.lr()
+    }
+
+    fn set_lr(&mut self, value: &Self::REG) {
+        self.inner.set_lr(value);
+    }
+
+    fn set_stack_guard(&mut self, start: u64, end: u64) -> Result<(), Error> {
+        self.inner.set_stack_guard(start, end)
+    }
+
+    fn stack_guard(&self) -> (u64, u64) {
+        self.inner.stack_guard()
+    }
+
+    fn take_uninitialized_read(&mut self) -> Option<u64> {
+        self.inner.take_uninitialized_read()
+    }
+
+    fn take_watchpoint_hit(&mut self) -> Option<(u64, WatchpointKind)> {
+        self.inner.take_watchpoint_hit()
+    }
+}
+
+// Snapshots in the log never reference external data.
+#[derive(Default)]
+struct EmptyDataSource;
+
+impl DataSource<u64> for EmptyDataSource {
+    fn load_data(&self, _id: &u64, _offset: u64, _length: u64) -> Result<Bytes, Error> {
+        Err(Error::Unexpected(String::from(
+            "Execution log snapshots have no data source",
+        )))
+    }
+}
+
+pub struct Recorder<W: Write> {
+    writer: W,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            buffer: Vec::new(),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    pub fn run<Inner, M>(&mut self, machine: &mut DefaultMachine<Inner>) -> Result<i8, Error>
+    where
+        Inner: SupportMachine<MEM = RecordingMemory<M>>,
+        M: Memory<REG = Inner::REG>,
+    {
+        let mut decoder = machine.build_decoder();
+        self.run_with_decoder(machine, &mut decoder)
+    }
+
+    pub fn run_with_decoder<Inner, M, D>(
+        &mut self,
+        machine: &mut DefaultMachine<Inner>,
+        decoder: &mut D,
+    ) -> Result<i8, Error>
+    where
+        Inner: SupportMachine<MEM = RecordingMemory<M>>,
+        M: Memory<REG = Inner::REG>,
+        D: InstDecoder,
+    {
+        if machine.isa() & ISA_MOP != 0 && machine.version() == VERSION0 {
+            return Err(Error::InvalidVersion);
+        }
+        self.buffer.clear();
+        self.buffer.extend_from_slice(LOG_MAGIC);
+        self.buffer.push(LOG_FORMAT_VERSION);
+        write_snapshot(&mut self.buffer, machine)?;
+        machine.memory_mut().take_stores();
+        self.flush_buffer()?;
+        machine.set_running(true);
+        let result = self.record_steps(machine, decoder);
+        match &result {
+            Ok(exit_code) => {
+                self.buffer.push(TAG_EXIT);
+                self.buffer.push(*exit_code as u8);
+            }
+            Err(e) => {
+                let message = e.to_string();
+                self.buffer.push(TAG_ERROR);
+                write_varint(&mut self.buffer, message.len() as u64);
+                self.buffer.extend_from_slice(message.as_bytes());
+            }
+        }
+        self.flush_buffer()?;
+        self.writer.flush()?;
+        result
+    }
+
+    fn record_steps<Inner, M, D>(
+        &mut self,
+        machine: &mut DefaultMachine<Inner>,
+        decoder: &mut D,
+    ) -> Result<i8, Error>
+    where
+        Inner: SupportMachine<MEM = RecordingMemory<M>>,
+        M: Memory<REG = Inner::REG>,
+        D: InstDecoder,
+    {
+        let mut registers = [0u64; RISCV_GENERAL_REGISTER_NUMBER];
+        while machine.running() {
+            if machine.pause().has_interrupted() {
+                machine.pause().free();
+                return Err(Error::Pause);
+            }
+            for (i, v) in machine.registers().iter().enumerate() {
+                registers[i] = v.to_u64();
+            }
+            let pc = machine.pc().to_u64();
+            let cycles = machine.cycles();
+            let instruction = decoder.decode(machine.memory_mut(), pc)?;
+            let syscall = if extract_opcode(instruction) == insts::OP_ECALL {
+                Some(machine.registers()[A7].to_u64())
+            } else {
+                None
+            };
+            machine.step(decoder)?;
+            let stores = machine.memory_mut().take_stores();
+            if machine.reset_signal() {
+                decoder.reset_instructions_cache()?;
+                self.buffer.push(TAG_SNAPSHOT);
+                write_snapshot(&mut self.buffer, machine)?;
+            } else {
+                let written: Vec<(usize, u64)> = machine
+                    .registers()
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| (i, v.to_u64()))
+                    .filter(|(i, v)| registers[*i] != *v)
+                    .collect();
+                let mut tag = 0;
+                if !written.is_empty() {
+                    tag |= TAG_REGISTERS;
+                }
+                if !stores.is_empty() {
+                    tag |= TAG_STORES;
+                }
+                if syscall.is_some() {
+                    tag |= TAG_SYSCALL;
+                }
+                self.buffer.push(tag);
+                write_varint(
+                    &mut self.buffer,
+                    zigzag_encode(machine.pc().to_u64().wrapping_sub(pc) as i64),
+                );
+                write_varint(&mut self.buffer, machine.cycles().wrapping_sub(cycles));
+                if !written.is_empty() {
+                    self.buffer.push(written.len() as u8);
+                    for (i, v) in written {
+                        self.buffer.push(i as u8);
+                        write_varint(&mut self.buffer, v);
+                    }
+                }
+                if !stores.is_empty() {
+                    write_varint(&mut self.buffer, stores.len() as u64);
+                    for (addr, data) in stores {
+                        write_varint(&mut self.buffer, addr);
+                        write_varint(&mut self.buffer, data.len() as u64);
+                        self.buffer.extend_from_slice(&data);
+                    }
+                }
+                if let Some(number) = syscall {
+                    write_varint(&mut self.buffer, number);
+                }
+            }
+            if self.buffer.len() >= 0x10000 {
+                self.flush_buffer()?;
+            }
+        }
+        Ok(machine.exit_code())
+    }
+
+    fn flush_buffer(&mut self) -> Result<(), Error> {
+        self.writer.write_all(&self.buffer)?;
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+/// The effect of running one instruction.
+#[derive(Clone, Debug)]
+pub enum LogEntry {
+    Step {
+        // PC and cycles after the instruction
+        pc: u64,
+        cycles: u64,
+        registers: Vec<(u8, u64)>,
+        stores: Vec<(u64, Bytes)>,
+        // Syscall number, if the instruction is ECALL
+        syscall: Option<u64>,
+    },
+    Reset(Box<Snapshot2<u64>>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LogOutcome {
+    Exited(i8),
+    Failed(String),
+    // The log was cut short, for example the process is killed in the
+    // middle of recording.
+    Truncated,
+}
+
+#[derive(Clone, Debug)]
+pub struct ExecutionLog {
+    pub initial: Snapshot2<u64>,
+    pub entries: Vec<LogEntry>,
+    pub outcome: LogOutcome,
+}
+
+impl ExecutionLog {
+    pub fn read<R: Read>(mut reader: R) -> Result<Self, Error> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        Self::parse(&data)
+    }
+
+    pub fn parse(mut data: &[u8]) -> Result<Self, Error> {
+        let data = &mut data;
+        if read_slice(data, LOG_MAGIC.len())? != LOG_MAGIC {
+            return Err(malformed_log("invalid header"));
+        }
+        let version = read_u8(data)?;
+        if version != LOG_FORMAT_VERSION {
+            return Err(malformed_log(&format!(
+                "unsupported format version {}, expected {}",
+                version, LOG_FORMAT_VERSION
+            )));
+        }
+        let initial = decode_snapshot(data)?;
+        let mut entries = Vec::new();
+        let (mut pc, mut cycles) = (initial.pc, initial.cycles);
+        let outcome = loop {
+            if data.is_empty() {
+                break LogOutcome::Truncated;
+            }
+            match read_record(data, pc, cycles) {
+                Ok(Record::Entry(entry)) => {
+                    match &entry {
+                        LogEntry::Step {
+                            pc: new_pc,
+                            cycles: new_cycles,
+                            ..
+                        } => {
+                            pc = *new_pc;
+                            cycles = *new_cycles;
+                        }
+                        LogEntry::Reset(snapshot) => {
+                            pc = snapshot.pc;
+                            cycles = snapshot.cycles;
+                        }
+                    }
+                    entries.push(entry);
+                }
+                Ok(Record::End(outcome)) => break outcome,
+                // The last entry is incomplete
+                Err(e) if e == unexpected_end() => break LogOutcome::Truncated,
+                Err(e) => return Err(e),
+            }
+        };
+        Ok(Self {
+            initial,
+            entries,
+            outcome,
+        })
+    }
+
+    /// PC of the machine after the first n entries are applied.
+    pub fn pc_at(&self, n: usize) -> u64 {
+        match n.checked_sub(1).map(|i| &self.entries[i]) {
+            None => self.initial.pc,
+            Some(LogEntry::Step { pc, .. }) => *pc,
+            Some(LogEntry::Reset(snapshot)) => snapshot.pc,
+        }
+    }
+}
+
+/// Replays an execution log into a machine. The machine should use memory
+/// without permission checks, like SparseMemory or FlatMemory, since replay
+/// writes to pages such as the code pages directly.
+pub struct Replayer<M: SupportMachine> {
+    machine: M,
+    log: ExecutionLog,
+    // Number of entries applied to the machine
+    position: usize,
+    checkpoint_interval: usize,
+    // (position, snapshot) sorted by position
+    checkpoints: Vec<(usize, Snapshot2<u64>)>,
+    breakpoints: HashSet<u64>,
+    context: Snapshot2Context<u64, EmptyDataSource>,
+}
+
+impl<M: SupportMachine> Replayer<M> {
+    pub fn new(machine: M, log: ExecutionLog) -> Result<Self, Error> {
+        Self::new_with_checkpoint_interval(machine, log, DEFAULT_CHECKPOINT_INTERVAL)
+    }
+
+    pub fn new_with_checkpoint_interval(
+        machine: M,
+        log: ExecutionLog,
+        checkpoint_interval: usize,
+    ) -> Result<Self, Error> {
+        let initial = log.initial.clone();
+        let mut replayer = Self {
+            machine,
+            log,
+            position: 0,
+            checkpoint_interval: checkpoint_interval.max(1),
+            checkpoints: vec![],
+            breakpoints: HashSet::default(),
+            context: Snapshot2Context::default(),
+        };
+        replayer.restore(&initial)?;
+        replayer.checkpoints.push((0, initial));
+        Ok(replayer)
+    }
+
+    pub fn machine(&self) -> &M {
+        &self.machine
+    }
+
+    pub fn machine_mut(&mut self) -> &mut M {
+        &mut self.machine
+    }
+
+    pub fn log(&self) -> &ExecutionLog {
+        &self.log
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn add_breakpoint(&mut self, pc: u64) -> bool {
+        self.breakpoints.insert(pc)
+    }
+
+    pub fn remove_breakpoint(&mut self, pc: u64) -> bool {
+        self.breakpoints.remove(&pc)
+    }
+
+    /// Applies the next entry, returns false when the end of log is reached.
+    pub fn step(&mut self) -> Result<bool, Error> {
+        if self.position >= self.log.entries.len() {
+            return Ok(false);
+        }
+        match &self.log.entries[self.position] {
+            LogEntry::Step {
+                pc,
+                cycles,
+                registers,
+                stores,
+                ..
+            } => {
+                for (i, v) in registers {
+                    self.machine.set_register(*i as usize, M::REG::from_u64(*v));
+                }
+                for (addr, data) in stores {
+                    self.machine.memory_mut().store_bytes(*addr, data)?;
+                }
+                self.machine.update_pc(M::REG::from_u64(*pc));
+                self.machine.commit_pc();
+                self.machine.set_cycles(*cycles);
+            }
+            LogEntry::Reset(snapshot) => {
+                let snapshot = snapshot.as_ref().clone();
+                self.restore(&snapshot)?;
+            }
+        }
+        self.position += 1;
+        if self.position % self.checkpoint_interval == 0
+            && self.checkpoints.last().map(|(p, _)| *p) < Some(self.position)
+        {
+            let snapshot = self.context.make_snapshot(&mut self.machine)?;
+            self.checkpoints.push((self.position, snapshot));
+        }
+        Ok(true)
+    }
+
+    /// Runs forward till a breakpoint or the end of log, returns true if a
+    /// breakpoint is hit.
+    pub fn resume(&mut self) -> Result<bool, Error> {
+        while self.step()? {
+            if self.breakpoints.contains(&self.machine.pc().to_u64()) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Undoes the last applied entry, returns false at the start of log.
+    pub fn reverse_step(&mut self) -> Result<bool, Error> {
+        if self.position == 0 {
+            return Ok(false);
+        }
+        self.seek(self.position - 1)?;
+        Ok(true)
+    }
+
+    /// Runs backward till a breakpoint or the start of log, returns true if
+    /// a breakpoint is hit.
+    pub fn reverse_resume(&mut self) -> Result<bool, Error> {
+        let target = (0..self.position)
+            .rev()
+            .find(|n| self.breakpoints.contains(&self.log.pc_at(*n)));
+        self.seek(target.unwrap_or(0))?;
+        Ok(target.is_some())
+    }
+
+    /// Moves the machine to the state after the first n entries.
+    pub fn seek(&mut self, n: usize) -> Result<(), Error> {
+        let n = n.min(self.log.entries.len());
+        if n < self.position {
+            let i = self
+                .checkpoints
+                .iter()
+                .rposition(|(p, _)| *p <= n)
+                .expect("the initial checkpoint always exists");
+            // Checkpoints after the target are kept, they are still valid
+            // since the log never changes.
+            let (position, snapshot) = self.checkpoints[i].clone();
+            self.restore(&snapshot)?;
+            self.position = position;
+        }
+        while self.position < n {
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    fn restore(&mut self, snapshot: &Snapshot2<u64>) -> Result<(), Error> {
+        self.machine.memory_mut().reset_memory()?;
+        self.context.resume(&mut self.machine, snapshot)
+    }
+}
+
+enum Record {
+    Entry(LogEntry),
+    End(LogOutcome),
+}
+
+fn read_record(data: &mut &[u8], pc: u64, cycles: u64) -> Result<Record, Error> {
+    let tag = read_u8(data)?;
+    match tag {
+        TAG_EXIT => Ok(Record::End(LogOutcome::Exited(read_u8(data)? as i8))),
+        TAG_ERROR => {
+            let length = read_varint(data)? as usize;
+            let message = read_slice(data, length)?;
+            Ok(Record::End(LogOutcome::Failed(
+                String::from_utf8_lossy(message).to_string(),
+            )))
+        }
+        TAG_SNAPSHOT => Ok(Record::Entry(LogEntry::Reset(Box::new(decode_snapshot(
+            data,
+        )?)))),
+        _ if tag & !(TAG_REGISTERS | TAG_STORES | TAG_SYSCALL) == 0 => {
+            let pc = pc.wrapping_add(zigzag_decode(read_varint(data)?) as u64);
+            let cycles = cycles.wrapping_add(read_varint(data)?);
+            let mut registers = Vec::new();
+            if tag & TAG_REGISTERS != 0 {
+                for _ in 0..read_u8(data)? {
+                    let i = read_u8(data)?;
+                    if i as usize >= RISCV_GENERAL_REGISTER_NUMBER {
+                        return Err(malformed_log("invalid register"));
+                    }
+                    registers.push((i, read_varint(data)?));
+                }
+            }
+            let mut stores = Vec::new();
+            if tag & TAG_STORES != 0 {
+                for _ in 0..read_varint(data)? {
+                    let addr = read_varint(data)?;
+                    let length = read_varint(data)? as usize;
+                    stores.push((addr, Bytes::copy_from_slice(read_slice(data, length)?)));
+                }
+            }
+            let syscall = if tag & TAG_SYSCALL != 0 {
+                Some(read_varint(data)?)
+            } else {
+                None
+            };
+            Ok(Record::Entry(LogEntry::Step {
+                pc,
+                cycles,
+                registers,
+                stores,
+                syscall,
+            }))
+        }
+        _ => Err(malformed_log("invalid tag")),
+    }
+}
+
+fn write_snapshot<M: SupportMachine>(buffer: &mut Vec<u8>, machine: &mut M) -> Result<(), Error> {
+    let snapshot = Snapshot2Context::<u64, EmptyDataSource>::default().make_snapshot(machine)?;
+    encode_snapshot(buffer, &snapshot);
+    Ok(())
+}
+
+/// Encodes a snapshot in the compact format used by execution logs, which is
+/// also suitable for storing snapshots as standalone files.
+pub fn encode_snapshot(buffer: &mut Vec<u8>, snapshot: &Snapshot2<u64>) {
+    write_varint(buffer, u64::from(snapshot.version));
+    for v in snapshot.registers.iter() {
+        write_varint(buffer, *v);
+    }
+    if snapshot.version >= VERSION3 {
+        match &snapshot.float {
+            Some(float) => {
+                buffer.push(1);
+                for v in float.fregisters.iter() {
+                    write_varint(buffer, *v);
+                }
+                write_varint(buffer, u64::from(float.fcsr));
+            }
+            None => buffer.push(0),
+        }
+        match &snapshot.vector {
+            Some(vector) => {
+                buffer.push(1);
+                write_varint(buffer, vector.vregisters.len() as u64);
+                buffer.extend_from_slice(&vector.vregisters);
+                write_varint(buffer, vector.vl);
+                write_varint(buffer, vector.vtype);
+            }
+            None => buffer.push(0),
+        }
+    }
+    write_varint(buffer, snapshot.pc);
+    write_varint(buffer, snapshot.cycles);
+    write_varint(buffer, snapshot.max_cycles);
+    write_varint(buffer, snapshot.load_reservation_address);
+    write_varint(buffer, snapshot.dirty_pages.len() as u64);
+    for (address, flag, content) in &snapshot.dirty_pages {
+        write_varint(buffer, *address);
+        buffer.push(*flag);
+        write_varint(buffer, content.len() as u64);
+        buffer.extend_from_slice(content);
+    }
+    write_varint(buffer, snapshot.pages_from_source.len() as u64);
+    for (address, flag, id, offset, length) in &snapshot.pages_from_source {
+        write_varint(buffer, *address);
+        buffer.push(*flag);
+        write_varint(buffer, *id);
+        write_varint(buffer, *offset);
+        write_varint(buffer, *length);
+    }
+}
+
+/// Decodes a snapshot written by encode_snapshot, advancing data past it.
+pub fn decode_snapshot(data: &mut &[u8]) -> Result<Snapshot2<u64>, Error> {
+    let version = read_varint(data)? as u32;
+    let mut registers = [0u64; RISCV_GENERAL_REGISTER_NUMBER];
+    for v in registers.iter_mut() {
+        *v = read_varint(data)?;
+    }
+    let mut float = None;
+    let mut vector = None;
+    if version >= VERSION3 {
+        if read_u8(data)? != 0 {
+            let mut fregisters = [0u64; RISCV_FLOAT_REGISTER_NUMBER];
+            for v in fregisters.iter_mut() {
+                *v = read_varint(data)?;
+            }
+            let fcsr = read_varint(data)? as u32;
+            float = Some(FloatState { fregisters, fcsr });
+        }
+        if read_u8(data)? != 0 {
+            let length = read_varint(data)? as usize;
+            vector = Some(VectorState {
+                vregisters: read_slice(data, length)?.to_vec(),
+                vl: read_varint(data)?,
+                vtype: read_varint(data)?,
+            });
+        }
+    }
+    let pc = read_varint(data)?;
+    let cycles = read_varint(data)?;
+    let max_cycles = read_varint(data)?;
+    let load_reservation_address = read_varint(data)?;
+    let mut dirty_pages = vec![];
+    for _ in 0..read_varint(data)? {
+        let address = read_varint(data)?;
+        let flag = read_u8(data)?;
+        let length = read_varint(data)? as usize;
+        dirty_pages.push((address, flag, read_slice(data, length)?.to_vec()));
+    }
+    let mut pages_from_source = vec![];
+    for _ in 0..read_varint(data)? {
+        let address = read_varint(data)?;
+        let flag = read_u8(data)?;
+        pages_from_source.push((
+            address,
+            flag,
+            read_varint(data)?,
+            read_varint(data)?,
+            read_varint(data)?,
+        ));
+    }
+    Ok(Snapshot2 {
+        pages_from_source,
+        dirty_pages,
+        version,
+        registers,
+        pc,
+        cycles,
+        max_cycles,
+        load_reservation_address,
+        float,
+        vector,
+    })
+}
+
+fn zigzag_encode(v: i64) -> u64 {
+    ((v << 1) ^ (v >> 63)) as u64
+}
+
+fn zigzag_decode(v: u64) -> i64 {
+    ((v >> 1) as i64) ^ -((v & 1) as i64)
+}
+
+fn write_varint(buffer: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        buffer.push((v as u8) | 0x80);
+        v >>= 7;
+    }
+    buffer.push(v as u8);
+}
+
+fn read_varint(data: &mut &[u8]) -> Result<u64, Error> {
+    let mut result = 0u64;
+    let mut shift = 0;
+    loop {
+        let b = read_u8(data)?;
+        if shift >= 64 {
+            return Err(malformed_log("varint overflow"));
+        }
+        result |= u64::from(b & 0x7f) << shift;
+        if b & 0x80 == 0 {
+            return Ok(result);
+        }
+        shift += 7;
+    }
+}
+
+fn read_u8(data: &mut &[u8]) -> Result<u8, Error> {
+    Ok(read_slice(data, 1)?[0])
+}
+
+fn read_slice<'a>(data: &mut &'a [u8], length: usize) -> Result<&'a [u8], Error> {
+    if data.len() < length {
+        return Err(unexpected_end());
+    }
+    let (head, tail) = data.split_at(length);
+    *data = tail;
+    Ok(head)
+}
+
+fn unexpected_end() -> Error {
+    malformed_log("unexpected end of log")
+}
+
+fn malformed_log(reason: &str) -> Error {
+    Error::IO {
+        kind: ErrorKind::InvalidData,
+        data: format!("malformed execution log: {}", reason),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_varint() {
+        for v in [0, 1, 0x7f, 0x80, 0x3fff, 0x4000, u64::MAX] {
+            let mut buffer = vec![];
+            write_varint(&mut buffer, v);
+            assert_eq!(read_varint(&mut &buffer[..]).unwrap(), v);
+        }
+        for v in [0, 1, -1, 4, -4, i64::MAX, i64::MIN] {
+            assert_eq!(zigzag_decode(zigzag_encode(v)), v);
+        }
+        assert!(read_varint(&mut &[0x80u8][..]).is_err());
+    }
+}
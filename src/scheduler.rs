@@ -0,0 +1,750 @@
+// Processes spawning other processes. Instead of running a child machine
+// from inside the syscall of its parent, which nests a machine on the host
+// stack for each level of spawning, a Scheduler owns every machine of the
+// process tree and runs them one after another from a loop. A process runs
+// until it exits or makes a syscall that cannot complete yet, such as
+// waiting for a child or reading from an empty pipe, then the syscall yields
+// and the next ready process runs. A yielded syscall is made again once it
+// can proceed, so processes only see blocking syscalls.
+//
+// The processes of a tree share a cycle budget, exchange data through pipes
+// whose ends are file descriptors passed to children when they are spawned,
+// and are paused and snapshotted together. The tree exits when the root
+// process does, a failing process fails the whole tree.
+//
+// Syscalls, their arguments are in A0 and onwards and their status is
+// returned in A0:
+//   2601 spawn(index, argc, argv, fds, pid): starts the program at index of
+//        the scheduler with argv, fds points to a list of descriptors ended
+//        by 0, which move to the child, and the pid of the child is written
+//        to the u64 at pid
+//   2602 wait(pid, code): waits for a child to exit and writes its exit code
+//        to the byte at code
+//   2603 process_id(): returns the pid of the caller in A0, the root is 0
+//   2604 pipe(fds): creates a pipe and writes its read and write ends to the
+//        two u64 at fds
+//   2605 write(fd, buffer, length): writes at most the u64 at length bytes,
+//        as many as fit in the pipe, and updates length to the bytes written
+//   2606 read(fd, buffer, length): reads at most the u64 at length bytes,
+//        and updates length to the bytes read, 0 once the write end is closed
+//        and the pipe is empty
+//   2607 inherited_fds(fds, length): writes at most the u64 at length of the
+//        descriptors passed by the parent, and updates length to their count
+//   2608 close(fd)
+use crate::{
+    elf::parse_elf,
+    machine::{trace::TraceMachine, DefaultMachine, Pause, SupportMachine},
+    memory::Memory,
+    registers::{A0, A1, A2, A3, A4, A7},
+    snapshot2::{DataSource, Snapshot2, Snapshot2Context},
+    Bytes, CoreMachine, Error, Register, Syscalls,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+pub const SPAWN: u64 = 2601;
+pub const WAIT: u64 = 2602;
+pub const PROCESS_ID: u64 = 2603;
+pub const PIPE: u64 = 2604;
+pub const WRITE: u64 = 2605;
+pub const READ: u64 = 2606;
+pub const INHERITED_FDS: u64 = 2607;
+pub const CLOSE: u64 = 2608;
+
+pub const SUCCESS: u64 = 0;
+pub const INDEX_OUT_OF_BOUND: u64 = 1;
+pub const WAIT_FAILURE: u64 = 5;
+pub const INVALID_FD: u64 = 6;
+pub const OTHER_END_CLOSED: u64 = 7;
+pub const MAX_PROCESSES_SPAWNED: u64 = 8;
+pub const MAX_FDS_CREATED: u64 = 9;
+
+pub const DEFAULT_MAX_PROCESSES: usize = 16;
+pub const DEFAULT_MAX_FDS: usize = 64;
+pub const DEFAULT_PIPE_CAPACITY: usize = 4096;
+
+/// A machine run by a Scheduler.
+pub trait ScheduledMachine {
+    type Inner: SupportMachine;
+
+    fn machine(&self) -> &DefaultMachine<Self::Inner>;
+    fn machine_mut(&mut self) -> &mut DefaultMachine<Self::Inner>;
+    fn run(&mut self) -> Result<i8, Error>;
+}
+
+impl<Inner: SupportMachine> ScheduledMachine for DefaultMachine<Inner> {
+    type Inner = Inner;
+
+    fn machine(&self) -> &DefaultMachine<Inner> {
+        self
+    }
+
+    fn machine_mut(&mut self) -> &mut DefaultMachine<Inner> {
+        self
+    }
+
+    fn run(&mut self) -> Result<i8, Error> {
+        DefaultMachine::run(self)
+    }
+}
+
+impl<Inner: SupportMachine> ScheduledMachine for TraceMachine<Inner> {
+    type Inner = Inner;
+
+    fn machine(&self) -> &DefaultMachine<Inner> {
+        &self.machine
+    }
+
+    fn machine_mut(&mut self) -> &mut DefaultMachine<Inner> {
+        &mut self.machine
+    }
+
+    fn run(&mut self) -> Result<i8, Error> {
+        TraceMachine::run(self)
+    }
+}
+
+#[cfg(has_asm)]
+impl ScheduledMachine for crate::machine::asm::AsmMachine {
+    type Inner = Box<crate::machine::asm::AsmCoreMachine>;
+
+    fn machine(&self) -> &DefaultMachine<Self::Inner> {
+        &self.machine
+    }
+
+    fn machine_mut(&mut self) -> &mut DefaultMachine<Self::Inner> {
+        &mut self.machine
+    }
+
+    fn run(&mut self) -> Result<i8, Error> {
+        crate::machine::asm::AsmMachine::run(self)
+    }
+}
+
+/// Builds the machine of a process, the given syscalls implement the
+/// syscalls of the scheduler and must be added to the machine.
+pub type MachineFactory<M> =
+    dyn Fn(Box<dyn Syscalls<<M as ScheduledMachine>::Inner>>) -> M + Send + Sync;
+
+// The programs of a scheduler, pages loaded from them are left out of
+// snapshots.
+#[derive(Clone)]
+struct Programs(Arc<Vec<Bytes>>);
+
+impl DataSource<u64> for Programs {
+    fn load_data(&self, id: &u64, offset: u64, length: u64) -> Result<Bytes, Error> {
+        let program = self
+            .0
+            .get(*id as usize)
+            .ok_or_else(|| Error::Unexpected(format!("program {} is missing", id)))?;
+        match offset.checked_add(length) {
+            Some(end) if end <= program.len() as u64 => {
+                Ok(program.slice(offset as usize..end as usize))
+            }
+            _ => Err(Error::Unexpected(format!(
+                "data out of program {} is requested",
+                id
+            ))),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+enum Blocked {
+    Wait(u64),
+    Read(u64),
+    Write(u64),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+enum Status {
+    Ready,
+    Blocked(Blocked),
+    Exited(i8),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Process {
+    parent: Option<u64>,
+    program: u64,
+    // Kept until the machine is created.
+    args: Vec<Vec<u8>>,
+    status: Status,
+    inherited_fds: Vec<u64>,
+    // Cycles of an exited process, whose machine is gone.
+    cycles: u64,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct Pipe {
+    data: VecDeque<u8>,
+    read_open: bool,
+    write_open: bool,
+}
+
+// The state shared by the scheduler and the syscalls of its processes.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct State {
+    program_count: u64,
+    max_processes: usize,
+    max_fds: usize,
+    pipe_capacity: usize,
+    processes: BTreeMap<u64, Process>,
+    next_pid: u64,
+    pipes: BTreeMap<u64, Pipe>,
+    next_pipe: u64,
+    // Descriptor -> owner. The read end of pipe n is 2n and its write end is
+    // 2n + 1, pipes are numbered from 1.
+    fds: BTreeMap<u64, u64>,
+    // The last process run, the next one is searched from it. A process
+    // stops running only when it blocks or exits, unless it is interrupted,
+    // so it goes on first when the tree runs again.
+    current: u64,
+}
+
+impl State {
+    fn alive(&self) -> usize {
+        self.processes
+            .values()
+            .filter(|p| !matches!(p.status, Status::Exited(_)))
+            .count()
+    }
+
+    fn ready(&self, status: Status) -> bool {
+        match status {
+            Status::Ready => true,
+            Status::Blocked(Blocked::Wait(pid)) => {
+                matches!(self.processes[&pid].status, Status::Exited(_))
+            }
+            Status::Blocked(Blocked::Read(fd)) => {
+                let pipe = &self.pipes[&(fd / 2)];
+                !pipe.data.is_empty() || !pipe.write_open
+            }
+            Status::Blocked(Blocked::Write(fd)) => {
+                let pipe = &self.pipes[&(fd / 2)];
+                pipe.data.len() < self.pipe_capacity || !pipe.read_open
+            }
+            Status::Exited(_) => false,
+        }
+    }
+
+    // The first ready process from the current one, in the order of pids.
+    fn next(&self) -> Option<u64> {
+        let after = self.processes.range(self.current..);
+        let before = self.processes.range(..self.current);
+        after
+            .chain(before)
+            .find(|(_, p)| self.ready(p.status))
+            .map(|(pid, _)| *pid)
+    }
+
+    fn close(&mut self, fd: u64) {
+        self.fds.remove(&fd);
+        let pipe = self.pipes.get_mut(&(fd / 2)).unwrap();
+        if fd % 2 == 0 {
+            pipe.read_open = false;
+        } else {
+            pipe.write_open = false;
+        }
+        if !pipe.read_open && !pipe.write_open {
+            self.pipes.remove(&(fd / 2));
+        }
+    }
+
+    fn exit(&mut self, pid: u64, code: i8, cycles: u64) {
+        let owned: Vec<u64> = self
+            .fds
+            .iter()
+            .filter(|(_, owner)| **owner == pid)
+            .map(|(fd, _)| *fd)
+            .collect();
+        for fd in owned {
+            self.close(fd);
+        }
+        let process = self.processes.get_mut(&pid).unwrap();
+        process.status = Status::Exited(code);
+        process.cycles = cycles;
+    }
+}
+
+// The syscalls of the scheduler, as seen by process pid.
+struct ProcessSyscalls {
+    pid: u64,
+    state: Arc<Mutex<State>>,
+}
+
+impl ProcessSyscalls {
+    fn spawn<Mac: SupportMachine>(&mut self, machine: &mut Mac) -> Result<u64, Error> {
+        let arg = |i: usize| machine.registers()[i].to_u64();
+        let (index, argc, argv, fds, pid_address) = (arg(A0), arg(A1), arg(A2), arg(A3), arg(A4));
+        let word_size = u64::from(Mac::REG::BITS / 8);
+        let mut args = vec![];
+        for i in 0..argc {
+            let address = load_word(machine, argv.wrapping_add(i * word_size), word_size)?;
+            args.push(load_c_string(machine, address)?);
+        }
+        let fds = load_fds(machine, fds)?;
+        let mut state = self.state.lock().unwrap();
+        if index >= state.program_count {
+            return Ok(INDEX_OUT_OF_BOUND);
+        }
+        if fds.iter().any(|fd| state.fds.get(fd) != Some(&self.pid)) {
+            return Ok(INVALID_FD);
+        }
+        if state.alive() >= state.max_processes {
+            return Ok(MAX_PROCESSES_SPAWNED);
+        }
+        let pid = state.next_pid;
+        store_u64(machine, pid_address, pid)?;
+        for fd in &fds {
+            state.fds.insert(*fd, pid);
+        }
+        state.next_pid += 1;
+        state.processes.insert(
+            pid,
+            Process {
+                parent: Some(self.pid),
+                program: index,
+                args,
+                status: Status::Ready,
+                inherited_fds: fds,
+                cycles: 0,
+            },
+        );
+        Ok(SUCCESS)
+    }
+
+    fn wait<Mac: SupportMachine>(&mut self, machine: &mut Mac) -> Result<u64, Error> {
+        let (pid, code_address) = (
+            machine.registers()[A0].to_u64(),
+            machine.registers()[A1].to_u64(),
+        );
+        let state = self.state.lock().unwrap();
+        match state.processes.get(&pid) {
+            Some(child) if child.parent == Some(self.pid) => match child.status {
+                Status::Exited(code) => {
+                    machine
+                        .memory_mut()
+                        .store_bytes(code_address, &[code as u8])?;
+                    Ok(SUCCESS)
+                }
+                _ => Err(Error::Yield),
+            },
+            _ => Ok(WAIT_FAILURE),
+        }
+    }
+
+    fn pipe<Mac: SupportMachine>(&mut self, machine: &mut Mac) -> Result<u64, Error> {
+        let address = machine.registers()[A0].to_u64();
+        let mut state = self.state.lock().unwrap();
+        if state.fds.len() + 2 > state.max_fds {
+            return Ok(MAX_FDS_CREATED);
+        }
+        let id = state.next_pipe;
+        store_u64(machine, address, id * 2)?;
+        store_u64(machine, address.wrapping_add(8), id * 2 + 1)?;
+        state.next_pipe += 1;
+        state.pipes.insert(
+            id,
+            Pipe {
+                data: VecDeque::new(),
+                read_open: true,
+                write_open: true,
+            },
+        );
+        state.fds.insert(id * 2, self.pid);
+        state.fds.insert(id * 2 + 1, self.pid);
+        Ok(SUCCESS)
+    }
+
+    fn transfer<Mac: SupportMachine>(
+        &mut self,
+        machine: &mut Mac,
+        write: bool,
+    ) -> Result<u64, Error> {
+        let arg = |i: usize| machine.registers()[i].to_u64();
+        let (fd, buffer, length_address) = (arg(A0), arg(A1), arg(A2));
+        let length = load_u64(machine, length_address)?;
+        let mut state = self.state.lock().unwrap();
+        if state.fds.get(&fd) != Some(&self.pid) || (fd % 2 == 1) != write {
+            return Ok(INVALID_FD);
+        }
+        let capacity = state.pipe_capacity;
+        let pipe = state.pipes.get_mut(&(fd / 2)).unwrap();
+        let count = if write {
+            if !pipe.read_open {
+                return Ok(OTHER_END_CLOSED);
+            }
+            let count = length.min((capacity - pipe.data.len()) as u64);
+            if count == 0 && length > 0 {
+                return Err(Error::Yield);
+            }
+            let data = machine.memory_mut().load_bytes(buffer, count)?;
+            pipe.data.extend(data.iter());
+            count
+        } else {
+            let count = length.min(pipe.data.len() as u64);
+            if count == 0 && length > 0 && pipe.write_open {
+                return Err(Error::Yield);
+            }
+            let data: Vec<u8> = pipe.data.drain(..count as usize).collect();
+            machine.memory_mut().store_bytes(buffer, &data)?;
+            count
+        };
+        store_u64(machine, length_address, count)?;
+        Ok(SUCCESS)
+    }
+
+    fn inherited_fds<Mac: SupportMachine>(&mut self, machine: &mut Mac) -> Result<u64, Error> {
+        let (address, length_address) = (
+            machine.registers()[A0].to_u64(),
+            machine.registers()[A1].to_u64(),
+        );
+        let length = load_u64(machine, length_address)?;
+        let fds = self.state.lock().unwrap().processes[&self.pid]
+            .inherited_fds
+            .clone();
+        for (i, fd) in fds.iter().take(length as usize).enumerate() {
+            store_u64(machine, address.wrapping_add(i as u64 * 8), *fd)?;
+        }
+        store_u64(machine, length_address, fds.len() as u64)?;
+        Ok(SUCCESS)
+    }
+
+    fn close<Mac: SupportMachine>(&mut self, machine: &mut Mac) -> Result<u64, Error> {
+        let fd = machine.registers()[A0].to_u64();
+        let mut state = self.state.lock().unwrap();
+        if state.fds.get(&fd) != Some(&self.pid) {
+            return Ok(INVALID_FD);
+        }
+        state.close(fd);
+        Ok(SUCCESS)
+    }
+
+    // Marks the process as blocked before a syscall yields.
+    fn block(&mut self, blocked: Blocked) {
+        let mut state = self.state.lock().unwrap();
+        state.processes.get_mut(&self.pid).unwrap().status = Status::Blocked(blocked);
+    }
+}
+
+impl<Mac: SupportMachine> Syscalls<Mac> for ProcessSyscalls {
+    fn initialize(&mut self, _machine: &mut Mac) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn ecall(&mut self, machine: &mut Mac) -> Result<bool, Error> {
+        let a0 = machine.registers()[A0].to_u64();
+        let result = match machine.registers()[A7].to_u64() {
+            SPAWN => self.spawn(machine),
+            WAIT => self.wait(machine).map_err(|e| {
+                if e == Error::Yield {
+                    self.block(Blocked::Wait(a0));
+                }
+                e
+            }),
+            PROCESS_ID => Ok(self.pid),
+            PIPE => self.pipe(machine),
+            WRITE | READ => {
+                let write = machine.registers()[A7].to_u64() == WRITE;
+                self.transfer(machine, write).map_err(|e| {
+                    if e == Error::Yield {
+                        self.block(if write {
+                            Blocked::Write(a0)
+                        } else {
+                            Blocked::Read(a0)
+                        });
+                    }
+                    e
+                })
+            }
+            INHERITED_FDS => self.inherited_fds(machine),
+            CLOSE => self.close(machine),
+            _ => return Ok(false),
+        }?;
+        machine.set_register(A0, Mac::REG::from_u64(result));
+        Ok(true)
+    }
+
+    fn name(&self) -> &str {
+        "scheduler"
+    }
+}
+
+fn load_u64<Mac: SupportMachine>(machine: &mut Mac, address: u64) -> Result<u64, Error> {
+    let data = machine.memory_mut().load_bytes(address, 8)?;
+    Ok(u64::from_le_bytes(data[..].try_into().unwrap()))
+}
+
+fn store_u64<Mac: SupportMachine>(
+    machine: &mut Mac,
+    address: u64,
+    value: u64,
+) -> Result<(), Error> {
+    machine
+        .memory_mut()
+        .store_bytes(address, &value.to_le_bytes())
+}
+
+fn load_word<Mac: SupportMachine>(
+    machine: &mut Mac,
+    address: u64,
+    word_size: u64,
+) -> Result<u64, Error> {
+    let data = machine.memory_mut().load_bytes(address, word_size)?;
+    let mut word = [0u8; 8];
+    word[..data.len()].copy_from_slice(&data);
+    Ok(u64::from_le_bytes(word))
+}
+
+fn load_c_string<Mac: SupportMachine>(machine: &mut Mac, address: u64) -> Result<Vec<u8>, Error> {
+    let mut data = vec![];
+    loop {
+        let byte = machine
+            .memory_mut()
+            .load8(&Mac::REG::from_u64(address.wrapping_add(data.len() as u64)))?
+            .to_u8();
+        if byte == 0 {
+            return Ok(data);
+        }
+        data.push(byte);
+    }
+}
+
+fn load_fds<Mac: SupportMachine>(machine: &mut Mac, address: u64) -> Result<Vec<u64>, Error> {
+    let mut fds = vec![];
+    if address == 0 {
+        return Ok(fds);
+    }
+    loop {
+        let fd = load_u64(machine, address.wrapping_add(fds.len() as u64 * 8))?;
+        if fd == 0 {
+            return Ok(fds);
+        }
+        fds.push(fd);
+    }
+}
+
+/// The state of a process tree, see Scheduler::snapshot.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SchedulerSnapshot {
+    state: State,
+    machines: Vec<(u64, Snapshot2<u64>)>,
+}
+
+pub struct Scheduler<M: ScheduledMachine> {
+    programs: Programs,
+    factory: Box<MachineFactory<M>>,
+    max_cycles: u64,
+    pause: Pause,
+    state: Arc<Mutex<State>>,
+    machines: BTreeMap<u64, (M, Snapshot2Context<u64, Programs>)>,
+}
+
+impl<M: ScheduledMachine> Scheduler<M> {
+    /// Creates a scheduler running programs, the root process runs the first
+    /// one with args, and the whole tree consumes at most max_cycles.
+    pub fn new(
+        programs: Vec<Bytes>,
+        args: &[Bytes],
+        max_cycles: u64,
+        factory: Box<MachineFactory<M>>,
+    ) -> Self {
+        assert!(!programs.is_empty(), "the root process needs a program");
+        let root = Process {
+            parent: None,
+            program: 0,
+            args: args.iter().map(|a| a.to_vec()).collect(),
+            status: Status::Ready,
+            inherited_fds: vec![],
+            cycles: 0,
+        };
+        let state = State {
+            program_count: programs.len() as u64,
+            max_processes: DEFAULT_MAX_PROCESSES,
+            max_fds: DEFAULT_MAX_FDS,
+            pipe_capacity: DEFAULT_PIPE_CAPACITY,
+            processes: vec![(0, root)].into_iter().collect(),
+            next_pid: 1,
+            next_pipe: 1,
+            ..Default::default()
+        };
+        Self::with_state(programs, max_cycles, factory, state)
+    }
+
+    /// Rebuilds the process tree of a snapshot made by a scheduler of the
+    /// same programs, whose machines are built the same way.
+    pub fn resume(
+        programs: Vec<Bytes>,
+        snapshot: &SchedulerSnapshot,
+        max_cycles: u64,
+        factory: Box<MachineFactory<M>>,
+    ) -> Result<Self, Error> {
+        let mut scheduler = Self::with_state(programs, max_cycles, factory, snapshot.state.clone());
+        for (pid, machine_snapshot) in &snapshot.machines {
+            let mut machine = scheduler.build_machine(*pid);
+            let mut context = Snapshot2Context::new(scheduler.programs.clone());
+            context.resume(machine.machine_mut(), machine_snapshot)?;
+            scheduler.machines.insert(*pid, (machine, context));
+        }
+        Ok(scheduler)
+    }
+
+    fn with_state(
+        programs: Vec<Bytes>,
+        max_cycles: u64,
+        factory: Box<MachineFactory<M>>,
+        state: State,
+    ) -> Self {
+        Self {
+            programs: Programs(Arc::new(programs)),
+            factory,
+            max_cycles,
+            pause: Pause::new(),
+            state: Arc::new(Mutex::new(state)),
+            machines: BTreeMap::new(),
+        }
+    }
+
+    /// At most count processes are alive at the same time, spawning more
+    /// fails with MAX_PROCESSES_SPAWNED.
+    pub fn max_processes(self, count: usize) -> Self {
+        self.state.lock().unwrap().max_processes = count;
+        self
+    }
+
+    /// At most count descriptors are open at the same time, creating more
+    /// fails with MAX_FDS_CREATED.
+    pub fn max_fds(self, count: usize) -> Self {
+        self.state.lock().unwrap().max_fds = count;
+        self
+    }
+
+    /// Bytes a pipe holds before writes to it block.
+    pub fn pipe_capacity(self, capacity: usize) -> Self {
+        assert!(capacity > 0, "a pipe must hold at least one byte");
+        self.state.lock().unwrap().pipe_capacity = capacity;
+        self
+    }
+
+    /// Pausing stops the running process, run returns Error::Pause and the
+    /// tree resumes on the next run.
+    pub fn pause(&self) -> Pause {
+        self.pause.clone()
+    }
+
+    pub fn max_cycles(&self) -> u64 {
+        self.max_cycles
+    }
+
+    /// Changes the budget, such as to continue a tree which has run out of
+    /// cycles.
+    pub fn set_max_cycles(&mut self, max_cycles: u64) {
+        self.max_cycles = max_cycles;
+    }
+
+    /// Cycles consumed by every process of the tree.
+    pub fn cycles(&self) -> u64 {
+        let state = self.state.lock().unwrap();
+        let exited: u64 = state.processes.values().map(|p| p.cycles).sum();
+        self.machines.values().fold(exited, |sum, (machine, _)| {
+            sum.wrapping_add(machine.machine().cycles())
+        })
+    }
+
+    /// Pids of the processes which have not exited.
+    pub fn processes(&self) -> Vec<u64> {
+        let state = self.state.lock().unwrap();
+        state
+            .processes
+            .iter()
+            .filter(|(_, p)| !matches!(p.status, Status::Exited(_)))
+            .map(|(pid, _)| *pid)
+            .collect()
+    }
+
+    /// Runs the tree until the root process exits, and returns its exit code.
+    pub fn run(&mut self) -> Result<i8, Error> {
+        loop {
+            if let Status::Exited(code) = self.state.lock().unwrap().processes[&0].status {
+                return Ok(code);
+            }
+            let pid = {
+                let mut state = self.state.lock().unwrap();
+                let pid = state.next().ok_or(Error::Deadlock)?;
+                state.current = pid;
+                state.processes.get_mut(&pid).unwrap().status = Status::Ready;
+                pid
+            };
+            if !self.machines.contains_key(&pid) {
+                self.start(pid)?;
+            }
+            let cycles = self.cycles();
+            let remaining = self.max_cycles.saturating_sub(cycles);
+            let (machine, _) = self.machines.get_mut(&pid).unwrap();
+            let max_cycles = machine.machine_mut().cycles().saturating_add(remaining);
+            machine.machine_mut().set_max_cycles(max_cycles);
+            match machine.run() {
+                Ok(code) => {
+                    let cycles = machine.machine_mut().cycles();
+                    self.machines.remove(&pid);
+                    self.state.lock().unwrap().exit(pid, code, cycles);
+                }
+                Err(Error::Yield) => {
+                    // Runs the ecall again once the process can proceed.
+                    let pc = machine.machine_mut().pc().to_u64().wrapping_sub(4);
+                    machine
+                        .machine_mut()
+                        .update_pc(<M::Inner as CoreMachine>::REG::from_u64(pc));
+                    machine.machine_mut().commit_pc();
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Takes a snapshot of the tree, which must not be running, such as
+    /// after run returns Error::Pause or Error::CyclesExceeded.
+    pub fn snapshot(&mut self) -> Result<SchedulerSnapshot, Error> {
+        let mut machines = vec![];
+        for (pid, (machine, context)) in self.machines.iter_mut() {
+            machines.push((*pid, context.make_snapshot(machine.machine_mut())?));
+        }
+        Ok(SchedulerSnapshot {
+            state: self.state.lock().unwrap().clone(),
+            machines,
+        })
+    }
+
+    fn build_machine(&self, pid: u64) -> M {
+        let syscalls = ProcessSyscalls {
+            pid,
+            state: Arc::clone(&self.state),
+        };
+        let mut machine = (self.factory)(Box::new(syscalls));
+        machine.machine_mut().set_pause(self.pause.clone());
+        machine
+    }
+
+    // Creates the machine of a spawned process and loads its program.
+    fn start(&mut self, pid: u64) -> Result<(), Error> {
+        let (index, args) = {
+            let mut state = self.state.lock().unwrap();
+            let process = state.processes.get_mut(&pid).unwrap();
+            (process.program, std::mem::take(&mut process.args))
+        };
+        let args: Vec<Bytes> = args.into_iter().map(Bytes::from).collect();
+        let program = self.programs.0[index as usize].clone();
+        let mut machine = self.build_machine(pid);
+        let mut context = Snapshot2Context::new(self.programs.clone());
+        let metadata =
+            parse_elf::<<M::Inner as CoreMachine>::REG>(&program, machine.machine_mut().version())?;
+        machine
+            .machine_mut()
+            .load_program_with_metadata(&program, &metadata, &args)?;
+        context.mark_program(machine.machine_mut(), &metadata, &index, 0)?;
+        self.machines.insert(pid, (machine, context));
+        Ok(())
+    }
+}
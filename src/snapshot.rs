@@ -1,7 +1,10 @@
 use crate::instructions::Register;
 use crate::memory::Memory;
 use crate::memory::FLAG_DIRTY;
-use crate::{CoreMachine, Error, RISCV_GENERAL_REGISTER_NUMBER, RISCV_PAGESIZE, RISCV_PAGE_SHIFTS};
+use crate::{
+    CoreMachine, Error, ISA_D, ISA_F, ISA_V, RISCV_FLOAT_REGISTER_NUMBER,
+    RISCV_GENERAL_REGISTER_NUMBER, RISCV_PAGESIZE, RISCV_PAGE_SHIFTS, RISCV_VTYPE_VILL,
+};
 use serde::{Deserialize, Serialize};
 
 // Snapshot provides a mechanism for suspending and resuming a virtual machine.
@@ -15,6 +18,11 @@ use serde::{Deserialize, Serialize};
 //   - machine.version
 //   - machine.pc
 //   - machine.registers
+//   - machine.fregisters and machine.fcsr, only for machines with the F or D
+//     extension, other snapshots leave them out when serialized
+//   - machine.vregisters, machine.vl and machine.vtype, only for machines
+//     with the V extension, the vector registers are left empty when they
+//     are all zero
 //
 // For memory, the situation becomes more complicated. Every memory page has
 // page flag where each page flag stores a optional FLAG_DIRTY. When this page
@@ -33,6 +41,120 @@ pub struct Snapshot {
     pub page_flags: Vec<u8>,
     pub pages: Vec<Vec<u8>>,
     pub load_reservation_address: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub float: Option<FloatState>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vector: Option<VectorState>,
+}
+
+/// The registers of the F and D extensions.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct FloatState {
+    pub fregisters: [u64; RISCV_FLOAT_REGISTER_NUMBER],
+    pub fcsr: u32,
+}
+
+impl FloatState {
+    /// Saves the registers of a machine with the F or D extension.
+    pub fn save<T: CoreMachine>(machine: &T) -> Option<Self> {
+        if machine.isa() & (ISA_F | ISA_D) == 0 {
+            return None;
+        }
+        let mut fregisters = [0u64; RISCV_FLOAT_REGISTER_NUMBER];
+        fregisters.copy_from_slice(machine.fregisters());
+        Some(Self {
+            fregisters,
+            fcsr: machine.fcsr(),
+        })
+    }
+
+    /// Restores the registers saved by save, they are cleared when the
+    /// snapshot has none.
+    pub fn restore<T: CoreMachine>(state: Option<&Self>, machine: &mut T) -> Result<(), Error> {
+        let supported = machine.isa() & (ISA_F | ISA_D) != 0;
+        match state {
+            Some(state) if supported => {
+                for (i, v) in state.fregisters.iter().enumerate() {
+                    machine.set_fregister(i, *v)?;
+                }
+                machine.set_fcsr(state.fcsr)?;
+            }
+            Some(_) => {
+                return Err(Error::Unexpected(String::from(
+                    "Floating-point registers are not supported by the machine",
+                )))
+            }
+            None if supported => {
+                for i in 0..RISCV_FLOAT_REGISTER_NUMBER {
+                    machine.set_fregister(i, 0)?;
+                }
+                machine.set_fcsr(0)?;
+            }
+            None => {}
+        }
+        Ok(())
+    }
+}
+
+/// The registers and CSRs of the V extension.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct VectorState {
+    /// Empty when every register is zero.
+    pub vregisters: Vec<u8>,
+    pub vl: u64,
+    pub vtype: u64,
+}
+
+impl VectorState {
+    /// Saves the registers of a machine with the V extension.
+    pub fn save<T: CoreMachine>(machine: &T) -> Option<Self> {
+        if machine.isa() & ISA_V == 0 {
+            return None;
+        }
+        let vregisters = machine.vregisters();
+        Some(Self {
+            vregisters: if vregisters.iter().all(|v| *v == 0) {
+                Vec::new()
+            } else {
+                vregisters.to_vec()
+            },
+            vl: machine.vl(),
+            vtype: machine.vtype(),
+        })
+    }
+
+    /// Restores the registers saved by save, they are cleared when the
+    /// snapshot has none.
+    pub fn restore<T: CoreMachine>(state: Option<&Self>, machine: &mut T) -> Result<(), Error> {
+        let supported = machine.isa() & ISA_V != 0;
+        match state {
+            Some(state) if supported => {
+                let target = machine.vregisters_mut();
+                if state.vregisters.is_empty() {
+                    target.fill(0);
+                } else if state.vregisters.len() == target.len() {
+                    target.copy_from_slice(&state.vregisters);
+                } else {
+                    return Err(Error::Unexpected(format!(
+                        "Invalid vector registers length {}",
+                        state.vregisters.len()
+                    )));
+                }
+                machine.set_vl(state.vl, state.vtype)?;
+            }
+            Some(_) => {
+                return Err(Error::Unexpected(String::from(
+                    "Vector registers are not supported by the machine",
+                )))
+            }
+            None if supported => {
+                machine.vregisters_mut().fill(0);
+                machine.set_vl(0, RISCV_VTYPE_VILL)?;
+            }
+            None => {}
+        }
+        Ok(())
+    }
 }
 
 pub fn make_snapshot<T: CoreMachine>(machine: &mut T) -> Result<Snapshot, Error> {
@@ -40,6 +162,8 @@ pub fn make_snapshot<T: CoreMachine>(machine: &mut T) -> Result<Snapshot, Error>
         version: machine.version(),
         pc: machine.pc().to_u64(),
         load_reservation_address: machine.memory().lr().to_u64(),
+        float: FloatState::save(machine),
+        vector: VectorState::save(machine),
         ..Default::default()
     };
     for (i, v) in machine.registers().iter().enumerate() {
@@ -84,6 +208,8 @@ pub fn resume<T: CoreMachine>(machine: &mut T, snapshot: &Snapshot) -> Result<()
     for (i, v) in snapshot.registers.iter().enumerate() {
         machine.set_register(i, T::REG::from_u64(*v));
     }
+    FloatState::restore(snapshot.float.as_ref(), machine)?;
+    VectorState::restore(snapshot.vector.as_ref(), machine)?;
     machine.update_pc(T::REG::from_u64(snapshot.pc));
     machine.commit_pc();
     for i in 0..snapshot.page_indices.len() {
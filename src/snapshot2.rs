@@ -3,6 +3,7 @@ use crate::{
     elf::{LoadingAction, ProgramMetadata},
     machine::SupportMachine,
     memory::{Memory, FLAG_DIRTY},
+    snapshot::{FloatState, VectorState},
     Error, Register, RISCV_GENERAL_REGISTER_NUMBER, RISCV_PAGESIZE,
 };
 use bytes::Bytes;
@@ -60,6 +61,8 @@ impl<I: Clone + PartialEq, D: DataSource<I>> Snapshot2Context<I, D> {
         for (i, v) in snapshot.registers.iter().enumerate() {
             machine.set_register(i, M::REG::from_u64(*v));
         }
+        FloatState::restore(snapshot.float.as_ref(), machine)?;
+        VectorState::restore(snapshot.vector.as_ref(), machine)?;
         machine.update_pc(M::REG::from_u64(snapshot.pc));
         machine.commit_pc();
         machine.set_cycles(snapshot.cycles);
@@ -208,6 +211,8 @@ impl<I: Clone + PartialEq, D: DataSource<I>> Snapshot2Context<I, D> {
             cycles: machine.cycles(),
             max_cycles: machine.max_cycles(),
             load_reservation_address: machine.memory().lr().to_u64(),
+            float: FloatState::save(machine),
+            vector: VectorState::save(machine),
         })
     }
 
@@ -266,4 +271,10 @@ pub struct Snapshot2<I: Clone + PartialEq> {
     pub cycles: u64,
     pub max_cycles: u64,
     pub load_reservation_address: u64,
+    // Only for machines with the F or D extension.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub float: Option<FloatState>,
+    // Only for machines with the V extension.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vector: Option<VectorState>,
 }
@@ -0,0 +1,361 @@
+// A deterministic subset of the Linux system calls of RISC-V, enough for the
+// C libraries of stock toolchains, newlib and musl, to run programs such as
+// unit tests. Nothing reaches the host: stdin is a buffer given upfront,
+// stdout and stderr are captured, the clock is derived from cycles and
+// getrandom is a seeded generator, so a program behaves the same on every
+// run. Other system calls are left to the next Syscalls module.
+//
+// Memory above the program break is shared by brk growing upwards and
+// anonymous mmap regions allocated downwards from mmap_end, which is the
+// bottom of the default stack unless set.
+use super::Syscalls;
+use crate::{
+    machine::SupportMachine,
+    memory::{round_page_up, Memory},
+    registers::{A0, A1, A2, A3, A4, A7},
+    Bytes, Error, Register, RISCV_PAGESIZE,
+};
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+use std::sync::{Arc, Mutex};
+
+pub const SYS_IOCTL: u64 = 29;
+pub const SYS_CLOSE: u64 = 57;
+pub const SYS_READ: u64 = 63;
+pub const SYS_WRITE: u64 = 64;
+pub const SYS_READV: u64 = 65;
+pub const SYS_WRITEV: u64 = 66;
+pub const SYS_FSTAT: u64 = 80;
+pub const SYS_EXIT_GROUP: u64 = 94;
+pub const SYS_SET_TID_ADDRESS: u64 = 96;
+pub const SYS_CLOCK_GETTIME: u64 = 113;
+pub const SYS_BRK: u64 = 214;
+pub const SYS_MUNMAP: u64 = 215;
+pub const SYS_MMAP: u64 = 222;
+pub const SYS_GETRANDOM: u64 = 278;
+
+pub const EBADF: i64 = 9;
+pub const ENOMEM: i64 = 12;
+pub const EINVAL: i64 = 22;
+pub const ENOTTY: i64 = 25;
+pub const ENOSYS: i64 = 38;
+
+const MAP_PRIVATE: u64 = 0x02;
+const MAP_ANONYMOUS: u64 = 0x20;
+// CLOCK_REALTIME to CLOCK_BOOTTIME, every clock reads the virtual time.
+const MAX_CLOCK_ID: u64 = 7;
+
+/// Captured output of stdout or stderr, the handle stays valid after the
+/// syscalls are moved into a machine.
+#[derive(Clone, Default)]
+pub struct Output(Arc<Mutex<Vec<u8>>>);
+
+impl Output {
+    pub fn contents(&self) -> Vec<u8> {
+        self.0.lock().unwrap().clone()
+    }
+
+    fn write(&self, data: &[u8]) {
+        self.0.lock().unwrap().extend_from_slice(data);
+    }
+}
+
+pub struct LinuxSyscalls {
+    stdin: Bytes,
+    stdin_offset: usize,
+    stdout: Output,
+    stderr: Output,
+    exit_code: Option<i8>,
+    program_break: u64,
+    break_start: u64,
+    mmap_end: Option<u64>,
+    mmap_start: u64,
+    clock_start: u64,
+    nanoseconds_per_cycle: u64,
+    random_seed: u64,
+    random: StdRng,
+}
+
+impl LinuxSyscalls {
+    /// Creates the syscalls of a program whose break starts at
+    /// program_break, see elf::program_break.
+    pub fn new(program_break: u64) -> Self {
+        Self {
+            stdin: Bytes::new(),
+            stdin_offset: 0,
+            stdout: Output::default(),
+            stderr: Output::default(),
+            exit_code: None,
+            program_break,
+            break_start: program_break,
+            mmap_end: None,
+            mmap_start: 0,
+            clock_start: 0,
+            nanoseconds_per_cycle: 1,
+            random_seed: 0,
+            random: StdRng::seed_from_u64(0),
+        }
+    }
+
+    /// Data read from fd 0.
+    pub fn stdin(mut self, data: Bytes) -> Self {
+        self.stdin = data;
+        self
+    }
+
+    /// The virtual clock reads start nanoseconds since the epoch when the
+    /// program starts, and advances by nanoseconds_per_cycle per cycle.
+    pub fn clock(mut self, start: u64, nanoseconds_per_cycle: u64) -> Self {
+        self.clock_start = start;
+        self.nanoseconds_per_cycle = nanoseconds_per_cycle;
+        self
+    }
+
+    /// Seed of the bytes returned by getrandom.
+    pub fn random_seed(mut self, seed: u64) -> Self {
+        self.random_seed = seed;
+        self
+    }
+
+    /// Upper end of the memory used by mmap.
+    pub fn mmap_end(mut self, address: u64) -> Self {
+        self.mmap_end = Some(address);
+        self
+    }
+
+    pub fn stdout(&self) -> Output {
+        self.stdout.clone()
+    }
+
+    pub fn stderr(&self) -> Output {
+        self.stderr.clone()
+    }
+
+    fn read<Mac: SupportMachine>(
+        &mut self,
+        machine: &mut Mac,
+        fd: u64,
+        buffer: u64,
+        count: u64,
+    ) -> Result<i64, Error> {
+        if fd != 0 {
+            return Ok(-EBADF);
+        }
+        let remaining = &self.stdin[self.stdin_offset..];
+        let length = remaining.len().min(count as usize);
+        machine
+            .memory_mut()
+            .store_bytes(buffer, &remaining[..length])?;
+        self.stdin_offset += length;
+        Ok(length as i64)
+    }
+
+    fn write<Mac: SupportMachine>(
+        &mut self,
+        machine: &mut Mac,
+        fd: u64,
+        buffer: u64,
+        count: u64,
+    ) -> Result<i64, Error> {
+        let output = match fd {
+            1 => &self.stdout,
+            2 => &self.stderr,
+            _ => return Ok(-EBADF),
+        };
+        let data = machine.memory_mut().load_bytes(buffer, count)?;
+        output.write(&data);
+        Ok(count as i64)
+    }
+
+    // readv and writev, which musl uses for stdio.
+    fn vectored<Mac: SupportMachine>(
+        &mut self,
+        machine: &mut Mac,
+        fd: u64,
+        iov: u64,
+        count: u64,
+        write: bool,
+    ) -> Result<i64, Error> {
+        let word_size = u64::from(Mac::REG::BITS / 8);
+        let mut total = 0;
+        for i in 0..count {
+            let entry = iov.wrapping_add(i * word_size * 2);
+            let buffer = load_word(machine, entry)?;
+            let length = load_word(machine, entry.wrapping_add(word_size))?;
+            let result = if write {
+                self.write(machine, fd, buffer, length)?
+            } else {
+                self.read(machine, fd, buffer, length)?
+            };
+            if result < 0 {
+                return Ok(result);
+            }
+            total += result;
+            if (result as u64) < length {
+                break;
+            }
+        }
+        Ok(total)
+    }
+
+    // Moving the break outside of the memory between its start and the
+    // lowest mmap region fails by returning the current break.
+    fn brk<Mac: SupportMachine>(&mut self, machine: &mut Mac, address: u64) -> Result<i64, Error> {
+        if address >= self.break_start && address <= self.mmap_start {
+            if address > self.program_break {
+                // Memory given back by a lower break may hold old data.
+                machine.memory_mut().store_byte(
+                    self.program_break,
+                    address - self.program_break,
+                    0,
+                )?;
+            }
+            self.program_break = address;
+        }
+        Ok(self.program_break as i64)
+    }
+
+    // Anonymous private mappings are supported, address hints are ignored
+    // and munmap does not give memory back.
+    fn mmap<Mac: SupportMachine>(
+        &mut self,
+        machine: &mut Mac,
+        length: u64,
+        flags: u64,
+        fd: u64,
+    ) -> Result<i64, Error> {
+        if flags & MAP_ANONYMOUS == 0 || flags & MAP_PRIVATE == 0 || fd as i32 != -1 {
+            return Ok(-ENOSYS);
+        }
+        if length == 0 {
+            return Ok(-EINVAL);
+        }
+        let start = match self.mmap_start.checked_sub(round_page_up(length)) {
+            Some(start) if start >= round_page_up(self.program_break) => start,
+            _ => return Ok(-ENOMEM),
+        };
+        // A region may hold the data of an unmapped one.
+        machine.memory_mut().store_byte(start, length, 0)?;
+        self.mmap_start = start;
+        Ok(start as i64)
+    }
+
+    fn clock_gettime<Mac: SupportMachine>(
+        &mut self,
+        machine: &mut Mac,
+        clock: u64,
+        timespec: u64,
+    ) -> Result<i64, Error> {
+        if clock > MAX_CLOCK_ID {
+            return Ok(-EINVAL);
+        }
+        let time = self
+            .clock_start
+            .wrapping_add(machine.cycles().wrapping_mul(self.nanoseconds_per_cycle));
+        let word_size = u64::from(Mac::REG::BITS / 8);
+        store_word(machine, timespec, time / 1_000_000_000)?;
+        store_word(
+            machine,
+            timespec.wrapping_add(word_size),
+            time % 1_000_000_000,
+        )?;
+        Ok(0)
+    }
+
+    fn getrandom<Mac: SupportMachine>(
+        &mut self,
+        machine: &mut Mac,
+        buffer: u64,
+        length: u64,
+    ) -> Result<i64, Error> {
+        // The length comes from the guest, bytes are generated a page at a
+        // time so the memory bounds the work done.
+        let mut offset = 0;
+        while offset < length {
+            let mut data = vec![0; (length - offset).min(RISCV_PAGESIZE as u64) as usize];
+            self.random.fill_bytes(&mut data);
+            machine
+                .memory_mut()
+                .store_bytes(buffer.wrapping_add(offset), &data)?;
+            offset += data.len() as u64;
+        }
+        Ok(length as i64)
+    }
+}
+
+fn load_word<Mac: SupportMachine>(machine: &mut Mac, address: u64) -> Result<u64, Error> {
+    let address = Mac::REG::from_u64(address);
+    let value = if Mac::REG::BITS == 64 {
+        machine.memory_mut().load64(&address)?
+    } else {
+        machine.memory_mut().load32(&address)?
+    };
+    Ok(value.to_u64())
+}
+
+fn store_word<Mac: SupportMachine>(
+    machine: &mut Mac,
+    address: u64,
+    value: u64,
+) -> Result<(), Error> {
+    let address = Mac::REG::from_u64(address);
+    let value = Mac::REG::from_u64(value);
+    if Mac::REG::BITS == 64 {
+        machine.memory_mut().store64(&address, &value)
+    } else {
+        machine.memory_mut().store32(&address, &value)
+    }
+}
+
+impl<Mac: SupportMachine> Syscalls<Mac> for LinuxSyscalls {
+    fn initialize(&mut self, machine: &mut Mac) -> Result<(), Error> {
+        let memory_size = machine.memory().memory_size() as u64;
+        self.stdin_offset = 0;
+        self.exit_code = None;
+        self.program_break = self.break_start;
+        self.mmap_start = self
+            .mmap_end
+            .unwrap_or(memory_size - memory_size / 4)
+            .min(memory_size);
+        self.random = StdRng::seed_from_u64(self.random_seed);
+        Ok(())
+    }
+
+    fn ecall(&mut self, machine: &mut Mac) -> Result<bool, Error> {
+        let arg = |i: usize| machine.registers()[i].to_u64();
+        let (a0, a1, a2, a3, a4) = (arg(A0), arg(A1), arg(A2), arg(A3), arg(A4));
+        let result = match machine.registers()[A7].to_u64() {
+            SYS_READ => self.read(machine, a0, a1, a2)?,
+            SYS_WRITE => self.write(machine, a0, a1, a2)?,
+            SYS_READV => self.vectored(machine, a0, a1, a2, false)?,
+            SYS_WRITEV => self.vectored(machine, a0, a1, a2, true)?,
+            SYS_BRK => self.brk(machine, a0)?,
+            SYS_MMAP => self.mmap(machine, a1, a3, a4)?,
+            SYS_MUNMAP => 0,
+            SYS_CLOCK_GETTIME => self.clock_gettime(machine, a0, a1)?,
+            SYS_GETRANDOM => self.getrandom(machine, a0, a1)?,
+            SYS_EXIT_GROUP => {
+                self.exit_code = Some(a0 as i8);
+                machine.set_running(false);
+                return Ok(true);
+            }
+            // Startup and stdio code of the C libraries asks for these, a
+            // single threaded program without files needs no more.
+            SYS_SET_TID_ADDRESS => 1,
+            SYS_IOCTL => -ENOTTY,
+            SYS_CLOSE if a0 <= 2 => 0,
+            SYS_CLOSE => -EBADF,
+            SYS_FSTAT => -ENOSYS,
+            _ => return Ok(false),
+        };
+        machine.set_register(A0, Mac::REG::from_i64(result));
+        Ok(true)
+    }
+
+    fn name(&self) -> &str {
+        "linux"
+    }
+
+    fn exit_code(&self) -> Option<i8> {
+        self.exit_code
+    }
+}
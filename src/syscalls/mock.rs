@@ -0,0 +1,260 @@
+// Scripted syscalls for unit tests of programs. A test declares the syscalls
+// it expects, by number and optionally by arguments, along with the response
+// of each: bytes written to memory, the value left in A0 and the cycles
+// charged. After the run, the test checks the calls made by the program with
+// MockSyscalls::verify, or inspects them with MockSyscalls::calls.
+//
+// MockSyscalls is a handle, clones share the same expectations and calls, so
+// a test keeps a clone around while the machine owns the other.
+use super::Syscalls;
+use crate::{
+    machine::SupportMachine,
+    memory::Memory,
+    registers::{A0, A1, A2, A3, A4, A5, A7},
+    Bytes, Error, Register,
+};
+use std::sync::{Arc, Mutex};
+
+// Where a response writes its bytes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Target {
+    Address(u64),
+    // The address is the value of the n-th argument.
+    Argument(usize),
+}
+
+/// A syscall expected by MockSyscalls, along with its response.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MockSyscall {
+    number: u64,
+    arguments: [Option<u64>; 6],
+    writes: Vec<(Target, Bytes)>,
+    result: Option<u64>,
+    cycles: u64,
+    times: usize,
+}
+
+impl MockSyscall {
+    /// Expects syscall number to be made once, with any arguments, and
+    /// leaves the registers untouched.
+    pub fn new(number: u64) -> Self {
+        Self {
+            number,
+            arguments: [None; 6],
+            writes: vec![],
+            result: None,
+            cycles: 0,
+            times: 1,
+        }
+    }
+
+    /// Expects the n-th argument, n from 0 for A0 to 5 for A5, to be value.
+    pub fn arg(mut self, n: usize, value: u64) -> Self {
+        assert!(n < 6, "syscalls take at most 6 arguments");
+        self.arguments[n] = Some(value);
+        self
+    }
+
+    /// Expects the arguments to start with values.
+    pub fn args(mut self, values: &[u64]) -> Self {
+        for (n, value) in values.iter().enumerate() {
+            self = self.arg(n, *value);
+        }
+        self
+    }
+
+    /// Writes data to memory at address.
+    pub fn write(mut self, address: u64, data: Bytes) -> Self {
+        self.writes.push((Target::Address(address), data));
+        self
+    }
+
+    /// Writes data to memory at the address passed in the n-th argument.
+    pub fn write_to_arg(mut self, n: usize, data: Bytes) -> Self {
+        assert!(n < 6, "syscalls take at most 6 arguments");
+        self.writes.push((Target::Argument(n), data));
+        self
+    }
+
+    /// Leaves value in A0, a negative value is sign extended.
+    pub fn returns(mut self, value: i64) -> Self {
+        self.result = Some(value as u64);
+        self
+    }
+
+    pub fn cycles(mut self, cycles: u64) -> Self {
+        self.cycles = cycles;
+        self
+    }
+
+    /// Expects the syscall to be made count times.
+    pub fn times(mut self, count: usize) -> Self {
+        self.times = count;
+        self
+    }
+
+    fn matches(&self, number: u64, arguments: &[u64; 6]) -> bool {
+        self.number == number
+            && self
+                .arguments
+                .iter()
+                .zip(arguments.iter())
+                .all(|(expected, actual)| expected.map_or(true, |e| e == *actual))
+    }
+}
+
+/// A syscall made by the program.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MockCall {
+    pub number: u64,
+    /// A0 to A5 when the ecall is made.
+    pub arguments: [u64; 6],
+    /// Index of the expectation which answered the call, None when the call
+    /// was not expected.
+    pub expectation: Option<usize>,
+}
+
+#[derive(Default)]
+struct State {
+    expectations: Vec<MockSyscall>,
+    in_order: bool,
+    passthrough: bool,
+    calls: Vec<MockCall>,
+    // Calls answered by each expectation.
+    counts: Vec<usize>,
+}
+
+impl State {
+    fn find(&self, number: u64, arguments: &[u64; 6]) -> Option<usize> {
+        let available = |i: &usize| self.counts[*i] < self.expectations[*i].times;
+        if self.in_order {
+            // The first expectation not yet met is the only one allowed.
+            (0..self.expectations.len())
+                .find(available)
+                .filter(|i| self.expectations[*i].matches(number, arguments))
+        } else {
+            (0..self.expectations.len())
+                .filter(available)
+                .find(|i| self.expectations[*i].matches(number, arguments))
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct MockSyscalls(Arc<Mutex<State>>);
+
+impl MockSyscalls {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an expected syscall, expectations are tried in the order they
+    /// are added.
+    pub fn expect(self, syscall: MockSyscall) -> Self {
+        {
+            let mut state = self.0.lock().unwrap();
+            state.expectations.push(syscall);
+            state.counts.push(0);
+        }
+        self
+    }
+
+    /// Requires the syscalls to be made in the order of the expectations,
+    /// each one made the times it expects before the next one.
+    pub fn in_order(self) -> Self {
+        self.0.lock().unwrap().in_order = true;
+        self
+    }
+
+    /// Leaves syscalls no expectation matches to the next Syscalls module,
+    /// instead of failing them with InvalidEcall.
+    pub fn passthrough(self) -> Self {
+        self.0.lock().unwrap().passthrough = true;
+        self
+    }
+
+    /// Syscalls made since the program is loaded, passed through ones
+    /// included.
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.0.lock().unwrap().calls.clone()
+    }
+
+    /// Number of calls made to syscall number.
+    pub fn count(&self, number: u64) -> usize {
+        let state = self.0.lock().unwrap();
+        state.calls.iter().filter(|c| c.number == number).count()
+    }
+
+    /// Checks every expectation is met, and no unexpected syscall is made
+    /// unless passthrough is enabled. The error describes each problem on a
+    /// line.
+    pub fn verify(&self) -> Result<(), String> {
+        let state = self.0.lock().unwrap();
+        let mut problems = vec![];
+        if !state.passthrough {
+            for call in state.calls.iter().filter(|c| c.expectation.is_none()) {
+                problems.push(format!(
+                    "unexpected syscall {} with arguments {:?}",
+                    call.number, call.arguments
+                ));
+            }
+        }
+        for (expectation, count) in state.expectations.iter().zip(state.counts.iter()) {
+            if *count != expectation.times {
+                problems.push(format!(
+                    "syscall {} is expected {} times, made {} times",
+                    expectation.number, expectation.times, count
+                ));
+            }
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems.join("\n"))
+        }
+    }
+}
+
+impl<Mac: SupportMachine> Syscalls<Mac> for MockSyscalls {
+    fn initialize(&mut self, _machine: &mut Mac) -> Result<(), Error> {
+        let mut state = self.0.lock().unwrap();
+        state.calls.clear();
+        state.counts.iter_mut().for_each(|c| *c = 0);
+        Ok(())
+    }
+
+    fn ecall(&mut self, machine: &mut Mac) -> Result<bool, Error> {
+        let number = machine.registers()[A7].to_u64();
+        let arguments = [A0, A1, A2, A3, A4, A5].map(|r| machine.registers()[r].to_u64());
+        let mut state = self.0.lock().unwrap();
+        let index = state.find(number, &arguments);
+        state.calls.push(MockCall {
+            number,
+            arguments,
+            expectation: index,
+        });
+        let index = match index {
+            Some(index) => index,
+            None if state.passthrough => return Ok(false),
+            None => return Err(Error::InvalidEcall(number)),
+        };
+        state.counts[index] += 1;
+        let syscall = &state.expectations[index];
+        for (target, data) in &syscall.writes {
+            let address = match target {
+                Target::Address(address) => *address,
+                Target::Argument(n) => arguments[*n],
+            };
+            machine.memory_mut().store_bytes(address, data)?;
+        }
+        if let Some(result) = syscall.result {
+            machine.set_register(A0, Mac::REG::from_u64(result));
+        }
+        machine.add_cycles(syscall.cycles)?;
+        Ok(true)
+    }
+
+    fn name(&self) -> &str {
+        "mock"
+    }
+}
@@ -1,10 +1,28 @@
+pub mod linux;
+pub mod mock;
+pub mod strace;
+pub mod table;
+
 use super::Error;
 use crate::machine::SupportMachine;
 
+pub use strace::{SyscallRecord, SyscallSchema, SyscallTrace};
+pub use table::{SyscallHandler, SyscallInfo, SyscallTable};
+
 pub trait Syscalls<Mac: SupportMachine>: Send + Sync {
     fn initialize(&mut self, machine: &mut Mac) -> Result<(), Error>;
     // Returned bool means if the syscall has been processed, if
     // a module returns false, Machine would continue to leverage
     // the next syscall module to process.
     fn ecall(&mut self, machine: &mut Mac) -> Result<bool, Error>;
+    // Name of the module in syscall traces.
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+    // Exit code of the program once the module stopped the machine on an
+    // exit syscall of its own, such as exit_group. Machine returns it from
+    // run like the code of the exit syscall it handles.
+    fn exit_code(&self) -> Option<i8> {
+        None
+    }
 }
@@ -0,0 +1,255 @@
+// Syscall tracing in the manner of strace. A SyscallTrace given to
+// DefaultMachineBuilder::syscall_trace records every ecall handled by the
+// machine: the number, A0 to A5, the A0 left by the handler, the cycles it
+// charged and where it was handled. Records are kept in memory and can also
+// be written as they happen to a human readable log and to JSON lines.
+//
+// The machine only sees raw registers, names of syscalls and arguments come
+// from a SyscallSchema provided by the user, names of syscalls registered in
+// the SyscallTable are used when the schema does not know a number.
+use super::linux::*;
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// Where an ecall was handled.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SyscallHandledBy {
+    /// The exit syscall, handled by the machine itself.
+    Machine,
+    /// A handler of the SyscallTable, with the name it is registered with.
+    Table(String),
+    /// A Syscalls module of the chain, see Syscalls::name.
+    Module(String),
+    /// No one, the ecall fails with InvalidEcall.
+    Unhandled,
+}
+
+impl std::fmt::Display for SyscallHandledBy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SyscallHandledBy::Machine => write!(f, "machine"),
+            SyscallHandledBy::Table(name) => write!(f, "table:{}", name),
+            SyscallHandledBy::Module(name) => write!(f, "module:{}", name),
+            SyscallHandledBy::Unhandled => write!(f, "unhandled"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyscallRecord {
+    /// PC of the ecall instruction.
+    pub pc: u64,
+    pub number: u64,
+    /// Name given by the schema or the syscall table, if any.
+    pub name: Option<String>,
+    /// A0 to A5 when the ecall is made.
+    pub arguments: [u64; 6],
+    /// A0 when the handler returns, sign extended.
+    pub result: i64,
+    /// Cycles charged by the syscall.
+    pub cycles: u64,
+    pub handled_by: SyscallHandledBy,
+    /// The error returned by the handler, if any.
+    pub error: Option<String>,
+}
+
+/// Name and argument names of a syscall.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SyscallDescription {
+    pub name: String,
+    /// Names of the arguments in A0 and onwards, at most 6.
+    pub arguments: Vec<String>,
+}
+
+/// Syscall descriptions keyed by number, used to decode the arguments of
+/// traced syscalls.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SyscallSchema {
+    syscalls: BTreeMap<u64, SyscallDescription>,
+}
+
+impl SyscallSchema {
+    /// Describes a syscall, a later description of the same number replaces
+    /// the former one.
+    pub fn syscall(mut self, number: u64, name: &str, arguments: &[&str]) -> Self {
+        assert!(arguments.len() <= 6, "syscalls take at most 6 arguments");
+        self.syscalls.insert(
+            number,
+            SyscallDescription {
+                name: name.to_string(),
+                arguments: arguments.iter().map(|a| a.to_string()).collect(),
+            },
+        );
+        self
+    }
+
+    /// The exit syscall and the Linux syscalls of LinuxSyscalls.
+    pub fn linux() -> Self {
+        Self::default()
+            .syscall(93, "exit", &["code"])
+            .syscall(SYS_IOCTL, "ioctl", &["fd", "request", "arg"])
+            .syscall(SYS_CLOSE, "close", &["fd"])
+            .syscall(SYS_READ, "read", &["fd", "buf", "count"])
+            .syscall(SYS_WRITE, "write", &["fd", "buf", "count"])
+            .syscall(SYS_READV, "readv", &["fd", "iov", "iovcnt"])
+            .syscall(SYS_WRITEV, "writev", &["fd", "iov", "iovcnt"])
+            .syscall(SYS_FSTAT, "fstat", &["fd", "statbuf"])
+            .syscall(SYS_EXIT_GROUP, "exit_group", &["code"])
+            .syscall(SYS_SET_TID_ADDRESS, "set_tid_address", &["tidptr"])
+            .syscall(SYS_CLOCK_GETTIME, "clock_gettime", &["clockid", "tp"])
+            .syscall(SYS_BRK, "brk", &["addr"])
+            .syscall(SYS_MUNMAP, "munmap", &["addr", "length"])
+            .syscall(
+                SYS_MMAP,
+                "mmap",
+                &["addr", "length", "prot", "flags", "fd", "offset"],
+            )
+            .syscall(SYS_GETRANDOM, "getrandom", &["buf", "buflen", "flags"])
+    }
+
+    pub fn get(&self, number: u64) -> Option<&SyscallDescription> {
+        self.syscalls.get(&number)
+    }
+
+    /// Named arguments of a record, empty when the schema does not describe
+    /// its syscall.
+    pub fn decode<'a>(&'a self, record: &SyscallRecord) -> Vec<(&'a str, u64)> {
+        self.get(record.number)
+            .map(|description| {
+                description
+                    .arguments
+                    .iter()
+                    .zip(record.arguments.iter())
+                    .map(|(name, value)| (name.as_str(), *value))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+type Sink = Box<dyn Write + Send + Sync>;
+
+/// Records the syscalls of a machine, see DefaultMachine::syscall_trace.
+#[derive(Default)]
+pub struct SyscallTrace {
+    schema: SyscallSchema,
+    records: Vec<SyscallRecord>,
+    log: Option<Sink>,
+    json_lines: Option<Sink>,
+}
+
+impl SyscallTrace {
+    pub fn new(schema: SyscallSchema) -> Self {
+        Self {
+            schema,
+            ..Default::default()
+        }
+    }
+
+    /// Writes a line per syscall to writer, such as
+    /// `0x10010: write(fd=0x1, buf=0x10060, count=0x7) = 7 <module:stdio, 0 cycles>`.
+    pub fn log(mut self, writer: Box<dyn Write + Send + Sync>) -> Self {
+        self.log = Some(writer);
+        self
+    }
+
+    /// Writes a JSON object per syscall to writer, see SyscallTrace::to_json.
+    pub fn json_lines(mut self, writer: Box<dyn Write + Send + Sync>) -> Self {
+        self.json_lines = Some(writer);
+        self
+    }
+
+    pub fn schema(&self) -> &SyscallSchema {
+        &self.schema
+    }
+
+    pub fn records(&self) -> &[SyscallRecord] {
+        &self.records
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+    }
+
+    /// Formats a record as a line of the log, arguments the schema does not
+    /// name are shown as raw A0 to A5.
+    pub fn format(&self, record: &SyscallRecord) -> String {
+        let arguments: Vec<String> = match self.schema.get(record.number) {
+            Some(_) => self
+                .schema
+                .decode(record)
+                .iter()
+                .map(|(name, value)| format!("{}={:#x}", name, value))
+                .collect(),
+            None => record
+                .arguments
+                .iter()
+                .map(|value| format!("{:#x}", value))
+                .collect(),
+        };
+        let name = match &record.name {
+            Some(name) => name.clone(),
+            None => format!("syscall_{}", record.number),
+        };
+        let mut line = format!(
+            "{:#x}: {}({}) = {}",
+            record.pc,
+            name,
+            arguments.join(", "),
+            record.result
+        );
+        if let Some(error) = &record.error {
+            line.push_str(&format!(" ({})", error));
+        }
+        line.push_str(&format!(
+            " <{}, {} cycles>",
+            record.handled_by, record.cycles
+        ));
+        line
+    }
+
+    /// Serializes a record to a JSON object, the fields of SyscallRecord so
+    /// the result can be read back with serde, plus the named_arguments
+    /// decoded by the schema.
+    pub fn to_json(&self, record: &SyscallRecord) -> String {
+        let line = JsonLine {
+            record,
+            named_arguments: self.schema.decode(record).into_iter().collect(),
+        };
+        serde_json::to_string(&line).expect("a syscall record serializes to JSON")
+    }
+
+    // Fills the name of the record and writes it to the sinks.
+    pub(crate) fn push(
+        &mut self,
+        mut record: SyscallRecord,
+        table_name: Option<&str>,
+    ) -> Result<(), Error> {
+        record.name = self
+            .schema
+            .get(record.number)
+            .map(|d| d.name.as_str())
+            .or(table_name)
+            .map(|name| name.to_string());
+        if self.log.is_some() {
+            let line = self.format(&record);
+            writeln!(self.log.as_mut().unwrap(), "{}", line)?;
+        }
+        if self.json_lines.is_some() {
+            let line = self.to_json(&record);
+            writeln!(self.json_lines.as_mut().unwrap(), "{}", line)?;
+        }
+        self.records.push(record);
+        Ok(())
+    }
+}
+
+// A line written to the json_lines sink.
+#[derive(Serialize)]
+struct JsonLine<'a> {
+    #[serde(flatten)]
+    record: &'a SyscallRecord,
+    named_arguments: BTreeMap<&'a str, u64>,
+}
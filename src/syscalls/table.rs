@@ -0,0 +1,87 @@
+use super::Error;
+use std::collections::BTreeMap;
+
+/// A handler bound to one syscall number, it reads its arguments from and
+/// writes its result to the registers of the machine.
+pub type SyscallHandler<Mac> = dyn FnMut(&mut Mac) -> Result<(), Error> + Send + Sync;
+
+/// Metadata of a syscall registered in a SyscallTable.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SyscallInfo {
+    pub name: String,
+    /// Number of arguments passed in A0 and onwards.
+    pub arguments: u8,
+    /// Cycles charged before the handler is called, the handler may charge
+    /// more depending on its arguments.
+    pub cycles: u64,
+}
+
+impl SyscallInfo {
+    pub fn new(name: &str, arguments: u8, cycles: u64) -> Self {
+        Self {
+            name: name.to_string(),
+            arguments,
+            cycles,
+        }
+    }
+}
+
+/// Syscall handlers keyed by syscall number, which the machine looks up
+/// before falling back to the chain of Syscalls modules.
+pub struct SyscallTable<Mac> {
+    handlers: BTreeMap<u64, (SyscallInfo, Box<SyscallHandler<Mac>>)>,
+}
+
+impl<Mac> Default for SyscallTable<Mac> {
+    fn default() -> Self {
+        Self {
+            handlers: BTreeMap::new(),
+        }
+    }
+}
+
+impl<Mac> SyscallTable<Mac> {
+    /// Binds a handler to a syscall number, a number can only be bound
+    /// once. Returns false and leaves the table as is when the number is
+    /// already bound.
+    pub fn insert(
+        &mut self,
+        number: u64,
+        info: SyscallInfo,
+        handler: Box<SyscallHandler<Mac>>,
+    ) -> bool {
+        if self.handlers.contains_key(&number) {
+            return false;
+        }
+        self.handlers.insert(number, (info, handler));
+        true
+    }
+
+    pub fn contains(&self, number: u64) -> bool {
+        self.handlers.contains_key(&number)
+    }
+
+    pub fn info(&self, number: u64) -> Option<&SyscallInfo> {
+        self.handlers.get(&number).map(|(info, _)| info)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handlers.is_empty()
+    }
+
+    /// Registered syscall numbers and their metadata, in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &SyscallInfo)> {
+        self.handlers
+            .iter()
+            .map(|(number, (info, _))| (*number, info))
+    }
+
+    pub(crate) fn get_mut(
+        &mut self,
+        number: u64,
+    ) -> Option<(&SyscallInfo, &mut Box<SyscallHandler<Mac>>)> {
+        self.handlers
+            .get_mut(&number)
+            .map(|(info, handler)| (&*info, handler))
+    }
+}
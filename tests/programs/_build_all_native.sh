@@ -7,13 +7,23 @@ riscv64-unknown-elf-as -o amo_compare.o amo_compare.S && riscv64-unknown-elf-ld
 riscv64-unknown-elf-as -o amo_write_permission.o amo_write_permission.S && riscv64-unknown-elf-ld -o amo_write_permission amo_write_permission.o && rm amo_write_permission.o
 # SKIP: andi
 riscv64-unknown-elf-gcc -o argv_null_test argv_null_test.c
+riscv64-unknown-elf-as -march=rv64imac -o backtrace.o backtrace.S && riscv64-unknown-elf-ld -Ttext=0x10000 -o backtrace backtrace.o && rm backtrace.o
 riscv64-unknown-elf-gcc -o big_binary big_binary.c
 riscv64-unknown-elf-as -march=rv64imc -o cadd_hints.o cadd_hints.S && riscv64-unknown-elf-ld -o cadd_hints cadd_hints.o && rm cadd_hints.o
+riscv64-unknown-elf-as -march=rv64imac -o compliance/rv64a.o compliance/rv64a.S && riscv64-unknown-elf-ld -T compliance/link.ld -o compliance/rv64a compliance/rv64a.o && rm compliance/rv64a.o
+riscv64-unknown-elf-as -march=rv64imac_zba_zbb_zbs -o compliance/rv64b.o compliance/rv64b.S && riscv64-unknown-elf-ld -T compliance/link.ld -o compliance/rv64b compliance/rv64b.o && rm compliance/rv64b.o
+riscv64-unknown-elf-as -march=rv64imac -o compliance/rv64i.o compliance/rv64i.S && riscv64-unknown-elf-ld -T compliance/link.ld -o compliance/rv64i compliance/rv64i.o && rm compliance/rv64i.o
+riscv64-unknown-elf-as -march=rv64imac -o compliance/rv64m.o compliance/rv64m.S && riscv64-unknown-elf-ld -T compliance/link.ld -o compliance/rv64m compliance/rv64m.o && rm compliance/rv64m.o
 riscv64-unknown-elf-as -o ckbforks.o ckbforks.S && riscv64-unknown-elf-ld -o ckbforks ckbforks.o && rm ckbforks.o
 # TODO: clzw_bug
+riscv64-unknown-elf-as -march=rv64imac -o code_size.o code_size.S && riscv64-unknown-elf-ld -Ttext=0x10000 -Tdata=0x20000 -o code_size code_size.o && rm code_size.o
+riscv64-unknown-elf-as -march=rv64imac_zbkb_zbkc_zbkx_zknh_zksh -o crypto.o crypto.S && riscv64-unknown-elf-ld -o crypto crypto.o && rm crypto.o
+riscv64-unknown-elf-as -march=rv64imac -o custom_instruction.o custom_instruction.S && riscv64-unknown-elf-ld -o custom_instruction custom_instruction.o && rm custom_instruction.o
 # SKIP: decoder_instructions_cache_pc_out_of_bound_timeout
 riscv64-unknown-elf-as -o ebreak.o ebreak.S && riscv64-unknown-elf-ld -o ebreak64 ebreak.o && rm ebreak.o
+riscv64-unknown-elf-as -march=rv64imac -o error_report.o error_report.S && riscv64-unknown-elf-ld -Ttext=0x10000 -o error_report error_report.o && rm error_report.o
 # SKIP: flat_crash_64
+riscv64-unknown-elf-as -march=rv64imafdc -o float.o float.S && riscv64-unknown-elf-ld -Ttext=0x10000 -Tdata=0x20000 -o float float.o && rm float.o
 # SKIP: goblin_overflow_elf
 # SKIP: invalid_file_offset64*
 riscv64-unknown-elf-as -o invalid_read.o invalid_read.S && riscv64-unknown-elf-ld -o invalid_read64 invalid_read.o && rm invalid_read.o
@@ -59,9 +69,11 @@ riscv64-unknown-elf-as -o sc_only.o sc_only.S && riscv64-unknown-elf-ld -T sc_on
 riscv64-unknown-elf-gcc -o simple64 simple.c
 riscv64-unknown-elf-as -o sp_alignment_test.o sp_alignment_test.S && riscv64-unknown-elf-ld -o sp_alignment_test sp_alignment_test.o && rm sp_alignment_test.o
 riscv64-unknown-elf-gcc -o spawn spawn.c
+riscv64-unknown-elf-as -o stdio.o stdio.S && riscv64-unknown-elf-ld -o stdio stdio.o && rm stdio.o
 riscv64-unknown-elf-as -o syscall.o syscall.S && riscv64-unknown-elf-ld -o syscall64 syscall.o && rm syscall.o
 riscv64-unknown-elf-as -o trace.o trace.S && riscv64-unknown-elf-ld -o trace64 trace.o && rm trace.o
 # SKIP: unaligned64
+riscv64-unknown-elf-as -march=rv64imacv -o vector.o vector.S && riscv64-unknown-elf-ld -Ttext=0x10000 -Tdata=0x20000 -o vector vector.o && rm vector.o
 riscv64-unknown-elf-gcc -o writable_page writable_page.c && riscv64-unknown-elf-objdump -h writable_page > writable_page.dump
 riscv64-unknown-elf-as -o write_at_boundary.o write_at_boundary.S && riscv64-unknown-elf-ld -o write_at_boundary64 write_at_boundary.o && rm write_at_boundary.o
 riscv64-unknown-elf-as -o write_large_address.o write_large_address.S && riscv64-unknown-elf-ld -o write_large_address64 write_large_address.o && rm write_large_address.o
@@ -71,4 +83,16 @@ riscv64-unknown-elf-as -o zero_address.o zero_address.S && riscv64-unknown-elf-l
 riscv64-unknown-elf-as -o mop_jump_rel_version1_bug.o mop_jump_rel_version1_bug.S && riscv64-unknown-elf-ld -o mop_jump_rel_version1_bug mop_jump_rel_version1_bug.o && rm mop_jump_rel_version1_bug.o
 riscv64-unknown-elf-as -o mop_jump_rel_version1_reg_not_updated_bug.o mop_jump_rel_version1_reg_not_updated_bug.S && riscv64-unknown-elf-ld -o mop_jump_rel_version1_reg_not_updated_bug mop_jump_rel_version1_reg_not_updated_bug.o && rm mop_jump_rel_version1_reg_not_updated_bug.o
 riscv64-unknown-elf-as -o mop_jump_abs_version1_reg_not_updated_bug.o mop_jump_abs_version1_reg_not_updated_bug.S && riscv64-unknown-elf-ld -o mop_jump_abs_version1_reg_not_updated_bug mop_jump_abs_version1_reg_not_updated_bug.o && rm mop_jump_abs_version1_reg_not_updated_bug.o
+riscv64-unknown-elf-as -o stack_overflow.o stack_overflow.S && riscv64-unknown-elf-ld -o stack_overflow stack_overflow.o && rm stack_overflow.o
+riscv64-unknown-elf-as -o memcheck.o memcheck.S && riscv64-unknown-elf-ld -o memcheck memcheck.o && rm memcheck.o
+riscv64-unknown-elf-as -o linux_syscalls.o linux_syscalls.S && riscv64-unknown-elf-ld -o linux_syscalls linux_syscalls.o && rm linux_syscalls.o
+riscv64-unknown-elf-as -o mock_syscalls.o mock_syscalls.S && riscv64-unknown-elf-ld -o mock_syscalls mock_syscalls.o && rm mock_syscalls.o
+riscv64-unknown-elf-as -o spawn_pipe.o spawn_pipe.S && riscv64-unknown-elf-ld -o spawn_pipe spawn_pipe.o && rm spawn_pipe.o
+riscv64-unknown-elf-as -o spawn_chain.o spawn_chain.S && riscv64-unknown-elf-ld -o spawn_chain spawn_chain.o && rm spawn_chain.o
+riscv64-unknown-elf-as -o spawn_deadlock.o spawn_deadlock.S && riscv64-unknown-elf-ld -o spawn_deadlock spawn_deadlock.o && rm spawn_deadlock.o
+riscv64-unknown-elf-as -o stack_guard_edge.o stack_guard_edge.S && riscv64-unknown-elf-ld -o stack_guard_edge stack_guard_edge.o && rm stack_guard_edge.o
+riscv64-unknown-elf-as -march=rv64imacv -o vector_cycles.o vector_cycles.S && riscv64-unknown-elf-ld -o vector_cycles vector_cycles.o && rm vector_cycles.o
+riscv64-unknown-elf-as -march=rv64imafdv -o watchpoint_classes.o watchpoint_classes.S && riscv64-unknown-elf-ld -Ttext=0x10000 -Tdata=0x20000 -o watchpoint_classes watchpoint_classes.o && rm watchpoint_classes.o
+riscv64-unknown-elf-as -march=rv64imac -o watchpoint_zcmp.o watchpoint_zcmp.S && riscv64-unknown-elf-ld -Ttext=0x10000 -Tdata=0x20000 -o watchpoint_zcmp watchpoint_zcmp.o && rm watchpoint_zcmp.o
+riscv64-unknown-elf-as -march=rv64ima -o gdb_loop.o gdb_loop.S && riscv64-unknown-elf-ld -Ttext=0x10000 -o gdb_loop gdb_loop.o && rm gdb_loop.o
 echo "done"
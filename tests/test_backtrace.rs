@@ -0,0 +1,141 @@
+use ckb_vm::backtrace::{Backtrace, Unwinder};
+use ckb_vm::dwarf::{CallFrameTable, RegisterRule};
+#[cfg(has_asm)]
+use ckb_vm::machine::asm::{AsmCoreMachine, AsmMachine};
+use ckb_vm::machine::{trace::TraceMachine, VERSION1};
+use ckb_vm::{
+    DefaultCoreMachine, DefaultMachineBuilder, Error, SparseMemory, WXorXMemory, ISA_IMC,
+};
+use std::fs;
+
+type Core = DefaultCoreMachine<u64, WXorXMemory<SparseMemory<u64>>>;
+
+fn functions(backtrace: &Backtrace) -> Vec<&str> {
+    backtrace
+        .frames
+        .iter()
+        .map(|f| {
+            f.function
+                .as_ref()
+                .map(|(name, _)| name.as_str())
+                .unwrap_or("??")
+        })
+        .collect()
+}
+
+#[test]
+pub fn test_backtrace_mem_out_of_bound() {
+    let program = fs::read("tests/programs/backtrace").unwrap().into();
+    let core_machine = Core::new(ISA_IMC, VERSION1, u64::max_value());
+    let mut machine = DefaultMachineBuilder::new(core_machine).build();
+    machine
+        .load_program(&program, &vec!["backtrace".into()])
+        .unwrap();
+    assert!(matches!(machine.run(), Err(Error::MemOutOfBound(..))));
+    let backtrace = Unwinder::new(&program).unwrap().backtrace(&mut machine);
+    assert_eq!(
+        functions(&backtrace),
+        vec!["inner", "middle", "outer", "_start"]
+    );
+    // Callers are reported with their return addresses.
+    assert_eq!(backtrace.frames[1].pc, 0x1002c);
+    assert_eq!(backtrace.frames[2].pc, 0x1001c);
+    assert_eq!(
+        backtrace.frames[2].function,
+        Some(("outer".to_string(), 0xe))
+    );
+    assert!(backtrace.frames[1].sp < backtrace.frames[2].sp);
+    assert_eq!(
+        backtrace.to_string().lines().nth(1),
+        Some("#1  0x000000000001002c in middle+0x8")
+    );
+}
+
+#[test]
+pub fn test_backtrace_invalid_instruction() {
+    let program = fs::read("tests/programs/backtrace").unwrap().into();
+    let core_machine = Core::new(ISA_IMC, VERSION1, u64::max_value());
+    let mut machine = TraceMachine::new(DefaultMachineBuilder::new(core_machine).build());
+    machine
+        .load_program(&program, &vec!["backtrace".into(), "invalid".into()])
+        .unwrap();
+    assert!(matches!(
+        machine.run(),
+        Err(Error::InvalidInstruction { pc: 0x10044, .. })
+    ));
+    let backtrace = Unwinder::new(&program)
+        .unwrap()
+        .backtrace(&mut machine.machine);
+    assert_eq!(
+        functions(&backtrace),
+        vec!["inner", "middle", "outer", "_start"]
+    );
+    assert_eq!(backtrace.frames[0].pc, 0x10044);
+}
+
+#[cfg(has_asm)]
+#[test]
+pub fn test_backtrace_asm() {
+    let program = fs::read("tests/programs/backtrace").unwrap().into();
+    let core_machine = AsmCoreMachine::new(ISA_IMC, VERSION1, u64::max_value());
+    let mut machine = AsmMachine::new(DefaultMachineBuilder::new(core_machine).build());
+    machine
+        .load_program(&program, &vec!["backtrace".into()])
+        .unwrap();
+    assert!(matches!(machine.run(), Err(Error::MemOutOfBound(..))));
+    let backtrace = Unwinder::new(&program)
+        .unwrap()
+        .backtrace(&mut machine.machine);
+    assert_eq!(
+        functions(&backtrace),
+        vec!["inner", "middle", "outer", "_start"]
+    );
+}
+
+#[test]
+pub fn test_backtrace_cycles_exceeded() {
+    let program = fs::read("tests/programs/alloc_many").unwrap().into();
+    let core_machine = Core::new(ISA_IMC, VERSION1, 30000);
+    let mut machine = DefaultMachineBuilder::new(core_machine)
+        .instruction_cycle_func(Box::new(|_| 1))
+        .build();
+    machine
+        .load_program(&program, &vec!["alloc_many".into()])
+        .unwrap();
+    assert_eq!(machine.run(), Err(Error::CyclesExceeded));
+    let backtrace = Unwinder::new(&program).unwrap().backtrace(&mut machine);
+    // main keeps its frame with s0 and moves sp by a register, memset is a
+    // leaf function.
+    assert_eq!(functions(&backtrace), vec!["memset", "main", "_start"]);
+    let (file, _) = backtrace.frames[0].location.as_ref().unwrap();
+    assert!(file.ends_with("memset.S"));
+    assert_eq!(backtrace.frames[1].location, None);
+}
+
+#[test]
+pub fn test_call_frame_table() {
+    let program = fs::read("tests/programs/alloc_many").unwrap();
+    let table = CallFrameTable::parse(&program).unwrap();
+    assert!(!table.is_empty());
+    // __libc_init_array starts at 0x101d8
+    let row = table.find(0x101d8).unwrap();
+    assert_eq!(row.cfa, Some((2, 0)));
+    assert_eq!(row.return_address, 1);
+    assert_eq!(row.rule(1), RegisterRule::SameValue);
+    let row = table.find(0x101f0).unwrap();
+    assert_eq!(row.cfa, Some((2, 32)));
+    assert_eq!(row.rule(1), RegisterRule::Offset(-8));
+    assert_eq!(row.rule(8), RegisterRule::Offset(-16));
+    assert_eq!(row.rule(18), RegisterRule::Offset(-32));
+    // The epilogue restores registers one by one, then the saved state is
+    // brought back for the code after it.
+    let row = table.find(0x1021a).unwrap();
+    assert_eq!(row.rule(1), RegisterRule::SameValue);
+    assert_eq!(row.rule(9), RegisterRule::Offset(-24));
+    assert_eq!(table.find(0x10220).unwrap().cfa, Some((2, 0)));
+    let row = table.find(0x10222).unwrap();
+    assert_eq!(row.cfa, Some((2, 32)));
+    assert_eq!(row.rule(1), RegisterRule::Offset(-8));
+    // main has no call frame information.
+    assert_eq!(table.find(0x10146), None);
+}
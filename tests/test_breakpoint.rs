@@ -0,0 +1,219 @@
+use ckb_vm::error::WatchpointKind;
+#[cfg(has_asm)]
+use ckb_vm::machine::asm::{AsmCoreMachine, AsmMachine};
+use ckb_vm::machine::{trace::TraceMachine, VERSION1, VERSION3};
+use ckb_vm::{
+    CoreMachine, DefaultCoreMachine, DefaultMachine, DefaultMachineBuilder, Error, SparseMemory,
+    WatchpointMemory, ISA_D, ISA_F, ISA_IMC, ISA_V, ISA_ZC,
+};
+use std::fs;
+
+// Address of the first byte in .bss of simple64, which is cleared by memset
+// in _start, and read by __do_global_dtors_aux on exit.
+const BSS_START: u64 = 0x11ee0;
+const MAIN: u64 = 0x10146;
+const MEMSET_START: u64 = 0x105da;
+const MEMSET_END: u64 = 0x10698;
+const DTORS_LOAD: u64 = 0x10102;
+
+type Core = DefaultCoreMachine<u64, WatchpointMemory<SparseMemory<u64>>>;
+
+fn build_machine() -> DefaultMachine<Core> {
+    let buffer = fs::read("tests/programs/simple64").unwrap().into();
+    let core_machine = Core::new(ISA_IMC, VERSION1, u64::max_value());
+    let mut machine = DefaultMachineBuilder::new(core_machine).build();
+    machine
+        .load_program(&buffer, &vec!["simple".into()])
+        .unwrap();
+    machine
+}
+
+#[test]
+pub fn test_breakpoint() {
+    let mut machine = build_machine();
+    assert!(machine.add_breakpoint(MAIN));
+    assert_eq!(machine.run(), Err(Error::Breakpoint(MAIN)));
+    assert_eq!(*machine.pc(), MAIN);
+    // Resuming runs the instruction at the breakpoint
+    assert_eq!(machine.run(), Ok(0));
+}
+
+#[test]
+pub fn test_breakpoint_remove() {
+    let mut machine = build_machine();
+    machine.add_breakpoint(MAIN);
+    assert_eq!(machine.run(), Err(Error::Breakpoint(MAIN)));
+    assert!(machine.remove_breakpoint(MAIN));
+    assert!(!machine.remove_breakpoint(MAIN));
+    assert_eq!(machine.run(), Ok(0));
+}
+
+#[test]
+pub fn test_watchpoint() {
+    let mut machine = build_machine();
+    machine
+        .memory_mut()
+        .watchpoints_mut()
+        .add(BSS_START, 1, WatchpointKind::Write);
+    match machine.run() {
+        Err(Error::Watchpoint { pc, address, kind }) => {
+            assert!((MEMSET_START..MEMSET_END).contains(&pc));
+            assert!(address <= BSS_START);
+            assert_eq!(kind, WatchpointKind::Write);
+        }
+        r => panic!("unexpected result {:?}", r),
+    }
+    machine.memory_mut().watchpoints_mut().clear();
+    machine
+        .memory_mut()
+        .watchpoints_mut()
+        .add(BSS_START, 1, WatchpointKind::Read);
+    assert_eq!(
+        machine.run(),
+        Err(Error::Watchpoint {
+            pc: DTORS_LOAD,
+            address: BSS_START,
+            kind: WatchpointKind::Read,
+        })
+    );
+    assert_eq!(machine.run(), Ok(0));
+}
+
+#[cfg(has_asm)]
+fn build_asm_machine() -> AsmMachine {
+    let buffer = fs::read("tests/programs/simple64").unwrap().into();
+    let asm_core = AsmCoreMachine::new(ISA_IMC, VERSION1, u64::max_value());
+    let core = DefaultMachineBuilder::new(asm_core).build();
+    let mut machine = AsmMachine::new(core);
+    machine
+        .load_program(&buffer, &vec!["simple".into()])
+        .unwrap();
+    machine
+}
+
+#[cfg(has_asm)]
+#[test]
+pub fn test_asm_breakpoint() {
+    let mut machine = build_asm_machine();
+    machine.machine.add_breakpoint(MAIN);
+    assert_eq!(machine.run(), Err(Error::Breakpoint(MAIN)));
+    assert_eq!(*machine.machine.pc(), MAIN);
+    assert_eq!(machine.run(), Ok(0));
+}
+
+#[cfg(has_asm)]
+#[test]
+pub fn test_asm_watchpoint() {
+    let mut machine = build_asm_machine();
+    machine
+        .watchpoints_mut()
+        .add(BSS_START, 1, WatchpointKind::Access);
+    match machine.run() {
+        Err(Error::Watchpoint { pc, kind, .. }) => {
+            assert!((MEMSET_START..MEMSET_END).contains(&pc));
+            assert_eq!(kind, WatchpointKind::Access);
+        }
+        r => panic!("unexpected result {:?}", r),
+    }
+    machine.watchpoints_mut().clear();
+    machine
+        .watchpoints_mut()
+        .add(BSS_START, 1, WatchpointKind::Read);
+    assert_eq!(
+        machine.run(),
+        Err(Error::Watchpoint {
+            pc: DTORS_LOAD,
+            address: BSS_START,
+            kind: WatchpointKind::Read,
+        })
+    );
+    assert!(machine
+        .watchpoints_mut()
+        .remove(BSS_START, 1, WatchpointKind::Read));
+    assert_eq!(machine.run(), Ok(0));
+}
+
+// Runs a program twice, first watching writes then reads of address, and
+// returns the pcs reported by each engine.
+fn watch_classes(name: &str, isa: u8, address: u64) -> Vec<(u64, u64)> {
+    let buffer: bytes::Bytes = fs::read(format!("tests/programs/{}", name)).unwrap().into();
+    let args = vec![name.to_string().into()];
+    let watchpoint_pc = |result| match result {
+        Err(Error::Watchpoint { pc, .. }) => pc,
+        r => panic!("unexpected result {:?}", r),
+    };
+
+    let core_machine = Core::new(isa, VERSION3, u64::max_value());
+    let mut machine = DefaultMachineBuilder::new(core_machine).build();
+    machine.load_program(&buffer, &args).unwrap();
+    let watchpoints = machine.memory_mut().watchpoints_mut();
+    watchpoints.add(address, 1, WatchpointKind::Write);
+    let write = watchpoint_pc(machine.run());
+    let watchpoints = machine.memory_mut().watchpoints_mut();
+    watchpoints.clear();
+    watchpoints.add(address, 1, WatchpointKind::Read);
+    let read = watchpoint_pc(machine.run());
+    let mut pcs = vec![(write, read)];
+
+    let core_machine = Core::new(isa, VERSION3, u64::max_value());
+    let mut machine = TraceMachine::new(DefaultMachineBuilder::new(core_machine).build());
+    machine.load_program(&buffer, &args).unwrap();
+    let watchpoints = machine.machine.memory_mut().watchpoints_mut();
+    watchpoints.add(address, 1, WatchpointKind::Write);
+    let write = watchpoint_pc(machine.run());
+    let watchpoints = machine.machine.memory_mut().watchpoints_mut();
+    watchpoints.clear();
+    watchpoints.add(address, 1, WatchpointKind::Read);
+    let read = watchpoint_pc(machine.run());
+    pcs.push((write, read));
+
+    #[cfg(has_asm)]
+    {
+        let asm_core = AsmCoreMachine::new(isa, VERSION3, u64::max_value());
+        let mut machine = AsmMachine::new(DefaultMachineBuilder::new(asm_core).build());
+        machine.load_program(&buffer, &args).unwrap();
+        machine
+            .watchpoints_mut()
+            .add(address, 1, WatchpointKind::Write);
+        let write = watchpoint_pc(machine.run());
+        machine.watchpoints_mut().clear();
+        machine
+            .watchpoints_mut()
+            .add(address, 1, WatchpointKind::Read);
+        let read = watchpoint_pc(machine.run());
+        pcs.push((write, read));
+    }
+    pcs
+}
+
+const FLOAT_VECTOR: u8 = ISA_IMC | ISA_F | ISA_D | ISA_V;
+
+#[test]
+pub fn test_watchpoint_float() {
+    for pcs in watch_classes("watchpoint_classes", FLOAT_VECTOR, 0x20000) {
+        assert_eq!(pcs, (0x10004, 0x10008));
+    }
+    for pcs in watch_classes("watchpoint_classes", FLOAT_VECTOR, 0x2000c) {
+        assert_eq!(pcs, (0x1000c, 0x10010));
+    }
+}
+
+#[test]
+pub fn test_watchpoint_vector() {
+    for pcs in watch_classes("watchpoint_classes", FLOAT_VECTOR, 0x2001c) {
+        assert_eq!(pcs, (0x10020, 0x10024));
+    }
+    // The strided store and load run backwards from 0x20038.
+    for pcs in watch_classes("watchpoint_classes", FLOAT_VECTOR, 0x20028) {
+        assert_eq!(pcs, (0x10030, 0x10034));
+    }
+}
+
+#[test]
+pub fn test_watchpoint_zcmp() {
+    // cm.push stores ra and s0 right below sp, cm.pop loads them back. D is
+    // left out as c.fsdsp shares the encodings of Zcmp.
+    for pcs in watch_classes("watchpoint_zcmp", ISA_IMC | ISA_ZC, 0x20008) {
+        assert_eq!(pcs, (0x10008, 0x1000a));
+    }
+}
@@ -0,0 +1,64 @@
+use ckb_vm::compliance::{ComplianceTest, Signature, Termination};
+use ckb_vm::machine::VERSION2;
+use ckb_vm::{ISA_A, ISA_B, ISA_IMC};
+use std::fs;
+use std::path::Path;
+
+const DIR: &str = "tests/programs/compliance";
+
+fn run_compliance(name: &str, isa: u8) {
+    let path = Path::new(DIR).join(name);
+    let test = ComplianceTest::new(fs::read(&path).unwrap().into()).unwrap();
+    let reference = Signature::parse_reference(
+        &fs::read_to_string(path.with_extension("reference_output")).unwrap(),
+    )
+    .unwrap();
+    for (engine, outcome) in test.run_all(isa, VERSION2, 10_000) {
+        let outcome = outcome.unwrap_or_else(|e| panic!("{} on {}: {:?}", name, engine, e));
+        assert_eq!(outcome.termination, Termination::ToHost(1));
+        assert!(outcome.termination.passed());
+        if let Some(word) = outcome.signature.mismatch(&reference, 8) {
+            panic!(
+                "{} on {} differs at word {}, signature:\n{}",
+                name,
+                engine,
+                word,
+                outcome.signature.format(8)
+            );
+        }
+    }
+}
+
+#[test]
+pub fn test_compliance_rv64i() {
+    run_compliance("rv64i", ISA_IMC);
+}
+
+#[test]
+pub fn test_compliance_rv64m() {
+    run_compliance("rv64m", ISA_IMC);
+}
+
+#[test]
+pub fn test_compliance_rv64a() {
+    run_compliance("rv64a", ISA_IMC | ISA_A);
+}
+
+#[test]
+pub fn test_compliance_rv64b() {
+    run_compliance("rv64b", ISA_IMC | ISA_B);
+}
+
+#[test]
+pub fn test_compliance_signature() {
+    let test = ComplianceTest::new(fs::read(Path::new(DIR).join("rv64i")).unwrap().into()).unwrap();
+    assert_eq!(test.signature_range(), 0x20010..0x20050);
+
+    let reference = Signature::parse_reference("00000003\n0000000000000001\n").unwrap();
+    assert_eq!(reference.as_bytes(), &[3, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(reference.format(4), "00000003\n00000001\n00000000\n");
+    let signature = Signature::new(vec![3, 0, 0, 0, 1, 0, 0, 0]);
+    assert_eq!(signature.mismatch(&reference, 4), Some(2));
+    assert_eq!(signature.mismatch(&signature, 4), None);
+    assert!(Signature::parse_reference("xyz\n").is_err());
+}
@@ -0,0 +1,114 @@
+use ckb_vm::coredump::{signal, write_core_dump, SIGILL, SIGSEGV};
+use ckb_vm::machine::{trace::TraceMachine, VERSION1};
+use ckb_vm::registers::{A1, SP};
+use ckb_vm::{
+    CoreMachine, DefaultCoreMachine, DefaultMachineBuilder, Error, Memory, SparseMemory,
+    WXorXMemory, ISA_IMC,
+};
+use goblin_v040::elf::{program_header::PT_LOAD, Elf};
+use std::fs;
+
+#[test]
+pub fn test_coredump_write_on_executable_page() {
+    let program = fs::read("tests/programs/error_report").unwrap().into();
+    let core_machine = DefaultCoreMachine::<u64, WXorXMemory<SparseMemory<u64>>>::new(
+        ISA_IMC,
+        VERSION1,
+        u64::max_value(),
+    );
+    let mut machine = TraceMachine::new(DefaultMachineBuilder::new(core_machine).build());
+    machine
+        .load_program(&program, &vec!["error_report".into()])
+        .unwrap();
+    let error = machine.run().unwrap_err();
+    assert_eq!(signal(&error), SIGSEGV);
+    let mut core = vec![];
+    write_core_dump(&mut machine.machine, signal(&error), &mut core).unwrap();
+
+    let elf = Elf::parse(&core).unwrap();
+    assert!(elf.is_64);
+    assert_eq!(elf.header.e_type, goblin_v040::elf::header::ET_CORE);
+    assert_eq!(elf.header.e_machine, goblin_v040::elf::header::EM_RISCV);
+
+    let notes: Vec<_> = elf
+        .iter_note_headers(&core)
+        .unwrap()
+        .map(|n| n.unwrap())
+        .collect();
+    assert_eq!(notes.len(), 1);
+    assert_eq!(notes[0].n_type, 1);
+    let prstatus = notes[0].desc;
+    assert_eq!(prstatus.len(), 376);
+    let word = |offset: usize| u64::from_le_bytes(prstatus[offset..offset + 8].try_into().unwrap());
+    assert_eq!(
+        u32::from_le_bytes(prstatus[0..4].try_into().unwrap()),
+        SIGSEGV
+    );
+    assert_eq!(word(112), *machine.machine.pc());
+    assert_eq!(word(112 + A1 * 8), 0x12345678);
+    assert_eq!(word(112 + SP * 8), machine.machine.registers()[SP]);
+
+    // The code and the stack are dumped, the pages in between are all zero.
+    let loads: Vec<_> = elf
+        .program_headers
+        .iter()
+        .filter(|h| h.p_type == PT_LOAD)
+        .collect();
+    assert_eq!(loads.len(), 2);
+    assert_eq!(loads[0].p_vaddr, 0x10000);
+    assert!(loads[0].is_executable() && !loads[0].is_write());
+    assert!(loads[1].is_write() && !loads[1].is_executable());
+    for load in loads {
+        let expected = machine
+            .machine
+            .memory_mut()
+            .load_bytes(load.p_vaddr, load.p_filesz)
+            .unwrap();
+        assert_eq!(&core[load.file_range()], &expected[..]);
+        assert_eq!(load.p_offset % 4096, 0);
+    }
+}
+
+#[test]
+pub fn test_coredump_32() {
+    let program = fs::read("tests/programs/simple").unwrap().into();
+    let core_machine = DefaultCoreMachine::<u32, WXorXMemory<SparseMemory<u32>>>::new(
+        ISA_IMC,
+        VERSION1,
+        u64::max_value(),
+    );
+    let mut machine = DefaultMachineBuilder::new(core_machine).build();
+    machine
+        .load_program(&program, &vec!["simple".into()])
+        .unwrap();
+    assert_eq!(machine.run(), Ok(0));
+    let mut core = vec![];
+    write_core_dump(&mut machine, 0, &mut core).unwrap();
+
+    let elf = Elf::parse(&core).unwrap();
+    assert!(!elf.is_64);
+    let notes: Vec<_> = elf
+        .iter_note_headers(&core)
+        .unwrap()
+        .map(|n| n.unwrap())
+        .collect();
+    assert_eq!(notes[0].desc.len(), 204);
+    let pc = u32::from_le_bytes(notes[0].desc[72..76].try_into().unwrap());
+    assert_eq!(pc, *machine.pc());
+    assert!(elf
+        .program_headers
+        .iter()
+        .any(|h| h.p_type == PT_LOAD && h.is_executable()));
+}
+
+#[test]
+pub fn test_coredump_signal() {
+    assert_eq!(
+        signal(&Error::InvalidInstruction {
+            pc: 0,
+            instruction: 0
+        }),
+        SIGILL
+    );
+    assert_eq!(signal(&Error::MemWriteOnFreezedPage(1)), SIGSEGV);
+}
@@ -0,0 +1,117 @@
+use ckb_vm::coverage::{Coverage, CoverageDecoder, CoverageReport};
+use ckb_vm::decoder::build_decoder;
+use ckb_vm::dwarf::LineTable;
+#[cfg(has_asm)]
+use ckb_vm::machine::asm::{traces::SimpleFixedTraceDecoder, AsmCoreMachine, AsmMachine};
+use ckb_vm::machine::VERSION1;
+use ckb_vm::{DefaultCoreMachine, DefaultMachineBuilder, SparseMemory, ISA_IMC};
+use std::fs;
+
+const CRT0: &str = "/source/riscv-newlib/libgloss/riscv/crt0.S";
+const MEMSET: &str = "/source/riscv-newlib/newlib/libc/machine/riscv/memset.S";
+const ENTRY: u64 = 0x100c0;
+const MAIN: u64 = 0x10146;
+
+fn run_with_coverage() -> Coverage {
+    let buffer = fs::read("tests/programs/simple64").unwrap().into();
+    let core_machine =
+        DefaultCoreMachine::<u64, SparseMemory<u64>>::new(ISA_IMC, VERSION1, u64::max_value());
+    let mut machine = DefaultMachineBuilder::new(core_machine).build();
+    machine
+        .load_program(&buffer, &vec!["simple".into()])
+        .unwrap();
+    let mut decoder = CoverageDecoder::new(build_decoder::<u64>(ISA_IMC, VERSION1));
+    assert_eq!(machine.run_with_decoder(&mut decoder), Ok(0));
+    decoder.take_coverage()
+}
+
+#[test]
+pub fn test_line_table() {
+    let buffer = fs::read("tests/programs/simple64").unwrap();
+    let lines = LineTable::parse(&buffer).unwrap();
+    assert_eq!(lines.lookup(ENTRY), Some((CRT0, 25)));
+    assert_eq!(lines.lookup(0x100d2), Some((CRT0, 33)));
+    assert_eq!(lines.lookup(0x100fc), Some((CRT0, 55)));
+    assert_eq!(lines.lookup(0x105da), Some((MEMSET, 17)));
+    assert_eq!(
+        lines.lookup(0x10568),
+        Some(("/source/riscv-newlib/newlib/libc/stdlib/exit.c", 60))
+    );
+    // main is built without debug information
+    assert_eq!(lines.lookup(MAIN), None);
+}
+
+#[test]
+pub fn test_coverage_merge() {
+    let coverage = run_with_coverage();
+    assert_eq!(coverage.hits(ENTRY), 1);
+    assert_eq!(coverage.hits(MAIN), 1);
+    assert_eq!(coverage.hits(ENTRY + 2), 0);
+
+    let mut merged = Coverage::new();
+    merged.merge(&coverage);
+    merged.merge(&run_with_coverage());
+    assert_eq!(merged.len(), coverage.len());
+    assert!(merged
+        .iter()
+        .all(|(pc, hits)| hits == 2 * coverage.hits(pc)));
+}
+
+#[test]
+pub fn test_coverage_report() {
+    let buffer = fs::read("tests/programs/simple64").unwrap();
+    let report = CoverageReport::new(&buffer).unwrap();
+    let coverage = run_with_coverage();
+    let files = report.line_hits(&coverage);
+    assert_eq!(files[CRT0][&25], 1);
+    assert!(files[MEMSET][&17] > 0);
+    // Lines with code but never run are reported
+    assert!(files
+        .values()
+        .any(|lines| lines.values().any(|hits| *hits == 0)));
+
+    let mut lcov = Vec::new();
+    report.write_lcov(&coverage, "simple", &mut lcov).unwrap();
+    let lcov = String::from_utf8(lcov).unwrap();
+    assert!(lcov.starts_with("TN:simple\n"));
+    let record = lcov
+        .split("end_of_record\n")
+        .find(|r| r.contains(&format!("SF:{}\n", CRT0)))
+        .unwrap();
+    assert!(record.contains("FN:25,_start\n"));
+    assert!(record.contains("FNDA:1,_start\n"));
+    assert!(record.contains("DA:25,1\n"));
+    assert!(record.contains(&format!("LF:{}\n", files[CRT0].len())));
+
+    let mut cobertura = Vec::new();
+    report.write_cobertura(&coverage, &mut cobertura).unwrap();
+    let cobertura = String::from_utf8(cobertura).unwrap();
+    assert!(cobertura.contains(&format!(r#"<class name="{}" filename="{}""#, CRT0, CRT0)));
+    assert!(cobertura.contains(r#"<line number="25" hits="1" branch="false"/>"#));
+    assert!(cobertura.ends_with("</coverage>\n"));
+}
+
+#[cfg(has_asm)]
+#[test]
+pub fn test_asm_coverage() {
+    let buffer = fs::read("tests/programs/simple64").unwrap().into();
+    let asm_core = AsmCoreMachine::new(ISA_IMC, VERSION1, u64::max_value());
+    let core = DefaultMachineBuilder::new(asm_core).build();
+    let mut machine = AsmMachine::new(core);
+    machine
+        .load_program(&buffer, &vec!["simple".into()])
+        .unwrap();
+    let mut decoder = SimpleFixedTraceDecoder::new(CoverageDecoder::new(build_decoder::<u64>(
+        ISA_IMC, VERSION1,
+    )));
+    assert_eq!(machine.run_with_decoder(&mut decoder), Ok(0));
+    // Hit counts differ, but the same instructions are covered
+    let covered: Vec<u64> = run_with_coverage().iter().map(|(pc, _)| pc).collect();
+    let asm_covered: Vec<u64> = decoder
+        .decoder()
+        .coverage()
+        .iter()
+        .map(|(pc, _)| pc)
+        .collect();
+    assert_eq!(asm_covered, covered);
+}
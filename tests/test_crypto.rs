@@ -0,0 +1,114 @@
+use ckb_vm::differential::Differential;
+#[cfg(has_asm)]
+use ckb_vm::machine::asm::{AsmCoreMachine, AsmMachine};
+use ckb_vm::machine::{trace::TraceMachine, VERSION2, VERSION3};
+use ckb_vm::{
+    DefaultCoreMachine, DefaultMachine, DefaultMachineBuilder, Error, SparseMemory, WXorXMemory,
+    ISA_B, ISA_IMC, ISA_K,
+};
+use std::fs;
+
+type CoreMachine = DefaultCoreMachine<u64, WXorXMemory<SparseMemory<u64>>>;
+
+fn build(isa: u8, version: u32) -> DefaultMachine<CoreMachine> {
+    let core_machine = CoreMachine::new(isa, version, u64::max_value());
+    DefaultMachineBuilder::new(core_machine)
+        .instruction_cycle_func(Box::new(|_| 1))
+        .build()
+}
+
+fn program() -> bytes::Bytes {
+    fs::read("tests/programs/crypto").unwrap().into()
+}
+
+#[test]
+pub fn test_crypto_interpreter() {
+    let mut machine = build(ISA_IMC | ISA_K, VERSION3);
+    machine
+        .load_program(&program(), &vec!["crypto".into()])
+        .unwrap();
+    assert_eq!(machine.run(), Ok(0));
+}
+
+#[test]
+pub fn test_crypto_with_isa_b() {
+    // Instructions shared by B and Zbkb decode the same with both enabled.
+    let mut machine = build(ISA_IMC | ISA_B | ISA_K, VERSION3);
+    machine
+        .load_program(&program(), &vec!["crypto".into()])
+        .unwrap();
+    assert_eq!(machine.run(), Ok(0));
+}
+
+#[test]
+pub fn test_crypto_trace() {
+    let mut machine = TraceMachine::new(build(ISA_IMC | ISA_K, VERSION3));
+    machine
+        .load_program(&program(), &vec!["crypto".into()])
+        .unwrap();
+    assert_eq!(machine.run(), Ok(0));
+}
+
+#[cfg(has_asm)]
+#[test]
+pub fn test_crypto_asm() {
+    let core = DefaultMachineBuilder::new(AsmCoreMachine::new(
+        ISA_IMC | ISA_K,
+        VERSION3,
+        u64::max_value(),
+    ))
+    .instruction_cycle_func(Box::new(|_| 1))
+    .build();
+    let mut machine = AsmMachine::new(core);
+    machine
+        .load_program(&program(), &vec!["crypto".into()])
+        .unwrap();
+    assert_eq!(machine.run(), Ok(0));
+}
+
+#[test]
+pub fn test_crypto_differential() {
+    let differential = Differential::new(
+        build(ISA_IMC | ISA_K, VERSION3),
+        build(ISA_IMC | ISA_K, VERSION3),
+    );
+    #[cfg(has_asm)]
+    let differential = differential.with_asm(
+        DefaultMachineBuilder::new(AsmCoreMachine::new(
+            ISA_IMC | ISA_K,
+            VERSION3,
+            u64::max_value(),
+        ))
+        .instruction_cycle_func(Box::new(|_| 1))
+        .build(),
+    );
+    let mut differential = differential;
+    differential
+        .load_program(&program(), &vec!["crypto".into()])
+        .unwrap();
+    assert_eq!(differential.run().map_err(|d| d.to_string()), Ok(Ok(0)));
+}
+
+#[test]
+pub fn test_crypto_requires_version3() {
+    let mut machine = build(ISA_IMC | ISA_K, VERSION2);
+    machine
+        .load_program(&program(), &vec!["crypto".into()])
+        .unwrap();
+    assert!(matches!(
+        machine.run(),
+        Err(Error::InvalidInstruction { .. })
+    ));
+}
+
+#[test]
+pub fn test_crypto_requires_isa_k() {
+    let mut machine = build(ISA_IMC | ISA_B, VERSION3);
+    machine
+        .load_program(&program(), &vec!["crypto".into()])
+        .unwrap();
+    assert!(matches!(
+        machine.run(),
+        Err(Error::InvalidInstruction { .. })
+    ));
+}
@@ -0,0 +1,176 @@
+use ckb_vm::differential::Differential;
+use ckb_vm::disasm::Disassembler;
+use ckb_vm::instructions::{custom::CustomInstruction, Rtype};
+#[cfg(has_asm)]
+use ckb_vm::machine::asm::{AsmCoreMachine, AsmMachine};
+use ckb_vm::machine::{trace::TraceMachine, VERSION2};
+use ckb_vm::{
+    CoreMachine, DefaultCoreMachine, DefaultMachine, DefaultMachineBuilder, Error, Instruction,
+    Register, SparseMemory, SupportMachine, WXorXMemory, DEFAULT_MEMORY_SIZE, ISA_IMC,
+};
+use std::fs;
+
+type Core = DefaultCoreMachine<u64, WXorXMemory<SparseMemory<u64>>>;
+
+// madd rd, rs1, rs2 in custom-0 with funct3 and funct7 set to 0.
+fn decode_madd(instruction_bits: u32) -> Option<Instruction> {
+    if instruction_bits & 0b_1111111 != 0b_0001011 || instruction_bits & 0xfe00_7000 != 0 {
+        return None;
+    }
+    let rd = (instruction_bits >> 7) as usize & 0x1f;
+    let rs1 = (instruction_bits >> 15) as usize & 0x1f;
+    let rs2 = (instruction_bits >> 20) as usize & 0x1f;
+    Some(Rtype::new(0, rd, rs1, rs2).0)
+}
+
+fn madd<Mac: CoreMachine>(cycles: u64) -> CustomInstruction<Mac> {
+    CustomInstruction::new(
+        "madd",
+        cycles,
+        decode_madd,
+        Box::new(|machine: &mut Mac, inst| {
+            let i = Rtype(inst);
+            let product =
+                machine.registers()[i.rs1()].overflowing_mul(&machine.registers()[i.rs2()]);
+            let value = machine.registers()[i.rd()].overflowing_add(&product);
+            machine.set_register(i.rd(), value);
+            Ok(())
+        }),
+    )
+}
+
+fn build(cycles: u64) -> DefaultMachine<Core> {
+    let core_machine = Core::new(ISA_IMC, VERSION2, u64::max_value());
+    DefaultMachineBuilder::new(core_machine)
+        .instruction_cycle_func(Box::new(|_| 1))
+        .custom_instruction(madd(cycles))
+        .build()
+}
+
+#[cfg(has_asm)]
+fn build_asm(cycles: u64) -> DefaultMachine<Box<AsmCoreMachine>> {
+    let core_machine = AsmCoreMachine::new(ISA_IMC, VERSION2, u64::max_value());
+    DefaultMachineBuilder::new(core_machine)
+        .instruction_cycle_func(Box::new(|_| 1))
+        .custom_instruction(madd(cycles))
+        .build()
+}
+
+fn program() -> bytes::Bytes {
+    fs::read("tests/programs/custom_instruction")
+        .unwrap()
+        .into()
+}
+
+#[test]
+pub fn test_custom_instruction_interpreter() {
+    let mut machine = build(1);
+    machine
+        .load_program(&program(), &vec!["custom_instruction".into()])
+        .unwrap();
+    assert_eq!(machine.run(), Ok(0));
+    assert_eq!(machine.custom_instructions()[0].name(), "madd");
+}
+
+#[test]
+pub fn test_custom_instruction_trace() {
+    let mut machine = TraceMachine::new(build(1));
+    machine
+        .load_program(&program(), &vec!["custom_instruction".into()])
+        .unwrap();
+    assert_eq!(machine.run(), Ok(0));
+}
+
+#[cfg(has_asm)]
+#[test]
+pub fn test_custom_instruction_asm() {
+    let mut machine = AsmMachine::new(build_asm(1));
+    machine
+        .load_program(&program(), &vec!["custom_instruction".into()])
+        .unwrap();
+    assert_eq!(machine.run(), Ok(0));
+}
+
+#[test]
+pub fn test_custom_instruction_differential() {
+    let differential = Differential::new(build(1), build(1));
+    #[cfg(has_asm)]
+    let differential = differential.with_asm(build_asm(1));
+    let mut differential = differential;
+    differential
+        .load_program(&program(), &vec!["custom_instruction".into()])
+        .unwrap();
+    assert_eq!(differential.run().map_err(|d| d.to_string()), Ok(Ok(0)));
+}
+
+#[test]
+pub fn test_custom_instruction_cycles() {
+    let mut cheap = build(1);
+    cheap
+        .load_program(&program(), &vec!["custom_instruction".into()])
+        .unwrap();
+    assert_eq!(cheap.run(), Ok(0));
+    let mut machine = TraceMachine::new(build(10));
+    machine
+        .load_program(&program(), &vec!["custom_instruction".into()])
+        .unwrap();
+    assert_eq!(machine.run(), Ok(0));
+    // madd is executed 11 times.
+    assert_eq!(machine.machine.cycles(), cheap.cycles() + 11 * 9);
+    #[cfg(has_asm)]
+    {
+        let mut machine = AsmMachine::new(build_asm(10));
+        machine
+            .load_program(&program(), &vec!["custom_instruction".into()])
+            .unwrap();
+        assert_eq!(machine.run(), Ok(0));
+        assert_eq!(machine.machine.cycles(), cheap.cycles() + 11 * 9);
+    }
+}
+
+#[test]
+pub fn test_custom_instruction_unregistered() {
+    let core_machine = Core::new(ISA_IMC, VERSION2, u64::max_value());
+    let mut machine = DefaultMachineBuilder::new(core_machine).build();
+    machine
+        .load_program(&program(), &vec!["custom_instruction".into()])
+        .unwrap();
+    assert!(matches!(
+        machine.run(),
+        Err(Error::InvalidInstruction { .. })
+    ));
+}
+
+#[test]
+pub fn test_custom_instruction_disassembly() {
+    let mut disassembler =
+        Disassembler::new(&program(), ISA_IMC, VERSION2, DEFAULT_MEMORY_SIZE).unwrap();
+    disassembler.add_custom_instruction(&madd::<Core>(1));
+    let region = disassembler.regions()[0].clone();
+    let listing: Vec<String> = disassembler
+        .disassemble_region(&region)
+        .iter()
+        .map(|i| disassembler.format(i))
+        .collect();
+    assert_eq!(listing.iter().filter(|l| *l == "madd").count(), 2);
+    assert!(listing.iter().all(|l| !l.starts_with(".4byte")));
+
+    // Error reports name the custom instruction too.
+    let core_machine = Core::new(ISA_IMC, VERSION2, u64::max_value());
+    let failing = CustomInstruction::new(
+        "fail",
+        1,
+        decode_madd,
+        Box::new(|_: &mut Core, _| Err(Error::Unexpected("fail".to_string()))),
+    );
+    let mut machine = DefaultMachineBuilder::new(core_machine)
+        .custom_instruction(failing)
+        .error_report(true)
+        .build();
+    machine
+        .load_program(&program(), &vec!["custom_instruction".into()])
+        .unwrap();
+    assert!(machine.run().is_err());
+    let report = machine.error_report().unwrap();
+    assert_eq!(report.disassembly.as_deref(), Some("fail"));
+}
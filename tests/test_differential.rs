@@ -0,0 +1,68 @@
+use ckb_vm::differential::{Differential, Engine, Mismatch};
+#[cfg(has_asm)]
+use ckb_vm::machine::asm::AsmCoreMachine;
+use ckb_vm::machine::VERSION1;
+use ckb_vm::{
+    DefaultCoreMachine, DefaultMachine, DefaultMachineBuilder, SparseMemory, SupportMachine,
+    WXorXMemory, ISA_IMC,
+};
+use std::fs;
+
+type CoreMachine = DefaultCoreMachine<u64, WXorXMemory<SparseMemory<u64>>>;
+
+fn build() -> DefaultMachine<CoreMachine> {
+    let core_machine = CoreMachine::new(ISA_IMC, VERSION1, u64::max_value());
+    DefaultMachineBuilder::new(core_machine)
+        .instruction_cycle_func(Box::new(|_| 1))
+        .build()
+}
+
+fn differential(program: &str) -> Differential<CoreMachine> {
+    let differential = Differential::new(build(), build());
+    #[cfg(has_asm)]
+    let differential = differential.with_asm(
+        DefaultMachineBuilder::new(AsmCoreMachine::new(ISA_IMC, VERSION1, u64::max_value()))
+            .instruction_cycle_func(Box::new(|_| 1))
+            .build(),
+    );
+    let mut differential = differential;
+    let buffer = fs::read(program).unwrap().into();
+    differential
+        .load_program(&buffer, &vec!["differential".into()])
+        .unwrap();
+    differential
+}
+
+#[test]
+pub fn test_differential_simple() {
+    let mut differential = differential("tests/programs/simple64");
+    let result = differential.run();
+    assert_eq!(result.map_err(|d| d.to_string()), Ok(Ok(0)));
+    assert_eq!(differential.interpreter_mut().cycles(), 708);
+}
+
+#[test]
+pub fn test_differential_memory_check_interval() {
+    let mut differential = differential("tests/programs/simple64");
+    differential.set_memory_check_interval(8);
+    assert_eq!(differential.run().map_err(|d| d.to_string()), Ok(Ok(0)));
+}
+
+#[test]
+pub fn test_differential_divergence() {
+    let mut differential = differential("tests/programs/simple64");
+    for _ in 0..4 {
+        assert_eq!(differential.step().map_err(|d| d.to_string()), Ok(None));
+    }
+    let cycles = differential.trace_mut().machine.cycles();
+    differential.trace_mut().machine.set_cycles(cycles + 1);
+    let divergence = differential.step().unwrap_err();
+    assert_eq!(divergence.engine, Engine::Trace);
+    assert_eq!(divergence.mismatch, Mismatch::Cycles);
+    assert_eq!(divergence.trace, 4);
+    assert_eq!(divergence.actual.cycles, divergence.expected.cycles + 1);
+    assert!(!divergence.instructions.is_empty());
+    let report = divergence.to_string();
+    assert!(report.starts_with("Trace diverges from Interpreter on cycles after trace #4\n"));
+    assert!(report.contains(&format!("  0x{:x}: ", divergence.instructions[0].0)));
+}
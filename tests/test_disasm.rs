@@ -0,0 +1,71 @@
+use ckb_vm::disasm::Disassembler;
+use ckb_vm::machine::{VERSION0, VERSION1, VERSION3};
+use ckb_vm::{Error, DEFAULT_MEMORY_SIZE, ISA_IMC, ISA_MOP, ISA_ZC};
+use std::fs;
+
+fn listing(path: &str, isa: u8, version: u32, fusion: bool) -> String {
+    let program = fs::read(path).unwrap().into();
+    let mut disassembler = Disassembler::new(&program, isa, version, DEFAULT_MEMORY_SIZE).unwrap();
+    disassembler.set_fusion(fusion).unwrap();
+    let mut output = vec![];
+    disassembler
+        .write_objdump(path.rsplit('/').next().unwrap(), &mut output)
+        .unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+pub fn test_disasm_listing() {
+    let output = listing("tests/programs/mop_adc", ISA_IMC, VERSION1, false);
+    assert!(output.contains("mop_adc:     file format elf64-littleriscv"));
+    assert!(output.contains("Disassembly of section .text:"));
+    assert!(output.contains("0000000000010078 <_start>:"));
+    assert!(output.contains("   10078:\tfff0051b          \taddiw\ta0,zero,-1\n"));
+    assert!(output.contains("\tbne\ta0,t0,1019c <fail>\n"));
+    assert!(output.contains("\tadd\ta0,a0,a1\n"));
+}
+
+#[test]
+pub fn test_disasm_fusion() {
+    let output = listing("tests/programs/mop_adc", ISA_IMC | ISA_MOP, VERSION1, true);
+    assert!(output.contains("\tadc\ta0,a1,a2\n"));
+    assert!(!output.contains("\tsltu\ta1,a0,a1\n"));
+
+    let program = fs::read("tests/programs/mop_adc").unwrap().into();
+    let mut disassembler =
+        Disassembler::new(&program, ISA_IMC, VERSION0, DEFAULT_MEMORY_SIZE).unwrap();
+    assert_eq!(disassembler.set_fusion(true), Err(Error::InvalidVersion));
+}
+
+#[test]
+pub fn test_disasm_regions() {
+    let program = fs::read("tests/programs/mop_adc").unwrap().into();
+    let mut disassembler =
+        Disassembler::new(&program, ISA_IMC, VERSION1, DEFAULT_MEMORY_SIZE).unwrap();
+    let regions = disassembler.regions().to_vec();
+    assert_eq!(regions.len(), 1);
+    assert_eq!(regions[0].name, ".text");
+    let instructions = disassembler.disassemble_region(&regions[0]);
+    assert_eq!(instructions[0].address, regions[0].start);
+    assert_eq!(
+        instructions
+            .iter()
+            .map(|i| i.bytes.len() as u64)
+            .sum::<u64>(),
+        regions[0].end - regions[0].start
+    );
+    assert!(instructions.iter().all(|i| i.instruction.is_some()));
+}
+
+#[test]
+pub fn test_disasm_undecodable() {
+    let output = listing(
+        "tests/programs/code_size",
+        ISA_IMC | ISA_ZC,
+        VERSION3,
+        false,
+    );
+    assert!(output.contains("   10004:\t8008              \tlbu\ta0,0(s0)\n"));
+    let output = listing("tests/programs/code_size", ISA_IMC, VERSION3, false);
+    assert!(output.contains("   10004:\t8008              \t.2byte\t0x8008\n"));
+}
@@ -0,0 +1,239 @@
+use ckb_vm::error::{MemoryAccess, WatchpointKind};
+#[cfg(has_asm)]
+use ckb_vm::machine::asm::{AsmCoreMachine, AsmMachine};
+use ckb_vm::machine::{trace::TraceMachine, VERSION1};
+use ckb_vm::memory::FLAG_EXECUTABLE;
+use ckb_vm::registers::A1;
+use ckb_vm::{
+    DefaultCoreMachine, DefaultMachineBuilder, Error, ErrorReport, SparseMemory, WXorXMemory,
+    ISA_IMC,
+};
+use std::fs;
+
+type Core = DefaultCoreMachine<u64, WXorXMemory<SparseMemory<u64>>>;
+
+#[derive(Clone, Copy, Debug)]
+enum Engine {
+    Interpreter,
+    Trace,
+    #[cfg(has_asm)]
+    Asm,
+}
+
+fn engines() -> Vec<Engine> {
+    #[allow(unused_mut)]
+    let mut engines = vec![Engine::Interpreter, Engine::Trace];
+    #[cfg(has_asm)]
+    engines.push(Engine::Asm);
+    engines
+}
+
+fn run(
+    engine: Engine,
+    path: &str,
+    args: &[&str],
+    max_cycles: u64,
+) -> (Result<i8, Error>, Option<ErrorReport>) {
+    let program = fs::read(path).unwrap().into();
+    let args: Vec<_> = args.iter().map(|a| a.to_string().into()).collect();
+    match engine {
+        Engine::Interpreter => {
+            let core_machine = Core::new(ISA_IMC, VERSION1, max_cycles);
+            let mut machine = DefaultMachineBuilder::new(core_machine)
+                .instruction_cycle_func(Box::new(|_| 1))
+                .error_report(true)
+                .build();
+            machine.load_program(&program, &args).unwrap();
+            let result = machine.run();
+            (result, machine.error_report().cloned())
+        }
+        Engine::Trace => {
+            let core_machine = Core::new(ISA_IMC, VERSION1, max_cycles);
+            let mut machine = TraceMachine::new(
+                DefaultMachineBuilder::new(core_machine)
+                    .instruction_cycle_func(Box::new(|_| 1))
+                    .error_report(true)
+                    .build(),
+            );
+            machine.load_program(&program, &args).unwrap();
+            let result = machine.run();
+            (result, machine.machine.error_report().cloned())
+        }
+        #[cfg(has_asm)]
+        Engine::Asm => {
+            let core_machine = AsmCoreMachine::new(ISA_IMC, VERSION1, max_cycles);
+            let mut machine = AsmMachine::new(
+                DefaultMachineBuilder::new(core_machine)
+                    .instruction_cycle_func(Box::new(|_| 1))
+                    .error_report(true)
+                    .build(),
+            );
+            machine.load_program(&program, &args).unwrap();
+            let result = machine.run();
+            (result, machine.machine.error_report().cloned())
+        }
+    }
+}
+
+#[test]
+pub fn test_error_report_write_on_executable_page() {
+    for engine in engines() {
+        let (result, report) = run(
+            engine,
+            "tests/programs/error_report",
+            &["error_report"],
+            u64::max_value(),
+        );
+        assert_eq!(
+            result,
+            Err(Error::MemWriteOnExecutablePage(16)),
+            "{:?}",
+            engine
+        );
+        let report = report.unwrap();
+        assert_eq!(
+            report.error,
+            "memory error: write on executable page page_index=16"
+        );
+        assert_eq!(report.pc, 0x10014, "{:?}", engine);
+        assert_eq!(report.disassembly.as_deref(), Some("sw a1,8(t1)"));
+        assert_eq!(report.registers[A1], 0x12345678);
+        assert!(report.cycles > 0);
+        let access = report.access.unwrap();
+        assert_eq!(access.address, 0x10018);
+        assert_eq!(access.size, 4);
+        assert_eq!(access.kind, WatchpointKind::Write);
+        assert_ne!(access.page_flags.unwrap() & FLAG_EXECUTABLE, 0);
+    }
+}
+
+#[test]
+pub fn test_error_report_invalid_ecall() {
+    for engine in engines() {
+        let (result, report) = run(
+            engine,
+            "tests/programs/error_report",
+            &["error_report", "ecall"],
+            u64::max_value(),
+        );
+        assert_eq!(result, Err(Error::InvalidEcall(1000)), "{:?}", engine);
+        let report = report.unwrap();
+        assert_eq!(report.pc, 0x1001e, "{:?}", engine);
+        assert_eq!(report.disassembly.as_deref(), Some("ecall"));
+        assert_eq!(report.access, None);
+    }
+}
+
+#[test]
+pub fn test_error_report_out_of_bound() {
+    for engine in engines() {
+        let (result, report) = run(
+            engine,
+            "tests/programs/backtrace",
+            &["backtrace"],
+            u64::max_value(),
+        );
+        assert!(
+            matches!(result, Err(Error::MemOutOfBound(..))),
+            "{:?}",
+            engine
+        );
+        let report = report.unwrap();
+        assert_eq!(report.pc, 0x1003e, "{:?}", engine);
+        let access = report.access.unwrap();
+        assert_eq!(access.size, 8);
+        assert_eq!(access.kind, WatchpointKind::Read);
+        // The address is beyond the memory, there are no page flags for it.
+        assert_eq!(access.page_flags, None);
+    }
+}
+
+#[test]
+pub fn test_error_report_cycles_exceeded() {
+    for engine in engines() {
+        let (result, report) = run(engine, "tests/programs/alloc_many", &["alloc_many"], 3000);
+        assert_eq!(result, Err(Error::CyclesExceeded), "{:?}", engine);
+        let report = report.unwrap();
+        assert!(report.instruction.is_some());
+        assert!(report.disassembly.is_some());
+        assert_eq!(report.access, None);
+    }
+}
+
+#[test]
+pub fn test_error_report_disabled() {
+    let program = fs::read("tests/programs/error_report").unwrap().into();
+    let core_machine = Core::new(ISA_IMC, VERSION1, u64::max_value());
+    let mut machine = DefaultMachineBuilder::new(core_machine).build();
+    machine
+        .load_program(&program, &vec!["error_report".into()])
+        .unwrap();
+    assert!(machine.run().is_err());
+    assert_eq!(machine.error_report(), None);
+}
+
+#[test]
+pub fn test_error_report_json() {
+    let (_, report) = run(
+        Engine::Interpreter,
+        "tests/programs/error_report",
+        &["error_report"],
+        u64::max_value(),
+    );
+    let report = report.unwrap();
+    let json = report.to_json();
+    assert!(json.contains("\"disassembly\":\"sw a1,8(t1)\""));
+    assert!(json.contains("\"kind\":\"Write\""));
+    assert_eq!(serde_json::from_str::<ErrorReport>(&json).unwrap(), report);
+
+    let (_, report) = run(
+        Engine::Interpreter,
+        "tests/programs/error_report",
+        &["error_report", "ecall"],
+        u64::max_value(),
+    );
+    let report = report.unwrap();
+    assert!(report.to_json().ends_with("\"access\":null}"));
+    assert_eq!(
+        serde_json::from_str::<ErrorReport>(&report.to_json()).unwrap(),
+        report
+    );
+}
+
+#[test]
+pub fn test_error_report_json_round_trip() {
+    // Covers the fields and escapes the programs above never produce.
+    let mut report = ErrorReport {
+        error: "quote \" backslash \\ newline \n tab \t nul \u{0} \u{1f} unicode \u{e9}\u{1f600}"
+            .to_string(),
+        pc: u64::max_value(),
+        instruction: None,
+        disassembly: None,
+        registers: vec![0, 1, u64::max_value()],
+        cycles: 0,
+        access: None,
+    };
+    for kind in [
+        WatchpointKind::Read,
+        WatchpointKind::Write,
+        WatchpointKind::Access,
+    ] {
+        for page_flags in [None, Some(0), Some(FLAG_EXECUTABLE)] {
+            report.access = Some(MemoryAccess {
+                address: u64::max_value(),
+                size: 8,
+                kind,
+                page_flags,
+            });
+            report.instruction = page_flags.map(|_| u64::max_value());
+            report.disassembly = page_flags.map(|_| "\"\\\r".to_string());
+            let json = report.to_json();
+            assert_eq!(
+                serde_json::from_str::<ErrorReport>(&json).unwrap(),
+                report,
+                "{}",
+                json
+            );
+        }
+    }
+}
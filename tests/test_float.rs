@@ -0,0 +1,170 @@
+use ckb_vm::differential::Differential;
+#[cfg(has_asm)]
+use ckb_vm::machine::asm::{AsmCoreMachine, AsmMachine};
+use ckb_vm::machine::{trace::TraceMachine, VERSION2, VERSION3};
+use ckb_vm::{
+    snapshot, CoreMachine as _, DefaultCoreMachine, DefaultMachine, DefaultMachineBuilder, Error,
+    SparseMemory, WXorXMemory, ISA_D, ISA_F, ISA_IMC,
+};
+use std::fs;
+
+type CoreMachine = DefaultCoreMachine<u64, WXorXMemory<SparseMemory<u64>>>;
+
+const ISA: u8 = ISA_IMC | ISA_F | ISA_D;
+
+fn build(version: u32) -> DefaultMachine<CoreMachine> {
+    let core_machine = CoreMachine::new(ISA, version, u64::max_value());
+    DefaultMachineBuilder::new(core_machine)
+        .instruction_cycle_func(Box::new(|_| 1))
+        .build()
+}
+
+fn program() -> bytes::Bytes {
+    fs::read("tests/programs/float").unwrap().into()
+}
+
+#[test]
+pub fn test_float_interpreter() {
+    let mut machine = build(VERSION3);
+    machine
+        .load_program(&program(), &vec!["float".into()])
+        .unwrap();
+    assert_eq!(machine.run(), Ok(0));
+}
+
+#[test]
+pub fn test_float_trace() {
+    let mut machine = TraceMachine::new(build(VERSION3));
+    machine
+        .load_program(&program(), &vec!["float".into()])
+        .unwrap();
+    assert_eq!(machine.run(), Ok(0));
+}
+
+#[cfg(has_asm)]
+#[test]
+pub fn test_float_asm() {
+    let core = DefaultMachineBuilder::new(AsmCoreMachine::new(ISA, VERSION3, u64::max_value()))
+        .instruction_cycle_func(Box::new(|_| 1))
+        .build();
+    let mut machine = AsmMachine::new(core);
+    machine
+        .load_program(&program(), &vec!["float".into()])
+        .unwrap();
+    assert_eq!(machine.run(), Ok(0));
+}
+
+#[test]
+pub fn test_float_differential() {
+    let differential = Differential::new(build(VERSION3), build(VERSION3));
+    #[cfg(has_asm)]
+    let differential = differential.with_asm(
+        DefaultMachineBuilder::new(AsmCoreMachine::new(ISA, VERSION3, u64::max_value()))
+            .instruction_cycle_func(Box::new(|_| 1))
+            .build(),
+    );
+    let mut differential = differential;
+    differential
+        .load_program(&program(), &vec!["float".into()])
+        .unwrap();
+    assert_eq!(differential.run().map_err(|d| d.to_string()), Ok(Ok(0)));
+}
+
+#[test]
+pub fn test_float_requires_version3() {
+    let mut machine = build(VERSION2);
+    machine
+        .load_program(&program(), &vec!["float".into()])
+        .unwrap();
+    assert!(matches!(
+        machine.run(),
+        Err(Error::InvalidInstruction { .. })
+    ));
+}
+
+#[test]
+pub fn test_float_snapshot() {
+    let mut machine = build(VERSION3);
+    machine
+        .load_program(&program(), &vec!["float".into()])
+        .unwrap();
+    machine.run().unwrap();
+    let snapshot = snapshot::make_snapshot(&mut machine).unwrap();
+    let float = snapshot.float.as_ref().unwrap();
+    assert_eq!(float.fregisters, machine.fregisters());
+    let data = serde_json::to_string(&snapshot).unwrap();
+    let snapshot: snapshot::Snapshot = serde_json::from_str(&data).unwrap();
+
+    let mut resumed = build(VERSION3);
+    snapshot::resume(&mut resumed, &snapshot).unwrap();
+    assert_eq!(resumed.fregisters(), machine.fregisters());
+    assert_eq!(resumed.fcsr(), machine.fcsr());
+
+    // A machine without the extensions cannot take the registers.
+    let core_machine = CoreMachine::new(ISA_IMC, VERSION3, u64::max_value());
+    let mut resumed = DefaultMachineBuilder::new(core_machine).build();
+    assert!(snapshot::resume(&mut resumed, &snapshot).is_err());
+}
+
+#[test]
+pub fn test_float_snapshot_without_extensions() {
+    // Snapshots of machines without the F and D extensions serialize as
+    // they did before the extensions.
+    let core_machine = CoreMachine::new(ISA_IMC, VERSION2, u64::max_value());
+    let mut machine = DefaultMachineBuilder::new(core_machine).build();
+    let snapshot = snapshot::make_snapshot(&mut machine).unwrap();
+    assert!(snapshot.float.is_none());
+    let data = serde_json::to_value(&snapshot).unwrap();
+    assert!(data.get("float").is_none());
+    let snapshot: snapshot::Snapshot = serde_json::from_value(data).unwrap();
+    assert!(snapshot.float.is_none());
+}
+
+// A CoreMachine implemented outside the crate, without the float and vector
+// methods.
+struct IntegerMachine(CoreMachine);
+
+impl ckb_vm::CoreMachine for IntegerMachine {
+    type REG = u64;
+    type MEM = WXorXMemory<SparseMemory<u64>>;
+
+    fn pc(&self) -> &u64 {
+        self.0.pc()
+    }
+    fn update_pc(&mut self, pc: u64) {
+        self.0.update_pc(pc)
+    }
+    fn commit_pc(&mut self) {
+        self.0.commit_pc()
+    }
+    fn memory(&self) -> &Self::MEM {
+        self.0.memory()
+    }
+    fn memory_mut(&mut self) -> &mut Self::MEM {
+        self.0.memory_mut()
+    }
+    fn registers(&self) -> &[u64] {
+        self.0.registers()
+    }
+    fn set_register(&mut self, idx: usize, value: u64) {
+        self.0.set_register(idx, value)
+    }
+    fn version(&self) -> u32 {
+        self.0.version()
+    }
+    fn isa(&self) -> u8 {
+        self.0.isa()
+    }
+}
+
+#[test]
+pub fn test_float_default_methods() {
+    // Writing float or vector state a machine does not have fails instead of
+    // panicking.
+    let mut machine = IntegerMachine(CoreMachine::new(ISA, VERSION3, u64::max_value()));
+    assert!(machine.fregisters().is_empty());
+    assert!(machine.set_fregister(0, 1).is_err());
+    assert!(machine.set_fcsr(1).is_err());
+    assert_eq!(machine.fcsr(), 0);
+    assert!(machine.set_vl(1, 0).is_err());
+}
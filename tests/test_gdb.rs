@@ -0,0 +1,266 @@
+use ckb_vm::debugger::gdb::{GdbConnection, GdbStub};
+use ckb_vm::machine::{trace::TraceMachine, VERSION1, VERSION3};
+use ckb_vm::{
+    CoreMachine, DefaultCoreMachine, DefaultMachine, DefaultMachineBuilder, SparseMemory, ISA_D,
+    ISA_F, ISA_IMC, ISA_V,
+};
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+struct Client {
+    stream: TcpStream,
+}
+
+impl Client {
+    fn send(&mut self, data: &str) -> String {
+        let checksum = data.bytes().fold(0u8, |acc, c| acc.wrapping_add(c));
+        let packet = format!("${}#{:02x}", data, checksum);
+        self.stream.write_all(packet.as_bytes()).unwrap();
+        let mut buf = [0u8; 1];
+        self.stream.read_exact(&mut buf).unwrap();
+        assert_eq!(buf[0], b'+');
+        self.receive()
+    }
+
+    fn receive(&mut self) -> String {
+        let mut buf = [0u8; 1];
+        loop {
+            self.stream.read_exact(&mut buf).unwrap();
+            if buf[0] == b'$' {
+                break;
+            }
+        }
+        let mut data = Vec::new();
+        loop {
+            self.stream.read_exact(&mut buf).unwrap();
+            if buf[0] == b'#' {
+                break;
+            }
+            data.push(buf[0]);
+        }
+        let mut checksum = [0u8; 2];
+        self.stream.read_exact(&mut checksum).unwrap();
+        self.stream.write_all(b"+").unwrap();
+        String::from_utf8(data).unwrap()
+    }
+}
+
+fn pc_of(client: &mut Client) -> u64 {
+    let reply = client.send("p20");
+    let bytes: Vec<u8> = (0..8)
+        .map(|i| u8::from_str_radix(&reply[i * 2..i * 2 + 2], 16).unwrap())
+        .collect();
+    u64::from_le_bytes(bytes.try_into().unwrap())
+}
+
+#[test]
+pub fn test_gdb_stub() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        stream.set_nodelay(true).unwrap();
+        let buffer = fs::read("tests/programs/simple64").unwrap().into();
+        let mut stub = GdbStub::new(stream);
+        let core_machine =
+            DefaultCoreMachine::<u64, SparseMemory<u64>>::new(ISA_IMC, VERSION1, u64::max_value());
+        let mut machine = DefaultMachineBuilder::new(core_machine)
+            .debugger(Box::new(stub.ebreak_handler()))
+            .build();
+        machine
+            .load_program(&buffer, &vec!["simple".into()])
+            .unwrap();
+        stub.run(&mut machine)
+    });
+
+    let stream = TcpStream::connect(addr).unwrap();
+    stream.set_nodelay(true).unwrap();
+    let mut client = Client { stream };
+    assert!(client
+        .send("qSupported:swbreak+")
+        .contains("qXfer:features:read+"));
+    assert!(client
+        .send("qXfer:features:read:target.xml:0,fff")
+        .contains("riscv:rv64"));
+    assert_eq!(client.send("?"), "S05");
+    assert_eq!(client.send("g").len(), 33 * 16);
+    assert_eq!(pc_of(&mut client), 0x100c0);
+
+    // Break at main
+    assert_eq!(client.send("Z0,10146,2"), "OK");
+    assert_eq!(client.send("c"), "S05");
+    assert_eq!(pc_of(&mut client), 0x10146);
+    assert_eq!(client.send("s"), "S05");
+    assert_ne!(pc_of(&mut client), 0x10146);
+
+    assert_eq!(client.send("P5=efbeadde00000000"), "OK");
+    assert_eq!(client.send("p5"), "efbeadde00000000");
+    assert_eq!(client.send("p21"), "E00");
+    assert_eq!(client.send("M20000,4:01020304"), "OK");
+    assert_eq!(client.send("m20000,4"), "01020304");
+    assert_eq!(client.send("m7fffffffffff,4"), "E14");
+
+    // Malformed packets are rejected without ending the session.
+    assert_eq!(client.send("pzz"), "E01");
+    assert_eq!(client.send("P5"), "E01");
+    assert_eq!(client.send("m20000"), "E01");
+    assert_eq!(client.send("Z0,xyz,2"), "E01");
+    assert_eq!(client.send("p5"), "efbeadde00000000");
+
+    assert_eq!(client.send("z0,10146,2"), "OK");
+    assert_eq!(client.send("c"), "W00");
+    assert_eq!(server.join().unwrap().unwrap(), 0);
+}
+
+fn packet(data: &str) -> Vec<u8> {
+    let checksum = data.bytes().fold(0u8, |acc, c| acc.wrapping_add(c));
+    format!("${}#{:02x}", data, checksum).into_bytes()
+}
+
+// A connection replaying scripted input, the bytes of `running` are only
+// handed out while the stub polls for interrupts.
+struct ScriptedConnection {
+    input: VecDeque<u8>,
+    running: VecDeque<u8>,
+    output: Vec<u8>,
+}
+
+impl Read for ScriptedConnection {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.input.read(buf)
+    }
+}
+
+impl Write for ScriptedConnection {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.output.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl GdbConnection for ScriptedConnection {
+    fn try_read_byte(&mut self) -> std::io::Result<Option<u8>> {
+        Ok(self.running.pop_front())
+    }
+}
+
+type Core = DefaultCoreMachine<u64, SparseMemory<u64>>;
+
+fn load(program: &str, isa: u8, version: u32) -> DefaultMachine<Core> {
+    let buffer = fs::read(format!("tests/programs/{}", program))
+        .unwrap()
+        .into();
+    let core_machine = Core::new(isa, version, u64::max_value());
+    let mut machine = DefaultMachineBuilder::new(core_machine).build();
+    machine
+        .load_program(&buffer, &vec![program.to_string().into()])
+        .unwrap();
+    machine
+}
+
+// Serves the packets of script with no acks till the program exits, returns
+// the replies. The packet in running arrives while the machine runs.
+fn serve(
+    machine: &mut DefaultMachine<Core>,
+    script: &[&str],
+    running: Option<&str>,
+) -> Vec<String> {
+    let mut input = Vec::new();
+    for data in ["QStartNoAckMode"].iter().chain(script) {
+        input.extend(packet(data));
+        input.push(b'+');
+    }
+    let connection = ScriptedConnection {
+        input: input.into(),
+        running: running.map(packet).unwrap_or_default().into(),
+        output: Vec::new(),
+    };
+    let mut stub = GdbStub::new(connection);
+    assert_eq!(stub.run(machine), Ok(0));
+    let output = String::from_utf8(stub.connection_mut().output.clone()).unwrap();
+    output
+        .split('$')
+        .skip(1)
+        .map(|p| p.split('#').next().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+pub fn test_gdb_stub_watchpoints() {
+    // See tests/programs/watchpoint_classes for the accesses.
+    let mut machine = load(
+        "watchpoint_classes",
+        ISA_IMC | ISA_F | ISA_D | ISA_V,
+        VERSION3,
+    );
+    let replies = serve(
+        &mut machine,
+        &[
+            "Z2,20000,4",
+            "c",
+            "p20",
+            "z2,20000,4",
+            "Z3,2001c,4",
+            "c",
+            "p20",
+            "z3,2001c,4",
+            "Z4,20028,4",
+            "c",
+            "c",
+            "z4,20028,4",
+            "c",
+        ],
+        None,
+    );
+    assert_eq!(
+        replies,
+        [
+            "OK",
+            "OK",
+            "T05watch:20000;",
+            "0800010000000000",
+            "OK",
+            "OK",
+            "T05rwatch:20010;",
+            "2800010000000000",
+            "OK",
+            "OK",
+            "T05awatch:20020;",
+            "T05awatch:20020;",
+            "OK",
+            "W00",
+        ]
+    );
+}
+
+#[test]
+pub fn test_gdb_stub_keeps_bytes_read_while_running() {
+    // tests/programs/gdb_loop spins long enough for the stub to poll for
+    // interrupts a few times before reaching the breakpoint at its exit. The
+    // ? packet sent while running is answered after the breakpoint.
+    let replies = serve(
+        &mut load("gdb_loop", ISA_IMC, VERSION1),
+        &["Z0,1000c,4", "c", "c"],
+        Some("?"),
+    );
+    assert_eq!(replies, ["OK", "OK", "S05", "S05", "W00"]);
+}
+
+#[test]
+pub fn test_gdb_stub_trace_machine() {
+    // A TraceMachine is debugged through the DefaultMachine it wraps, here
+    // the first trace of tests/programs/gdb_loop is run before attaching.
+    let mut machine = TraceMachine::new(load("gdb_loop", ISA_IMC, VERSION1));
+    let mut decoder = machine.machine.build_decoder();
+    machine.step_trace(&mut decoder).unwrap();
+    assert_eq!(*machine.pc(), 0x10004);
+    let replies = serve(&mut machine.machine, &["Z0,1000c,4", "c", "p20", "D"], None);
+    assert_eq!(replies, ["OK", "OK", "S05", "0c00010000000000", "OK"]);
+    assert_eq!(*machine.pc(), 0x10018);
+}
@@ -0,0 +1,131 @@
+use ckb_vm::cost_model::constant_cycles;
+use ckb_vm::elf::program_break;
+#[cfg(has_asm)]
+use ckb_vm::machine::asm::{AsmCoreMachine, AsmMachine};
+use ckb_vm::machine::VERSION1;
+use ckb_vm::registers::{A0, A1, A7};
+use ckb_vm::syscalls::linux::{LinuxSyscalls, Output, SYS_GETRANDOM};
+use ckb_vm::{
+    Bytes, CoreMachine, DefaultCoreMachine, DefaultMachine, DefaultMachineBuilder, Error,
+    SparseMemory, SupportMachine, Syscalls, WXorXMemory, ISA_IMC, RISCV_PAGESIZE,
+};
+use std::fs;
+
+type Core = DefaultCoreMachine<u64, WXorXMemory<SparseMemory<u64>>>;
+
+// Runs tests/programs/linux_syscalls, returns the exit code, the cycles and
+// the captured stdout and stderr.
+fn run(syscalls: LinuxSyscalls, asm: bool) -> (Result<i8, Error>, u64, Vec<u8>, Vec<u8>) {
+    let program: Bytes = fs::read("tests/programs/linux_syscalls").unwrap().into();
+    let args = vec![Bytes::from("linux_syscalls")];
+    let (stdout, stderr): (Output, Output) = (syscalls.stdout(), syscalls.stderr());
+    let (result, cycles) = if asm {
+        #[cfg(has_asm)]
+        {
+            let core_machine = AsmCoreMachine::new(ISA_IMC, VERSION1, u64::max_value());
+            let mut machine = AsmMachine::new(
+                DefaultMachineBuilder::new(core_machine)
+                    .instruction_cycle_func(Box::new(constant_cycles))
+                    .syscall(Box::new(syscalls))
+                    .build(),
+            );
+            machine.load_program(&program, &args).unwrap();
+            let result = machine.run();
+            (result, machine.machine.cycles())
+        }
+        #[cfg(not(has_asm))]
+        unreachable!()
+    } else {
+        let core_machine = Core::new(ISA_IMC, VERSION1, u64::max_value());
+        let mut machine = DefaultMachineBuilder::new(core_machine)
+            .instruction_cycle_func(Box::new(constant_cycles))
+            .syscall(Box::new(syscalls))
+            .build();
+        machine.load_program(&program, &args).unwrap();
+        let result = machine.run();
+        (result, machine.cycles())
+    };
+    (result, cycles, stdout.contents(), stderr.contents())
+}
+
+fn syscalls(seed: u64) -> LinuxSyscalls {
+    let program: Bytes = fs::read("tests/programs/linux_syscalls").unwrap().into();
+    LinuxSyscalls::new(program_break(&program).unwrap())
+        .stdin(Bytes::from("input"))
+        .random_seed(seed)
+        .clock(7_000_000_000, 1000)
+}
+
+#[test]
+pub fn test_linux_syscalls() {
+    let mut engines = vec![false];
+    if cfg!(has_asm) {
+        engines.push(true);
+    }
+    let mut outputs = vec![];
+    for asm in engines {
+        let (result, cycles, stdout, stderr) = run(syscalls(5), asm);
+        assert_eq!(result, Ok(42));
+        assert_eq!(&stdout[..6], b"hello\n");
+        assert_eq!(stdout.len(), 6 + 8 + 16);
+        assert_eq!(stderr, b"input");
+        let seconds = u64::from_le_bytes(stdout[14..22].try_into().unwrap());
+        let nanoseconds = u64::from_le_bytes(stdout[22..30].try_into().unwrap());
+        assert_eq!(seconds, 7);
+        assert_eq!(nanoseconds % 1000, 0);
+        assert!(nanoseconds > 0 && nanoseconds < cycles * 1000);
+        outputs.push(stdout);
+    }
+    // Every engine sees the same random bytes and time.
+    outputs.dedup();
+    assert_eq!(outputs.len(), 1);
+}
+
+#[test]
+pub fn test_linux_syscalls_random_seed() {
+    let (_, _, first, _) = run(syscalls(5), false);
+    let (_, _, second, _) = run(syscalls(5), false);
+    let (_, _, other, _) = run(syscalls(6), false);
+    assert_eq!(first[6..14], second[6..14]);
+    assert_ne!(first[6..14], other[6..14]);
+}
+
+#[test]
+pub fn test_linux_syscalls_out_of_memory() {
+    // No room between the break and the mmap region, brk fails.
+    let program: Bytes = fs::read("tests/programs/linux_syscalls").unwrap().into();
+    let program_break = program_break(&program).unwrap();
+    let syscalls = LinuxSyscalls::new(program_break).mmap_end(program_break);
+    let (result, _, stdout, _) = run(syscalls, false);
+    assert_eq!(result, Ok(1));
+    assert_eq!(stdout, b"hello\n");
+
+    // Room for the break but not for the mapping.
+    let syscalls = LinuxSyscalls::new(program_break).mmap_end(program_break + 8192);
+    let (result, _, _, _) = run(syscalls, false);
+    assert_eq!(result, Ok(2));
+}
+
+#[test]
+pub fn test_linux_syscalls_getrandom_bounds() {
+    let mut syscalls = syscalls(5);
+    let core_machine = Core::new(ISA_IMC, VERSION1, u64::max_value());
+    let mut machine = DefaultMachineBuilder::new(core_machine).build();
+    syscalls.initialize(&mut machine).unwrap();
+    let mut getrandom = |machine: &mut DefaultMachine<Core>, buffer: u64, length: u64| {
+        machine.set_register(A0, buffer);
+        machine.set_register(A1, length);
+        machine.set_register(A7, SYS_GETRANDOM);
+        syscalls.ecall(machine)
+    };
+
+    // Spans several pages.
+    let length = RISCV_PAGESIZE as u64 * 3 + 1;
+    assert_eq!(getrandom(&mut machine, 0x1000, length), Ok(true));
+    assert_eq!(machine.registers()[A0], length);
+    // A length the host could not allocate fails on the memory bounds.
+    assert!(matches!(
+        getrandom(&mut machine, 0x1000, u64::max_value()),
+        Err(Error::MemOutOfBound(..))
+    ));
+}
@@ -0,0 +1,124 @@
+#[cfg(has_asm)]
+use ckb_vm::machine::asm::{AsmCoreMachine, AsmMachine};
+use ckb_vm::machine::{trace::TraceMachine, VERSION1};
+use ckb_vm::registers::SP;
+use ckb_vm::{
+    Bytes, CoreMachine, DefaultCoreMachine, DefaultMachineBuilder, Error, FlatMemory,
+    MemcheckMemory, Memory, SparseMemory, WXorXMemory, ISA_IMC, RISCV_PAGESIZE,
+};
+use std::fs;
+
+// Address of the load past the stored value in tests/programs/memcheck.
+const LOAD_PC: u64 = 0x10020;
+
+#[derive(Clone, Copy, Debug)]
+enum Engine {
+    Flat,
+    Sparse,
+    Trace,
+    #[cfg(has_asm)]
+    Asm,
+}
+
+fn engines() -> Vec<Engine> {
+    #[allow(unused_mut)]
+    let mut engines = vec![Engine::Flat, Engine::Sparse, Engine::Trace];
+    #[cfg(has_asm)]
+    engines.push(Engine::Asm);
+    engines
+}
+
+// Returns the result of the run and the SP set up by load_program.
+fn run(engine: Engine, args: &[&str]) -> (Result<i8, Error>, u64) {
+    let program: Bytes = fs::read("tests/programs/memcheck").unwrap().into();
+    let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+    macro_rules! interpret {
+        ($memory:ty) => {{
+            let core_machine = DefaultCoreMachine::<u64, MemcheckMemory<$memory>>::new(
+                ISA_IMC,
+                VERSION1,
+                u64::max_value(),
+            );
+            let mut machine = DefaultMachineBuilder::new(core_machine).build();
+            machine.load_program(&program, &args).unwrap();
+            let sp = machine.registers()[SP];
+            (machine.run(), sp)
+        }};
+    }
+    match engine {
+        Engine::Flat => interpret!(FlatMemory<u64>),
+        Engine::Sparse => interpret!(WXorXMemory<SparseMemory<u64>>),
+        Engine::Trace => {
+            let core_machine = DefaultCoreMachine::<u64, MemcheckMemory<SparseMemory<u64>>>::new(
+                ISA_IMC,
+                VERSION1,
+                u64::max_value(),
+            );
+            let mut machine = TraceMachine::new(DefaultMachineBuilder::new(core_machine).build());
+            machine.load_program(&program, &args).unwrap();
+            let sp = machine.registers()[SP];
+            (machine.run(), sp)
+        }
+        #[cfg(has_asm)]
+        Engine::Asm => {
+            let mut core_machine = AsmCoreMachine::new(ISA_IMC, VERSION1, u64::max_value());
+            core_machine.enable_memcheck();
+            let mut machine = AsmMachine::new(DefaultMachineBuilder::new(core_machine).build());
+            machine.load_program(&program, &args).unwrap();
+            let sp = machine.machine.registers()[SP];
+            (machine.run(), sp)
+        }
+    }
+}
+
+#[test]
+pub fn test_memcheck_uninitialized_read() {
+    for engine in engines() {
+        let (result, sp) = run(engine, &["memcheck", "uninitialized"]);
+        assert_eq!(
+            result,
+            Err(Error::UninitializedRead {
+                pc: LOAD_PC,
+                address: sp - 8
+            }),
+            "{:?}",
+            engine
+        );
+    }
+}
+
+#[test]
+pub fn test_memcheck_initialized_reads() {
+    for engine in engines() {
+        let (result, _) = run(engine, &["memcheck"]);
+        assert_eq!(result, Ok(0), "{:?}", engine);
+    }
+}
+
+#[test]
+pub fn test_memcheck_shadow() {
+    let mut memory = MemcheckMemory::new(SparseMemory::<u64>::new_with_memory(RISCV_PAGESIZE * 4));
+    assert!(!memory.is_defined(0, 1));
+    memory
+        .init_pages(0, RISCV_PAGESIZE as u64, 0, None, 0)
+        .unwrap();
+    assert!(memory.is_defined(0, RISCV_PAGESIZE as u64));
+    assert!(!memory.is_defined(RISCV_PAGESIZE as u64 - 1, 2));
+
+    let address = RISCV_PAGESIZE as u64 * 2 + 3;
+    memory.store8(&address, &1).unwrap();
+    memory.store_bytes(address + 1, &[2, 3]).unwrap();
+    assert!(memory.is_defined(address, 3));
+    assert_eq!(memory.load16(&(address + 1)), Ok(0x0302));
+    assert_eq!(memory.take_uninitialized_read(), None);
+    assert_eq!(memory.load32(&(address + 1)), Ok(0x0302));
+    assert_eq!(memory.take_uninitialized_read(), Some(address + 3));
+    assert_eq!(memory.take_uninitialized_read(), None);
+
+    // Bulk reads made by the host are not checked.
+    memory.load_bytes(address, 16).unwrap();
+    assert_eq!(memory.take_uninitialized_read(), None);
+
+    memory.reset_memory().unwrap();
+    assert!(!memory.is_defined(0, 1));
+}
@@ -0,0 +1,144 @@
+#[cfg(has_asm)]
+use ckb_vm::machine::asm::{AsmCoreMachine, AsmMachine};
+use ckb_vm::machine::VERSION1;
+use ckb_vm::syscalls::mock::{MockSyscall, MockSyscalls};
+use ckb_vm::{
+    Bytes, DefaultCoreMachine, DefaultMachineBuilder, Error, SparseMemory, SupportMachine,
+    WXorXMemory, ISA_IMC,
+};
+use std::fs;
+
+type Core = DefaultCoreMachine<u64, WXorXMemory<SparseMemory<u64>>>;
+
+// Runs tests/programs/mock_syscalls, returns the exit code and the cycles.
+fn run(mock: &MockSyscalls, asm: bool) -> (Result<i8, Error>, u64) {
+    let program: Bytes = fs::read("tests/programs/mock_syscalls").unwrap().into();
+    let args = vec![Bytes::from("mock_syscalls")];
+    if asm {
+        #[cfg(has_asm)]
+        {
+            let core_machine = AsmCoreMachine::new(ISA_IMC, VERSION1, u64::max_value());
+            let mut machine = AsmMachine::new(
+                DefaultMachineBuilder::new(core_machine)
+                    .syscall(Box::new(mock.clone()))
+                    .build(),
+            );
+            machine.load_program(&program, &args).unwrap();
+            let result = machine.run();
+            return (result, machine.machine.cycles());
+        }
+        #[cfg(not(has_asm))]
+        unreachable!()
+    }
+    let core_machine = Core::new(ISA_IMC, VERSION1, u64::max_value());
+    let mut machine = DefaultMachineBuilder::new(core_machine)
+        .syscall(Box::new(mock.clone()))
+        .build();
+    machine.load_program(&program, &args).unwrap();
+    let result = machine.run();
+    (result, machine.cycles())
+}
+
+fn engines() -> Vec<bool> {
+    let mut engines = vec![false];
+    if cfg!(has_asm) {
+        engines.push(true);
+    }
+    engines
+}
+
+#[test]
+pub fn test_mock_syscalls() {
+    for asm in engines() {
+        let word = 0x1122334455667788u64;
+        let mock = MockSyscalls::new()
+            .expect(
+                MockSyscall::new(2000)
+                    .arg(1, 8)
+                    .write_to_arg(0, Bytes::from(word.to_le_bytes().to_vec()))
+                    .returns(0)
+                    .cycles(100),
+            )
+            .expect(MockSyscall::new(2001).args(&[word]).returns(42))
+            .expect(MockSyscall::new(2002).cycles(20))
+            .in_order();
+        let (result, cycles) = run(&mock, asm);
+        assert_eq!(result, Ok(42));
+        assert_eq!(cycles, 120);
+        assert_eq!(mock.verify(), Ok(()));
+        let calls = mock.calls();
+        assert_eq!(
+            calls.iter().map(|c| c.number).collect::<Vec<_>>(),
+            vec![2000, 2001, 2002]
+        );
+        assert_eq!(calls[1].arguments[0], word);
+        assert_eq!(calls[2].expectation, Some(2));
+        assert_eq!(mock.count(2001), 1);
+    }
+}
+
+#[test]
+pub fn test_mock_syscalls_unexpected() {
+    for asm in engines() {
+        // The written word is not the expected argument.
+        let mock = MockSyscalls::new()
+            .expect(MockSyscall::new(2000).write_to_arg(0, Bytes::from(vec![1; 8])))
+            .expect(MockSyscall::new(2001).arg(0, 2))
+            .expect(MockSyscall::new(2002));
+        let (result, _) = run(&mock, asm);
+        assert_eq!(result, Err(Error::InvalidEcall(2001)));
+        assert_eq!(mock.calls()[1].expectation, None);
+        assert_eq!(
+            mock.verify(),
+            Err(format!(
+                "unexpected syscall 2001 with arguments {:?}\n\
+                 syscall 2001 is expected 1 times, made 0 times\n\
+                 syscall 2002 is expected 1 times, made 0 times",
+                [0x0101010101010101u64, 8, 0, 0, 0, 0]
+            ))
+        );
+    }
+}
+
+#[test]
+pub fn test_mock_syscalls_order_and_counts() {
+    // Made out of order.
+    let mock = MockSyscalls::new()
+        .expect(MockSyscall::new(2001))
+        .expect(MockSyscall::new(2000))
+        .in_order();
+    let (result, _) = run(&mock, false);
+    assert_eq!(result, Err(Error::InvalidEcall(2000)));
+
+    // Any order is fine unless in_order is set, expected counts are checked
+    // after the run.
+    let mock = MockSyscalls::new()
+        .expect(MockSyscall::new(2002).times(2))
+        .expect(MockSyscall::new(2001))
+        .expect(MockSyscall::new(2000));
+    let (result, _) = run(&mock, false);
+    assert!(result.is_ok());
+    assert_eq!(
+        mock.verify(),
+        Err("syscall 2002 is expected 2 times, made 1 times".to_string())
+    );
+
+    // Calls no expectation matches go to the next module with passthrough.
+    let mock = MockSyscalls::new()
+        .expect(MockSyscall::new(2000).returns(7))
+        .passthrough();
+    let (result, _) = run(&mock, false);
+    assert_eq!(result, Err(Error::InvalidEcall(2001)));
+    assert_eq!(mock.count(2001), 1);
+    assert_eq!(mock.verify(), Ok(()));
+
+    // Calls are cleared when the program is loaded again.
+    let mock = MockSyscalls::new()
+        .expect(MockSyscall::new(2000))
+        .expect(MockSyscall::new(2001).returns(3))
+        .expect(MockSyscall::new(2002));
+    assert_eq!(run(&mock, false).0, Ok(3));
+    assert_eq!(run(&mock, false).0, Ok(3));
+    assert_eq!(mock.calls().len(), 3);
+    assert_eq!(mock.verify(), Ok(()));
+}
@@ -0,0 +1,75 @@
+use bytes::Bytes;
+use ckb_vm::cost_model::constant_cycles;
+#[cfg(has_asm)]
+use ckb_vm::machine::asm::{AsmCoreMachine, AsmMachine};
+use ckb_vm::machine::VERSION1;
+use ckb_vm::profiler::Profiler;
+use ckb_vm::{DefaultCoreMachine, DefaultMachineBuilder, SparseMemory, SupportMachine, ISA_IMC};
+use std::fs;
+
+fn profile_interpreter(buffer: &Bytes) -> (Profiler, u64) {
+    let core_machine =
+        DefaultCoreMachine::<u64, SparseMemory<u64>>::new(ISA_IMC, VERSION1, u64::max_value());
+    let mut machine = DefaultMachineBuilder::new(core_machine)
+        .instruction_cycle_func(Box::new(constant_cycles))
+        .build();
+    machine
+        .load_program(buffer, &vec!["simple".into()])
+        .unwrap();
+    let mut profiler = Profiler::new(buffer).unwrap();
+    assert_eq!(profiler.run(&mut machine), Ok(0));
+    (profiler, machine.cycles())
+}
+
+#[test]
+pub fn test_profiler() {
+    let buffer: Bytes = fs::read("tests/programs/simple64").unwrap().into();
+    let (profiler, cycles) = profile_interpreter(&buffer);
+    let self_cycles = profiler.self_cycles();
+    assert_eq!(self_cycles.values().sum::<u64>(), cycles);
+    assert!(self_cycles["main"] > 0);
+    assert!(self_cycles["memset"] > 0);
+
+    let mut folded = Vec::new();
+    profiler.write_folded(&mut folded).unwrap();
+    let folded = String::from_utf8(folded).unwrap();
+    let mut total = 0;
+    for line in folded.lines() {
+        let (stack, cycles) = line.rsplit_once(' ').unwrap();
+        assert!(stack.starts_with("_start"));
+        total += cycles.parse::<u64>().unwrap();
+    }
+    assert_eq!(total, cycles);
+    assert!(folded.contains(&format!("_start;main {}\n", self_cycles["main"])));
+    assert!(folded.contains("_start;memset "));
+
+    let mut callgrind = Vec::new();
+    profiler.write_callgrind(&mut callgrind).unwrap();
+    let callgrind = String::from_utf8(callgrind).unwrap();
+    assert!(callgrind.starts_with("# callgrind format\n"));
+    assert!(callgrind.contains(&format!("summary: {}\n", cycles)));
+    assert!(callgrind.contains(&format!("fn=main\n0 {}\n", self_cycles["main"])));
+    assert!(callgrind.contains("fn=_start\n"));
+    assert!(callgrind.contains("cfn=main\ncalls=1 0\n"));
+}
+
+#[cfg(has_asm)]
+#[test]
+pub fn test_asm_profiler() {
+    let buffer: Bytes = fs::read("tests/programs/simple64").unwrap().into();
+    let asm_core = AsmCoreMachine::new(ISA_IMC, VERSION1, u64::max_value());
+    let core = DefaultMachineBuilder::new(asm_core)
+        .instruction_cycle_func(Box::new(constant_cycles))
+        .build();
+    let mut machine = AsmMachine::new(core);
+    machine
+        .load_program(&buffer, &vec!["simple".into()])
+        .unwrap();
+    let mut profiler = Profiler::new(&buffer).unwrap();
+    assert_eq!(profiler.run_asm(&mut machine), Ok(0));
+
+    let (expected, cycles) = profile_interpreter(&buffer);
+    assert_eq!(machine.machine.cycles(), cycles);
+    let self_cycles = profiler.self_cycles();
+    assert_eq!(self_cycles, expected.self_cycles());
+}
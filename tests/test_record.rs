@@ -0,0 +1,155 @@
+use ckb_vm::cost_model::constant_cycles;
+use ckb_vm::machine::VERSION1;
+use ckb_vm::record::{ExecutionLog, LogOutcome, Recorder, RecordingMemory, Replayer};
+use ckb_vm::registers::SP;
+use ckb_vm::{
+    CoreMachine, DefaultCoreMachine, DefaultMachine, DefaultMachineBuilder, Error, Memory,
+    SparseMemory, SupportMachine, ISA_IMC,
+};
+use std::fs;
+
+const MAIN: u64 = 0x10146;
+
+fn record(max_cycles: u64) -> (Result<i8, Error>, Vec<u8>, [u64; 32], u64, u64) {
+    let buffer = fs::read("tests/programs/simple64").unwrap().into();
+    let core_machine = DefaultCoreMachine::<u64, RecordingMemory<SparseMemory<u64>>>::new(
+        ISA_IMC, VERSION1, max_cycles,
+    );
+    let mut machine = DefaultMachineBuilder::new(core_machine)
+        .instruction_cycle_func(Box::new(constant_cycles))
+        .build();
+    machine
+        .load_program(&buffer, &vec!["simple".into()])
+        .unwrap();
+    let mut recorder = Recorder::new(Vec::new());
+    let result = recorder.run(&mut machine);
+    let mut registers = [0u64; 32];
+    registers.copy_from_slice(machine.registers());
+    (
+        result,
+        recorder.into_inner(),
+        registers,
+        *machine.pc(),
+        machine.cycles(),
+    )
+}
+
+fn replay_machine() -> DefaultCoreMachine<u64, SparseMemory<u64>> {
+    DefaultCoreMachine::new(ISA_IMC, VERSION1, u64::max_value())
+}
+
+fn reference_machine() -> DefaultMachine<DefaultCoreMachine<u64, SparseMemory<u64>>> {
+    let buffer = fs::read("tests/programs/simple64").unwrap().into();
+    let core_machine = DefaultCoreMachine::new(ISA_IMC, VERSION1, u64::max_value());
+    let mut machine = DefaultMachineBuilder::new(core_machine)
+        .instruction_cycle_func(Box::new(constant_cycles))
+        .build();
+    machine
+        .load_program(&buffer, &vec!["simple".into()])
+        .unwrap();
+    machine
+}
+
+#[test]
+pub fn test_record_replay() {
+    let (result, log, registers, pc, cycles) = record(u64::max_value());
+    assert_eq!(result, Ok(0));
+    let log = ExecutionLog::read(&log[..]).unwrap();
+    assert_eq!(log.outcome, LogOutcome::Exited(0));
+    let steps = log.entries.len();
+
+    let mut replayer = Replayer::new(replay_machine(), log).unwrap();
+    assert_eq!(*replayer.machine().pc(), 0x100c0);
+    assert!(!replayer.resume().unwrap());
+    assert_eq!(replayer.position(), steps);
+    assert_eq!(replayer.machine().registers(), &registers[..]);
+    assert_eq!(*replayer.machine().pc(), pc);
+    assert_eq!(replayer.machine().cycles(), cycles);
+}
+
+#[test]
+pub fn test_replay_seek() {
+    let (_, log, _, _, _) = record(u64::max_value());
+    let log = ExecutionLog::parse(&log).unwrap();
+    let steps = log.entries.len();
+    let mut replayer = Replayer::new_with_checkpoint_interval(replay_machine(), log, 64).unwrap();
+    replayer.seek(steps).unwrap();
+
+    let mut reference = reference_machine();
+    let mut decoder = ckb_vm::decoder::build_decoder::<u64>(ISA_IMC, VERSION1);
+    let mut position = 0;
+    for target in [100, 317, 500] {
+        while position < target {
+            reference.step(&mut decoder).unwrap();
+            position += 1;
+        }
+        // Going backward restores a checkpoint then replays the log
+        replayer.seek(target).unwrap();
+        assert_eq!(replayer.position(), target);
+        assert_eq!(replayer.machine().registers(), reference.registers());
+        assert_eq!(replayer.machine().pc(), reference.pc());
+        assert_eq!(replayer.machine().cycles(), reference.cycles());
+        let sp = reference.registers()[SP];
+        let size = reference.memory().memory_size() as u64 - sp;
+        assert_eq!(
+            replayer.machine_mut().memory_mut().load_bytes(sp, size),
+            reference.memory_mut().load_bytes(sp, size)
+        );
+        replayer.seek(steps).unwrap();
+    }
+}
+
+#[test]
+pub fn test_replay_reverse() {
+    let (_, log, _, _, _) = record(u64::max_value());
+    let log = ExecutionLog::parse(&log).unwrap();
+    let steps = log.entries.len();
+    let mut replayer = Replayer::new_with_checkpoint_interval(replay_machine(), log, 100).unwrap();
+    replayer.seek(steps).unwrap();
+
+    assert!(replayer.reverse_step().unwrap());
+    assert_eq!(replayer.position(), steps - 1);
+    assert_eq!(*replayer.machine().pc(), replayer.log().pc_at(steps - 1));
+
+    replayer.add_breakpoint(MAIN);
+    assert!(replayer.reverse_resume().unwrap());
+    assert_eq!(*replayer.machine().pc(), MAIN);
+    let position = replayer.position();
+    assert!(!replayer.reverse_resume().unwrap());
+    assert_eq!(replayer.position(), 0);
+    assert!(!replayer.reverse_step().unwrap());
+    assert!(replayer.resume().unwrap());
+    assert_eq!(replayer.position(), position);
+}
+
+#[test]
+pub fn test_record_failure() {
+    let (result, log, registers, pc, _) = record(300);
+    assert_eq!(result, Err(Error::CyclesExceeded));
+    let log = ExecutionLog::parse(&log).unwrap();
+    assert_eq!(
+        log.outcome,
+        LogOutcome::Failed(Error::CyclesExceeded.to_string())
+    );
+    let mut replayer = Replayer::new(replay_machine(), log).unwrap();
+    replayer.resume().unwrap();
+    assert_eq!(replayer.machine().registers(), &registers[..]);
+    assert_eq!(*replayer.machine().pc(), pc);
+
+    // A truncated log can still be replayed
+    let (_, mut log, _, _, _) = record(u64::max_value());
+    log.truncate(log.len() - 10);
+    let log = ExecutionLog::parse(&log).unwrap();
+    assert_eq!(log.outcome, LogOutcome::Truncated);
+}
+
+#[test]
+pub fn test_record_rejects_other_format_versions() {
+    let (_, mut log, _, _, _) = record(u64::max_value());
+    assert!(ExecutionLog::parse(&log).is_ok());
+    log[8] = 1;
+    match ExecutionLog::parse(&log) {
+        Err(Error::IO { data, .. }) => assert!(data.contains("unsupported format version 1")),
+        other => panic!("unexpected result: {:?}", other.map(|log| log.outcome)),
+    }
+}
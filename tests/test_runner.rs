@@ -0,0 +1,183 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn runner(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_ckb-vm-runner"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn cycles(output: &Output) -> u64 {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let line = stderr.lines().find(|l| l.starts_with("cycles: ")).unwrap();
+    line["cycles: ".len()..].parse().unwrap()
+}
+
+#[test]
+pub fn test_runner_engines() {
+    let mut engines = vec!["interpreter", "trace"];
+    if cfg!(has_asm) {
+        engines.push("asm");
+    }
+    for engine in engines {
+        let output = runner(&[
+            "--engine",
+            engine,
+            "--cost-model",
+            "constant",
+            "--isa",
+            "imc",
+            "--vm-version",
+            "1",
+            "tests/programs/simple64",
+        ]);
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(cycles(&output), 708);
+    }
+}
+
+#[test]
+pub fn test_runner_snapshot_resume() {
+    let path = std::env::temp_dir().join(format!("ckb-vm-runner-{}.snapshot", std::process::id()));
+    let snapshot = path.to_str().unwrap();
+    let full = runner(&["--engine", "interpreter", "tests/programs/simple64"]);
+    assert_eq!(full.status.code(), Some(0));
+
+    let output = runner(&[
+        "--engine",
+        "interpreter",
+        "--max-cycles",
+        "300",
+        "--snapshot",
+        snapshot,
+        "tests/programs/simple64",
+    ]);
+    assert_ne!(output.status.code(), Some(0));
+    assert!(cycles(&output) <= 300);
+
+    let output = runner(&[
+        "--resume",
+        snapshot,
+        "--dump-registers",
+        "tests/programs/simple64",
+    ]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(cycles(&output), cycles(&full));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("  sp 0x"));
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+pub fn test_runner_stdio() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ckb-vm-runner"))
+        .arg("tests/programs/stdio")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"world").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(5));
+    assert_eq!(output.stdout, b"hello, world");
+}
+
+#[test]
+pub fn test_runner_strace() {
+    let path = std::env::temp_dir().join(format!("ckb-vm-runner-{}.strace", std::process::id()));
+    let output = runner(&["--strace", path.to_str().unwrap(), "tests/programs/stdio"]);
+    // stdin is empty, the program exits with the length read.
+    assert_eq!(output.status.code(), Some(0));
+    let log = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let lines: Vec<&str> = log.lines().collect();
+    assert_eq!(lines.len(), 5);
+    assert_eq!(
+        lines[0],
+        "0x10010: write(fd=0x1, buf=0x10060, count=0x7) = 7 <module:stdio, 0 cycles>"
+    );
+    assert!(lines[3].contains("write(fd=0x5, ") && lines[3].contains(" = -9 "));
+    assert_eq!(lines[4], "0x10050: exit(code=0x0) = 0 <machine, 0 cycles>");
+}
+
+#[test]
+pub fn test_runner_invalid_options() {
+    let output = runner(&["--engine", "jit", "tests/programs/simple64"]);
+    assert_eq!(output.status.code(), Some(2));
+    let output = runner(&["--memory-size", "1000", "tests/programs/simple64"]);
+    assert_eq!(output.status.code(), Some(2));
+    let output = runner(&[]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+pub fn test_runner_backtrace() {
+    let output = runner(&["--engine", "interpreter", "tests/programs/backtrace"]);
+    assert_eq!(output.status.code(), Some(255));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("backtrace:\n#0  0x"));
+    assert!(stderr.contains("#1  0x000000000001002c in middle+0x8\n"));
+    assert!(stderr.contains("#3  0x0000000000010006 in _start+0x6\n"));
+    assert!(stderr.contains("error: memory error: out of bound"));
+}
+
+#[test]
+pub fn test_runner_error_report() {
+    let path = std::env::temp_dir().join("ckb-vm-runner-error-report.json");
+    let output = runner(&[
+        "--engine",
+        "trace",
+        "--error-report",
+        path.to_str().unwrap(),
+        "tests/programs/error_report",
+    ]);
+    assert_eq!(output.status.code(), Some(255));
+    let report = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(
+        report.starts_with("{\"error\":\"memory error: write on executable page page_index=16\"")
+    );
+    assert!(report.contains("\"pc\":65556,"));
+    assert!(report.contains("\"kind\":\"Write\""));
+}
+
+#[test]
+pub fn test_runner_core() {
+    let path = std::env::temp_dir().join(format!("ckb-vm-runner-{}.core", std::process::id()));
+    let output = runner(&["--core", path.to_str().unwrap(), "tests/programs/backtrace"]);
+    assert_eq!(output.status.code(), Some(255));
+    let core = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(&core[..4], b"\x7fELF");
+    // e_type is ET_CORE
+    assert_eq!(&core[16..18], &[4, 0]);
+}
+
+#[test]
+pub fn test_runner_stack() {
+    let output = runner(&[
+        "--env",
+        "HOME=/",
+        "--auxv",
+        "--stack-size",
+        "64K",
+        "tests/programs/simple64",
+    ]);
+    assert_eq!(output.status.code(), Some(0));
+    let output = runner(&["--stack-size", "16", "tests/programs/simple64", "argument"]);
+    assert_ne!(output.status.code(), Some(0));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("error: memory error: out of stack"));
+
+    let output = runner(&[
+        "--stack-guard",
+        "4K",
+        "tests/programs/stack_overflow",
+        "stack_overflow",
+        "forever",
+    ]);
+    assert_ne!(output.status.code(), Some(0));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("error: stack overflow pc=0x1002c"));
+}
@@ -0,0 +1,172 @@
+use ckb_vm::cost_model::constant_cycles;
+#[cfg(has_asm)]
+use ckb_vm::machine::asm::{AsmCoreMachine, AsmMachine};
+use ckb_vm::machine::{DefaultMachine, VERSION1};
+use ckb_vm::scheduler::{MachineFactory, ScheduledMachine, Scheduler, SchedulerSnapshot};
+use ckb_vm::{
+    Bytes, DefaultCoreMachine, DefaultMachineBuilder, Error, SparseMemory, TraceMachine,
+    WXorXMemory, ISA_IMC,
+};
+use std::fs;
+
+type Core = DefaultCoreMachine<u64, WXorXMemory<SparseMemory<u64>>>;
+
+fn interpreter() -> Box<MachineFactory<DefaultMachine<Core>>> {
+    Box::new(|syscalls| {
+        DefaultMachineBuilder::new(Core::new(ISA_IMC, VERSION1, u64::max_value()))
+            .instruction_cycle_func(Box::new(constant_cycles))
+            .syscall(syscalls)
+            .build()
+    })
+}
+
+fn trace() -> Box<MachineFactory<TraceMachine<Core>>> {
+    Box::new(|syscalls| {
+        TraceMachine::new(
+            DefaultMachineBuilder::new(Core::new(ISA_IMC, VERSION1, u64::max_value()))
+                .instruction_cycle_func(Box::new(constant_cycles))
+                .syscall(syscalls)
+                .build(),
+        )
+    })
+}
+
+#[cfg(has_asm)]
+fn asm() -> Box<MachineFactory<AsmMachine>> {
+    Box::new(|syscalls| {
+        AsmMachine::new(
+            DefaultMachineBuilder::new(AsmCoreMachine::new(ISA_IMC, VERSION1, u64::max_value()))
+                .instruction_cycle_func(Box::new(constant_cycles))
+                .syscall(syscalls)
+                .build(),
+        )
+    })
+}
+
+fn program(name: &str) -> Vec<Bytes> {
+    vec![fs::read(format!("tests/programs/{}", name)).unwrap().into()]
+}
+
+fn run<M: ScheduledMachine>(
+    name: &str,
+    factory: Box<MachineFactory<M>>,
+    pipe_capacity: usize,
+) -> (Result<i8, Error>, u64) {
+    let args = vec![Bytes::from(name.to_string())];
+    let mut scheduler = Scheduler::new(program(name), &args, u64::max_value(), factory)
+        .pipe_capacity(pipe_capacity)
+        .max_processes(100);
+    let result = scheduler.run();
+    (result, scheduler.cycles())
+}
+
+#[test]
+pub fn test_scheduler_pipe() {
+    let mut results = vec![];
+    for capacity in [1, 10, 4096] {
+        results.push(run("spawn_pipe", interpreter(), capacity));
+        results.push(run("spawn_pipe", trace(), capacity));
+        #[cfg(has_asm)]
+        results.push(run("spawn_pipe", asm(), capacity));
+    }
+    for (result, _) in &results {
+        assert_eq!(*result, Ok(64 + 7));
+    }
+    // Smaller pipes take more reads and writes.
+    assert!(results[0].1 > results.last().unwrap().1);
+}
+
+#[test]
+pub fn test_scheduler_chain() {
+    // Every level of spawning is run by the scheduler loop, not nested on
+    // the host stack.
+    assert_eq!(run("spawn_chain", interpreter(), 1).0, Ok(64));
+    assert_eq!(run("spawn_chain", trace(), 1).0, Ok(64));
+    #[cfg(has_asm)]
+    assert_eq!(run("spawn_chain", asm(), 1).0, Ok(64));
+
+    // Pids 0 to 15 are alive when the last one fails to spawn.
+    let args = vec![Bytes::from("spawn_chain")];
+    let mut scheduler = Scheduler::new(program("spawn_chain"), &args, u64::max_value(), trace());
+    assert_eq!(scheduler.run(), Ok(108 + 15));
+}
+
+#[test]
+pub fn test_scheduler_shared_cycles() {
+    let (result, cycles) = run("spawn_chain", interpreter(), 1);
+    assert_eq!(result, Ok(64));
+    // Every process counts against the budget of the tree.
+    let args = vec![Bytes::from("spawn_chain")];
+    let mut scheduler =
+        Scheduler::new(program("spawn_chain"), &args, cycles - 1, interpreter()).max_processes(100);
+    assert_eq!(scheduler.run(), Err(Error::CyclesExceeded));
+    assert!(scheduler.cycles() < cycles);
+
+    let mut scheduler =
+        Scheduler::new(program("spawn_chain"), &args, cycles, interpreter()).max_processes(100);
+    assert_eq!(scheduler.run(), Ok(64));
+    assert_eq!(scheduler.cycles(), cycles);
+}
+
+#[test]
+pub fn test_scheduler_deadlock() {
+    let args = vec![Bytes::from("spawn_deadlock")];
+    let mut scheduler = Scheduler::new(program("spawn_deadlock"), &args, 1000, trace());
+    assert_eq!(scheduler.run(), Err(Error::Deadlock));
+}
+
+#[test]
+pub fn test_scheduler_pause() {
+    let args = vec![Bytes::from("spawn_pipe")];
+    let mut scheduler = Scheduler::new(program("spawn_pipe"), &args, 1000, interpreter());
+    scheduler.pause().interrupt();
+    assert_eq!(scheduler.run(), Err(Error::Pause));
+    // Pausing does not lose any progress.
+    assert_eq!(scheduler.run(), Ok(64 + 7));
+}
+
+#[test]
+pub fn test_scheduler_snapshot() {
+    let name = "spawn_pipe";
+    let args = vec![Bytes::from(name)];
+    let (expected, total) = run(name, interpreter(), 8);
+    // Suspends the tree every few cycles, and resumes it from a snapshot
+    // which went through serde.
+    let step = total / 7;
+    let mut max_cycles = step;
+    let mut scheduler =
+        Scheduler::new(program(name), &args, max_cycles, interpreter()).pipe_capacity(8);
+    let mut suspensions = 0;
+    let result = loop {
+        match scheduler.run() {
+            Err(Error::CyclesExceeded) => {
+                let snapshot = scheduler.snapshot().unwrap();
+                let data = serde_json::to_string(&snapshot).unwrap();
+                let snapshot: SchedulerSnapshot = serde_json::from_str(&data).unwrap();
+                max_cycles += step;
+                scheduler =
+                    Scheduler::resume(program(name), &snapshot, max_cycles, interpreter()).unwrap();
+                suspensions += 1;
+            }
+            result => break result,
+        }
+    };
+    assert_eq!(result, expected);
+    assert_eq!(scheduler.cycles(), total);
+    assert!(suspensions > 3);
+}
+
+#[cfg(has_asm)]
+#[test]
+pub fn test_scheduler_snapshot_asm() {
+    let name = "spawn_pipe";
+    let args = vec![Bytes::from(name)];
+    let (_, total) = run(name, asm(), 8);
+    let mut scheduler = Scheduler::new(program(name), &args, total / 2, asm()).pipe_capacity(8);
+    assert_eq!(scheduler.run(), Err(Error::CyclesExceeded));
+    let snapshot = scheduler.snapshot().unwrap();
+    // The tree moves to another engine.
+    let mut scheduler =
+        Scheduler::resume(program(name), &snapshot, u64::max_value(), interpreter()).unwrap();
+    assert_eq!(scheduler.run(), Ok(64 + 7));
+}
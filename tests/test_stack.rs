@@ -0,0 +1,160 @@
+use ckb_vm::machine::stack::{StackConfig, AT_ENTRY, AT_PAGESZ, AT_PHDR, AT_PHNUM, AT_RANDOM};
+use ckb_vm::machine::{trace::TraceMachine, VERSION1};
+use ckb_vm::registers::SP;
+use ckb_vm::{
+    Bytes, CoreMachine, DefaultCoreMachine, DefaultMachine, DefaultMachineBuilder, Error, Memory,
+    SparseMemory, WXorXMemory, DEFAULT_MEMORY_SIZE, ISA_IMC,
+};
+use std::fs;
+
+type Core = DefaultCoreMachine<u64, WXorXMemory<SparseMemory<u64>>>;
+
+fn build(stack: StackConfig) -> DefaultMachine<Core> {
+    let core_machine = Core::new(ISA_IMC, VERSION1, u64::max_value());
+    DefaultMachineBuilder::new(core_machine)
+        .stack(stack)
+        .build()
+}
+
+fn load64(machine: &mut DefaultMachine<Core>, address: u64) -> u64 {
+    machine.memory_mut().load64(&address).unwrap()
+}
+
+fn load_string(machine: &mut DefaultMachine<Core>, address: u64) -> Vec<u8> {
+    let mut string = vec![];
+    loop {
+        let byte = machine
+            .memory_mut()
+            .load8(&(address + string.len() as u64))
+            .unwrap();
+        if byte == 0 {
+            return string;
+        }
+        string.push(byte as u8);
+    }
+}
+
+#[test]
+pub fn test_stack_default_layout() {
+    let program: Bytes = fs::read("tests/programs/alloc_many").unwrap().into();
+    let mut machine = build(StackConfig::default());
+    machine
+        .load_program(&program, &vec!["alloc_many".into()])
+        .unwrap();
+    let sp = machine.registers()[SP];
+    assert_eq!(sp % 16, 0);
+    assert!(sp > (DEFAULT_MEMORY_SIZE as u64) * 3 / 4);
+    assert_eq!(load64(&mut machine, sp), 1);
+    assert_eq!(load64(&mut machine, sp + 16), 0);
+}
+
+#[test]
+pub fn test_stack_envs_and_auxv() {
+    let program: Bytes = fs::read("tests/programs/alloc_many").unwrap().into();
+    let config = StackConfig {
+        envs: vec!["HOME=/".into(), "LANG=C".into()],
+        auxv: true,
+        random_seed: 7,
+        ..Default::default()
+    };
+    let mut machine = build(config.clone());
+    machine
+        .load_program(&program, &vec!["alloc_many".into(), "x".into()])
+        .unwrap();
+    let mut sp = machine.registers()[SP];
+    assert_eq!(sp % 16, 0);
+    assert_eq!(load64(&mut machine, sp), 2);
+    let argv1 = load64(&mut machine, sp + 16);
+    assert_eq!(load_string(&mut machine, argv1), b"x");
+    assert_eq!(load64(&mut machine, sp + 24), 0);
+    sp += 32;
+    let mut envs = vec![];
+    loop {
+        let address = load64(&mut machine, sp);
+        sp += 8;
+        if address == 0 {
+            break;
+        }
+        envs.push(load_string(&mut machine, address));
+    }
+    assert_eq!(envs, vec![b"HOME=/".to_vec(), b"LANG=C".to_vec()]);
+    let mut auxv = vec![];
+    loop {
+        let (kind, value) = (load64(&mut machine, sp), load64(&mut machine, sp + 8));
+        sp += 16;
+        if kind == 0 {
+            break;
+        }
+        auxv.push((kind, value));
+    }
+    let value = |kind| auxv.iter().find(|(k, _)| *k == kind).unwrap().1;
+    assert_eq!(value(AT_PAGESZ), 4096);
+    assert_eq!(value(AT_ENTRY), 0x100c0);
+    assert_eq!(value(AT_PHDR), 0x10040);
+    assert_eq!(value(AT_PHNUM), 2);
+    let random = machine
+        .memory_mut()
+        .load_bytes(value(AT_RANDOM), 16)
+        .unwrap();
+    assert_eq!(&random[..], &config.random_bytes()[..]);
+    assert_ne!(config.random_bytes(), [0; 16]);
+
+    // Startup code skips over envp and auxv.
+    let mut machine = TraceMachine::new(build(config));
+    machine
+        .load_program(&program, &vec!["alloc_many".into()])
+        .unwrap();
+    assert_eq!(machine.run(), Ok(0));
+}
+
+#[test]
+pub fn test_stack_location() {
+    let program: Bytes = fs::read("tests/programs/alloc_many").unwrap().into();
+    let config = StackConfig {
+        start: Some(0x200000),
+        size: Some(0x10000),
+        ..Default::default()
+    };
+    let mut machine = TraceMachine::new(build(config));
+    machine
+        .load_program(&program, &vec!["alloc_many".into()])
+        .unwrap();
+    let sp = machine.machine.registers()[SP];
+    assert!(sp > 0x200000 && sp < 0x210000);
+    assert_eq!(machine.run(), Ok(0));
+
+    let mut machine = build(StackConfig {
+        size: Some(0x1000),
+        ..Default::default()
+    });
+    machine
+        .load_program(&program, &vec!["alloc_many".into()])
+        .unwrap();
+    assert!(machine.registers()[SP] > DEFAULT_MEMORY_SIZE as u64 - 0x1000);
+}
+
+#[test]
+pub fn test_stack_too_small() {
+    let program: Bytes = fs::read("tests/programs/alloc_many").unwrap().into();
+    let args: Vec<Bytes> = vec![vec![b'a'; 100].into()];
+    for auxv in [false, true] {
+        let mut machine = build(StackConfig {
+            size: Some(64),
+            auxv,
+            ..Default::default()
+        });
+        assert_eq!(
+            machine.load_program(&program, &args),
+            Err(Error::MemOutOfStack)
+        );
+    }
+    let mut machine = build(StackConfig {
+        start: Some(DEFAULT_MEMORY_SIZE as u64),
+        size: Some(16),
+        ..Default::default()
+    });
+    assert_eq!(
+        machine.load_program(&program, &args),
+        Err(Error::MemOutOfStack)
+    );
+}
@@ -0,0 +1,187 @@
+#[cfg(has_asm)]
+use ckb_vm::machine::asm::{AsmCoreMachine, AsmMachine};
+use ckb_vm::machine::stack::StackConfig;
+use ckb_vm::machine::{trace::TraceMachine, VERSION1};
+use ckb_vm::registers::A0;
+use ckb_vm::{
+    Bytes, CoreMachine, DefaultCoreMachine, DefaultMachineBuilder, Error, FlatMemory, Memory,
+    SparseMemory, WXorXMemory, DEFAULT_MEMORY_SIZE, ISA_IMC,
+};
+use std::fs;
+
+const STACK_START: u64 = DEFAULT_MEMORY_SIZE as u64 / 4 * 3;
+// Address of the store of ra in tests/programs/stack_overflow.
+const STORE_PC: u64 = 0x1002c;
+
+#[derive(Clone, Copy, Debug)]
+enum Engine {
+    Flat,
+    Sparse,
+    WXorX,
+    #[cfg(has_asm)]
+    Asm,
+}
+
+fn engines() -> Vec<Engine> {
+    #[allow(unused_mut)]
+    let mut engines = vec![Engine::Flat, Engine::Sparse, Engine::WXorX];
+    #[cfg(has_asm)]
+    engines.push(Engine::Asm);
+    engines
+}
+
+fn run(engine: Engine, guard_size: u64, args: &[&str]) -> Result<i8, Error> {
+    let program: Bytes = fs::read("tests/programs/stack_overflow").unwrap().into();
+    let args: Vec<Bytes> = args.iter().map(|a| Bytes::from(a.to_string())).collect();
+    let stack = StackConfig {
+        guard_size,
+        ..Default::default()
+    };
+    macro_rules! interpret {
+        ($memory:ty) => {{
+            let core_machine =
+                DefaultCoreMachine::<u64, $memory>::new(ISA_IMC, VERSION1, u64::max_value());
+            let mut machine = TraceMachine::new(
+                DefaultMachineBuilder::new(core_machine)
+                    .stack(stack)
+                    .build(),
+            );
+            machine.load_program(&program, &args).unwrap();
+            machine.run()
+        }};
+    }
+    match engine {
+        Engine::Flat => interpret!(FlatMemory<u64>),
+        Engine::Sparse => interpret!(SparseMemory<u64>),
+        Engine::WXorX => interpret!(WXorXMemory<SparseMemory<u64>>),
+        #[cfg(has_asm)]
+        Engine::Asm => {
+            let core_machine = AsmCoreMachine::new(ISA_IMC, VERSION1, u64::max_value());
+            let mut machine = AsmMachine::new(
+                DefaultMachineBuilder::new(core_machine)
+                    .stack(stack)
+                    .build(),
+            );
+            machine.load_program(&program, &args).unwrap();
+            machine.run()
+        }
+    }
+}
+
+#[test]
+pub fn test_stack_guard_overflow() {
+    for engine in engines() {
+        match run(engine, 4096, &["stack_overflow", "forever"]) {
+            Err(Error::StackOverflow { pc, sp }) => {
+                assert_eq!(pc, STORE_PC, "{:?}", engine);
+                assert!(
+                    (STACK_START - 64..STACK_START).contains(&sp),
+                    "{:?}",
+                    engine
+                );
+            }
+            result => panic!("{:?}: {:?}", engine, result),
+        }
+    }
+}
+
+#[test]
+pub fn test_stack_guard_within_stack() {
+    for engine in engines() {
+        assert_eq!(
+            run(engine, 4096, &["stack_overflow"]),
+            Ok(0),
+            "{:?}",
+            engine
+        );
+    }
+}
+
+#[test]
+pub fn test_stack_guard_disabled() {
+    for engine in engines() {
+        let result = run(engine, 0, &["stack_overflow", "forever"]);
+        assert!(
+            !matches!(result, Ok(_) | Err(Error::StackOverflow { .. })),
+            "{:?}: {:?}",
+            engine,
+            result
+        );
+    }
+}
+
+// Runs tests/programs/stack_guard_edge with a guard over the last page of
+// memory, making a doubleword access at address.
+fn run_edge(engine: Engine, address: u64) -> Result<i8, Error> {
+    let program: Bytes = fs::read("tests/programs/stack_guard_edge").unwrap().into();
+    let args = vec![Bytes::from("stack_guard_edge")];
+    let guard = (
+        DEFAULT_MEMORY_SIZE as u64 - 4096,
+        DEFAULT_MEMORY_SIZE as u64,
+    );
+    macro_rules! interpret {
+        ($memory:ty) => {{
+            let core_machine =
+                DefaultCoreMachine::<u64, $memory>::new(ISA_IMC, VERSION1, u64::max_value());
+            let mut machine = TraceMachine::new(DefaultMachineBuilder::new(core_machine).build());
+            machine.load_program(&program, &args).unwrap();
+            let memory = machine.machine.memory_mut();
+            memory.set_stack_guard(guard.0, guard.1).unwrap();
+            machine.machine.set_register(A0, address);
+            machine.run()
+        }};
+    }
+    match engine {
+        Engine::Flat => interpret!(FlatMemory<u64>),
+        Engine::Sparse => interpret!(SparseMemory<u64>),
+        Engine::WXorX => interpret!(WXorXMemory<SparseMemory<u64>>),
+        #[cfg(has_asm)]
+        Engine::Asm => {
+            let core_machine = AsmCoreMachine::new(ISA_IMC, VERSION1, u64::max_value());
+            let mut machine = AsmMachine::new(DefaultMachineBuilder::new(core_machine).build());
+            machine.load_program(&program, &args).unwrap();
+            let memory = machine.machine.memory_mut();
+            memory.set_stack_guard(guard.0, guard.1).unwrap();
+            machine.machine.set_register(A0, address);
+            machine.run()
+        }
+    }
+}
+
+#[test]
+pub fn test_stack_guard_before_bounds() {
+    for engine in engines() {
+        // Inside the guard and past the end of memory, the guard is checked
+        // first by every engine.
+        let result = run_edge(engine, DEFAULT_MEMORY_SIZE as u64 - 4);
+        assert!(
+            matches!(result, Err(Error::StackOverflow { .. })),
+            "{:?}: {:?}",
+            engine,
+            result
+        );
+        let result = run_edge(engine, DEFAULT_MEMORY_SIZE as u64);
+        assert!(
+            matches!(result, Err(Error::MemOutOfBound(..))),
+            "{:?}: {:?}",
+            engine,
+            result
+        );
+    }
+}
+
+#[test]
+pub fn test_stack_guard_host_access() {
+    let mut memory = SparseMemory::<u64>::new_with_memory(DEFAULT_MEMORY_SIZE);
+    memory.set_stack_guard(0x1000, 0x2000).unwrap();
+    assert_eq!(memory.stack_guard(), (0x1000, 0x2000));
+    assert_eq!(memory.load8(&0x1fff), Err(Error::MemOutOfStack));
+    assert_eq!(memory.store64(&0xffc, &0), Err(Error::MemOutOfStack));
+    assert_eq!(memory.load64(&0x2000), Ok(0));
+    assert_eq!(memory.load64(&0xff8), Ok(0));
+    // Bulk accesses made by the host are not guarded.
+    memory.store_bytes(0x1000, &[1, 2, 3]).unwrap();
+    assert_eq!(&memory.load_bytes(0x1000, 3).unwrap()[..], &[1, 2, 3]);
+    memory.reset_memory().unwrap();
+    assert_eq!(memory.stack_guard(), (0, 0));
+}
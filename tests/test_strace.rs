@@ -0,0 +1,181 @@
+use ckb_vm::cost_model::constant_cycles;
+use ckb_vm::elf::program_break;
+#[cfg(has_asm)]
+use ckb_vm::machine::asm::{AsmCoreMachine, AsmMachine};
+use ckb_vm::machine::VERSION1;
+use ckb_vm::registers::A0;
+use ckb_vm::syscalls::linux::{LinuxSyscalls, SYS_BRK, SYS_EXIT_GROUP, SYS_WRITE};
+use ckb_vm::syscalls::strace::{SyscallHandledBy, SyscallRecord};
+use ckb_vm::{
+    Bytes, CoreMachine, DefaultCoreMachine, DefaultMachineBuilder, Error, SparseMemory,
+    SyscallInfo, SyscallSchema, SyscallTrace, WXorXMemory, ISA_IMC,
+};
+use std::fs;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+type Core = DefaultCoreMachine<u64, WXorXMemory<SparseMemory<u64>>>;
+
+// A writer whose contents stay readable after it is moved into a trace.
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Buffer {
+    fn lines(&self) -> Vec<String> {
+        String::from_utf8(self.0.lock().unwrap().clone())
+            .unwrap()
+            .lines()
+            .map(|l| l.to_string())
+            .collect()
+    }
+}
+
+impl Write for Buffer {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+// Runs tests/programs/linux_syscalls, returns the exit code and the records.
+fn run_linux(trace: SyscallTrace, asm: bool) -> (Result<i8, Error>, Vec<SyscallRecord>) {
+    let program: Bytes = fs::read("tests/programs/linux_syscalls").unwrap().into();
+    let args = vec![Bytes::from("linux_syscalls")];
+    let syscalls = LinuxSyscalls::new(program_break(&program).unwrap()).stdin("input".into());
+    if asm {
+        #[cfg(has_asm)]
+        {
+            let core_machine = AsmCoreMachine::new(ISA_IMC, VERSION1, u64::max_value());
+            let mut machine = AsmMachine::new(
+                DefaultMachineBuilder::new(core_machine)
+                    .instruction_cycle_func(Box::new(constant_cycles))
+                    .syscall(Box::new(syscalls))
+                    .syscall_trace(trace)
+                    .build(),
+            );
+            machine.load_program(&program, &args).unwrap();
+            let result = machine.run();
+            let records = machine.machine.syscall_trace().unwrap().records().to_vec();
+            return (result, records);
+        }
+        #[cfg(not(has_asm))]
+        unreachable!()
+    }
+    let core_machine = Core::new(ISA_IMC, VERSION1, u64::max_value());
+    let mut machine = DefaultMachineBuilder::new(core_machine)
+        .instruction_cycle_func(Box::new(constant_cycles))
+        .syscall(Box::new(syscalls))
+        .syscall_trace(trace)
+        .build();
+    machine.load_program(&program, &args).unwrap();
+    let result = machine.run();
+    let records = machine.syscall_trace().unwrap().records().to_vec();
+    (result, records)
+}
+
+#[test]
+pub fn test_strace_records() {
+    let mut engines = vec![false];
+    if cfg!(has_asm) {
+        engines.push(true);
+    }
+    let mut traces = vec![];
+    for asm in engines {
+        let (result, records) = run_linux(SyscallTrace::new(SyscallSchema::linux()), asm);
+        assert_eq!(result, Ok(42));
+        let write = &records[0];
+        assert_eq!(write.number, SYS_WRITE);
+        assert_eq!(write.name.as_deref(), Some("write"));
+        assert_eq!(write.arguments[0], 1);
+        assert_eq!(write.arguments[2], 6);
+        assert_eq!(write.result, 6);
+        assert_eq!(write.handled_by, SyscallHandledBy::Module("linux".into()));
+        assert!(records.iter().any(|r| r.number == SYS_BRK));
+        let exit = records.last().unwrap();
+        assert_eq!(exit.number, SYS_EXIT_GROUP);
+        assert_eq!(exit.result, 42);
+        traces.push(records);
+    }
+    // Every engine makes the same syscalls from the same PCs.
+    traces.dedup();
+    assert_eq!(traces.len(), 1);
+}
+
+#[test]
+pub fn test_strace_outputs() {
+    let (log, json_lines) = (Buffer::default(), Buffer::default());
+    let schema = SyscallSchema::default().syscall(SYS_WRITE, "write", &["fd", "buf", "count"]);
+    let trace = SyscallTrace::new(schema)
+        .log(Box::new(log.clone()))
+        .json_lines(Box::new(json_lines.clone()));
+    let (_, records) = run_linux(trace, false);
+    let (log, json_lines) = (log.lines(), json_lines.lines());
+    assert_eq!(log.len(), records.len());
+    assert_eq!(json_lines.len(), records.len());
+
+    assert!(log[0].contains(": write(fd=0x1, buf=0x"));
+    assert!(log[0].ends_with(", count=0x6) = 6 <module:linux, 0 cycles>"));
+    // Syscalls missing from the schema show raw arguments.
+    let brk = records.iter().position(|r| r.number == SYS_BRK).unwrap();
+    assert!(log[brk].contains(": syscall_214(0x0, "));
+
+    let json: serde_json::Value = serde_json::from_str(&json_lines[0]).unwrap();
+    assert_eq!(json["name"], "write");
+    assert_eq!(json["named_arguments"]["fd"], 1);
+    assert_eq!(json["named_arguments"]["count"], 6);
+    assert_eq!(json["handled_by"]["Module"], "linux");
+    // The fields of SyscallRecord read back with serde.
+    for (line, record) in json_lines.iter().zip(records.iter()) {
+        let decoded: SyscallRecord = serde_json::from_str(line).unwrap();
+        assert_eq!(&decoded, record);
+    }
+}
+
+#[test]
+pub fn test_strace_table_and_errors() {
+    let program: Bytes = fs::read("tests/programs/syscall64").unwrap().into();
+    let args = vec![Bytes::from("syscall")];
+    let core_machine = Core::new(ISA_IMC, VERSION1, u64::max_value());
+    let mut machine = DefaultMachineBuilder::new(core_machine)
+        .syscall_handler(
+            1111,
+            SyscallInfo::new("sum", 6, 500),
+            Box::new(|machine: &mut Core| {
+                let value = machine.registers()[A0] + 1;
+                machine.set_register(A0, value);
+                Ok(())
+            }),
+        )
+        .syscall_trace(SyscallTrace::default())
+        .build();
+    machine.load_program(&program, &args).unwrap();
+    assert_eq!(machine.run(), Ok(5));
+    let records = machine.syscall_trace().unwrap().records();
+    assert_eq!(records.len(), 2);
+    // The name comes from the table when the schema misses the number.
+    assert_eq!(records[0].name.as_deref(), Some("sum"));
+    assert_eq!(records[0].arguments, [4, 5, 6, 7, 8, 9]);
+    assert_eq!(records[0].result, 5);
+    assert_eq!(records[0].cycles, 500);
+    assert_eq!(records[0].handled_by, SyscallHandledBy::Table("sum".into()));
+    assert_eq!(records[1].handled_by, SyscallHandledBy::Machine);
+
+    // Unhandled syscalls are recorded along with the error.
+    let core_machine = Core::new(ISA_IMC, VERSION1, u64::max_value());
+    let mut machine = DefaultMachineBuilder::new(core_machine)
+        .syscall_trace(SyscallTrace::default())
+        .build();
+    machine.load_program(&program, &args).unwrap();
+    assert_eq!(machine.run(), Err(Error::InvalidEcall(1111)));
+    let records = machine.syscall_trace().unwrap().records();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].handled_by, SyscallHandledBy::Unhandled);
+    assert_eq!(
+        records[0].error,
+        Some(Error::InvalidEcall(1111).to_string())
+    );
+}
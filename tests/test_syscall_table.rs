@@ -0,0 +1,129 @@
+#[cfg(has_asm)]
+use ckb_vm::machine::asm::{AsmCoreMachine, AsmMachine};
+use ckb_vm::machine::VERSION1;
+use ckb_vm::registers::{A0, A1, A2, A3, A4, A5};
+use ckb_vm::{
+    Bytes, DefaultCoreMachine, DefaultMachineBuilder, Error, Register, SparseMemory,
+    SupportMachine, SyscallInfo, Syscalls, WXorXMemory, ISA_IMC,
+};
+use std::fs;
+
+type Core = DefaultCoreMachine<u64, WXorXMemory<SparseMemory<u64>>>;
+
+// Sums the arguments of syscall 1111 made by tests/programs/syscall64.
+fn sum<Mac: SupportMachine>(machine: &mut Mac) -> Result<(), Error> {
+    let result = [A0, A1, A2, A3, A4, A5]
+        .iter()
+        .map(|r| machine.registers()[*r].to_u64())
+        .sum();
+    machine.set_register(A0, Mac::REG::from_u64(result));
+    Ok(())
+}
+
+// A module of the fallback chain answering every syscall with 100.
+struct Fallback;
+
+impl<Mac: SupportMachine> Syscalls<Mac> for Fallback {
+    fn initialize(&mut self, _machine: &mut Mac) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn ecall(&mut self, machine: &mut Mac) -> Result<bool, Error> {
+        machine.set_register(A0, Mac::REG::from_u64(100));
+        Ok(true)
+    }
+}
+
+fn program() -> (Bytes, Vec<Bytes>) {
+    let program = fs::read("tests/programs/syscall64").unwrap().into();
+    (program, vec!["syscall".into()])
+}
+
+#[test]
+pub fn test_syscall_table() {
+    let (program, args) = program();
+    let core_machine = Core::new(ISA_IMC, VERSION1, u64::max_value());
+    let mut machine = DefaultMachineBuilder::new(core_machine)
+        .syscall(Box::new(Fallback))
+        .syscall_handler(1111, SyscallInfo::new("sum", 6, 500), Box::new(sum))
+        .build();
+    machine.load_program(&program, &args).unwrap();
+    // The table wins over the chain.
+    assert_eq!(machine.run(), Ok(39));
+    assert_eq!(machine.cycles(), 500);
+    let info = machine.syscall_table().info(1111).unwrap();
+    assert_eq!(info.name, "sum");
+    assert_eq!(info.arguments, 6);
+    assert_eq!(
+        machine
+            .syscall_table()
+            .iter()
+            .map(|(n, _)| n)
+            .collect::<Vec<_>>(),
+        vec![1111]
+    );
+
+    // Numbers missing from the table go to the chain.
+    let core_machine = Core::new(ISA_IMC, VERSION1, u64::max_value());
+    let mut machine = DefaultMachineBuilder::new(core_machine)
+        .syscall(Box::new(Fallback))
+        .syscall_handler(2000, SyscallInfo::new("sum", 6, 0), Box::new(sum))
+        .build();
+    machine.load_program(&program, &args).unwrap();
+    assert_eq!(machine.run(), Ok(100));
+}
+
+#[test]
+pub fn test_syscall_table_cycles() {
+    let (program, args) = program();
+    let core_machine = Core::new(ISA_IMC, VERSION1, 499);
+    let mut machine = DefaultMachineBuilder::new(core_machine)
+        .syscall_handler(1111, SyscallInfo::new("sum", 6, 500), Box::new(sum))
+        .build();
+    machine.load_program(&program, &args).unwrap();
+    assert_eq!(machine.run(), Err(Error::CyclesExceeded));
+}
+
+#[cfg(has_asm)]
+#[test]
+pub fn test_syscall_table_asm() {
+    let (program, args) = program();
+    let core_machine = AsmCoreMachine::new(ISA_IMC, VERSION1, u64::max_value());
+    let mut machine = AsmMachine::new(
+        DefaultMachineBuilder::new(core_machine)
+            .syscall_handler(1111, SyscallInfo::new("sum", 6, 500), Box::new(sum))
+            .build(),
+    );
+    machine.load_program(&program, &args).unwrap();
+    assert_eq!(machine.run(), Ok(39));
+    assert_eq!(machine.machine.cycles(), 500);
+}
+
+#[test]
+#[should_panic(expected = "syscall 1111 is registered twice")]
+pub fn test_syscall_table_duplicate() {
+    let core_machine = Core::new(ISA_IMC, VERSION1, u64::max_value());
+    let _ = DefaultMachineBuilder::new(core_machine)
+        .syscall_handler(1111, SyscallInfo::new("sum", 6, 0), Box::new(sum))
+        .syscall_handler(1111, SyscallInfo::new("other", 0, 0), Box::new(sum));
+}
+
+#[test]
+pub fn test_syscall_table_stops_machine() {
+    let (program, args) = program();
+    let core_machine = Core::new(ISA_IMC, VERSION1, u64::max_value());
+    let mut machine = DefaultMachineBuilder::new(core_machine)
+        .syscall_handler(
+            1111,
+            SyscallInfo::new("exit_group", 1, 0),
+            Box::new(|machine: &mut Core| {
+                machine.set_running(false);
+                Ok(())
+            }),
+        )
+        .build();
+    machine.load_program(&program, &args).unwrap();
+    // A handler only stops the machine, the exit code is left at 0.
+    assert_eq!(machine.run(), Ok(0));
+    assert!(!machine.running());
+}
@@ -0,0 +1,188 @@
+use ckb_vm::differential::Differential;
+#[cfg(has_asm)]
+use ckb_vm::machine::asm::{AsmCoreMachine, AsmMachine};
+use ckb_vm::machine::{trace::TraceMachine, VERSION2, VERSION3};
+use ckb_vm::registers::A0;
+use ckb_vm::{
+    cost_model, snapshot, CoreMachine as _, DefaultCoreMachine, DefaultMachine,
+    DefaultMachineBuilder, Error, SparseMemory, SupportMachine, WXorXMemory, ISA_IMC, ISA_V,
+    RISCV_VTYPE_VILL,
+};
+use std::fs;
+
+type CoreMachine = DefaultCoreMachine<u64, WXorXMemory<SparseMemory<u64>>>;
+
+const ISA: u8 = ISA_IMC | ISA_V;
+
+fn build(version: u32) -> DefaultMachine<CoreMachine> {
+    let core_machine = CoreMachine::new(ISA, version, u64::max_value());
+    DefaultMachineBuilder::new(core_machine)
+        .instruction_cycle_func(Box::new(|_| 1))
+        .build()
+}
+
+fn program() -> bytes::Bytes {
+    fs::read("tests/programs/vector").unwrap().into()
+}
+
+#[test]
+pub fn test_vector_interpreter() {
+    let mut machine = build(VERSION3);
+    machine
+        .load_program(&program(), &vec!["vector".into()])
+        .unwrap();
+    assert_eq!(machine.run(), Ok(0));
+}
+
+#[test]
+pub fn test_vector_trace() {
+    let mut machine = TraceMachine::new(build(VERSION3));
+    machine
+        .load_program(&program(), &vec!["vector".into()])
+        .unwrap();
+    assert_eq!(machine.run(), Ok(0));
+}
+
+#[cfg(has_asm)]
+#[test]
+pub fn test_vector_asm() {
+    let core = DefaultMachineBuilder::new(AsmCoreMachine::new(ISA, VERSION3, u64::max_value()))
+        .instruction_cycle_func(Box::new(|_| 1))
+        .build();
+    let mut machine = AsmMachine::new(core);
+    machine
+        .load_program(&program(), &vec!["vector".into()])
+        .unwrap();
+    assert_eq!(machine.run(), Ok(0));
+}
+
+#[test]
+pub fn test_vector_differential() {
+    let differential = Differential::new(build(VERSION3), build(VERSION3));
+    #[cfg(has_asm)]
+    let differential = differential.with_asm(
+        DefaultMachineBuilder::new(AsmCoreMachine::new(ISA, VERSION3, u64::max_value()))
+            .instruction_cycle_func(Box::new(|_| 1))
+            .build(),
+    );
+    let mut differential = differential;
+    differential
+        .load_program(&program(), &vec!["vector".into()])
+        .unwrap();
+    assert_eq!(differential.run().map_err(|d| d.to_string()), Ok(Ok(0)));
+}
+
+// Runs tests/programs/vector_cycles, which adds avl 64-bit elements with LMUL
+// 8, and returns the cycles spent by each engine.
+fn vector_cycles(avl: u64) -> Vec<u64> {
+    let program: bytes::Bytes = fs::read("tests/programs/vector_cycles").unwrap().into();
+    let args = vec!["vector_cycles".into()];
+    let mut machine = build(VERSION3);
+    machine.load_program(&program, &args).unwrap();
+    machine.set_register(A0, avl);
+    assert_eq!(machine.run(), Ok(0));
+    let mut cycles = vec![machine.cycles()];
+
+    let mut machine = TraceMachine::new(build(VERSION3));
+    machine.load_program(&program, &args).unwrap();
+    machine.set_register(A0, avl);
+    assert_eq!(machine.run(), Ok(0));
+    cycles.push(machine.machine.cycles());
+
+    #[cfg(has_asm)]
+    {
+        let core = DefaultMachineBuilder::new(AsmCoreMachine::new(ISA, VERSION3, u64::max_value()))
+            .instruction_cycle_func(Box::new(|_| 1))
+            .build();
+        let mut machine = AsmMachine::new(core);
+        machine.load_program(&program, &args).unwrap();
+        machine.machine.set_register(A0, avl);
+        assert_eq!(machine.run(), Ok(0));
+        cycles.push(machine.machine.cycles());
+    }
+    cycles
+}
+
+#[test]
+pub fn test_vector_cycles_scale_with_vl() {
+    // The vadd is charged once for every register holding its elements,
+    // VLEN is 256 bits so 32 elements fill all 8 registers of the group.
+    for cycles in vector_cycles(1) {
+        assert_eq!(cycles, 5);
+    }
+    for cycles in vector_cycles(5) {
+        assert_eq!(cycles, 6);
+    }
+    for cycles in vector_cycles(32) {
+        assert_eq!(cycles, 12);
+    }
+}
+
+#[test]
+pub fn test_vector_cost_model() {
+    let vsetvli = 0x0db572d7u32;
+    let vadd = 0x02880457u32;
+    let decode = |bits| ckb_vm::instructions::v::factory::<u64>(bits, VERSION3).unwrap();
+    let vtype = 0b011011;
+    assert_eq!(cost_model::vector_registers(decode(vsetvli), 32, vtype), 1);
+    assert_eq!(cost_model::vector_registers(decode(vadd), 0, vtype), 1);
+    assert_eq!(cost_model::vector_registers(decode(vadd), 9, vtype), 3);
+    assert_eq!(cost_model::vector_registers(decode(vadd), 32, vtype), 8);
+}
+
+#[test]
+pub fn test_vector_requires_version3() {
+    let mut machine = build(VERSION2);
+    machine
+        .load_program(&program(), &vec!["vector".into()])
+        .unwrap();
+    assert!(matches!(
+        machine.run(),
+        Err(Error::InvalidInstruction { .. })
+    ));
+}
+
+#[test]
+pub fn test_vector_requires_isa_v() {
+    let core_machine = CoreMachine::new(ISA_IMC, VERSION3, u64::max_value());
+    let mut machine = DefaultMachineBuilder::new(core_machine)
+        .instruction_cycle_func(Box::new(|_| 1))
+        .build();
+    machine
+        .load_program(&program(), &vec!["vector".into()])
+        .unwrap();
+    assert!(matches!(
+        machine.run(),
+        Err(Error::InvalidInstruction { .. })
+    ));
+}
+
+#[test]
+pub fn test_vector_snapshot() {
+    let mut machine = build(VERSION3);
+    machine
+        .load_program(&program(), &vec!["vector".into()])
+        .unwrap();
+    machine.run().unwrap();
+    let snapshot = snapshot::make_snapshot(&mut machine).unwrap();
+    let vector = snapshot.vector.as_ref().unwrap();
+    assert_eq!(vector.vregisters, machine.vregisters());
+    assert_eq!(vector.vl, 1);
+    assert!(snapshot.float.is_none());
+    let data = serde_json::to_string(&snapshot).unwrap();
+    let snapshot: snapshot::Snapshot = serde_json::from_str(&data).unwrap();
+
+    let mut resumed = build(VERSION3);
+    assert_eq!(resumed.vtype(), RISCV_VTYPE_VILL);
+    snapshot::resume(&mut resumed, &snapshot).unwrap();
+    assert_eq!(resumed.vregisters(), machine.vregisters());
+    assert_eq!(resumed.vl(), machine.vl());
+    assert_eq!(resumed.vtype(), machine.vtype());
+
+    // Snapshots of machines without the extension leave the registers out.
+    let core_machine = CoreMachine::new(ISA_IMC, VERSION3, u64::max_value());
+    let mut other = DefaultMachineBuilder::new(core_machine).build();
+    assert!(snapshot::resume(&mut other, &snapshot).is_err());
+    let data = serde_json::to_value(snapshot::make_snapshot(&mut other).unwrap()).unwrap();
+    assert!(data.get("vector").is_none());
+}